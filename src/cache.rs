@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Remembers files that were already evaluated and found (or made)
+/// conforming, keyed by filesystem identity so repeated runs can skip them.
+/// A file is only skipped while its device, inode, name and mtime all still
+/// match; renamed-back or re-added files get a new identity and are checked
+/// again. The cache is discarded whenever the configuration changes.
+pub struct IdentityCache {
+    path: PathBuf,
+    config_hash: String,
+    entries: HashMap<(u64, u64), (String, i64)>,
+    dirty: bool,
+}
+
+struct Identity {
+    dev: u64,
+    ino: u64,
+    mtime: i64,
+}
+
+#[cfg(unix)]
+fn identity(path: &Path) -> Option<Identity> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = fs::symlink_metadata(path).ok()?;
+    Some(Identity {
+        dev: meta.dev(),
+        ino: meta.ino(),
+        mtime: meta.mtime() * 1_000_000_000 + meta.mtime_nsec(),
+    })
+}
+
+#[cfg(not(unix))]
+fn identity(_path: &Path) -> Option<Identity> {
+    None
+}
+
+impl IdentityCache {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::cache_dir()?.join("namefmt").join("identity-cache.tsv"))
+    }
+
+    pub fn load(path: PathBuf, config_hash: &str) -> IdentityCache {
+        let mut cache = IdentityCache {
+            path,
+            config_hash: config_hash.to_string(),
+            entries: HashMap::new(),
+            dirty: false,
+        };
+
+        let Ok(content) = fs::read_to_string(&cache.path) else {
+            return cache;
+        };
+        let mut lines = content.lines();
+        if lines.next() != Some(&format!("# config {}", config_hash)) {
+            // Different rules may give different answers; start over
+            cache.dirty = true;
+            return cache;
+        }
+        for line in lines {
+            let mut fields = line.splitn(4, '\t');
+            let (Some(dev), Some(ino), Some(mtime), Some(name)) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            if let (Ok(dev), Ok(ino), Ok(mtime)) = (dev.parse(), ino.parse(), mtime.parse()) {
+                cache.entries.insert((dev, ino), (name.to_string(), mtime));
+            }
+        }
+        cache
+    }
+
+    /// True if this exact file was already settled by a previous run.
+    pub fn is_settled(&self, path: &Path) -> bool {
+        let (Some(id), Some(name)) = (identity(path), path.file_name()) else {
+            return false;
+        };
+        matches!(self.entries.get(&(id.dev, id.ino)),
+            Some((cached_name, mtime)) if *mtime == id.mtime && cached_name.as_str() == name.to_string_lossy())
+    }
+
+    pub fn remember(&mut self, path: &Path) {
+        let (Some(id), Some(name)) = (identity(path), path.file_name()) else {
+            return;
+        };
+        let name = name.to_string_lossy().to_string();
+        // Names with tabs or newlines would corrupt the cache file
+        if name.contains(['\t', '\n']) {
+            return;
+        }
+        self.entries.insert((id.dev, id.ino), (name, id.mtime));
+        self.dirty = true;
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Concurrent runs (e.g. sharded workers) share the cache: merge with
+        // what they saved meanwhile instead of overwriting it
+        let lock = OpenOptions::new().create(true).truncate(false).write(true).open(self.path.with_extension("lock"))?;
+        lock.lock()?;
+        let mut entries = IdentityCache::load(self.path.clone(), &self.config_hash).entries;
+        entries.extend(self.entries.iter().map(|(k, v)| (*k, v.clone())));
+
+        let mut out = format!("# config {}\n", self.config_hash);
+        for ((dev, ino), (name, mtime)) in &entries {
+            out.push_str(&format!("{}\t{}\t{}\t{}\n", dev, ino, mtime, name));
+        }
+        let tmp = self.path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, out)?;
+        fs::rename(tmp, &self.path)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settled_files_are_remembered_until_they_or_the_rules_change() {
+        let scratch = tempfile::tempdir().unwrap();
+        let (file, path) = (scratch.path().join("notes.txt"), scratch.path().join("cache.tsv"));
+        fs::write(&file, "notes").unwrap();
+        let mut cache = IdentityCache::load(path.clone(), "rules");
+        assert!(!cache.is_settled(&file));
+        cache.remember(&file);
+        cache.save().unwrap();
+
+        assert!(IdentityCache::load(path.clone(), "rules").is_settled(&file));
+        assert!(!IdentityCache::load(path.clone(), "other rules").is_settled(&file));
+        let renamed = scratch.path().join("Notes.txt");
+        fs::rename(&file, &renamed).unwrap();
+        assert!(!IdentityCache::load(path, "rules").is_settled(&renamed));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::plan::Plan;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Status `namefmt check` exits with when every name is fine.
+pub const CLEAN: i32 = 0;
+/// Some files don't follow the configured format.
+pub const VIOLATIONS: i32 = 1;
+/// The check itself failed, e.g. an unreadable tree or a bad configuration.
+pub const FAILED: i32 = 2;
+
+/// One line per file the plan would rename: where it is, the name it should
+/// have and the rule asking for it, then a summary line.
+pub fn render(plan: &Plan) -> String {
+    let mut out = String::new();
+    let relative = |path: &Path| match path.strip_prefix(&plan.root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.display().to_string(),
+        _ => path.display().to_string(),
+    };
+    for rename in &plan.renames {
+        // Just the new name when the file stays where it is
+        let expected = match (rename.source.parent() == rename.target.parent(), rename.target.file_name()) {
+            (true, Some(name)) => name.to_string_lossy().to_string(),
+            _ => relative(&rename.target),
+        };
+        let _ = writeln!(out, "{}: should be {} ({})", relative(&rename.source), expected, rename.rule);
+    }
+    if plan.renames.is_empty() {
+        let _ = writeln!(out, "All {} file(s) follow the configured format", plan.files.len());
+    } else {
+        let _ = writeln!(out, "\n{} of {} file(s) don't follow the configured format", plan.renames.len(), plan.files.len());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::Rename;
+    use std::path::PathBuf;
+
+    fn plan(renames: Vec<Rename>) -> Plan {
+        let root = PathBuf::from("/repo");
+        Plan {
+            files: vec![root.join("a"), root.join("b"), root.join("c")],
+            root,
+            renames,
+            skipped: Vec::new(),
+            coverage: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn violations_are_listed_per_file_with_their_rule() {
+        let rename = |source: &str, target: &str, rule: &str| Rename { source: PathBuf::from(source), target: PathBuf::from(target), rule: rule.to_string() };
+        let listing = render(&plan(vec![
+            rename("/repo/src/Http Client.rs", "/repo/src/http_client.rs", "*.rs (snake_case)"),
+            rename("/repo/My Docs/Notes.md", "/repo/my-docs/Notes.md", "My Docs (directory)"),
+        ]));
+        assert_eq!(listing, "src/Http Client.rs: should be http_client.rs (*.rs (snake_case))\n\
+            My Docs/Notes.md: should be my-docs/Notes.md (My Docs (directory))\n\
+            \n2 of 3 file(s) don't follow the configured format\n");
+        assert_eq!(render(&plan(Vec::new())), "All 3 file(s) follow the configured format\n");
+    }
+}
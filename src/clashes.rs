@@ -0,0 +1,131 @@
+use crate::name;
+use crate::plan::{Plan, Rename};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Files in different directories that end up with the same name.
+pub struct Clash {
+    /// The shared name (as first seen, when case is ignored)
+    pub name: String,
+    /// (current path, path after the plan), in path order
+    pub files: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Group the plan's files by the name each ends up with and keep the
+/// names found in more than one directory.
+pub fn find(plan: &Plan, ignore_case: bool) -> Vec<Clash> {
+    let targets: HashMap<&Path, &Path> = plan.renames.iter().map(|r| (r.source.as_path(), r.target.as_path())).collect();
+    let mut by_name: BTreeMap<String, Clash> = BTreeMap::new();
+    for file in &plan.files {
+        let target = targets.get(file.as_path()).copied().unwrap_or(file);
+        let Some(name) = target.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+        let key = if ignore_case { name.to_lowercase() } else { name.clone() };
+        by_name.entry(key).or_insert_with(|| Clash { name, files: Vec::new() }).files.push((file.clone(), target.to_path_buf()));
+    }
+    by_name
+        .into_values()
+        .filter(|clash| clash.files.iter().map(|(_, target)| target.parent()).collect::<HashSet<_>>().len() > 1)
+        .collect()
+}
+
+/// Renames giving every file in `clash` a suffix made of as many parent
+/// directory names as it takes to tell them apart: `report.pdf` in
+/// `2023/q1` and `2024/q1` become `report_2023_q1.pdf` and
+/// `report_2024_q1.pdf`. `rule` describes the rename each file already had.
+pub fn disambiguate(root: &Path, clash: &Clash, rules: &HashMap<&Path, &str>) -> Vec<Rename> {
+    let parents: Vec<Vec<String>> = clash
+        .files
+        .iter()
+        .map(|(_, target)| {
+            let dir = target.parent().unwrap_or(root);
+            let relative = dir.strip_prefix(root).unwrap_or(dir);
+            relative.components().map(|c| c.as_os_str().to_string_lossy().replace(' ', "_")).collect()
+        })
+        .collect();
+    let deepest = parents.iter().map(Vec::len).max().unwrap_or(0);
+    // Files directly in the root have no parent name to borrow
+    let suffix = |dirs: &[String], depth: usize| dirs[dirs.len().saturating_sub(depth)..].join("_");
+    let depth = (1..=deepest)
+        .find(|&depth| parents.iter().map(|dirs| suffix(dirs, depth)).collect::<HashSet<_>>().len() == parents.len())
+        .unwrap_or(deepest);
+
+    clash
+        .files
+        .iter()
+        .zip(&parents)
+        .filter(|(_, dirs)| !dirs.is_empty())
+        .map(|((source, target), dirs)| {
+            let name = target.file_name().unwrap_or_default().to_string_lossy();
+            let (stem, extension) = name::split_extension(&name);
+            let rule = match rules.get(source.as_path()) {
+                Some(rule) => format!("{} + parent suffix", rule),
+                None => String::from("parent suffix"),
+            };
+            Rename {
+                source: source.clone(),
+                target: target.with_file_name(format!("{}_{}{}", stem, suffix(dirs, depth), extension)),
+                rule,
+            }
+        })
+        .collect()
+}
+
+/// Human-readable listing of `clashes`, ending in a one-line summary.
+pub fn render(root: &Path, scanned: usize, clashes: &[Clash]) -> String {
+    let mut out = String::new();
+    let relative = |path: &Path| path.strip_prefix(root).unwrap_or(path).display().to_string();
+    for clash in clashes {
+        let _ = writeln!(out, "{} ({} files)", clash.name, clash.files.len());
+        for (source, target) in &clash.files {
+            if source == target {
+                let _ = writeln!(out, "    {}", relative(source));
+            } else {
+                let _ = writeln!(out, "    {} (renamed from {})", relative(target), relative(source));
+            }
+        }
+    }
+    if clashes.is_empty() {
+        let _ = writeln!(out, "No clashes: the {} scanned file(s) keep distinct names", scanned);
+    } else {
+        let files: usize = clashes.iter().map(|c| c.files.len()).sum();
+        let _ = writeln!(out, "\n{} name(s) shared by {} files in different directories", clashes.len(), files);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_names_get_the_fewest_parents_that_tell_them_apart() {
+        let root = PathBuf::from("/nonexistent/namefmt-clashes");
+        let plan = Plan {
+            root: root.clone(),
+            files: ["2023/q1/Report.pdf", "2024/q1/report.pdf", "other/REPORT.PDF", "other/notes.txt"].iter().map(|f| root.join(f)).collect(),
+            renames: vec![Rename { source: root.join("2023/q1/Report.pdf"), target: root.join("2023/q1/report.pdf"), rule: String::from("*.pdf (snake_case)") }],
+            skipped: Vec::new(),
+            coverage: Vec::new(),
+            suggestions: Vec::new(),
+        };
+        let clashes = find(&plan, false);
+        assert_eq!(clashes.len(), 1);
+        assert_eq!(clashes[0].name, "report.pdf");
+        assert_eq!(find(&plan, true)[0].files.len(), 3);
+
+        let rules = HashMap::from([(root.join("2023/q1/Report.pdf"), "*.pdf (snake_case)")]);
+        let rules = rules.iter().map(|(path, rule)| (path.as_path(), *rule)).collect();
+        let renames: Vec<_> = disambiguate(&root, &clashes[0], &rules).into_iter().map(|r| (r.target, r.rule)).collect();
+        assert_eq!(renames, [
+            (root.join("2023/q1/report_2023_q1.pdf"), String::from("*.pdf (snake_case) + parent suffix")),
+            (root.join("2024/q1/report_2024_q1.pdf"), String::from("parent suffix")),
+        ]);
+
+        let listing = render(&root, plan.files.len(), &clashes);
+        assert!(listing.starts_with("report.pdf (2 files)\n    2023/q1/report.pdf (renamed from 2023/q1/Report.pdf)\n    2024/q1/report.pdf\n"), "{}", listing);
+        assert!(listing.ends_with("\n1 name(s) shared by 2 files in different directories\n"), "{}", listing);
+    }
+}
@@ -0,0 +1,208 @@
+use clap::{Command, ValueHint};
+use std::fmt::Write;
+
+/// A shell `namefmt completions` can write a script for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// One command or subcommand: the words leading to it, what can follow it.
+struct Node {
+    path: Vec<String>,
+    subcommands: Vec<(String, String)>,
+    options: Vec<Opt>,
+}
+
+struct Opt {
+    longs: Vec<String>,
+    short: Option<char>,
+    help: String,
+    takes_value: bool,
+    values: Vec<String>,
+    /// Whether the value is a path, so file names are worth offering
+    path: bool,
+}
+
+impl Opt {
+    fn words(&self) -> Vec<String> {
+        let longs = self.longs.iter().map(|long| format!("--{}", long));
+        longs.chain(self.short.map(|short| format!("-{}", short))).collect()
+    }
+}
+
+impl Shell {
+    /// The completion script for `command` and everything below it.
+    pub fn script(self, command: &Command) -> String {
+        let mut command = command.clone();
+        command.build();
+        let mut nodes = Vec::new();
+        walk(&command, Vec::new(), &mut nodes);
+        let name = command.get_name();
+        match self {
+            Shell::Bash => bash(name, &nodes),
+            Shell::Zsh => format!("#compdef {}\nautoload -U +X bashcompinit && bashcompinit\n\n{}", name, bash(name, &nodes)),
+            Shell::Fish => fish(name, &nodes),
+        }
+    }
+}
+
+fn walk(command: &Command, path: Vec<String>, nodes: &mut Vec<Node>) {
+    let visible = || command.get_subcommands().filter(|sub| !sub.is_hide_set());
+    let options = command.get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+        .map(|arg| Opt {
+            longs: arg.get_long().into_iter().chain(arg.get_visible_aliases().unwrap_or_default()).map(str::to_string).collect(),
+            short: arg.get_short(),
+            help: first_line(arg.get_help().map(|help| help.to_string())),
+            takes_value: arg.get_action().takes_values(),
+            path: matches!(arg.get_value_hint(), ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath),
+            values: arg.get_possible_values().iter().filter(|value| !value.is_hide_set()).map(|value| value.get_name().to_string()).collect(),
+        })
+        .collect();
+    nodes.push(Node {
+        path: path.clone(),
+        subcommands: visible().map(|sub| (sub.get_name().to_string(), first_line(sub.get_about().map(|about| about.to_string())))).collect(),
+        options,
+    });
+    // `help <subcommand>` completes like the subcommand it names; not worth spelling out
+    for sub in visible().filter(|sub| sub.get_name() != "help") {
+        let mut path = path.clone();
+        path.push(sub.get_name().to_string());
+        walk(sub, path, nodes);
+    }
+}
+
+fn first_line(text: Option<String>) -> String {
+    text.unwrap_or_default().lines().next().unwrap_or_default().trim_end_matches('.').to_string()
+}
+
+fn bash(name: &str, nodes: &[Node]) -> String {
+    let function = format!("_{}", name.replace('-', "_"));
+    let command_path = |node: &Node| std::iter::once(name).chain(node.path.iter().map(String::as_str)).collect::<Vec<_>>().join(" ");
+    let mut script = String::new();
+    let _ = writeln!(script, "{}() {{", function);
+    let _ = writeln!(script, "    local cur prev cmd opts subcommands i");
+    let _ = writeln!(script, "    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"");
+    let _ = writeln!(script, "    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"");
+    let _ = writeln!(script, "    cmd=\"{}\"", name);
+    let _ = writeln!(script, "    for ((i = 1; i < COMP_CWORD; i++)); do");
+    let _ = writeln!(script, "        case \"$cmd ${{COMP_WORDS[i]}}\" in");
+    let nested: Vec<String> = nodes.iter().filter(|node| !node.path.is_empty()).map(|node| format!("\"{}\"", command_path(node))).collect();
+    let _ = writeln!(script, "            {}) cmd=\"$cmd ${{COMP_WORDS[i]}}\" ;;", nested.join("|"));
+    let _ = writeln!(script, "        esac");
+    let _ = writeln!(script, "    done");
+    let _ = writeln!(script, "    case \"$cmd\" in");
+    for node in nodes {
+        let _ = writeln!(script, "        \"{}\")", command_path(node));
+        let valued: Vec<&Opt> = node.options.iter().filter(|opt| opt.takes_value).collect();
+        if !valued.is_empty() {
+            let _ = writeln!(script, "            case \"$prev\" in");
+            for opt in valued {
+                // Values that are neither listed nor paths get no suggestions
+                let reply = match (opt.values.is_empty(), opt.path) {
+                    (false, _) => format!("($(compgen -W \"{}\" -- \"$cur\"))", opt.values.join(" ")),
+                    (true, true) => "($(compgen -f -- \"$cur\"))".to_string(),
+                    (true, false) => "()".to_string(),
+                };
+                let _ = writeln!(script, "                {}) COMPREPLY={}; return ;;", opt.words().join("|"), reply);
+            }
+            let _ = writeln!(script, "            esac");
+        }
+        let opts: Vec<String> = node.options.iter().flat_map(Opt::words).collect();
+        let subcommands: Vec<&str> = node.subcommands.iter().map(|(name, _)| name.as_str()).collect();
+        let _ = writeln!(script, "            opts=\"{}\"", opts.join(" "));
+        let _ = writeln!(script, "            subcommands=\"{}\"", subcommands.join(" "));
+        let _ = writeln!(script, "            ;;");
+    }
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(script, "    if [[ $cur == -* ]]; then");
+    let _ = writeln!(script, "        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))");
+    let _ = writeln!(script, "    else");
+    let _ = writeln!(script, "        COMPREPLY=($(compgen -W \"$subcommands\" -- \"$cur\") $(compgen -f -- \"$cur\"))");
+    let _ = writeln!(script, "    fi");
+    let _ = writeln!(script, "}}");
+    let _ = writeln!(script, "complete -o filenames -F {} {}", function, name);
+    script
+}
+
+fn fish(name: &str, nodes: &[Node]) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut script = String::new();
+    for node in nodes {
+        // Top-level options only before a subcommand; nested ones once
+        // every word leading to them has been seen
+        let condition = if node.path.is_empty() {
+            "__fish_use_subcommand".to_string()
+        } else {
+            node.path.iter().map(|word| format!("__fish_seen_subcommand_from {}", word)).collect::<Vec<_>>().join("; and ")
+        };
+        let names: Vec<&str> = node.subcommands.iter().map(|(name, _)| name.as_str()).collect();
+        for (sub, about) in &node.subcommands {
+            let condition = if node.path.is_empty() {
+                condition.clone()
+            } else {
+                format!("{}; and not __fish_seen_subcommand_from {}", condition, names.join(" "))
+            };
+            let _ = writeln!(script, "complete -c {} -n {} -f -a {} -d {}", name, quote(&condition), sub, quote(about));
+        }
+        for opt in &node.options {
+            let mut line = format!("complete -c {} -n {}", name, quote(&condition));
+            for long in &opt.longs {
+                let _ = write!(line, " -l {}", long);
+            }
+            if let Some(short) = opt.short {
+                let _ = write!(line, " -s {}", short);
+            }
+            if opt.takes_value {
+                line.push_str(if opt.path { " -r -F" } else { " -x" });
+            }
+            if !opt.values.is_empty() {
+                let _ = write!(line, " -f -a {}", quote(&opt.values.join(" ")));
+            }
+            let _ = writeln!(script, "{} -d {}", line, quote(&opt.help));
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn command() -> Command {
+        Command::new("tool")
+            .arg(Arg::new("output").long("output").value_parser(["text", "json"]))
+            .arg(Arg::new("config").short('c').long("config").value_hint(ValueHint::FilePath))
+            .arg(Arg::new("quiet").short('q').long("quiet").action(clap::ArgAction::SetTrue))
+            .subcommand(Command::new("config").about("Manage the configuration.\nMore detail.")
+                .subcommand(Command::new("validate").about("Check the file").arg(Arg::new("strict").long("strict").action(clap::ArgAction::SetTrue))))
+            .subcommand(Command::new("internal").hide(true))
+    }
+
+    #[test]
+    fn bash_scripts_complete_each_subcommand_and_value() {
+        let script = Shell::Bash.script(&command());
+        assert!(script.contains("            \"tool config\"|\"tool config validate\") cmd=\"$cmd ${COMP_WORDS[i]}\" ;;\n"), "{}", script);
+        assert!(script.contains("                --output) COMPREPLY=($(compgen -W \"text json\" -- \"$cur\")); return ;;\n"), "{}", script);
+        assert!(script.contains("                --config|-c) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;\n"), "{}", script);
+        assert!(script.contains("        \"tool config validate\")\n            opts=\"--strict --help -h\"\n"), "{}", script);
+        assert!(!script.contains("internal"), "{}", script);
+        assert!(script.ends_with("complete -o filenames -F _tool tool\n"));
+        assert!(Shell::Zsh.script(&command()).starts_with("#compdef tool\n"));
+        let checked = std::process::Command::new("bash").args(["-n", "-c", &script]).status();
+        assert!(checked.is_ok_and(|status| status.success()));
+    }
+
+    #[test]
+    fn fish_scripts_offer_subcommands_in_their_place() {
+        let script = Shell::Fish.script(&command());
+        assert!(script.contains("complete -c tool -n '__fish_use_subcommand' -f -a config -d 'Manage the configuration'\n"), "{}", script);
+        assert!(script.contains("complete -c tool -n '__fish_seen_subcommand_from config; and not __fish_seen_subcommand_from validate help' -f -a validate -d 'Check the file'\n"), "{}", script);
+        assert!(script.contains("complete -c tool -n '__fish_use_subcommand' -l output -x -f -a 'text json' -d ''\n"), "{}", script);
+        assert!(script.contains("complete -c tool -n '__fish_use_subcommand' -l config -s c -r -F -d ''\n"), "{}", script);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::plan::{Plan, Rename};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+/// A file the two plans disagree about. `None` means it keeps its name.
+pub struct Difference<'a> {
+    pub source: &'a Path,
+    pub old: Option<&'a Rename>,
+    pub new: Option<&'a Rename>,
+    /// Why each set of rules leaves the file alone, if it skips it
+    pub old_skipped: Option<&'a str>,
+    pub new_skipped: Option<&'a str>,
+}
+
+/// Files that `new` would rename differently from `old`, in path order.
+/// Both plans must have been made against the same tree.
+pub fn compare<'a>(old: &'a Plan, new: &'a Plan) -> Vec<Difference<'a>> {
+    let mut sources: BTreeMap<&Path, (Option<&Rename>, Option<&Rename>)> = BTreeMap::new();
+    for rename in &old.renames {
+        sources.entry(&rename.source).or_default().0 = Some(rename);
+    }
+    for rename in &new.renames {
+        sources.entry(&rename.source).or_default().1 = Some(rename);
+    }
+    let old_skipped = skip_reasons(old);
+    let new_skipped = skip_reasons(new);
+
+    sources
+        .into_iter()
+        .filter(|(_, (old, new))| old.map(|r| &r.target) != new.map(|r| &r.target))
+        .map(|(source, (old, new))| Difference {
+            source,
+            old,
+            new,
+            old_skipped: old_skipped.get(source).copied(),
+            new_skipped: new_skipped.get(source).copied(),
+        })
+        .collect()
+}
+
+fn skip_reasons(plan: &Plan) -> HashMap<&Path, &str> {
+    plan.skipped.iter().map(|(file, reason)| (file.as_path(), reason.as_str())).collect()
+}
+
+/// Human-readable listing of `differences`, ending in a one-line summary.
+pub fn render(root: &Path, scanned: usize, differences: &[Difference]) -> String {
+    let mut out = String::new();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for difference in differences {
+        let marker = match (difference.old, difference.new) {
+            (None, _) => {
+                added += 1;
+                '+'
+            }
+            (_, None) => {
+                removed += 1;
+                '-'
+            }
+            _ => {
+                changed += 1;
+                '~'
+            }
+        };
+        let _ = writeln!(out, "{} {}", marker, relative(root, difference.source));
+        let _ = writeln!(out, "    old: {}", describe(root, difference.old, difference.old_skipped));
+        let _ = writeln!(out, "    new: {}", describe(root, difference.new, difference.new_skipped));
+    }
+
+    if differences.is_empty() {
+        let _ = writeln!(out, "No differences: both configurations rename the {} scanned file(s) the same way", scanned);
+    } else {
+        out.push('\n');
+        let _ = writeln!(
+            out,
+            "{} of {} file(s) would be renamed differently: {} newly renamed, {} no longer renamed, {} renamed to a different name",
+            differences.len(),
+            scanned,
+            added,
+            removed,
+            changed
+        );
+    }
+    out
+}
+
+fn describe(root: &Path, rename: Option<&Rename>, skipped: Option<&str>) -> String {
+    match (rename, skipped) {
+        (Some(rename), _) => {
+            // Only spell out the directory when the file would move
+            let target = if rename.target.parent() == rename.source.parent() {
+                rename.target.file_name().unwrap_or_default().to_string_lossy().to_string()
+            } else {
+                relative(root, &rename.target)
+            };
+            format!("{} ({})", target, rename.rule)
+        }
+        (None, Some(reason)) => format!("keeps its name ({})", reason),
+        (None, None) => String::from("keeps its name"),
+    }
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn plan(root: &Path, renames: &[(&str, &str)], skipped: &[(&str, &str)]) -> Plan {
+        Plan {
+            root: root.to_path_buf(),
+            files: Vec::new(),
+            renames: renames
+                .iter()
+                .map(|(source, target)| Rename { source: root.join(source), target: root.join(target), rule: String::from("rule") })
+                .collect(),
+            skipped: skipped.iter().map(|(file, reason)| (root.join(file), reason.to_string())).collect(),
+            coverage: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn only_files_renamed_differently_are_listed() {
+        let root = PathBuf::from("/tree");
+        let old = plan(&root, &[("Same Name.txt", "same_name.txt"), ("Old Only.txt", "old_only.txt"), ("Both.txt", "both.txt")], &[]);
+        let new = plan(
+            &root,
+            &[("Same Name.txt", "same_name.txt"), ("Both.txt", "sub/BOTH.txt"), ("New Only.txt", "new-only.txt")],
+            &[("Old Only.txt", "protected name")],
+        );
+        let differences = compare(&old, &new);
+        assert_eq!(render(&root, 4, &differences), "\
+~ Both.txt
+    old: both.txt (rule)
+    new: sub/BOTH.txt (rule)
++ New Only.txt
+    old: keeps its name
+    new: new-only.txt (rule)
+- Old Only.txt
+    old: old_only.txt (rule)
+    new: keeps its name (protected name)
+
+3 of 4 file(s) would be renamed differently: 1 newly renamed, 1 no longer renamed, 1 renamed to a different name
+");
+        assert_eq!(
+            render(&root, 4, &compare(&old, &old)),
+            "No differences: both configurations rename the 4 scanned file(s) the same way\n"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use crate::name::split_extension;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// What to do with files carrying a sync-client conflict marker.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Treat conflict copies like any other file
+    #[default]
+    Ignore,
+    /// Replace the marker with a uniform `_conflict_<date>` suffix
+    Rename,
+    /// Move conflict copies into the quarantine directory
+    Quarantine,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConflictRules {
+    #[serde(default)]
+    pub policy: ConflictPolicy,
+
+    #[serde(default = "default_quarantine_dir")]
+    pub quarantine_dir: PathBuf,
+}
+
+impl Default for SyncConflictRules {
+    fn default() -> Self {
+        SyncConflictRules {
+            policy: ConflictPolicy::default(),
+            quarantine_dir: default_quarantine_dir(),
+        }
+    }
+}
+
+fn default_quarantine_dir() -> PathBuf {
+    PathBuf::from(".sync-conflicts")
+}
+
+impl SyncConflictRules {
+    /// Resolve the quarantine directory against the root being processed.
+    pub fn quarantine_root(&self, root: &Path) -> PathBuf {
+        if self.quarantine_dir.is_absolute() {
+            self.quarantine_dir.clone()
+        } else {
+            root.join(&self.quarantine_dir)
+        }
+    }
+}
+
+/// A filename recognized as a sync conflict copy.
+#[derive(Debug, PartialEq)]
+pub struct SyncConflict {
+    /// The name the file would have without the conflict marker
+    pub original: String,
+    /// Conflict date as `YYYY-MM-DD`, when the marker carries one
+    pub date: Option<String>,
+}
+
+impl SyncConflict {
+    pub fn renamed(&self) -> String {
+        let (stem, ext) = split_extension(&self.original);
+        match &self.date {
+            Some(date) => format!("{}_conflict_{}{}", stem, date, ext),
+            None => format!("{}_conflict{}", stem, ext),
+        }
+    }
+}
+
+/// Recognize Dropbox, Nextcloud/ownCloud and Syncthing conflict markers.
+pub fn detect(name: &str) -> Option<SyncConflict> {
+    detect_parenthesized(name)
+        .or_else(|| detect_syncthing(name))
+        .or_else(|| detect_owncloud(name))
+}
+
+// Dropbox: `foo (conflicted copy 2024-01-02).txt`, `foo (Jane's conflicted copy 2024-01-02).txt`,
+// `foo (Jane's conflicted copy 2024-01-02 (1)).txt`
+// Nextcloud: `foo (conflicted copy 2024-01-02 103000).txt`
+fn detect_parenthesized(name: &str) -> Option<SyncConflict> {
+    let marker = name.find("conflicted copy")?;
+    let open = name[..marker].rfind(" (")?;
+    // The parenthesis closing the marker, past any numbered copy inside it
+    let mut depth = 0;
+    let close = marker + name[marker..].char_indices().find_map(|(i, c)| match c {
+        '(' => {
+            depth += 1;
+            None
+        }
+        ')' if depth == 0 => Some(i),
+        ')' => {
+            depth -= 1;
+            None
+        }
+        _ => None,
+    })?;
+    let inner = &name[open + 2..close];
+
+    Some(SyncConflict {
+        original: format!("{}{}", &name[..open], &name[close + 1..]),
+        date: find_dashed_date(inner),
+    })
+}
+
+// Syncthing: `foo.sync-conflict-20240102-103000-ABCDEFG.txt`
+fn detect_syncthing(name: &str) -> Option<SyncConflict> {
+    let marker = ".sync-conflict-";
+    let start = name.find(marker)?;
+    let rest = &name[start + marker.len()..];
+    let end = rest.find('.').unwrap_or(rest.len());
+    let date = compact_date(&rest[..end])?;
+
+    Some(SyncConflict {
+        original: format!("{}{}", &name[..start], &rest[end..]),
+        date: Some(date),
+    })
+}
+
+// ownCloud: `foo_conflict-20240102-103000.txt`
+fn detect_owncloud(name: &str) -> Option<SyncConflict> {
+    let marker = "_conflict-";
+    let start = name.find(marker)?;
+    let rest = &name[start + marker.len()..];
+    let end = rest.find('.').unwrap_or(rest.len());
+    let date = compact_date(&rest[..end])?;
+
+    Some(SyncConflict {
+        original: format!("{}{}", &name[..start], &rest[end..]),
+        date: Some(date),
+    })
+}
+
+fn find_dashed_date(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    (0..bytes.len().saturating_sub(9)).find_map(|i| {
+        let candidate = &s.get(i..i + 10)?;
+        let b = candidate.as_bytes();
+        let digits = [0, 1, 2, 3, 5, 6, 8, 9].iter().all(|&j| b[j].is_ascii_digit());
+        (digits && b[4] == b'-' && b[7] == b'-').then(|| candidate.to_string())
+    })
+}
+
+fn compact_date(s: &str) -> Option<String> {
+    let digits = s.get(..8)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..8]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_real_world_conflict_names() {
+        let cases = [
+            // Dropbox
+            ("Budget (conflicted copy 2024-01-02).xlsx", Some(("Budget.xlsx", Some("2024-01-02")))),
+            ("Budget (Jane Doe's conflicted copy 2024-01-02).xlsx", Some(("Budget.xlsx", Some("2024-01-02")))),
+            ("Budget (Jane Doe's conflicted copy 2024-01-02 (1)).xlsx", Some(("Budget.xlsx", Some("2024-01-02")))),
+            ("Q3 plan (v2) (MacBook's conflicted copy 2023-11-30).key", Some(("Q3 plan (v2).key", Some("2023-11-30")))),
+            ("Makefile (conflicted copy 2024-01-02)", Some(("Makefile", Some("2024-01-02")))),
+            // Nextcloud and ownCloud 2.5+ desktop clients
+            ("notes (conflicted copy 2024-03-15 091502).md", Some(("notes.md", Some("2024-03-15")))),
+            ("notes (conflicted copy).md", Some(("notes.md", None))),
+            // Older ownCloud and Nextcloud clients
+            ("notes_conflict-20240315-091502.md", Some(("notes.md", Some("2024-03-15")))),
+            ("archive_conflict-20240315-091502", Some(("archive", Some("2024-03-15")))),
+            // Syncthing
+            ("photo.sync-conflict-20240102-103000-ABCDEFG.jpg", Some(("photo.jpg", Some("2024-01-02")))),
+            ("backup.tar.sync-conflict-20240102-103000-ABCDEFG.gz", Some(("backup.tar.gz", Some("2024-01-02")))),
+            (".bashrc.sync-conflict-20240102-103000-ABCDEFG", Some((".bashrc", Some("2024-01-02")))),
+            // Near misses
+            ("conflicted copy of the contract.pdf", None),
+            ("Holiday (copy).jpg", None),
+            ("Holiday (2).jpg", None),
+            ("merge_conflict-notes.md", None),
+            ("resolve_conflict-2024.md", None),
+            ("notes.sync-conflict-resolution.md", None),
+            ("Sync conflict 2024-01-02.txt", None),
+        ];
+        for (name, expected) in cases {
+            let found = detect(name);
+            let found = found.as_ref().map(|c| (c.original.as_str(), c.date.as_deref()));
+            assert_eq!(found, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn conflicts_get_a_uniform_suffix() {
+        let renamed = |name: &str| detect(name).map(|c| c.renamed());
+        assert_eq!(renamed("Budget (Jane Doe's conflicted copy 2024-01-02).xlsx").as_deref(), Some("Budget_conflict_2024-01-02.xlsx"));
+        assert_eq!(renamed("notes (conflicted copy).md").as_deref(), Some("notes_conflict.md"));
+    }
+}
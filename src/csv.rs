@@ -0,0 +1,105 @@
+/// Quote a field if it contains the delimiter, quotes or line breaks.
+pub fn escape(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn write_row(fields: &[&str], delimiter: char) -> String {
+    let escaped: Vec<String> = fields.iter().map(|f| escape(f, delimiter)).collect();
+    escaped.join(&delimiter.to_string())
+}
+
+/// Parse delimited text into rows, honoring quoted fields that may contain
+/// delimiters, doubled quotes and newlines. Blank lines are dropped.
+pub fn parse(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                line += 1;
+                row.push(std::mem::take(&mut field));
+                if !(row.len() == 1 && row[0].is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!("unterminated quoted field at line {}", line));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|field| field.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_and_doubled_quotes() {
+        let parsed = parse("a,\"b,c\",\"say \"\"hi\"\"\"\n\"\",d\n", ',').unwrap();
+        assert_eq!(parsed, rows(&[&["a", "b,c", "say \"hi\""], &["", "d"]]));
+        assert_eq!(parse("a;\"b;c\"\tx\n", ';').unwrap(), rows(&[&["a", "b;c\tx"]]));
+    }
+
+    #[test]
+    fn quoted_fields_span_lines() {
+        let parsed = parse("\"line 1\nline 2\",x\ny,z", ',').unwrap();
+        assert_eq!(parsed, rows(&[&["line 1\nline 2", "x"], &["y", "z"]]));
+        // Lines inside quotes still count toward the line reported
+        assert_eq!(parse("a\n\"b\nc", ',').unwrap_err(), "unterminated quoted field at line 3");
+    }
+
+    #[test]
+    fn crlf_line_endings_and_blank_lines_are_dropped() {
+        let parsed = parse("a,b\r\n\r\nc,d\r\n", ',').unwrap();
+        assert_eq!(parsed, rows(&[&["a", "b"], &["c", "d"]]));
+        // but a line break inside quotes is kept as written
+        assert_eq!(parse("\"x\r\ny\"\r\n", ',').unwrap(), rows(&[&["x\r\ny"]]));
+    }
+
+    #[test]
+    fn written_rows_parse_back() {
+        let fields = ["plain", "with,comma", "with \"quotes\"", "two\r\nlines", ""];
+        let text = format!("{}\r\n{}\r\n", write_row(&fields, ','), write_row(&["a", "b"], ','));
+        assert_eq!(parse(&text, ',').unwrap(), rows(&[&fields, &["a", "b"]]));
+    }
+}
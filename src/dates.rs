@@ -0,0 +1,202 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+const MONTHS: &[&str] = &[
+    "january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december",
+];
+
+const DEFAULT_FORMAT: &str = "%Y-%m-%d";
+
+/// Rewriting the dates names already carry into one form, so that an
+/// archive of `05-01-2024 minutes`, `Minutes Jan 5 2024` and
+/// `minutes_20240105` ends up dated the same way.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateRules {
+    /// Rewrite the dates found in names, as if --normalize-dates were
+    /// always given
+    pub normalize: bool,
+    /// strftime pattern dates are rewritten to
+    pub format: String,
+    /// Read `05-01-2024` as May 1st rather than 5 January
+    pub month_first: bool,
+}
+
+impl Default for DateRules {
+    fn default() -> Self {
+        DateRules { normalize: false, format: DEFAULT_FORMAT.to_string(), month_first: false }
+    }
+}
+
+impl DateRules {
+    /// Why `format` can't be used, if it can't.
+    pub fn problem(&self) -> Option<String> {
+        let invalid = chrono::format::StrftimeItems::new(&self.format).any(|item| item == chrono::format::Item::Error);
+        invalid.then(|| format!("dates.format \"{}\" is not a valid strftime pattern; using \"{}\"", self.format, DEFAULT_FORMAT))
+    }
+
+    /// `name` with every date in it written in `format`: `05-01-2024`,
+    /// `5.1.2024`, `2024_01_05`, `20240105`, `Jan 5 2024`, `5th January,
+    /// 2024` and the like. Unless `normalize` is set, `name` as it is.
+    pub fn apply(&self, name: &str) -> String {
+        if !self.normalize {
+            return name.to_string();
+        }
+        let chars: Vec<char> = name.chars().collect();
+        let mut out = String::new();
+        let mut at = 0;
+        while at < chars.len() {
+            match self.date_at(&chars, at) {
+                Some((date, len)) => {
+                    out.push_str(&self.write(date));
+                    at += len;
+                }
+                None => {
+                    out.push(chars[at]);
+                    at += 1;
+                }
+            }
+        }
+        out
+    }
+
+    fn write(&self, date: NaiveDate) -> String {
+        use std::fmt::Write as _;
+        let mut text = String::new();
+        if write!(text, "{}", date.format(&self.format)).is_err() {
+            return date.format(DEFAULT_FORMAT).to_string();
+        }
+        text
+    }
+
+    /// The date starting at `at` and how many characters it takes up.
+    fn date_at(&self, chars: &[char], at: usize) -> Option<(NaiveDate, usize)> {
+        let previous = at.checked_sub(1).map(|i| chars[i]);
+        let rest = &chars[at..];
+        if previous.is_some_and(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        // `IMG20240105`, but not the `1.2.2024` of `v1.2.2024` or the
+        // month of `Dismay 5 2024`
+        if previous.is_some_and(char::is_alphabetic) {
+            return compact(rest);
+        }
+        self.numeric(rest).or_else(|| compact(rest)).or_else(|| named(rest))
+    }
+
+    /// `05-01-2024`, `5.1.2024` or `2024_01_05`: three numbers joined by
+    /// the same `-`, `.` or `_`, with the year first or last.
+    fn numeric(&self, chars: &[char]) -> Option<(NaiveDate, usize)> {
+        let a = number(chars, 0)?;
+        let mut len = a.1;
+        let separator = *chars.get(len).filter(|c| matches!(c, '-' | '.' | '_'))?;
+        let b = number(chars, len + 1)?;
+        len += 1 + b.1;
+        if chars.get(len) != Some(&separator) {
+            return None;
+        }
+        let c = number(chars, len + 1)?;
+        len += 1 + c.1;
+        let date = match (a, b, c) {
+            ((year, 4), (month, 1..=2), (day, 1..=2)) => plausible(year, month, day),
+            ((first, 1..=2), (second, 1..=2), (year, 4)) => {
+                let (day, month) = if self.month_first { (second, first) } else { (first, second) };
+                // The other order when this one can't be a date, e.g. 01-31-2024
+                plausible(year, month, day).or_else(|| plausible(year, day, month))
+            }
+            _ => None,
+        }?;
+        Some((date, len))
+    }
+}
+
+/// `20240105`: eight digits on their own.
+fn compact(chars: &[char]) -> Option<(NaiveDate, usize)> {
+    let (digits, 8) = number(chars, 0)? else {
+        return None;
+    };
+    let date = plausible(digits / 10_000, digits / 100 % 100, digits % 100)?;
+    Some((date, 8))
+}
+
+/// `Jan 5 2024`, `January 5th, 2024`, `5 Jan 2024` or `05-Jan-2024`.
+fn named(chars: &[char]) -> Option<(NaiveDate, usize)> {
+    let gap = |at: usize| chars[at.min(chars.len())..].iter().take_while(|c| matches!(c, ' ' | '-' | '_' | '.' | ',')).count();
+    let month_first = month(chars).and_then(|(month, mut len)| {
+        len += gap(len);
+        let (day, day_len) = day(chars, len)?;
+        len += day_len;
+        len += gap(len);
+        Some((month, day, len))
+    });
+    let day_first = || {
+        let (day, mut len) = day(chars, 0)?;
+        len += gap(len);
+        let (month, month_len) = month(&chars[len..])?;
+        len += month_len;
+        len += gap(len);
+        Some((month, day, len))
+    };
+    let (month, day, len) = month_first.or_else(day_first)?;
+    let (year, 4) = number(chars, len)? else {
+        return None;
+    };
+    Some((plausible(year, month, day)?, len + 4))
+}
+
+/// The number at `at` and how many digits it has, up to eight.
+fn number(chars: &[char], at: usize) -> Option<(u32, usize)> {
+    let digits: String = chars[at.min(chars.len())..].iter().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() || digits.len() > 8 {
+        return None;
+    }
+    Some((digits.parse().ok()?, digits.len()))
+}
+
+/// A day of the month at `at`, with its ordinal suffix if it has one
+/// (`5th`), and how many characters it takes up.
+fn day(chars: &[char], at: usize) -> Option<(u32, usize)> {
+    let (day, mut len) = number(chars, at).filter(|&(_, len)| len <= 2)?;
+    let suffix: String = chars[at + len..].iter().take(2).collect::<String>().to_lowercase();
+    if ["st", "nd", "rd", "th"].contains(&suffix.as_str()) && !chars.get(at + len + 2).is_some_and(|c| c.is_alphanumeric()) {
+        len += 2;
+    } else if chars.get(at + len).is_some_and(|c| c.is_alphanumeric()) {
+        return None;
+    }
+    Some((day, len))
+}
+
+/// The month named at the start of `chars`, in full or cut to three or
+/// more letters (`Jan`, `Sept`), and how many letters name it.
+fn month(chars: &[char]) -> Option<(u32, usize)> {
+    let word: String = chars.iter().take_while(|c| c.is_alphabetic()).collect::<String>().to_lowercase();
+    if word.len() < 3 {
+        return None;
+    }
+    let month = MONTHS.iter().position(|month| month.starts_with(&word))?;
+    Some((month as u32 + 1, word.chars().count()))
+}
+
+fn plausible(year: u32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1900..2100).contains(&year).then(|| NaiveDate::from_ymd_opt(year as i32, month, day)).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_in_any_form_are_rewritten_to_one() {
+        let rules = DateRules { normalize: true, format: String::from("%Y_%m_%d"), ..DateRules::default() };
+        assert_eq!(rules.apply("minutes 05-01-2024"), "minutes 2024_01_05");
+        assert_eq!(rules.apply("Minutes Jan 5 2024"), "Minutes 2024_01_05");
+        assert_eq!(rules.apply("5th January, 2024 minutes"), "2024_01_05 minutes");
+        assert_eq!(rules.apply("IMG_20240105_123456"), "IMG_2024_01_05_123456");
+        assert_eq!(rules.apply("report 01-31-2024 v1.2.3"), "report 2024_01_31 v1.2.3");
+        assert_eq!(rules.apply("2024_01_05"), "2024_01_05");
+        // Not dates: a serial number, a month-like word, a year on its own
+        assert_eq!(rules.apply("invoice 123456789 mayor 2024"), "invoice 123456789 mayor 2024");
+        let rules = DateRules { month_first: true, ..rules };
+        assert_eq!(rules.apply("05.01.2024"), "2024_05_01");
+    }
+}
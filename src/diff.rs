@@ -0,0 +1,89 @@
+/// What a rename takes away, and what it adds.
+const REMOVED: &str = "\x1b[31m";
+const ADDED: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Most characters compared pairwise; names differing over a longer
+/// stretch are marked as changed throughout it.
+const MAX_CELLS: usize = 1 << 20;
+
+/// `old` and `new` with the characters only one of them has colored: red
+/// in `old`, green in `new`. The characters kept are a longest common
+/// subsequence of the two.
+pub fn highlight(old: &str, new: &str) -> (String, String) {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    // Most of a path is usually the same on both sides
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (kept_old, kept_new) = common(&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    let paint = |chars: &[char], kept: Vec<bool>, color: &str| {
+        let kept: Vec<bool> = std::iter::repeat_n(true, prefix).chain(kept).chain(std::iter::repeat_n(true, suffix)).collect();
+        let mut out = String::new();
+        for (i, c) in chars.iter().enumerate() {
+            if !kept[i] && (i == 0 || kept[i - 1]) {
+                out.push_str(color);
+            }
+            out.push(*c);
+            if !kept[i] && kept.get(i + 1).is_none_or(|&next| next) {
+                out.push_str(RESET);
+            }
+        }
+        out
+    };
+    (paint(&old, kept_old, REMOVED), paint(&new, kept_new, ADDED))
+}
+
+/// Which characters of `a` and of `b` belong to a longest subsequence
+/// they have in common.
+fn common(a: &[char], b: &[char]) -> (Vec<bool>, Vec<bool>) {
+    let mut kept_a = vec![false; a.len()];
+    let mut kept_b = vec![false; b.len()];
+    if a.len().saturating_mul(b.len()) > MAX_CELLS {
+        return (kept_a, kept_b);
+    }
+    // lengths[i][j]: the longest common subsequence of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            kept_a[i] = true;
+            kept_b[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (kept_a, kept_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_changed_characters_are_colored() {
+        assert_eq!(
+            highlight("dir/My File.txt", "dir/my_file.txt"),
+            (
+                "dir/\x1b[31mM\x1b[0my\x1b[31m F\x1b[0mile.txt".to_string(),
+                "dir/\x1b[32mm\x1b[0my\x1b[32m_f\x1b[0mile.txt".to_string(),
+            )
+        );
+        assert_eq!(highlight("same", "same"), ("same".to_string(), "same".to_string()));
+        assert_eq!(highlight("a", "ab"), ("a".to_string(), "a\x1b[32mb\x1b[0m".to_string()));
+    }
+}
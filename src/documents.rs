@@ -0,0 +1,349 @@
+use crate::glob_match;
+use crate::pdf;
+use crate::template::{Field, Template};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const TOKENS: &[&str] = &["doc.date", "doc.vendor", "doc.keyword", "name", "n"];
+
+const MONTHS: &[&str] = &[
+    "january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december",
+];
+
+/// Name PDFs after what they contain, e.g. `{doc.date}_{doc.vendor}`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentRules {
+    /// Naming scheme; content-derived naming is off while this is unset.
+    /// Leading `../` climbs out of the document's directory first
+    #[serde(default)]
+    pub template: Option<String>,
+
+    /// Which PDFs to rename (globs on the file name)
+    #[serde(default = "default_files")]
+    pub files: Vec<String>,
+
+    /// Known senders, matched case-insensitively; the first line of text is used otherwise
+    #[serde(default)]
+    pub vendors: Vec<String>,
+
+    /// Document kinds to look for, e.g. ["invoice", "receipt"]
+    #[serde(default)]
+    pub keywords: Vec<String>,
+
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+}
+
+impl Default for DocumentRules {
+    fn default() -> Self {
+        DocumentRules {
+            template: None,
+            files: default_files(),
+            vendors: Vec::new(),
+            keywords: Vec::new(),
+            date_format: default_date_format(),
+        }
+    }
+}
+
+fn default_files() -> Vec<String> {
+    vec!["*.pdf".to_string(), "*.PDF".to_string()]
+}
+
+fn default_date_format() -> String {
+    String::from("%Y-%m-%d")
+}
+
+/// What was learned from a document's text.
+#[derive(Debug, Default)]
+pub struct DocumentInfo {
+    pub date: Option<NaiveDate>,
+    pub vendor: Option<String>,
+    pub keyword: Option<String>,
+}
+
+impl DocumentRules {
+    /// The configured template, if any, checked for unknown tokens.
+    pub fn compile(&self) -> Option<Result<Template, String>> {
+        let (_, source) = climb(self.template.as_deref()?);
+        Some(Template::parse(source).and_then(|template| {
+            let unknown = template.tokens().find(|t| !TOKENS.contains(t)).map(str::to_string);
+            match unknown {
+                Some(unknown) => Err(format!("unknown token '{{{}}}' in document template (known: {})", unknown, TOKENS.join(", "))),
+                None => Ok(template),
+            }
+        }))
+    }
+
+    /// Plan content-derived names for matching PDFs. A template may move
+    /// documents into directories (`{doc.date}/{doc.vendor}` with a
+    /// `%Y/%m` date format), also shared ones (`../archive/{doc.date}`).
+    /// Names are made unique per destination directory, counting files
+    /// already there: `{n}` takes the first free number, and without it
+    /// `_2`, `_3`, ... is appended when needed.
+    pub fn plan(&self, files: &[PathBuf]) -> HashMap<PathBuf, String> {
+        let Some(Ok(template)) = self.compile() else {
+            return HashMap::new();
+        };
+        let (up, _) = climb(self.template.as_deref().unwrap_or_default());
+        let base = |file: &Path| {
+            let mut dir = file.parent()?.to_path_buf();
+            (0..up).all(|_| dir.pop()).then_some(dir)
+        };
+        let numbered = template.tokens().any(|t| t == "n");
+
+        let mut documents = Vec::new();
+        for file in files.iter().filter(|f| self.matches(f)) {
+            let Ok(data) = fs::read(file) else {
+                continue;
+            };
+            let info = self.inspect(&pdf::extract_text(&data));
+            if info.date.is_none() && info.vendor.is_none() && info.keyword.is_none() {
+                continue;
+            }
+            documents.push((file, info));
+        }
+        let render = |file: &Path, info: &DocumentInfo, n: u64| {
+            let stem = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let rendered = template.render(|token| match token {
+                "doc.date" => info.date.and_then(|d| format_date(d, &self.date_format)).map(Field::Text),
+                "doc.vendor" => info.vendor.as_deref().map(|v| Field::Text(sanitize(v))),
+                "doc.keyword" => info.keyword.as_deref().map(|k| Field::Text(sanitize(k))),
+                "name" => Some(Field::Text(stem.clone())),
+                "n" => Some(Field::Number(n)),
+                _ => None,
+            });
+            let ext = file.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+            // Only ever below `base`: tokens can't climb or start from the root
+            let below = Path::new(&rendered).components().all(|c| matches!(c, Component::Normal(_)));
+            (!rendered.is_empty() && below).then_some((rendered, ext))
+        };
+
+        // Grouped by where the documents end up, not where they are now
+        let mut by_dest: BTreeMap<PathBuf, Vec<(&PathBuf, &DocumentInfo)>> = BTreeMap::new();
+        for (file, info) in &documents {
+            let (Some((rendered, ext)), Some(base)) = (render(file, info, 1), base(file)) else {
+                continue;
+            };
+            let target = base.join(format!("{}.{}", rendered, ext));
+            by_dest.entry(target.parent().unwrap_or(Path::new(".")).to_path_buf()).or_default().push((file, info));
+        }
+        let moving: HashSet<&Path> = by_dest.values().flatten().map(|(file, _)| file.as_path()).collect();
+
+        let mut renames = HashMap::new();
+        for (dest, documents) in &by_dest {
+            // Names held by files that stay where they are
+            let mut taken: HashSet<String> = fs::read_dir(dest)
+                .map(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .filter(|e| !moving.contains(e.path().as_path()))
+                        .map(|e| e.file_name().to_string_lossy().to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            for (file, info) in documents {
+                let name_for = |n: u64| {
+                    let (rendered, ext) = render(file, info, if numbered { n } else { 1 })?;
+                    let name = Path::new(&rendered).file_name()?.to_string_lossy().to_string();
+                    Some(if numbered || n == 1 { format!("{}.{}", name, ext) } else { format!("{}_{}.{}", name, n, ext) })
+                };
+                let Some(name) = (1..).map_while(name_for).find(|candidate| !taken.contains(candidate)) else {
+                    continue;
+                };
+                taken.insert(name.clone());
+                let target = dest.join(&name);
+                if target != **file {
+                    let relative = target.strip_prefix(file.parent().unwrap_or(Path::new(""))).unwrap_or(&target);
+                    renames.insert(file.to_path_buf(), relative.to_string_lossy().to_string());
+                }
+            }
+        }
+        renames
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name()
+            .map(|n| n.to_string_lossy())
+            .is_some_and(|n| self.files.iter().any(|p| glob_match(p, &n)))
+    }
+
+    pub fn inspect(&self, text: &str) -> DocumentInfo {
+        let lower = text.to_lowercase();
+        let vendor = self
+            .vendors
+            .iter()
+            .find(|v| lower.contains(&v.to_lowercase()))
+            .cloned()
+            .or_else(|| first_line(text));
+        let keyword = self.keywords.iter().find(|k| lower.contains(&k.to_lowercase())).cloned();
+        DocumentInfo { date: find_date(text), vendor, keyword }
+    }
+}
+
+/// How many directories `template` climbs with leading `../`, and the rest.
+fn climb(template: &str) -> (usize, &str) {
+    let mut rest = template;
+    let mut up = 0;
+    while let Some(after) = rest.strip_prefix("../") {
+        rest = after;
+        up += 1;
+    }
+    (up, rest)
+}
+
+/// Format with a user-supplied pattern; a bad pattern yields nothing
+/// instead of panicking.
+fn format_date(date: NaiveDate, format: &str) -> Option<String> {
+    use std::fmt::Write;
+    let mut out = String::new();
+    write!(out, "{}", date.format(format)).ok()?;
+    Some(out)
+}
+
+/// The first line that reads like a name: a letterhead, usually the sender.
+fn first_line(text: &str) -> Option<String> {
+    text.lines()
+        .map(str::trim)
+        .find(|line| line.chars().filter(|c| c.is_alphabetic()).count() >= 3)
+        .map(|line| line.split_whitespace().take(4).collect::<Vec<_>>().join(" "))
+}
+
+/// Keep letters and digits; everything else becomes a single `_`.
+fn sanitize(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            out.push(c);
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    out.trim_end_matches('_').to_string()
+}
+
+/// The first date written in any common form: `2024-01-31`, `31.01.2024`,
+/// `01/31/2024`, `31 January 2024` or `January 31, 2024`.
+pub fn find_date(text: &str) -> Option<NaiveDate> {
+    let words: Vec<&str> = text.split(|c: char| c.is_whitespace() || c == ',').filter(|w| !w.is_empty()).collect();
+
+    for (i, word) in words.iter().enumerate() {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if let Some(date) = numeric_date(word) {
+            return Some(date);
+        }
+        // `31 January 2024` / `January 31 2024`
+        let next = |n: usize| words.get(i + n).map(|w| w.trim_matches(|c: char| !c.is_alphanumeric())).unwrap_or_default();
+        if let Some(month) = month_number(word) {
+            if let (Ok(day), Ok(year)) = (next(1).trim_end_matches(['s', 't', 'n', 'd', 'r', 'h']).parse(), next(2).parse()) {
+                if let Some(date) = plausible(year, month, day) {
+                    return Some(date);
+                }
+            }
+        }
+        if let (Ok(day), Some(month)) = (word.trim_end_matches(['s', 't', 'n', 'd', 'r', 'h']).parse(), month_number(next(1))) {
+            if let Some(date) = next(2).parse().ok().and_then(|year| plausible(year, month, day)) {
+                return Some(date);
+            }
+        }
+    }
+    None
+}
+
+fn numeric_date(word: &str) -> Option<NaiveDate> {
+    let separator = word.chars().find(|c| matches!(c, '-' | '.' | '/'))?;
+    let parts: Vec<&str> = word.split(separator).collect();
+    let [a, b, c] = parts.as_slice() else {
+        return None;
+    };
+    let (a, b, c): (i32, u32, u32) = (a.parse().ok()?, b.parse().ok()?, c.parse().ok()?);
+    if a >= 1000 {
+        return plausible(a, b, c);
+    }
+    let year = c as i32;
+    match separator {
+        // US style unless the first number can't be a month
+        '/' if a <= 12 => plausible(year, a as u32, b),
+        _ => plausible(year, b, a as u32),
+    }
+}
+
+fn month_number(word: &str) -> Option<u32> {
+    let lower = word.to_lowercase();
+    if lower.len() < 3 {
+        return None;
+    }
+    MONTHS.iter().position(|m| m.starts_with(&lower)).map(|i| i as u32 + 1)
+}
+
+fn plausible(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    let date = NaiveDate::from_ymd_opt(year, month, day)?;
+    (1990..2100).contains(&year).then_some(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::normalize;
+
+    /// A PDF whose text layer is `text`.
+    fn document(path: &Path, text: &str) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("%PDF-1.7\n1 0 obj\n<< >>\nstream\nBT ({}) Tj ET\nendstream\nendobj\n", text)).unwrap();
+        path.to_path_buf()
+    }
+
+    fn rules(template: &str) -> DocumentRules {
+        DocumentRules {
+            template: Some(template.to_string()),
+            vendors: vec![String::from("ACME")],
+            date_format: String::from("%Y/%m"),
+            ..DocumentRules::default()
+        }
+    }
+
+    /// Where each document ends up, from the directory it came from.
+    fn destinations(renames: &HashMap<PathBuf, String>, files: &[PathBuf]) -> Vec<PathBuf> {
+        files.iter().map(|file| normalize(&file.with_file_name(&renames[file]))).collect()
+    }
+
+    #[test]
+    fn numbers_continue_after_files_already_filed() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        document(&root.join("archive/2024/01/ACME_1.pdf"), "ACME Invoice 2024-01-03");
+        let files = [
+            document(&root.join("inbox/scan.pdf"), "ACME Invoice 2024-01-15"),
+            document(&root.join("mail/attachment.pdf"), "ACME Invoice 2024-01-20"),
+        ];
+        let renames = rules("../archive/{doc.date}/{doc.vendor}_{n}").plan(&files);
+        assert_eq!(destinations(&renames, &files), [
+            root.join("archive/2024/01/ACME_2.pdf"),
+            root.join("archive/2024/01/ACME_3.pdf"),
+        ]);
+    }
+
+    #[test]
+    fn documents_from_different_places_never_share_a_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let files = [
+            document(&root.join("inbox/scan.pdf"), "ACME Invoice 2024-01-15"),
+            document(&root.join("mail/attachment.pdf"), "ACME Invoice 2024-01-15"),
+        ];
+        let renames = rules("../archive/{doc.date}/{doc.vendor}").plan(&files);
+        assert_eq!(destinations(&renames, &files), [
+            root.join("archive/2024/01/ACME.pdf"),
+            root.join("archive/2024/01/ACME_2.pdf"),
+        ]);
+
+        // Only the template itself climbs; a date can't lead out of the tree
+        let escaping = DocumentRules { date_format: String::from("../../%Y"), ..rules("{doc.date}/{doc.vendor}") };
+        assert!(escaping.plan(&files).is_empty());
+    }
+}
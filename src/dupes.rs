@@ -0,0 +1,147 @@
+use crate::hash::sha256_file;
+use crate::name::split_extension;
+use crate::plan::Rename;
+use crate::warn;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What to do with files whose content another file of the run has.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    /// Don't compare contents
+    #[default]
+    Off,
+    /// Warn about every set of files with the same content
+    Report,
+    /// Leave all but the first file of each set alone
+    Skip,
+    /// Add _dup1, _dup2, ... to the names of all but the first file of each set
+    Suffix,
+}
+
+/// Sets of `files` with the same content, each in path order; the first
+/// file of a set is taken as the original. Only files of the same size
+/// are hashed, on rayon's current pool, and empty files are never counted
+/// as duplicates.
+pub fn find(files: &[PathBuf]) -> Vec<Vec<PathBuf>> {
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for file in files {
+        if let Ok(metadata) = fs::metadata(file) {
+            if metadata.is_file() && metadata.len() > 0 {
+                by_size.entry(metadata.len()).or_default().push(file);
+            }
+        }
+    }
+    let same_size: Vec<&PathBuf> = by_size.into_values().filter(|files| files.len() > 1).flatten().collect();
+    let digests: Vec<Option<String>> = same_size.par_iter().map(|file| sha256_file(file).ok()).collect();
+    let mut by_content: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for (file, digest) in same_size.into_iter().zip(digests) {
+        if let Some(digest) = digest {
+            by_content.entry(digest).or_default().push(file.clone());
+        }
+    }
+    let mut sets: Vec<Vec<PathBuf>> = by_content.into_values().filter(|files| files.len() > 1).collect();
+    for set in &mut sets {
+        set.sort();
+    }
+    sets.sort();
+    sets
+}
+
+/// Apply `policy` to the planned `renames` for the duplicate `sets`.
+/// Renames left out are added to `skipped`.
+pub fn apply(policy: DuplicatePolicy, sets: &[Vec<PathBuf>], mut renames: Vec<Rename>, skipped: &mut Vec<(PathBuf, String)>) -> Vec<Rename> {
+    match policy {
+        DuplicatePolicy::Off => {}
+        DuplicatePolicy::Report => {
+            for set in sets {
+                let files: Vec<String> = set.iter().map(|file| file.display().to_string()).collect();
+                warn!("Same content: {}", files.join(", "));
+            }
+        }
+        DuplicatePolicy::Skip => {
+            let copies: HashMap<&Path, &Path> = copies(sets).map(|(_, copy, original)| (copy, original)).collect();
+            renames.retain(|rename| {
+                let copy = copies.contains_key(rename.source.as_path());
+                if copy {
+                    skipped.push((rename.source.clone(), String::from("duplicate content")));
+                }
+                !copy
+            });
+        }
+        DuplicatePolicy::Suffix => {
+            for (n, copy, original) in copies(sets) {
+                let original = original.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                let planned = renames.iter().position(|rename| rename.source == copy);
+                let target = planned.map_or(copy, |i| renames[i].target.as_path());
+                let Some(name) = target.file_name().map(|name| name.to_string_lossy()) else {
+                    continue;
+                };
+                let (stem, extension) = split_extension(&name);
+                let target = target.with_file_name(format!("{}_dup{}{}", without_suffix(stem), n, extension));
+                match planned {
+                    Some(i) => {
+                        let rename = &mut renames[i];
+                        rename.target = target;
+                        rename.rule = format!("{} (duplicate of {})", rename.rule, original);
+                    }
+                    None if target != copy => renames.push(Rename {
+                        source: copy.to_path_buf(),
+                        target,
+                        rule: format!("duplicate of {}", original),
+                    }),
+                    None => {}
+                }
+            }
+        }
+    }
+    renames
+}
+
+/// (number, copy, original) for all but the first file of each set,
+/// numbered from 1 within it.
+fn copies(sets: &[Vec<PathBuf>]) -> impl Iterator<Item = (usize, &Path, &Path)> {
+    sets.iter().flat_map(|set| set.iter().skip(1).enumerate().map(|(i, copy)| (i + 1, copy.as_path(), set[0].as_path())))
+}
+
+/// `stem` without the _dupN an earlier run gave it, so that runs agree.
+fn without_suffix(stem: &str) -> &str {
+    match stem.rsplit_once("_dup") {
+        Some((base, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => stem,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_are_found_and_skipped_or_numbered() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        for (name, content) in [("a.jpg", "photo"), ("b copy.jpg", "photo"), ("c.jpg", "photo"), ("d.jpg", "other"), ("e.txt", ""), ("f.txt", "")] {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        let files: Vec<PathBuf> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        let sets = find(&files);
+        assert_eq!(sets, [vec![dir.join("a.jpg"), dir.join("b copy.jpg"), dir.join("c.jpg")]]);
+
+        let planned = || vec![Rename { source: dir.join("b copy.jpg"), target: dir.join("b_copy.jpg"), rule: String::from("replace_spaces") }];
+        let mut skipped = Vec::new();
+        assert!(apply(DuplicatePolicy::Skip, &sets, planned(), &mut skipped).is_empty());
+        assert_eq!(skipped, [(dir.join("b copy.jpg"), String::from("duplicate content"))]);
+
+        let renames: Vec<_> = apply(DuplicatePolicy::Suffix, &sets, planned(), &mut Vec::new()).into_iter().map(|r| (r.target, r.rule)).collect();
+        assert_eq!(renames, [
+            (dir.join("b_copy_dup1.jpg"), String::from("replace_spaces (duplicate of a.jpg)")),
+            (dir.join("c_dup2.jpg"), String::from("duplicate of a.jpg")),
+        ]);
+        // A name numbered by an earlier run is numbered again, not twice
+        assert_eq!((without_suffix("c_dup2"), without_suffix("c_dup"), without_suffix("c_duplex")), ("c", "c_dup", "c_duplex"));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::name::{Platform, SanitizedName};
+use crate::plan::Rename;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const EDITING_HELP: &str = "\
+# Renames namefmt is about to perform, one per line: number, current name,
+# new name. Change a new name to choose another one, or delete a line to
+# leave that file alone. Save and quit to go ahead; delete every line to
+# rename nothing.
+";
+
+/// Open `path` in $VISUAL or $EDITOR (vi when neither is set) and wait
+/// for the editor to exit.
+pub fn open_editor(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| String::from("vi"));
+    // Editors are often given with arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or("$EDITOR is empty")?;
+    let status = std::process::Command::new(program).args(words).arg(path).status()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", program, status).into());
+    }
+    Ok(())
+}
+
+/// Let the planned renames be edited in a text editor, vidir-style, and
+/// return the ones kept, under the names saved. Nothing is returned for
+/// lines deleted or set back to the current name.
+pub fn review(renames: &[Rename], platform: Platform) -> Result<Vec<(PathBuf, PathBuf)>, Box<dyn std::error::Error>> {
+    if renames.is_empty() {
+        return Ok(Vec::new());
+    }
+    let file = std::env::temp_dir().join(format!("namefmt-edit-{}.txt", std::process::id()));
+    fs::write(&file, render(renames))?;
+    let edited = open_editor(&file).and_then(|()| Ok(fs::read_to_string(&file)?));
+    let _ = fs::remove_file(&file);
+    parse(&edited?, renames, platform).map_err(|problems| {
+        let listed: String = problems.iter().map(|problem| format!("\n    {}", problem)).collect();
+        format!("{} line(s) of the edited renames can't be used, nothing was renamed:{}", problems.len(), listed).into()
+    })
+}
+
+/// The file the editor is opened on: the help, then `N<TAB>source -> target`
+/// for each rename.
+fn render(renames: &[Rename]) -> String {
+    let mut text = EDITING_HELP.to_string();
+    for (i, rename) in renames.iter().enumerate() {
+        text.push_str(&format!("{}\t{} -> {}\n", i + 1, rename.source.display(), rename.target.display()));
+    }
+    text
+}
+
+/// The renames in the edited `text`. Lines are matched to `renames` by
+/// number, so only the new name is read from each; the current name must
+/// be left as it was. Returns every line that can't be used otherwise.
+fn parse(text: &str, renames: &[Rename], platform: Platform) -> Result<Vec<(PathBuf, PathBuf)>, Vec<String>> {
+    let mut pairs = Vec::new();
+    let mut seen = HashSet::new();
+    let mut problems = Vec::new();
+    for (n, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim_end_matches('\r'))) {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let rename = line.split_once('\t')
+            .and_then(|(number, rest)| Some((number.trim().parse::<usize>().ok()?, rest)))
+            .and_then(|(number, rest)| Some((number, renames.get(number.checked_sub(1)?)?, rest)));
+        let Some((number, rename, rest)) = rename else {
+            problems.push(format!("line {}: doesn't start with the number of a rename", n));
+            continue;
+        };
+        if !seen.insert(number) {
+            problems.push(format!("line {}: rename {} is listed twice", n, number));
+            continue;
+        }
+        let source = format!("{} -> ", rename.source.display());
+        let Some(target) = rest.strip_prefix(&source) else {
+            problems.push(format!("line {}: the current name of {} was changed; only edit the new name", n, rename.source.display()));
+            continue;
+        };
+        // A name kept as planned is taken as it was, even when it isn't
+        // valid UTF-8 and so can't be written out exactly
+        let target = if target == rename.target.display().to_string() { rename.target.clone() } else { PathBuf::from(target) };
+        match target.file_name().map(|name| name.to_string_lossy()) {
+            None => problems.push(format!("line {}: {:?} has no file name", n, target)),
+            Some(name) if SanitizedName::new(&name, platform).as_str() != name => {
+                problems.push(format!("line {}: \"{}\" is not a valid name (\"{}\" would be)", n, name, SanitizedName::new(&name, platform)));
+            }
+            Some(_) if target == rename.source => {}
+            Some(_) => pairs.push((rename.source.clone(), target)),
+        }
+    }
+    if problems.is_empty() { Ok(pairs) } else { Err(problems) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_lines_are_skipped_and_edited_names_used() {
+        let rename = |source: &str, target: &str| Rename { source: source.into(), target: target.into(), rule: "style".to_string() };
+        let renames = [rename("d/A B.txt", "d/a_b.txt"), rename("d/C.txt", "d/c.txt"), rename("d/E.txt", "d/e.txt")];
+        let text = render(&renames);
+        assert!(text.ends_with("3\td/E.txt -> d/e.txt\n"), "{}", text);
+        let edited = text.replace("d/a_b.txt", "d/ab.txt").replace("2\td/C.txt -> d/c.txt\n", "");
+        assert_eq!(
+            parse(&edited, &renames, Platform::Posix),
+            Ok(vec![("d/A B.txt".into(), "d/ab.txt".into()), ("d/E.txt".into(), "d/e.txt".into())])
+        );
+        let broken = text.replace("d/C.txt ->", "d/X.txt ->");
+        assert_eq!(parse(&broken, &renames, Platform::Posix).unwrap_err().len(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::template::{Field, Template};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const TOKENS: &[&str] = &["show", "season", "episode", "title"];
+
+/// Release-name noise that ends an episode title.
+const RELEASE_TAGS: &[&str] = &[
+    "480p", "576p", "720p", "1080p", "2160p", "4k", "x264", "x265", "h264", "h265", "hevc", "avc", "web", "webrip",
+    "web-dl", "webdl", "bluray", "bdrip", "brrip", "hdtv", "dvdrip", "hdrip", "proper", "repack", "internal", "aac",
+    "ac3", "dts", "ddp5", "10bit", "hdr",
+];
+
+/// Rewrite recognised TV episode names (`Show.S01E02.Title`, `show 1x02`)
+/// into one scheme, e.g. `{show} - S{season:02}E{episode:02} - {title}`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EpisodeRules {
+    /// Naming scheme; episode renaming is off while this is unset
+    #[serde(default)]
+    pub template: Option<String>,
+
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl Default for EpisodeRules {
+    fn default() -> Self {
+        EpisodeRules {
+            template: None,
+            extensions: default_extensions(),
+        }
+    }
+}
+
+fn default_extensions() -> Vec<String> {
+    ["mkv", "mp4", "m4v", "avi", "mov", "webm", "wmv", "ts"].iter().map(|s| s.to_string()).collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Episode {
+    pub show: String,
+    pub season: u64,
+    pub episode: u64,
+    pub title: String,
+}
+
+impl EpisodeRules {
+    /// The configured template, if any, checked for unknown tokens.
+    pub fn compile(&self) -> Option<Result<Template, String>> {
+        let source = self.template.as_deref()?;
+        Some(Template::parse(source).and_then(|template| {
+            let unknown = template.tokens().find(|t| !TOKENS.contains(t)).map(str::to_string);
+            match unknown {
+                Some(unknown) => Err(format!("unknown token '{{{}}}' in episode template (known: {})", unknown, TOKENS.join(", "))),
+                None => Ok(template),
+            }
+        }))
+    }
+
+    /// New file name for an episode file, if the name is recognised.
+    pub fn rename(&self, path: &Path) -> Option<String> {
+        let template = self.compile()?.ok()?;
+        let ext = path.extension()?.to_string_lossy();
+        if !self.extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext)) {
+            return None;
+        }
+        let episode = parse(&path.file_stem()?.to_string_lossy())?;
+
+        let stem = template.render(|token| match token {
+            "show" => Some(Field::Text(episode.show.clone())),
+            "season" => Some(Field::Number(episode.season)),
+            "episode" => Some(Field::Number(episode.episode)),
+            "title" => Some(Field::Text(episode.title.clone())),
+            _ => None,
+        });
+        Some(format!("{}.{}", stem, ext))
+    }
+}
+
+/// Recognise `S01E02` (also `s1e2`) or `1x02` in a file stem.
+pub fn parse(stem: &str) -> Option<Episode> {
+    let chars: Vec<char> = stem.chars().collect();
+
+    for start in 0..chars.len() {
+        if start > 0 && chars[start - 1].is_alphanumeric() {
+            continue;
+        }
+        let Some((season, episode, end)) = season_episode(&chars, start).or_else(|| cross_form(&chars, start)) else {
+            continue;
+        };
+        if chars.get(end).is_some_and(|c| c.is_alphanumeric()) {
+            continue;
+        }
+        let show = clean(&chars[..start].iter().collect::<String>());
+        let title = clean(&strip_release_tags(&chars[end..].iter().collect::<String>()));
+        return Some(Episode { show, season, episode, title });
+    }
+    None
+}
+
+/// `S01E02` at `start`; returns season, episode and the index after it.
+fn season_episode(chars: &[char], start: usize) -> Option<(u64, u64, usize)> {
+    if !chars.get(start)?.eq_ignore_ascii_case(&'s') {
+        return None;
+    }
+    let (season, after_season) = number(chars, start + 1, 2)?;
+    if !chars.get(after_season)?.eq_ignore_ascii_case(&'e') {
+        return None;
+    }
+    let (episode, mut end) = number(chars, after_season + 1, 3)?;
+    // Double episodes (`S01E02E03`) are named after the first one
+    while chars.get(end).is_some_and(|c| c.eq_ignore_ascii_case(&'e')) {
+        let Some((_, next)) = number(chars, end + 1, 3) else { break };
+        end = next;
+    }
+    Some((season, episode, end))
+}
+
+/// `1x02` at `start`.
+fn cross_form(chars: &[char], start: usize) -> Option<(u64, u64, usize)> {
+    let (season, after_season) = number(chars, start, 2)?;
+    if !chars.get(after_season)?.eq_ignore_ascii_case(&'x') {
+        return None;
+    }
+    let (episode, end) = number(chars, after_season + 1, 3)?;
+    (end - after_season > 2).then_some((season, episode, end))
+}
+
+/// Parse 1..=`max` digits at `start`.
+fn number(chars: &[char], start: usize, max: usize) -> Option<(u64, usize)> {
+    let digits: String = chars.iter().skip(start).take_while(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() || digits.len() > max {
+        return None;
+    }
+    Some((digits.parse().ok()?, start + digits.len()))
+}
+
+/// Drop everything from the first release tag or bracket on.
+fn strip_release_tags(text: &str) -> String {
+    let mut kept = Vec::new();
+    for word in text.split(['.', '_', ' ']) {
+        let lower = word.to_lowercase();
+        if word.starts_with(['[', '(']) || RELEASE_TAGS.contains(&lower.as_str()) {
+            break;
+        }
+        kept.push(word);
+    }
+    kept.join(" ")
+}
+
+/// Turn dotted/underscored release names into words.
+fn clean(text: &str) -> String {
+    let spaced = text.replace(['.', '_'], " ");
+    let words: Vec<&str> = spaced.split_whitespace().collect();
+    words.join(" ").trim_matches(|c: char| c == '-' || c.is_whitespace()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn episodes_are_recognised_and_renamed_to_the_template() {
+        assert_eq!(parse("The.Office.S02E05.Halloween.720p.WEB-DL"), Some(Episode {
+            show: String::from("The Office"),
+            season: 2,
+            episode: 5,
+            title: String::from("Halloween"),
+        }));
+        assert_eq!(parse("show 1x02").map(|e| (e.season, e.episode)), Some((1, 2)));
+        assert_eq!(parse("Mass Effect 2 Soundtrack"), None);
+        assert_eq!(parse("lens4x02"), None);
+
+        let rules = EpisodeRules { template: Some(String::from("{show} - S{season:02}E{episode:02} - {title}")), ..EpisodeRules::default() };
+        let rename = |name: &str| rules.rename(Path::new(name));
+        assert_eq!(rename("the.office.s2e5e6.Halloween.mkv").as_deref(), Some("the office - S02E05 - Halloween.mkv"));
+        assert_eq!(rename("The.Office.S02E05.srt"), None);
+
+        let unknown = EpisodeRules { template: Some(String::from("{show} {year}")), ..EpisodeRules::default() };
+        assert_eq!(unknown.compile().unwrap().unwrap_err(), "unknown token '{year}' in episode template (known: show, season, episode, title)");
+    }
+}
@@ -0,0 +1,198 @@
+use chrono::NaiveDateTime;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// How much of a file is read looking for EXIF data. JPEG, PNG and WebP
+/// keep it near the start; HEIC and raw files usually do too.
+const READ_LIMIT: u64 = 4 << 20;
+
+const EXIF_IFD: u16 = 0x8769;
+const DATE_TIME: u16 = 0x0132;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const DATE_TIME_DIGITIZED: u16 = 0x9004;
+
+/// When the photo at `path` was taken, from its EXIF `DateTimeOriginal`
+/// (else `DateTimeDigitized`, else `DateTime`), in the camera's local time.
+pub fn capture_time(path: &Path) -> Option<NaiveDateTime> {
+    let mut data = Vec::new();
+    File::open(path).ok()?.take(READ_LIMIT).read_to_end(&mut data).ok()?;
+    date_time_original(&data)
+}
+
+/// The capture time in the EXIF data of a JPEG, PNG, WebP or TIFF-based
+/// (TIFF, DNG, most raw formats) file. Other containers, such as HEIC,
+/// are searched for an EXIF block.
+pub fn date_time_original(data: &[u8]) -> Option<NaiveDateTime> {
+    let tiff = if data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_exif(data)?
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_exif(data)?
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        webp_exif(data)?
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        data
+    } else {
+        let start = data.windows(8).position(|w| w.starts_with(b"Exif\0\0") && (&w[6..] == b"II" || &w[6..] == b"MM"))?;
+        &data[start + 6..]
+    };
+    Tiff::new(tiff)?.capture_time()
+}
+
+/// The APP1 segment holding EXIF data, without its `Exif\0\0` header.
+fn jpeg_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut at = 2;
+    while data.get(at) == Some(&0xFF) {
+        let marker = *data.get(at + 1)?;
+        // Start of scan: the image data follows, no more metadata
+        if marker == 0xDA {
+            return None;
+        }
+        let length = u16::from_be_bytes([*data.get(at + 2)?, *data.get(at + 3)?]) as usize;
+        let segment = data.get(at + 4..at + 2 + length)?;
+        if marker == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return Some(tiff);
+            }
+        }
+        at += 2 + length;
+    }
+    None
+}
+
+fn png_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut at = 8;
+    while at + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[at..at + 4].try_into().ok()?) as usize;
+        let kind = &data[at + 4..at + 8];
+        let body = data.get(at + 8..at + 8 + length)?;
+        if kind == b"eXIf" {
+            return Some(body);
+        }
+        if kind == b"IDAT" || kind == b"IEND" {
+            return None;
+        }
+        // Length, type, data and CRC
+        at += 12 + length;
+    }
+    None
+}
+
+fn webp_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let length = u32::from_le_bytes(data[at + 4..at + 8].try_into().ok()?) as usize;
+        let body = data.get(at + 8..at + 8 + length)?;
+        if &data[at..at + 4] == b"EXIF" {
+            return Some(body.strip_prefix(b"Exif\0\0").unwrap_or(body));
+        }
+        // Chunks are padded to an even length
+        at += 8 + length + length % 2;
+    }
+    None
+}
+
+/// A TIFF structure: the byte order, then directories of tagged values.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Tiff<'a>> {
+        let big_endian = match data.get(..2)? {
+            b"MM" => true,
+            b"II" => false,
+            _ => return None,
+        };
+        let tiff = Tiff { data, big_endian };
+        (tiff.u16(2)? == 42).then_some(tiff)
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    /// The entry for `tag` in the directory at `ifd`: where its value is
+    /// (inline for values of up to four bytes) and how many values it has.
+    fn find(&self, ifd: usize, tag: u16) -> Option<(usize, usize)> {
+        let count = self.u16(ifd)? as usize;
+        (0..count).map(|i| ifd + 2 + i * 12).find(|&entry| self.u16(entry) == Some(tag)).and_then(|entry| {
+            let kind = self.u16(entry + 2)?;
+            let values = self.u32(entry + 4)? as usize;
+            let size = match kind {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 => 4,
+                _ => 8,
+            };
+            let at = if size * values <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+            Some((at, values))
+        })
+    }
+
+    fn date(&self, ifd: usize, tag: u16) -> Option<NaiveDateTime> {
+        let (at, length) = self.find(ifd, tag)?;
+        let text = std::str::from_utf8(self.data.get(at..at + length)?).ok()?;
+        NaiveDateTime::parse_from_str(text.trim_end_matches('\0').trim(), "%Y:%m:%d %H:%M:%S").ok()
+    }
+
+    fn capture_time(&self) -> Option<NaiveDateTime> {
+        let ifd0 = self.u32(4)? as usize;
+        let exif = self.find(ifd0, EXIF_IFD).and_then(|(at, _)| self.u32(at)).map(|at| at as usize);
+        exif.and_then(|exif| self.date(exif, DATE_TIME_ORIGINAL).or_else(|| self.date(exif, DATE_TIME_DIGITIZED)))
+            .or_else(|| self.date(ifd0, DATE_TIME))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A little-endian TIFF block: IFD0 with only a pointer to an Exif IFD
+    /// holding DateTimeOriginal.
+    fn tiff(date: &str) -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend(8u32.to_le_bytes());
+        // IFD0 at 8: one entry, then the next-IFD offset
+        data.extend(1u16.to_le_bytes());
+        data.extend(EXIF_IFD.to_le_bytes());
+        data.extend(4u16.to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.extend(26u32.to_le_bytes());
+        data.extend(0u32.to_le_bytes());
+        // Exif IFD at 26, its string at 44
+        data.extend(1u16.to_le_bytes());
+        data.extend(DATE_TIME_ORIGINAL.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend((date.len() as u32 + 1).to_le_bytes());
+        data.extend(44u32.to_le_bytes());
+        data.extend(0u32.to_le_bytes());
+        data.extend(date.as_bytes());
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn reads_date_time_original_from_jpeg_and_tiff() {
+        let expected = NaiveDateTime::parse_from_str("2023-07-14 18:32:05", "%Y-%m-%d %H:%M:%S").ok();
+        let block = tiff("2023:07:14 18:32:05");
+        assert_eq!(date_time_original(&block), expected);
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend((block.len() as u16 + 8).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(&block);
+        jpeg.extend([0xFF, 0xDA, 0, 2]);
+        assert_eq!(date_time_original(&jpeg), expected);
+
+        assert_eq!(date_time_original(&tiff("0000:00:00 00:00:00")), None);
+        assert_eq!(date_time_original(b"\xFF\xD8\xFF\xDA\0\x02"), None);
+    }
+}
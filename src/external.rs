@@ -0,0 +1,43 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run `command` with `name` on its stdin, followed by a newline, and
+/// return the first line it writes to stdout: the new name.
+pub fn transform(command: &str, name: &str) -> Result<String, String> {
+    // Commands are often given with arguments, e.g. "sed s/draft/final/"
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("command is empty")?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    // A command that exits without reading its input has still answered
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "{}", name);
+    }
+    let output = child.wait_with_output().map_err(|e| format!("{} failed: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{} exited with {}: {}", program, output.status, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let stdout = String::from_utf8(output.stdout).map_err(|_| format!("{} wrote a name that isn't UTF-8", program))?;
+    match stdout.lines().next().map(str::trim) {
+        Some(new) if !new.is_empty() => Ok(new.to_string()),
+        _ => Err(format!("{} wrote no name", program)),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_rewrite_names_from_stdin() {
+        assert_eq!(transform("tr a-z A-Z", "notes.txt"), Ok(String::from("NOTES.TXT")));
+        assert_eq!(transform("sed s/draft/final/", "report draft.pdf"), Ok(String::from("report final.pdf")));
+        assert!(transform("false", "notes.txt").is_err());
+        assert!(transform("true", "notes.txt").unwrap_err().contains("no name"));
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// How many directories the confirmation prompt lists.
+const LISTED_DIRS: usize = 10;
+
+/// Checks that keep an --inplace run from sweeping across a whole disk or
+/// home directory by accident.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GuardRules {
+    /// Directories, besides `/` and the home directory, that --inplace
+    /// refuses to run on without --allow-root. A leading `~/` is expanded.
+    #[serde(default)]
+    pub protected_roots: Vec<PathBuf>,
+
+    /// Ask before renaming files in more than this many top-level directories
+    #[serde(default = "default_confirm_top_level_dirs")]
+    pub confirm_top_level_dirs: usize,
+
+    /// Set from --yes; the confirmation is taken as given
+    #[serde(skip)]
+    pub confirmed: bool,
+}
+
+impl Default for GuardRules {
+    fn default() -> Self {
+        GuardRules {
+            protected_roots: Vec::new(),
+            confirm_top_level_dirs: default_confirm_top_level_dirs(),
+            confirmed: false,
+        }
+    }
+}
+
+fn default_confirm_top_level_dirs() -> usize {
+    10
+}
+
+impl GuardRules {
+    /// Refuse `path` if it is the filesystem root, the home directory or a
+    /// configured protected root, unless `allow_root` is set.
+    pub fn check_root(&self, path: &Path, allow_root: bool) -> Result<(), Box<dyn std::error::Error>> {
+        if allow_root {
+            return Ok(());
+        }
+        // A path that can't be resolved doesn't exist, which is reported later
+        let Ok(path) = path.canonicalize() else {
+            return Ok(());
+        };
+        let home = dirs::home_dir().and_then(|home| home.canonicalize().ok());
+        let what = if path.parent().is_none() {
+            "the filesystem root"
+        } else if home.as_deref() == Some(path.as_path()) {
+            "your home directory"
+        } else if self.protected_roots.iter().any(|root| expand_home(root).canonicalize().is_ok_and(|root| root == path)) {
+            "a protected root (guardrails.protected_roots)"
+        } else {
+            return Ok(());
+        };
+        Err(format!("Refusing to rename files in place under {}: it is {}. Pass --allow-root if that is really what you want.", path.display(), what).into())
+    }
+
+    /// Ask before going ahead with renames spread over more top-level
+    /// directories of `root` than configured. Without a terminal to ask on,
+    /// only --yes lets the run continue.
+    pub fn confirm_spread<'a>(&self, root: &Path, sources: impl Iterator<Item = &'a Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let dirs = top_level_dirs(root, sources);
+        if self.confirmed || dirs.len() <= self.confirm_top_level_dirs {
+            return Ok(());
+        }
+        let stdin = io::stdin();
+        if !stdin.is_terminal() {
+            return Err(format!(
+                "This run would rename files in {} top-level directories of {} (more than {}). Nothing was renamed; pass --yes to confirm.",
+                dirs.len(),
+                root.display(),
+                self.confirm_top_level_dirs
+            )
+            .into());
+        }
+        if ask(&mut stdin.lock(), &mut io::stderr(), root, &dirs)? {
+            Ok(())
+        } else {
+            Err("Not confirmed; nothing was renamed".into())
+        }
+    }
+
+    /// The warning a dry run prints where an --inplace run would ask.
+    pub fn spread_warning<'a>(&self, root: &Path, sources: impl Iterator<Item = &'a Path>) -> Option<String> {
+        let dirs = top_level_dirs(root, sources);
+        (!self.confirmed && dirs.len() > self.confirm_top_level_dirs).then(|| {
+            format!(
+                "Renames span {} top-level directories (more than {}); an --inplace run would ask for confirmation",
+                dirs.len(),
+                self.confirm_top_level_dirs
+            )
+        })
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The distinct first directories under `root` that `sources` lie in.
+/// Files directly in `root` don't count.
+fn top_level_dirs<'a>(root: &Path, sources: impl Iterator<Item = &'a Path>) -> BTreeSet<PathBuf> {
+    sources
+        .filter_map(|source| {
+            let relative = source.strip_prefix(root).ok()?;
+            let mut components = relative.components();
+            let first = components.next()?;
+            // At least one more component: the file itself
+            components.next()?;
+            matches!(first, Component::Normal(_)).then(|| root.join(first))
+        })
+        .collect()
+}
+
+fn ask(input: &mut impl BufRead, out: &mut impl Write, root: &Path, dirs: &BTreeSet<PathBuf>) -> io::Result<bool> {
+    writeln!(out, "This run would rename files in {} top-level directories of {}:", dirs.len(), root.display())?;
+    for dir in dirs.iter().take(LISTED_DIRS) {
+        writeln!(out, "  {}", dir.strip_prefix(root).unwrap_or(dir).display())?;
+    }
+    if dirs.len() > LISTED_DIRS {
+        writeln!(out, "  ... and {} more", dirs.len() - LISTED_DIRS)?;
+    }
+    write!(out, "Continue? [y/N]: ")?;
+    out.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roots_and_protected_directories_are_refused() {
+        let scratch = tempfile::tempdir().unwrap();
+        let (photos, inside) = (scratch.path().join("photos"), scratch.path().join("photos/2024"));
+        std::fs::create_dir_all(&inside).unwrap();
+        let rules = GuardRules { protected_roots: vec![photos.clone()], ..GuardRules::default() };
+
+        let error = rules.check_root(Path::new("/"), false).unwrap_err().to_string();
+        assert!(error.contains("it is the filesystem root. Pass --allow-root"), "{}", error);
+        let error = rules.check_root(&photos.join("2024/.."), false).unwrap_err().to_string();
+        assert!(error.ends_with("it is a protected root (guardrails.protected_roots). Pass --allow-root if that is really what you want."), "{}", error);
+        assert!(rules.check_root(&inside, false).is_ok());
+        assert!(rules.check_root(&photos, true).is_ok());
+    }
+
+    #[test]
+    fn renames_spread_wide_need_confirming() {
+        let root = Path::new("/data");
+        let sources: Vec<PathBuf> = ["a/1.txt", "b/c/2.txt", "c/3.txt", "top.txt"].iter().map(|s| root.join(s)).collect();
+        let rules = GuardRules { confirm_top_level_dirs: 2, ..GuardRules::default() };
+        let warning = rules.spread_warning(root, sources.iter().map(PathBuf::as_path));
+        assert_eq!(warning.as_deref(), Some("Renames span 3 top-level directories (more than 2); an --inplace run would ask for confirmation"));
+        assert!(rules.spread_warning(root, sources[..2].iter().map(PathBuf::as_path)).is_none());
+
+        let dirs = top_level_dirs(root, sources.iter().map(PathBuf::as_path));
+        let mut out = Vec::new();
+        assert!(ask(&mut io::Cursor::new("yes\n"), &mut out, root, &dirs).unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), "This run would rename files in 3 top-level directories of /data:\n  a\n  b\n  c\nContinue? [y/N]: ");
+        assert!(!ask(&mut io::Cursor::new("\n"), &mut Vec::new(), root, &dirs).unwrap());
+    }
+}
@@ -0,0 +1,63 @@
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Parse the digest asked for on the command line: an algorithm, for now
+/// only `sha256`, optionally with how many hex digits to keep
+/// (`sha256:12`). Returns that number, all 64 when not given.
+pub fn parse_spec(input: &str) -> Result<usize, String> {
+    let (algorithm, length) = input.split_once(':').unwrap_or((input, "64"));
+    if !algorithm.eq_ignore_ascii_case("sha256") {
+        return Err(format!("unknown hash '{}' (supported: sha256)", algorithm));
+    }
+    match length.parse() {
+        Ok(length @ 1..=64) => Ok(length),
+        _ => Err(format!("invalid length '{}' in '{}' (sha256 has 1 to 64 hex digits)", length, input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_match_known_answers() {
+        // FIPS 180-2 examples
+        assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("million");
+        std::fs::write(&path, "a".repeat(1_000_000)).unwrap();
+        assert_eq!(sha256_file(&path).unwrap(), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn specs_give_digest_lengths() {
+        assert_eq!(parse_spec("sha256"), Ok(64));
+        assert_eq!(parse_spec("SHA256:12"), Ok(12));
+        assert!(parse_spec("sha256:0").is_err());
+        assert!(parse_spec("sha256:65").is_err());
+        assert!(parse_spec("md5").is_err());
+    }
+}
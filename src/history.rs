@@ -0,0 +1,91 @@
+use crate::journal::{Entry, Op};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+/// Which moves `namefmt history` lists.
+#[derive(Debug, Default)]
+pub struct Query {
+    /// Moves of this file, or of anything under this directory, from or
+    /// to it. A bare name also matches files of that name anywhere.
+    pub path: Option<PathBuf>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Query {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        if self.since.is_some_and(|since| entry.time < since) || self.until.is_some_and(|until| entry.time > until) {
+            return false;
+        }
+        let Some(path) = &self.path else {
+            return true;
+        };
+        let bare = path.components().count() == 1 && path.file_name().is_some();
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        [&entry.source, &entry.target].into_iter().any(|moved| {
+            moved.starts_with(&absolute) || (bare && moved.file_name() == path.file_name())
+        })
+    }
+}
+
+/// The entries that moved files, renames and undos alike, oldest first.
+pub fn moves(entries: &[Entry]) -> impl Iterator<Item = &Entry> {
+    entries.iter().filter(|entry| matches!(entry.op, Op::Rename | Op::Undo))
+}
+
+/// Every move of the file that was last at `path`, or last left it,
+/// oldest first: back through the names it had before, and on through
+/// the names it was given after.
+pub fn trace<'a>(entries: &'a [Entry], path: &Path) -> Vec<&'a Entry> {
+    let moves: Vec<&Entry> = moves(entries).collect();
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let Some(last) = moves.iter().rposition(|entry| entry.source == path || entry.target == path) else {
+        return Vec::new();
+    };
+    let mut trace = Vec::new();
+    let mut name = path.as_path();
+    for entry in moves[..=last].iter().rev() {
+        if entry.target == name {
+            trace.push(*entry);
+            name = &entry.source;
+        }
+    }
+    trace.reverse();
+    let mut name = path.as_path();
+    for entry in &moves[last..] {
+        if entry.source == name {
+            trace.push(*entry);
+            name = &entry.target;
+        }
+    }
+    trace
+}
+
+/// One line for a move: when, what and by which run.
+pub fn describe(entry: &Entry) -> String {
+    let time = entry.time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S");
+    let run = entry.run.as_deref().map(|run| format!("  (run {})", run)).unwrap_or_default();
+    format!("{}  {:<6}  {} -> {}{}", time, entry.op.as_str(), entry.source.display(), entry.target.display(), run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_follows_one_file_both_ways() {
+        let entry = |op: Op, source: &str, target: &str| Entry { op, source: source.into(), target: target.into(), ..Entry::default() };
+        let entries = [
+            entry(Op::Rename, "/d/Report.docx", "/d/report.docx"),
+            entry(Op::Plan, "", ""),
+            entry(Op::Rename, "/d/report.docx", "/d/report_final_v2.docx"),
+            entry(Op::Rename, "/d/report_final_v2.docx", "/d/old/report.docx"),
+            // Another file takes the name later
+            entry(Op::Rename, "/d/Notes.docx", "/d/report_final_v2.docx"),
+            entry(Op::Undo, "/d/old/report.docx", "/d/report_final_v2.docx"),
+        ];
+        let targets = |trace: Vec<&Entry>| trace.iter().map(|e| e.target.display().to_string()).collect::<Vec<_>>();
+        assert_eq!(targets(trace(&entries, Path::new("/d/report.docx"))), ["/d/report.docx", "/d/report_final_v2.docx", "/d/old/report.docx", "/d/report_final_v2.docx"]);
+        assert_eq!(targets(trace(&entries, Path::new("/d/Notes.docx"))), ["/d/report_final_v2.docx"]);
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+use ureq::Agent;
+
+/// The client webhooks and preset downloads go through: redirects are
+/// followed, https is verified against the bundled web roots, and statuses
+/// outside 2xx are errors.
+fn agent(timeout: Duration) -> Agent {
+    Agent::config_builder()
+        .timeout_global(Some(timeout))
+        .user_agent(concat!("namefmt/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into()
+}
+
+/// The body at `url`.
+pub fn get(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    agent(timeout)
+        .get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_vec())
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))
+}
+
+/// Send `body` to `url` as JSON.
+pub fn post_json(url: &str, body: &str, timeout: Duration) -> Result<(), String> {
+    agent(timeout)
+        .post(url)
+        .header("Content-Type", "application/json")
+        .send(body)
+        .map(drop)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// A server on localhost answering one connection per response, in
+    /// order; joining it gives the requests it got, bodies included.
+    pub(crate) fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    request.push_str(&line);
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8_lossy(&body));
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    pub(crate) fn response(status: &str, headers: &str, body: &str) -> String {
+        format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}", status, body.len(), headers, body)
+    }
+
+    #[test]
+    fn gets_follow_redirects() {
+        let (url, server) = serve(vec![
+            response("302 Found", "Location: /moved.toml\r\n", ""),
+            response("200 OK", "", "[[preset]]\n"),
+        ]);
+        assert_eq!(get(&format!("{}/index.toml", url), Duration::from_secs(5)).unwrap(), b"[[preset]]\n");
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /index.toml HTTP/1.1\r\n"));
+        assert!(requests[1].starts_with("GET /moved.toml HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn error_statuses_fail() {
+        let (url, server) = serve(vec![response("404 Not Found", "", "")]);
+        let error = get(&format!("{}/missing.toml", url), Duration::from_secs(5)).unwrap_err();
+        assert!(error.starts_with(&format!("Failed to fetch {}/missing.toml: ", url)), "{}", error);
+        assert!(error.contains("404"), "{}", error);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn posts_send_json() {
+        let (url, server) = serve(vec![response("204 No Content", "", "")]);
+        post_json(&format!("{}/hook", url), "{\"text\":\"hi\"}", Duration::from_secs(5)).unwrap();
+        let request = server.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(request.to_ascii_lowercase().contains("content-type: application/json\r\n"));
+        assert!(request.ends_with("\r\n\r\n{\"text\":\"hi\"}"));
+    }
+}
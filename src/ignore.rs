@@ -0,0 +1,155 @@
+use crate::warn;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Ignore file read in every directory of a run.
+pub const IGNORE_FILE: &str = ".namefmtignore";
+
+/// Rules that apply below `base`, in `.gitignore` syntax. They are matched
+/// by the `ignore` crate's gitignore engine, so `**`, character classes,
+/// escapes, negation and anchoring behave exactly as git has them.
+#[derive(Debug, Clone)]
+struct Rules {
+    base: PathBuf,
+    matcher: Gitignore,
+}
+
+impl Rules {
+    fn new(base: &Path, lines: &str) -> Rules {
+        let mut builder = GitignoreBuilder::new(base);
+        for line in lines.lines() {
+            if let Err(e) = builder.add_line(None, line) {
+                warn!("Ignoring pattern in {}: {}", base.display(), e);
+            }
+        }
+        let matcher = builder.build().unwrap_or_else(|e| {
+            warn!("Ignoring patterns in {}: {}", base.display(), e);
+            Gitignore::empty()
+        });
+        Rules { base: base.to_path_buf(), matcher }
+    }
+
+    /// Some(true) if the last rule matching `path` ignores it, Some(false)
+    /// if it un-ignores it, None if no rule matches.
+    fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        if path == self.base || !path.starts_with(&self.base) {
+            return None;
+        }
+        match self.matcher.matched(path, is_dir) {
+            Match::Ignore(_) => Some(true),
+            Match::Whitelist(_) => Some(false),
+            Match::None => None,
+        }
+    }
+}
+
+/// What a run below one root leaves alone: `.namefmtignore` files, the
+/// configured patterns and, when asked for, `.gitignore` files. Deeper
+/// files win over shallower ones and the configured patterns win over all
+/// files, the way git weighs them. Git's other sources of excludes,
+/// `.git/info/exclude` and `core.excludesFile`, are not read.
+pub struct Ignore {
+    patterns: Rules,
+    /// `.gitignore` files above the root, up to the repository's top
+    above: Vec<Rules>,
+    /// Rules read from each directory below the root, loaded as the walk
+    /// reaches it
+    loaded: HashMap<PathBuf, Option<Rules>>,
+    gitignore: bool,
+}
+
+impl Ignore {
+    pub fn new(root: &Path, patterns: &[String], gitignore: bool) -> Ignore {
+        let mut above = Vec::new();
+        if gitignore {
+            let repository = root.ancestors().skip(1).find(|dir| dir.join(".git").exists());
+            if let Some(repository) = repository {
+                for dir in root.ancestors().skip(1).take_while(|dir| dir.starts_with(repository)) {
+                    if let Ok(content) = fs::read_to_string(dir.join(".gitignore")) {
+                        above.push(Rules::new(dir, &content));
+                    }
+                }
+            }
+            above.reverse();
+        }
+        Ignore {
+            patterns: Rules::new(root, &patterns.join("\n")),
+            above,
+            loaded: HashMap::new(),
+            gitignore,
+        }
+    }
+
+    /// Whether the walk should skip `path`. A skipped directory is not
+    /// descended into, so nothing below it is looked at either.
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if self.gitignore && is_dir && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        let mut ignored = None;
+        for rules in &self.above {
+            ignored = rules.decide(path, is_dir).or(ignored);
+        }
+        // Directories from the root down to the one holding `path`
+        let dirs: Vec<&Path> = path.ancestors().skip(1).take_while(|dir| dir.starts_with(&self.patterns.base)).collect();
+        for dir in dirs.into_iter().rev() {
+            let gitignore = self.gitignore;
+            let rules = self.loaded.entry(dir.to_path_buf()).or_insert_with(|| read_rules(dir, gitignore));
+            if let Some(rules) = rules {
+                ignored = rules.decide(path, is_dir).or(ignored);
+            }
+        }
+        ignored = self.patterns.decide(path, is_dir).or(ignored);
+        ignored.unwrap_or(false)
+    }
+}
+
+/// The rules of `dir`'s ignore files; `.namefmtignore` comes after
+/// `.gitignore`, so it wins where they disagree.
+fn read_rules(dir: &Path, gitignore: bool) -> Option<Rules> {
+    let mut content = String::new();
+    if gitignore {
+        content.push_str(&fs::read_to_string(dir.join(".gitignore")).unwrap_or_default());
+        content.push('\n');
+    }
+    content.push_str(&fs::read_to_string(dir.join(IGNORE_FILE)).unwrap_or_default());
+    let rules = Rules::new(dir, &content);
+    (!rules.matcher.is_empty()).then_some(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_follow_gitignore_syntax() {
+        let rules = Rules::new(Path::new("/repo"), "# build output\ntarget/\n*.log\n!keep.log\n/docs/*.pdf\n\\#notes\n");
+        let ignored = |path: &str, is_dir: bool| rules.decide(Path::new(path), is_dir) == Some(true);
+        assert!(ignored("/repo/target", true));
+        assert!(ignored("/repo/crates/a/target", true));
+        assert!(!ignored("/repo/target", false));
+        assert!(ignored("/repo/src/debug.log", false));
+        assert!(!ignored("/repo/src/keep.log", false));
+        assert!(ignored("/repo/docs/manual.pdf", false));
+        assert!(!ignored("/repo/src/docs/manual.pdf", false));
+        assert!(ignored("/repo/#notes", false));
+        assert_eq!(rules.decide(Path::new("/repo/src/main.rs"), false), None);
+    }
+
+    #[test]
+    fn rules_match_double_stars_and_classes() {
+        let rules = Rules::new(Path::new("/repo"), "**/cache\ndocs/**/*.tmp\n*.[oa]\nfoo\\ \n");
+        let ignored = |path: &str, is_dir: bool| rules.decide(Path::new(path), is_dir) == Some(true);
+        assert!(ignored("/repo/cache", true));
+        assert!(ignored("/repo/a/b/cache", false));
+        assert!(ignored("/repo/docs/x.tmp", false));
+        assert!(ignored("/repo/docs/a/b/x.tmp", false));
+        assert!(!ignored("/repo/src/docs/x.tmp", false));
+        assert!(ignored("/repo/lib/main.o", false));
+        assert!(!ignored("/repo/lib/main.c", false));
+        assert!(ignored("/repo/foo ", false));
+    }
+}
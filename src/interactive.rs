@@ -0,0 +1,117 @@
+use crate::name::{Platform, SanitizedName};
+use crate::plan::Rename;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+/// Ask about each planned rename in turn: make it, skip it, make it and
+/// every one after it, make it under an edited name, or stop asking.
+/// Returns the renames to make; stopping, or the end of the input, leaves
+/// the rest undone.
+pub fn review(renames: &[Rename], platform: Platform, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut accepted = Vec::new();
+    let mut all = false;
+    for rename in renames {
+        let pair = (rename.source.clone(), rename.target.clone());
+        if all {
+            accepted.push(pair);
+            continue;
+        }
+        writeln!(out, "{} -> {} ({})", rename.source.display(), rename.target.display(), rename.rule)?;
+        loop {
+            write!(out, "Rename? [y]es / [n]o / [a]ll / [e]dit / [q]uit: ")?;
+            out.flush()?;
+            let Some(answer) = read_answer(input)? else {
+                return Ok(accepted);
+            };
+            match answer.to_lowercase().as_str() {
+                "y" | "yes" => accepted.push(pair),
+                "n" | "no" => {}
+                "a" | "all" => {
+                    accepted.push(pair);
+                    all = true;
+                }
+                "e" | "edit" => {
+                    let edited = edit(rename, &accepted, platform, input, out)?;
+                    accepted.extend(edited);
+                }
+                "q" | "quit" => return Ok(accepted),
+                _ => {
+                    writeln!(out, "Please answer y, n, a, e or q")?;
+                    continue;
+                }
+            }
+            break;
+        }
+    }
+    Ok(accepted)
+}
+
+/// Ask for the new name, offering the planned one, until it is one no
+/// file has and no accepted rename takes. Names that aren't valid on
+/// `platform` are sanitized like any other.
+fn edit(rename: &Rename, accepted: &[(PathBuf, PathBuf)], platform: Platform, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<Option<(PathBuf, PathBuf)>> {
+    let planned = rename.target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    loop {
+        write!(out, "New name [{}]: ", planned)?;
+        out.flush()?;
+        let Some(answer) = read_answer(input)? else {
+            return Ok(None);
+        };
+        let name = if answer.is_empty() { planned.clone() } else { answer };
+        let sanitized = SanitizedName::new(&name, platform);
+        if sanitized.as_str() != name {
+            writeln!(out, "\"{}\" is not a valid name; using \"{}\"", name, sanitized)?;
+        }
+        let target = rename.target.with_file_name(sanitized.as_str());
+        if target == rename.source {
+            writeln!(out, "That is the current name; skipped")?;
+            return Ok(None);
+        }
+        let taken = accepted.iter().any(|(_, t)| *t == target);
+        if taken || fs::symlink_metadata(&target).is_ok() {
+            writeln!(out, "{} already exists; choose another name", target.display())?;
+            continue;
+        }
+        return Ok(Some((rename.source.clone(), target)));
+    }
+}
+
+fn read_answer(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn renames(dir: &Path) -> Vec<Rename> {
+        ["a", "b", "c", "d", "e"].iter()
+            .map(|name| Rename { source: dir.join(name), target: dir.join(name.to_uppercase()), rule: String::from("uppercase") })
+            .collect()
+    }
+
+    #[test]
+    fn answers_decide_each_rename_until_all_or_quit() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let mut out = Vec::new();
+        let answers = "y\nmaybe\nn\ne\nA\n\nall\n";
+        let accepted = review(&renames(dir), Platform::Posix, &mut answers.as_bytes(), &mut out).unwrap();
+        let targets: Vec<_> = accepted.iter().map(|(_, target)| target.strip_prefix(dir).unwrap()).collect();
+        assert_eq!(targets, [Path::new("A"), Path::new("C"), Path::new("D"), Path::new("E")]);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Please answer y, n, a, e or q"), "{}", out);
+        assert!(out.contains(&format!("{} already exists; choose another name", dir.join("A").display())), "{}", out);
+
+        let accepted = review(&renames(dir), Platform::Posix, &mut "n\ne\nb-c\nq\n".as_bytes(), &mut Vec::new()).unwrap();
+        assert_eq!(accepted, [(dir.join("b"), dir.join("b-c"))]);
+        // The input running out stops the questions too
+        assert!(review(&renames(dir), Platform::Posix, &mut "".as_bytes(), &mut Vec::new()).unwrap().is_empty());
+    }
+}
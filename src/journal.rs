@@ -0,0 +1,709 @@
+use crate::csv;
+use crate::hash::sha256_hex;
+use crate::rename::Moved;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Append-only JSONL record of every rename performed with `--inplace`.
+///
+/// Entries form a hash chain: each line carries the digest of the line
+/// before it (`prev`) and its own digest (`hash`), computed over the line's
+/// JSON without the `hash` field. Editing, removing or reordering any entry
+/// breaks the chain for every entry after it.
+pub struct Journal {
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Op {
+    #[default]
+    Rename,
+    /// Reverts the entry named in `undoes`
+    Undo,
+    /// Checkpoint written before an `--inplace` run starts, listing every
+    /// rename the run intends to perform
+    Plan,
+    /// The run finished; nothing is left to resume
+    Complete,
+    /// Written (and synced) right before a rename is attempted. A matching
+    /// `rename` entry referencing it marks the rename as done.
+    Intent,
+}
+
+impl Op {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Op::Rename => "rename",
+            Op::Undo => "undo",
+            Op::Plan => "plan",
+            Op::Complete => "complete",
+            Op::Intent => "intent",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Entry {
+    pub id: String,
+    pub time: DateTime<Utc>,
+    pub op: Op,
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub method: String,
+    pub source_sha256: Option<String>,
+    pub target_sha256: Option<String>,
+    /// Size and mtime of the target right after the rename, used to detect
+    /// later modification before reverting
+    pub target_size: Option<u64>,
+    pub target_mtime: Option<String>,
+    pub undoes: Option<String>,
+    /// Run that performed the rename and the root it was invoked on
+    pub run: Option<String>,
+    pub root: Option<PathBuf>,
+    /// Planned (source, target) pairs of a `plan` checkpoint
+    pub renames: Vec<(PathBuf, PathBuf)>,
+    /// The intent entry a rename or undo completes
+    pub intent: Option<String>,
+}
+
+/// One invocation's view of the journal. Every entry written through it
+/// carries the same run ID so the whole batch can be undone together.
+pub struct Run<'a> {
+    journal: &'a Journal,
+    pub id: String,
+    root: PathBuf,
+    recorded: Cell<usize>,
+}
+
+impl Run<'_> {
+    fn entry(&self, op: Op) -> Entry {
+        let time = Utc::now();
+        Entry {
+            id: next_id(time),
+            time,
+            op,
+            run: Some(self.id.clone()),
+            root: Some(self.root.clone()),
+            ..Entry::default()
+        }
+    }
+
+    fn moved_entry(&self, op: Op, source: &Path, target: &Path, moved: &Moved) -> io::Result<Entry> {
+        let fingerprint = file_fingerprint(target).ok();
+        Ok(Entry {
+            source: std::path::absolute(source)?,
+            target: std::path::absolute(target)?,
+            method: moved.method.as_str().to_string(),
+            source_sha256: moved.source_sha256.clone(),
+            target_sha256: moved.target_sha256.clone(),
+            target_size: fingerprint.as_ref().map(|f| f.0),
+            target_mtime: fingerprint.map(|f| f.1),
+            ..self.entry(op)
+        })
+    }
+
+    /// Durably record that `source` is about to be renamed to `target`.
+    /// Returns the intent ID to pass to `record` once the rename is done.
+    pub fn intend(&self, source: &Path, target: &Path) -> io::Result<String> {
+        let entry = Entry {
+            source: std::path::absolute(source)?,
+            target: std::path::absolute(target)?,
+            ..self.entry(Op::Intent)
+        };
+        self.journal.append_entries(std::slice::from_ref(&entry))?;
+        Ok(entry.id)
+    }
+
+    /// Record a rename that was made. Returns its entry, which an undo
+    /// of it refers to.
+    pub fn record(&self, source: &Path, target: &Path, moved: &Moved, intent: Option<&str>) -> io::Result<Entry> {
+        let entry = Entry {
+            intent: intent.map(str::to_string),
+            ..self.moved_entry(Op::Rename, source, target, moved)?
+        };
+        self.journal.append_entries(std::slice::from_ref(&entry))?;
+        self.recorded.set(self.recorded.get() + 1);
+        Ok(entry)
+    }
+
+    pub fn record_undo(&self, reverted: &Entry, moved: &Moved, intent: Option<&str>) -> io::Result<()> {
+        let entry = Entry {
+            undoes: Some(reverted.id.clone()),
+            intent: intent.map(str::to_string),
+            ..self.moved_entry(Op::Undo, &reverted.target, &reverted.source, moved)?
+        };
+        self.journal.append_entries(&[entry])?;
+        self.recorded.set(self.recorded.get() + 1);
+        Ok(())
+    }
+
+    /// Checkpoint the full set of renames before performing any of them, so
+    /// an interrupted run can be resumed.
+    pub fn begin(&self, renames: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+        let renames = renames
+            .iter()
+            .map(|(source, target)| Ok((std::path::absolute(source)?, std::path::absolute(target)?)))
+            .collect::<io::Result<_>>()?;
+        self.journal.append_entries(&[Entry { renames, ..self.entry(Op::Plan) }])
+    }
+
+    pub fn complete(&self) -> io::Result<()> {
+        self.journal.append_entries(&[self.entry(Op::Complete)])
+    }
+
+    /// Number of entries written during this run.
+    pub fn recorded(&self) -> usize {
+        self.recorded.get()
+    }
+}
+
+/// Outcome of `Journal::verify`.
+#[derive(Debug, Default)]
+pub struct ChainReport {
+    pub verified: usize,
+    /// Hash of the last entry, to be recorded elsewhere so truncation shows up
+    pub head: Option<String>,
+    pub problems: Vec<String>,
+}
+
+fn chain(entry: Value, prev: Option<String>) -> Value {
+    let Value::Object(mut fields) = entry else {
+        return entry;
+    };
+    fields.shift_remove("hash");
+    fields.shift_remove("prev");
+    fields.insert(String::from("prev"), prev.into());
+    let digest = sha256_hex(Value::Object(fields.clone()).to_string().as_bytes());
+    fields.insert(String::from("hash"), digest.into());
+    Value::Object(fields)
+}
+
+/// The string field `key` of an object value.
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Runs started by this process, so two in the same second get different IDs.
+static RUNS: AtomicU64 = AtomicU64::new(0);
+
+fn next_id(time: DateTime<Utc>) -> String {
+    let seq = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:x}-{}", time.timestamp_micros(), std::process::id(), seq)
+}
+
+/// Size and modification time of a file as recorded in the journal.
+pub fn file_fingerprint(path: &Path) -> io::Result<(u64, String)> {
+    let meta = fs::metadata(path)?;
+    let mtime: DateTime<Utc> = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH).into();
+    Ok((meta.len(), mtime.to_rfc3339()))
+}
+
+impl Entry {
+    fn to_json(&self) -> Value {
+        let mut value = json!({
+            "id": self.id,
+            "time": self.time.to_rfc3339(),
+            "op": self.op.as_str(),
+            "source": self.source.to_string_lossy(),
+            "target": self.target.to_string_lossy(),
+            "method": self.method,
+            "source_sha256": self.source_sha256,
+            "target_sha256": self.target_sha256,
+            "target_size": self.target_size,
+            "target_mtime": self.target_mtime,
+            "undoes": self.undoes,
+            "run": self.run,
+            "root": self.root.as_ref().map(|r| r.to_string_lossy()),
+            "intent": self.intent,
+        });
+
+        if self.op == Op::Plan {
+            let renames: Vec<[std::borrow::Cow<str>; 2]> = self
+                .renames
+                .iter()
+                .map(|(source, target)| [source.to_string_lossy(), target.to_string_lossy()])
+                .collect();
+            value["renames"] = json!(renames);
+        }
+        value
+    }
+
+    fn from_json(value: &Value) -> Result<Entry, String> {
+        let required = |key: &str| str_field(value, key).ok_or(format!("missing '{}'", key));
+        let time = DateTime::parse_from_rfc3339(&required("time")?)
+            .map_err(|e| format!("invalid time: {}", e))?
+            .with_timezone(&Utc);
+        let op = match value.get("op").and_then(Value::as_str) {
+            None | Some("rename") => Op::Rename,
+            Some("undo") => Op::Undo,
+            Some("plan") => Op::Plan,
+            Some("complete") => Op::Complete,
+            Some("intent") => Op::Intent,
+            Some(other) => return Err(format!("unknown op '{}'", other)),
+        };
+        let path_field = |key: &str| match op {
+            Op::Rename | Op::Undo | Op::Intent => required(key).map(PathBuf::from),
+            _ => Ok(str_field(value, key).map(PathBuf::from).unwrap_or_default()),
+        };
+
+        Ok(Entry {
+            id: str_field(value, "id").unwrap_or_else(|| time.to_rfc3339()),
+            time,
+            op,
+            source: path_field("source")?,
+            target: path_field("target")?,
+            method: str_field(value, "method").unwrap_or_else(|| "rename".to_string()),
+            source_sha256: str_field(value, "source_sha256"),
+            target_sha256: str_field(value, "target_sha256"),
+            target_size: value.get("target_size").and_then(Value::as_u64),
+            target_mtime: str_field(value, "target_mtime"),
+            undoes: str_field(value, "undoes"),
+            run: str_field(value, "run"),
+            root: str_field(value, "root").map(PathBuf::from),
+            renames: parse_renames(value.get("renames"))?,
+            intent: str_field(value, "intent"),
+        })
+    }
+}
+
+/// Planned renames are stored as `[[source, target], ...]`. CSV exports
+/// carry the same array as JSON text.
+fn parse_renames(value: Option<&Value>) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let items = match value {
+        None | Some(Value::Null) => return Ok(Vec::new()),
+        Some(Value::String(text)) => match serde_json::from_str(text).map_err(|e| e.to_string())? {
+            Value::Array(items) => items,
+            _ => return Err("invalid 'renames'".to_string()),
+        },
+        Some(Value::Array(items)) => items.clone(),
+        Some(_) => return Err("invalid 'renames'".to_string()),
+    };
+    items
+        .iter()
+        .map(|pair| match pair {
+            Value::Array(paths) => match (paths.first().and_then(Value::as_str), paths.get(1).and_then(Value::as_str)) {
+                (Some(source), Some(target)) => Ok((PathBuf::from(source), PathBuf::from(target))),
+                _ => Err("invalid entry in 'renames'".to_string()),
+            },
+            _ => Err("invalid entry in 'renames'".to_string()),
+        })
+        .collect()
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Journal { path }
+    }
+
+    pub fn default_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let config_dir = dirs::config_dir()
+            .ok_or("Could not determine config directory")?;
+        Ok(config_dir.join("namefmt").join("journal.jsonl"))
+    }
+
+    /// Where concurrent workers sharing this journal keep their claim locks.
+    pub fn lock_dir(&self) -> PathBuf {
+        self.path.with_file_name("locks")
+    }
+
+    /// Start a new run rooted at `root`.
+    pub fn start_run(&self, root: &Path) -> Run<'_> {
+        let now = Utc::now();
+        let run = RUNS.fetch_add(1, Ordering::Relaxed);
+        let id = format!("{}-{:04x}", now.format("%Y%m%d-%H%M%S"), (u64::from(std::process::id()) ^ run) & 0xffff);
+        self.resume_run(&id, root)
+    }
+
+    /// Continue writing entries for an existing run.
+    pub fn resume_run(&self, id: &str, root: &Path) -> Run<'_> {
+        Run {
+            journal: self,
+            id: id.to_string(),
+            root: std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf()),
+            recorded: Cell::new(0),
+        }
+    }
+
+    fn append_entries(&self, entries: &[Entry]) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        // Other namefmt processes (e.g. sharded workers) append to the same
+        // journal; hold the lock from reading the chain head until written
+        file.lock()?;
+        let mut prev = self.last_hash()?;
+        for entry in entries {
+            let chained = chain(entry.to_json(), prev);
+            prev = str_field(&chained, "hash");
+            writeln!(file, "{}", chained)?;
+        }
+        // Entries must be on disk before the renames they describe happen
+        file.sync_data()
+    }
+
+    /// Digest of the newest entry, read from the tail of the file.
+    fn last_hash(&self) -> io::Result<Option<String>> {
+        let mut file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let len = file.metadata()?.len();
+        let mut window = 4096u64;
+        loop {
+            let start = len.saturating_sub(window);
+            file.seek(SeekFrom::Start(start))?;
+            let mut tail = String::new();
+            file.read_to_string(&mut tail)?;
+
+            let mut lines = tail.lines().filter(|l| !l.trim().is_empty());
+            let last = lines.next_back();
+            // Only trust the last line if it is known to be complete
+            if start == 0 || lines.next().is_some() {
+                return Ok(last.and_then(|l| serde_json::from_str(l).ok()).and_then(|v| str_field(&v, "hash")));
+            }
+            window *= 4;
+        }
+    }
+
+    /// Recompute the hash chain over the whole journal. With `anchor`, a head
+    /// recorded earlier must still be in the chain.
+    pub fn verify(&self, anchor: Option<&str>) -> Result<ChainReport, Box<dyn std::error::Error>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut report = ChainReport::default();
+        let mut prev: Option<String> = None;
+        let mut anchored = false;
+        for (line_no, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let line_no = line_no + 1;
+            let value: Value = match serde_json::from_str(line) {
+                Ok(value) => value,
+                Err(e) => {
+                    report.problems.push(format!("line {}: not valid JSON: {}", line_no, e));
+                    prev = None;
+                    continue;
+                }
+            };
+
+            let Some(hash) = str_field(&value, "hash") else {
+                report.problems.push(format!("line {}: entry has no hash (chain stripped)", line_no));
+                prev = None;
+                continue;
+            };
+
+            if str_field(&value, "prev") != prev {
+                report.problems.push(format!("line {}: does not follow the previous entry (entries removed, inserted or reordered)", line_no));
+            }
+            let mut unhashed = value;
+            if let Some(fields) = unhashed.as_object_mut() {
+                fields.shift_remove("hash");
+            }
+            if sha256_hex(unhashed.to_string().as_bytes()) != hash {
+                report.problems.push(format!("line {}: content does not match its hash (entry edited)", line_no));
+            }
+            report.verified += 1;
+            anchored |= anchor == Some(hash.as_str());
+            prev = Some(hash);
+        }
+        if let Some(anchor) = anchor.filter(|_| !anchored) {
+            report.problems.push(format!("head {} is no longer in the journal (entries removed from the end)", anchor));
+        }
+        report.head = prev;
+        Ok(report)
+    }
+
+    /// Read every entry in the journal, oldest first.
+    pub fn entries(&self) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        for (line_no, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(line)
+                .map_err(|e| e.to_string())
+                .and_then(|value| Entry::from_json(&value))
+                .map_err(|e| format!("{}:{}: {}", self.path.display(), line_no + 1, e))?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+const CSV_COLUMNS: [&str; 15] = [
+    "id", "time", "op", "run", "root", "source", "target", "method",
+    "source_sha256", "target_sha256", "target_size", "target_mtime", "undoes", "renames", "intent",
+];
+
+pub fn export(entries: &[Entry], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => {
+            let lines: Vec<String> = entries.iter().map(|e| e.to_json().to_string()).collect();
+            format!("[\n{}\n]\n", lines.join(",\n"))
+        }
+        ExportFormat::Csv => {
+            let mut out = csv::write_row(&CSV_COLUMNS, ',');
+            out.push('\n');
+            for entry in entries {
+                let json = entry.to_json();
+                let fields: Vec<String> = CSV_COLUMNS
+                    .iter()
+                    .map(|column| match json.get(column) {
+                        Some(Value::String(s)) => s.clone(),
+                        Some(Value::Null) | None => String::new(),
+                        Some(other) => other.to_string(),
+                    })
+                    .collect();
+                let refs: Vec<&str> = fields.iter().map(String::as_str).collect();
+                out.push_str(&csv::write_row(&refs, ','));
+                out.push('\n');
+            }
+            out
+        }
+    }
+}
+
+/// Parse an exported journal (JSON array, JSONL, or CSV with a header row).
+pub fn parse_export(content: &str, format: ExportFormat) -> Result<Vec<Entry>, String> {
+    match format {
+        ExportFormat::Json => {
+            let trimmed = content.trim_start();
+            let values = if trimmed.starts_with('[') {
+                match serde_json::from_str(trimmed).map_err(|e| e.to_string())? {
+                    Value::Array(items) => items,
+                    _ => return Err("expected a JSON array".to_string()),
+                }
+            } else {
+                content
+                    .lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .map(serde_json::from_str)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| e.to_string())?
+            };
+            values.iter().map(Entry::from_json).collect()
+        }
+        ExportFormat::Csv => {
+            let rows = csv::parse(content, ',')?;
+            let Some((header, records)) = rows.split_first() else {
+                return Ok(Vec::new());
+            };
+            records
+                .iter()
+                .enumerate()
+                .map(|(i, record)| {
+                    let mut fields = Map::new();
+                    for (column, field) in header.iter().zip(record) {
+                        // An empty cell is a missing value, except in the
+                        // columns every entry has
+                        if field.is_empty() && !matches!(column.as_str(), "source" | "target" | "method") {
+                            continue;
+                        }
+                        let value = match column.as_str() {
+                            "target_size" => field.parse::<u64>().map_err(|_| format!("row {}: invalid target_size", i + 2))?.into(),
+                            _ => field.as_str().into(),
+                        };
+                        fields.insert(column.clone(), value);
+                    }
+                    Entry::from_json(&Value::Object(fields)).map_err(|e| format!("row {}: {}", i + 2, e))
+                })
+                .collect()
+        }
+    }
+}
+
+impl Entry {
+    /// Rewrite paths under `from` to live under `to` instead, for journals
+    /// carried over to a machine where the tree lives elsewhere.
+    pub fn relocate(&mut self, from: &Path, to: &Path) {
+        let paths = [&mut self.source, &mut self.target].into_iter().chain(self.root.as_mut());
+        for path in paths {
+            if let Ok(rest) = path.strip_prefix(from) {
+                *path = if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) };
+            }
+        }
+    }
+}
+
+impl Journal {
+    /// Append previously exported entries, skipping any whose ID is already
+    /// present. Returns the number of entries added.
+    pub fn import(&self, entries: &[Entry]) -> Result<usize, Box<dyn std::error::Error>> {
+        let existing: HashSet<String> = self.entries()?.into_iter().map(|e| e.id).collect();
+        let new: Vec<Entry> = entries.iter().filter(|e| !existing.contains(&e.id)).cloned().collect();
+        // Imported entries are re-chained onto this journal
+        self.append_entries(&new)?;
+        Ok(new.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines of a freshly written journal of four entries.
+    fn chained() -> (tempfile::TempDir, Journal, Vec<String>) {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let journal = Journal::new(dir.join("journal.jsonl"));
+        let run = journal.start_run(dir);
+        run.begin(&[(dir.join("a"), dir.join("b"))]).unwrap();
+        run.intend(&dir.join("a"), &dir.join("b")).unwrap();
+        run.intend(&dir.join("c"), &dir.join("d")).unwrap();
+        run.complete().unwrap();
+        let lines = fs::read_to_string(&journal.path).unwrap().lines().map(str::to_string).collect();
+        (scratch, journal, lines)
+    }
+
+    /// What `journal verify` says once the journal holds `lines`.
+    fn problems(journal: &Journal, lines: &[String]) -> Vec<String> {
+        fs::write(&journal.path, lines.join("\n") + "\n").unwrap();
+        journal.verify(None).unwrap().problems
+    }
+
+    #[test]
+    fn entries_are_written_in_field_order_and_read_back_exactly() {
+        let scratch = tempfile::tempdir().unwrap();
+        let journal = Journal::new(scratch.path().join("journal.jsonl"));
+        let entry = Entry {
+            id: String::from("1"),
+            time: DateTime::parse_from_rfc3339("2024-05-01T10:00:00+00:00").unwrap().with_timezone(&Utc),
+            source: PathBuf::from("/photos/a \"b\".jpg"),
+            target: PathBuf::from("/photos/a_b.jpg"),
+            method: String::from("rename"),
+            // Past what an f64 holds exactly
+            target_size: Some(u64::MAX - 1),
+            ..Entry::default()
+        };
+        journal.append_entries(std::slice::from_ref(&entry)).unwrap();
+        let line = fs::read_to_string(&journal.path).unwrap();
+        assert!(line.starts_with(r#"{"id":"1","time":"2024-05-01T10:00:00+00:00","op":"rename","source":"/photos/a \"b\".jpg","#), "{}", line);
+        assert!(line.contains(r#""target_size":18446744073709551614,"#), "{}", line);
+        let read = journal.entries().unwrap().remove(0);
+        assert_eq!((read.source, read.target_size), (entry.source, entry.target_size));
+    }
+
+    #[test]
+    fn untouched_journals_verify() {
+        let (_dir, journal, lines) = chained();
+        assert!(problems(&journal, &lines).is_empty());
+    }
+
+    #[test]
+    fn edited_entries_break_the_chain_where_they_are() {
+        let (_dir, journal, mut lines) = chained();
+        lines[2] = lines[2].replace("/c\"", "/e\"");
+        assert_eq!(problems(&journal, &lines), ["line 3: content does not match its hash (entry edited)"]);
+    }
+
+    #[test]
+    fn removed_entries_break_the_chain_after_them() {
+        let (_dir, journal, mut lines) = chained();
+        lines.remove(1);
+        assert_eq!(
+            problems(&journal, &lines),
+            ["line 2: does not follow the previous entry (entries removed, inserted or reordered)"]
+        );
+    }
+
+    #[test]
+    fn reordered_entries_break_the_chain_at_each_move() {
+        let (_dir, journal, mut lines) = chained();
+        lines.swap(1, 2);
+        assert_eq!(
+            problems(&journal, &lines),
+            [
+                "line 2: does not follow the previous entry (entries removed, inserted or reordered)",
+                "line 3: does not follow the previous entry (entries removed, inserted or reordered)",
+                "line 4: does not follow the previous entry (entries removed, inserted or reordered)",
+            ]
+        );
+    }
+
+    #[test]
+    fn stripped_chains_fail_on_every_entry() {
+        let (_dir, journal, lines) = chained();
+        let stripped: Vec<String> =
+            lines.iter().map(|l| {
+                let mut value: Value = serde_json::from_str(l).unwrap();
+                let fields = value.as_object_mut().unwrap();
+                fields.shift_remove("hash");
+                fields.shift_remove("prev");
+                value.to_string()
+            }).collect();
+        assert_eq!(
+            problems(&journal, &stripped),
+            (1..=4).map(|n| format!("line {}: entry has no hash (chain stripped)", n)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn truncated_tails_lose_the_recorded_head() {
+        let (_dir, journal, mut lines) = chained();
+        let head = journal.verify(None).unwrap().head.unwrap();
+        assert!(lines[3].contains(&head));
+        assert!(journal.verify(Some(&head)).unwrap().problems.is_empty());
+
+        lines.pop();
+        assert!(problems(&journal, &lines).is_empty(), "a truncated chain is still consistent on its own");
+        let report = journal.verify(Some(&head)).unwrap();
+        assert_ne!(report.head.as_deref(), Some(head.as_str()));
+        assert_eq!(
+            report.problems,
+            [format!("head {} is no longer in the journal (entries removed from the end)", head)]
+        );
+    }
+
+    #[test]
+    fn exports_import_into_an_identical_journal() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let journal = Journal::new(dir.join("journal.jsonl"));
+        let (source, target) = (dir.join("Trip, \"day 1\".jpg"), dir.join("trip_day_1.jpg"));
+        fs::write(&target, "photo").unwrap();
+        let run = journal.start_run(dir);
+        run.begin(&[(source.clone(), target.clone())]).unwrap();
+        let intent = run.intend(&source, &target).unwrap();
+        let moved = Moved { method: crate::rename::Method::Copy, source_sha256: Some(sha256_hex(b"photo")), target_sha256: Some(sha256_hex(b"photo")) };
+        let renamed = run.record(&source, &target, &moved, Some(&intent)).unwrap();
+        run.record_undo(&renamed, &moved, None).unwrap();
+        run.complete().unwrap();
+        let written = fs::read_to_string(&journal.path).unwrap();
+
+        for format in [ExportFormat::Json, ExportFormat::Csv] {
+            let exported = export(&journal.entries().unwrap(), format);
+            let fresh = Journal::new(dir.join(format!("{:?}.jsonl", format)));
+            assert_eq!(fresh.import(&parse_export(&exported, format).unwrap()).unwrap(), 5);
+            // Same entries chained from the start give the same hashes
+            assert_eq!(fs::read_to_string(&fresh.path).unwrap(), written, "{:?}", format);
+            assert_eq!(fresh.verify(None).unwrap().head, journal.verify(None).unwrap().head);
+            assert_eq!(fresh.import(&parse_export(&exported, format).unwrap()).unwrap(), 0, "{:?}", format);
+        }
+    }
+}
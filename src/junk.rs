@@ -0,0 +1,212 @@
+use crate::name::{split_extension, split_extensions};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// Rounds of stripping at most; each takes off at least one piece.
+const MAX_ROUNDS: usize = 32;
+
+/// Clutter that copying, downloading and sharing leave in names, taken out
+/// before the name is styled.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct JunkRules {
+    /// Take out the usual junk, as if --strip-junk were always given:
+    /// "Copy of ", " - Copy", macOS's " copy 2", a browser's " (1)", wget's
+    /// ".1", a repeated "final_final" and [www.site.com] tags
+    pub strip: bool,
+
+    /// More junk to take out, as regexes matched against the name without
+    /// its extension, e.g. ["^DRAFT[ _-]", "_sent$"]
+    pub patterns: Vec<String>,
+
+    #[serde(skip)]
+    compiled: OnceLock<Vec<Regex>>,
+}
+
+impl JunkRules {
+    /// Why patterns can't be used, one problem each.
+    pub fn problems(&self) -> Vec<String> {
+        self.patterns.iter().filter_map(|pattern| Regex::new(pattern).err()).map(|e| format!("junk.patterns: {}", e)).collect()
+    }
+
+    fn regexes(&self) -> &[Regex] {
+        self.compiled.get_or_init(|| self.patterns.iter().filter_map(|pattern| Regex::new(pattern).ok()).collect())
+    }
+
+    /// `name` without its junk. Separators around a piece taken out are
+    /// merged, so `Movie [www.site.com] 2020` becomes `Movie 2020`; a name
+    /// that would be left with nothing is kept as it is.
+    pub fn apply(&self, name: &str) -> String {
+        if !self.strip && self.patterns.is_empty() {
+            return name.to_string();
+        }
+        let mut name = name.to_string();
+        if self.strip {
+            name = without_download_number(&name).to_string();
+        }
+        let (stem, extension) = split_extensions(&name);
+        let mut stem = stem.to_string();
+        for _ in 0..MAX_ROUNDS {
+            let builtin = self.strip.then(|| builtin_junk(&stem)).flatten();
+            let found = builtin.or_else(|| {
+                self.regexes().iter().filter_map(|regex| regex.find(&stem)).map(|found| found.range()).find(|range| !range.is_empty())
+            });
+            match found {
+                Some(range) => stem = cut(&stem, range),
+                None => break,
+            }
+        }
+        if stem.trim_matches(is_separator).is_empty() {
+            return name;
+        }
+        stem + extension
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, ' ' | '_' | '-')
+}
+
+/// `stem` without `range`, and with the separators on either side of it
+/// merged into the first of them.
+fn cut(stem: &str, range: Range<usize>) -> String {
+    let before = stem[..range.start].trim_end_matches(is_separator);
+    let after = stem[range.end..].trim_start_matches(is_separator);
+    let separator = stem[before.len()..range.start].chars().chain(stem[range.end..].chars().take_while(|&c| is_separator(c))).next();
+    match separator {
+        Some(separator) if !before.is_empty() && !after.is_empty() => format!("{}{}{}", before, separator, after),
+        _ => format!("{}{}", before, after),
+    }
+}
+
+/// `setup.exe` for wget's second download `setup.exe.1`.
+fn without_download_number(name: &str) -> &str {
+    let (stem, number) = split_extension(name);
+    let (_, extension) = split_extension(stem);
+    let numbered = number.len() > 1 && number.len() <= 4 && number[1..].bytes().all(|b| b.is_ascii_digit());
+    if numbered && extension.chars().any(|c| c.is_ascii_alphabetic()) { stem } else { name }
+}
+
+/// The first piece of built-in junk in `stem`.
+fn builtin_junk(stem: &str) -> Option<Range<usize>> {
+    copy_of(stem).or_else(|| copy_suffix(stem)).or_else(|| repeated_final(stem)).or_else(|| site_tag(stem))
+}
+
+/// `Copy of ` or Windows's `Copy (2) of ` at the start.
+fn copy_of(stem: &str) -> Option<Range<usize>> {
+    let rest = strip_prefix_ignore_case(stem, "copy ")?;
+    let rest = number_in_parentheses(rest).map_or(rest, |len| rest[len..].trim_start_matches(' '));
+    let rest = strip_prefix_ignore_case(rest, "of ")?;
+    Some(0..stem.len() - rest.len())
+}
+
+/// ` - Copy`, ` - Copy (2)`, macOS's ` copy` and ` copy 2`, or a
+/// browser's ` (1)` at the end.
+fn copy_suffix(stem: &str) -> Option<Range<usize>> {
+    let numbered = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    // ` copy 2`: Finder writes "copy" in lowercase, so `Hard Copy` stays
+    if let Some(rest) = numbered.strip_suffix(" copy ").filter(|_| numbered.len() < stem.len()).or_else(|| stem.strip_suffix(" copy")) {
+        return Some(rest.len()..stem.len());
+    }
+    let mut rest = stem.trim_end_matches(' ');
+    if let Some(start) = rest.rfind('(').filter(|&start| number_in_parentheses(&rest[start..]) == Some(rest.len() - start)) {
+        rest = rest[..start].trim_end_matches(' ');
+    }
+    let rest = strip_suffix_ignore_case(rest, " - copy").unwrap_or(rest);
+    (rest.len() < stem.len() && !rest.is_empty()).then_some(rest.len()..stem.len())
+}
+
+/// The second `final` of `final_final`, `Final Final` and the like.
+fn repeated_final(stem: &str) -> Option<Range<usize>> {
+    let words: Vec<Range<usize>> = words(stem);
+    let is_final = |range: &Range<usize>| stem[range.clone()].eq_ignore_ascii_case("final");
+    words.windows(2).find(|pair| is_final(&pair[0]) && is_final(&pair[1])).map(|pair| pair[1].clone())
+}
+
+/// A `[www.site.com]` or `(site.org)` tag: a domain name in brackets.
+fn site_tag(stem: &str) -> Option<Range<usize>> {
+    let mut from = 0;
+    while let Some(open) = stem[from..].find(['[', '(']).map(|i| from + i) {
+        let close = if stem[open..].starts_with('[') { ']' } else { ')' };
+        let Some(end) = stem[open..].find(close).map(|i| open + i) else {
+            break;
+        };
+        if is_domain(&stem[open + 1..end]) {
+            return Some(open..end + 1);
+        }
+        from = open + 1;
+    }
+    None
+}
+
+/// `www.site.com`, `YTS.MX`: labels of letters, digits and `-` joined by
+/// dots, ending in a top-level domain of two to six letters.
+fn is_domain(text: &str) -> bool {
+    let labels: Vec<&str> = text.split('.').collect();
+    let Some((tld, names)) = labels.split_last() else {
+        return false;
+    };
+    let label = |label: &&str| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    !names.is_empty() && names.iter().all(label) && (2..=6).contains(&tld.len()) && tld.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Ranges of the words of `stem`, split at separators.
+fn words(stem: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in stem.char_indices().chain(std::iter::once((stem.len(), ' '))) {
+        match (is_separator(c), start) {
+            (true, Some(from)) => {
+                words.push(from..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    words
+}
+
+/// The length of a `(2)` at the start of `text`: one to three digits in
+/// parentheses.
+fn number_in_parentheses(text: &str) -> Option<usize> {
+    let digits = text.strip_prefix('(')?.bytes().take_while(u8::is_ascii_digit).count();
+    ((1..=3).contains(&digits) && text[1 + digits..].starts_with(')')).then_some(digits + 2)
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &text[prefix.len()..])
+}
+
+fn strip_suffix_ignore_case<'a>(text: &'a str, suffix: &str) -> Option<&'a str> {
+    let start = text.len().checked_sub(suffix.len())?;
+    let tail = text.get(start..)?;
+    tail.eq_ignore_ascii_case(suffix).then(|| &text[..start])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_numbers_and_site_tags_are_taken_out() {
+        let rules = JunkRules { strip: true, ..JunkRules::default() };
+        assert_eq!(rules.apply("Copy of Copy of report.docx"), "report.docx");
+        assert_eq!(rules.apply("Copy (2) of notes.txt"), "notes.txt");
+        assert_eq!(rules.apply("budget - Copy (3).xlsx"), "budget.xlsx");
+        assert_eq!(rules.apply("photo copy 2.jpg"), "photo.jpg");
+        assert_eq!(rules.apply("Hard Copy.pdf"), "Hard Copy.pdf");
+        assert_eq!(rules.apply("invoice (1).pdf"), "invoice.pdf");
+        assert_eq!(rules.apply("setup.exe.1"), "setup.exe");
+        assert_eq!(rules.apply("v1.2.3"), "v1.2.3");
+        assert_eq!(rules.apply("thesis_final_FINAL_final_v2.pdf"), "thesis_final_v2.pdf");
+        assert_eq!(rules.apply("Movie [www.site.com] 2020.mkv"), "Movie 2020.mkv");
+        assert_eq!(rules.apply("[YTS.MX] Movie.mp4"), "Movie.mp4");
+        assert_eq!(rules.apply("(1).txt"), "(1).txt");
+        let rules = JunkRules { patterns: vec![String::from("^DRAFT[ _-]")], ..JunkRules::default() };
+        assert_eq!(rules.apply("DRAFT_plan (1).md"), "plan (1).md");
+    }
+}
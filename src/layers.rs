@@ -0,0 +1,256 @@
+use crate::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Top-level key of the system configuration listing settings users may
+/// not change, e.g. `enforce = ["platform", "protected"]`.
+const ENFORCE_KEY: &str = "enforce";
+
+/// Where the administrator's configuration lives: `/etc/namefmt/namefmt.toml`
+/// on Unix, `%ProgramData%\namefmt\namefmt.toml` on Windows.
+pub fn system_config_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("ProgramData").map(|dir| Path::new(&dir).join("namefmt").join("namefmt.toml"))
+    } else {
+        Some(PathBuf::from("/etc/namefmt/namefmt.toml"))
+    }
+}
+
+/// The system configuration as a table, or `None` if there is none.
+pub fn read_system() -> Result<Option<(PathBuf, Table)>, String> {
+    let Some(path) = system_config_path().filter(|p| p.exists()) else {
+        return Ok(None);
+    };
+    let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let table = content.parse::<Table>().map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(Some((path, table)))
+}
+
+/// Per-project configuration, found in the target directory and the ones
+/// above it, e.g. committed at the root of a repository.
+pub const PROJECT_CONFIG: &str = ".namefmt.toml";
+
+/// Project configurations that apply to `target`, outermost first so the
+/// nearest one is laid over the others last. A file that can't be read or
+/// doesn't describe a configuration is an error; when `lenient`, it is
+/// skipped with a warning instead, and keys namefmt doesn't know are left
+/// for `parse_config` to drop.
+pub fn read_projects(target: &Path, lenient: bool) -> Result<Vec<(PathBuf, Table)>, String> {
+    let target = std::path::absolute(target).unwrap_or_else(|_| target.to_path_buf());
+    let start = if target.is_dir() { target.as_path() } else { target.parent().unwrap_or(&target) };
+    let mut projects = Vec::new();
+    for dir in start.ancestors() {
+        let path = dir.join(PROJECT_CONFIG);
+        if !path.is_file() {
+            continue;
+        }
+        let table = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if !lenient {
+                    toml::from_str::<crate::Config>(&content).map_err(|e| explain(&e))?;
+                }
+                content.parse::<Table>().map_err(|e| e.to_string())
+            })
+            .and_then(|table| {
+                let mut known = table.clone();
+                prune_unknown(&mut known);
+                match Value::Table(known).try_into::<crate::Config>() {
+                    Ok(_) => Ok(table),
+                    Err(e) => Err(explain(&e)),
+                }
+            });
+        match table {
+            Ok(table) => projects.push((path, table)),
+            Err(e) if lenient => warn!("Ignoring {}: {}", path.display(), e),
+            Err(e) => return Err(format!("Failed to parse {}: {}", path.display(), e)),
+        }
+    }
+    projects.reverse();
+    Ok(projects)
+}
+
+/// `error` as it should be shown: a misspelled key names the key it was
+/// most likely meant to be instead of every key allowed there.
+pub fn explain(error: &toml::de::Error) -> String {
+    let text = error.to_string();
+    text.trim_end()
+        .lines()
+        .map(|line| match unknown_field(line) {
+            Some((key, expected)) => match closest(key, &expected) {
+                Some(known) => format!("unknown field `{}`; did you mean `{}`?", key, known),
+                None => line.to_string(),
+            },
+            None => line.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The key and the allowed keys in serde's "unknown field `x`, expected
+/// `a` or `b`" message.
+fn unknown_field(line: &str) -> Option<(&str, Vec<&str>)> {
+    let rest = line.strip_prefix("unknown field `")?;
+    let (key, expected) = rest.split_once('`')?;
+    Some((key, expected.split('`').skip(1).step_by(2).collect()))
+}
+
+/// The allowed key `key` is a likely typo of, if any.
+fn closest<'a>(key: &str, allowed: &[&'a str]) -> Option<&'a str> {
+    allowed.iter()
+        .map(|known| (distance(key, known), *known))
+        .filter(|(distance, known)| *distance <= (known.len() / 3).max(1))
+        .min()
+        .map(|(_, known)| known)
+}
+
+/// The fewest single-character edits, swaps of neighbours included, from
+/// `a` to `b` (optimal string alignment distance).
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Remove the keys a configuration doesn't have from `table`, for
+/// --lenient, and return them dotted (`behaviors.styel`). Stops at the
+/// first error of another kind, which is left to be reported.
+pub fn prune_unknown(table: &mut Table) -> Vec<String> {
+    let mut removed = Vec::new();
+    loop {
+        let Err(e) = Value::Table(table.clone()).try_into::<crate::Config>() else {
+            break;
+        };
+        let text = e.to_string();
+        let Some((key, _)) = text.lines().find_map(unknown_field) else {
+            break;
+        };
+        // Where the key is, e.g. "in `behaviors`"; nothing for the top level
+        let path: Vec<&str> = text.lines()
+            .find_map(|line| line.strip_prefix("in `")?.strip_suffix('`'))
+            .map(|path| path.split('.').collect())
+            .unwrap_or_default();
+        if !remove_key(table, &path, key) {
+            break;
+        }
+        removed.push(path.iter().chain([&key]).copied().collect::<Vec<&str>>().join("."));
+    }
+    removed
+}
+
+/// Remove `key` from the table at `path`, or from every table in the array there.
+fn remove_key(table: &mut Table, path: &[&str], key: &str) -> bool {
+    match path.split_first() {
+        None => table.remove(key).is_some(),
+        Some((first, rest)) => match table.get_mut(*first) {
+            Some(Value::Table(inner)) => remove_key(inner, rest, key),
+            Some(Value::Array(items)) => items.iter_mut()
+                .filter_map(Value::as_table_mut)
+                .fold(false, |removed, inner| remove_key(inner, rest, key) | removed),
+            _ => false,
+        },
+    }
+}
+
+/// Top-level table of named profiles: `[profile.photos]` holds settings
+/// laid over the rest of the configuration when `--profile photos` is given.
+const PROFILE_KEY: &str = "profile";
+
+/// Remove the profiles from `table` and return them by name.
+pub fn take_profiles(table: &mut Table) -> Table {
+    match table.remove(PROFILE_KEY) {
+        Some(Value::Table(profiles)) => profiles.into_iter()
+            .filter_map(|(name, profile)| match profile {
+                Value::Table(profile) => Some((name, Value::Table(profile))),
+                _ => None,
+            })
+            .collect(),
+        _ => Table::new(),
+    }
+}
+
+/// Lay `over` on `base` the way `merge` does, with nothing enforced.
+pub fn overlay(mut base: Table, over: Table) -> Table {
+    merge_into(&mut base, over, "", &[], &mut Vec::new());
+    base
+}
+
+/// Lay `user` over `system`. Tables merge key by key, user values replace
+/// system values, and lists are joined with the user's entries first, so
+/// user behaviors are tried before the organization's and protected names
+/// add up. Keys the system lists under `enforce` (dotted for nested keys,
+/// e.g. `detection.exe_extensions`) keep the system value; returns the
+/// enforced keys the user tried to change.
+pub fn merge(mut system: Table, user: Table) -> (Table, Vec<String>) {
+    let enforced: Vec<String> = match system.remove(ENFORCE_KEY) {
+        Some(Value::Array(keys)) => keys.iter().filter_map(|k| k.as_str().map(str::to_string)).collect(),
+        _ => Vec::new(),
+    };
+    let mut overridden = Vec::new();
+    merge_into(&mut system, user, "", &enforced, &mut overridden);
+    (system, overridden)
+}
+
+fn merge_into(base: &mut Table, over: Table, prefix: &str, enforced: &[String], overridden: &mut Vec<String>) {
+    for (key, value) in over {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        if base.contains_key(&key) && enforced.contains(&path) {
+            if base.get(&key) != Some(&value) {
+                overridden.push(path);
+            }
+            continue;
+        }
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(over)) => merge_into(base, over, &path, enforced, overridden),
+            (Some(Value::Array(base)), Value::Array(mut over)) => {
+                over.append(base);
+                *base = over;
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn users_cannot_relax_enforced_keys() {
+        let system: Table = r#"
+            enforce = ["protected", "detection.exe_extensions"]
+            protected = ["Makefile"]
+            max_length = 100
+            [detection]
+            exe_extensions = ["exe"]
+        "#.parse().unwrap();
+        let user: Table = r#"
+            protected = []
+            max_length = 200
+            [detection]
+            exe_extensions = []
+        "#.parse().unwrap();
+        let (merged, overridden) = merge(system, user);
+        assert_eq!(overridden, ["detection.exe_extensions", "protected"]);
+        assert_eq!(merged["protected"], Value::Array(vec![Value::from("Makefile")]));
+        assert_eq!(merged["detection"]["exe_extensions"], Value::Array(vec![Value::from("exe")]));
+        assert_eq!(merged["max_length"], Value::from(200));
+        assert!(!merged.contains_key(ENFORCE_KEY));
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Share of a group's telling names that must agree before a convention
+/// is adopted.
+const DOMINANT: f64 = 0.8;
+
+/// Fewest telling names a convention is inferred from.
+const MIN_EVIDENCE: usize = 3;
+
+const STYLES: &[&str] = &["snake_case", "kebab-case", "camelCase"];
+const SNAKE: usize = 0;
+const KEBAB: usize = 1;
+const CAMEL: usize = 2;
+
+/// Date formats people put at the start of names, with `d` for a digit.
+const DATE_PREFIXES: &[(&str, &str)] = &[
+    ("YYYY_MM_DD__ (namefmt --timestamp)", "dddd_dd_dd__"),
+    ("YYYY-MM-DD", "dddd-dd-dd"),
+    ("YYYY_MM_DD", "dddd_dd_dd"),
+    ("YYYYMMDD", "dddddddd"),
+];
+
+/// How a name is written, judged on the stem without any date prefix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    /// Fits every style, e.g. `readme` or `v2`
+    Neutral,
+    /// Index into `STYLES`
+    Style(usize),
+    /// Spaces, PascalCase, SHOUTING or a mix of separators
+    Other,
+}
+
+#[derive(Default, Clone)]
+struct Tally {
+    styles: [usize; 3],
+    other: usize,
+}
+
+impl Tally {
+    fn add(&mut self, shape: Shape) {
+        match shape {
+            Shape::Neutral => {}
+            Shape::Style(i) => self.styles[i] += 1,
+            Shape::Other => self.other += 1,
+        }
+    }
+
+    /// The style nearly all telling names share, with its count.
+    fn dominant(&self) -> Option<(usize, usize)> {
+        let telling = self.telling();
+        let (style, &count) = self.styles.iter().enumerate().max_by_key(|(_, &n)| n)?;
+        (count >= MIN_EVIDENCE && count as f64 >= telling as f64 * DOMINANT).then_some((style, count))
+    }
+
+    fn telling(&self) -> usize {
+        self.styles.iter().sum::<usize>() + self.other
+    }
+}
+
+/// Conventions observed in a tree.
+pub struct Learned {
+    root: PathBuf,
+    files: usize,
+    with_spaces: usize,
+    /// Extension (lowercase, without the dot) -> tally
+    extensions: BTreeMap<String, Tally>,
+    overall: Tally,
+    dates: Vec<(&'static str, usize)>,
+}
+
+/// Look at every file name under `root` and work out how they are written.
+pub fn learn(root: &Path, files: &[PathBuf]) -> Learned {
+    let mut learned = Learned {
+        root: root.to_path_buf(),
+        files: 0,
+        with_spaces: 0,
+        extensions: BTreeMap::new(),
+        overall: Tally::default(),
+        dates: DATE_PREFIXES.iter().map(|(name, _)| (*name, 0)).collect(),
+    };
+
+    for file in files {
+        let Some((date, shape)) = classify(file) else {
+            continue;
+        };
+        learned.files += 1;
+        if file.file_name().is_some_and(|n| n.to_string_lossy().contains(' ')) {
+            learned.with_spaces += 1;
+        }
+        if let Some(i) = date {
+            learned.dates[i].1 += 1;
+        }
+        learned.overall.add(shape);
+        if let Some(ext) = file.extension() {
+            learned.extensions.entry(ext.to_string_lossy().to_lowercase()).or_default().add(shape);
+        }
+    }
+    learned
+}
+
+/// The date prefix (index into `DATE_PREFIXES`) and shape of a file name.
+/// `None` for dotfiles, which follow their tool's conventions, not the tree's.
+fn classify(file: &Path) -> Option<(Option<usize>, Shape)> {
+    let name = file.file_name()?.to_string_lossy();
+    if name.starts_with('.') {
+        return None;
+    }
+    let mut stem = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let date = DATE_PREFIXES.iter().position(|(_, pattern)| date_shape(&stem, pattern));
+    if date.is_some() {
+        stem = strip_date(&stem);
+    }
+    Some((date, shape(&stem)))
+}
+
+/// True if the name follows none of the styles: spaces, PascalCase,
+/// SHOUTING or mixed separators. Dotfiles never count.
+pub fn unstyled(file: &Path) -> bool {
+    matches!(classify(file), Some((_, Shape::Other)))
+}
+
+/// How the names in one directory are written, for judging one of them
+/// against the rest.
+pub struct Siblings(Tally);
+
+impl Siblings {
+    pub fn new(files: &[PathBuf]) -> Siblings {
+        let mut tally = Tally::default();
+        for (_, shape) in files.iter().filter_map(|f| classify(f)) {
+            tally.add(shape);
+        }
+        Siblings(tally)
+    }
+
+    /// The style nearly all other telling names share, leaving `file`
+    /// itself out of the evidence.
+    pub fn style_around(&self, file: &Path) -> Option<&'static str> {
+        let mut others = self.0.clone();
+        match classify(file) {
+            Some((_, Shape::Style(i))) => others.styles[i] = others.styles[i].saturating_sub(1),
+            Some((_, Shape::Other)) => others.other = others.other.saturating_sub(1),
+            _ => {}
+        }
+        others.dominant().map(|(style, _)| STYLES[style])
+    }
+}
+
+impl Learned {
+    /// A candidate configuration, with the evidence for each rule as comments.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Learned by `namefmt learn` from {} ({} files)", self.root.display(), self.files);
+        let _ = writeln!(out, "# Review before use: rules only appear where at least {:.0}% of telling names agree.\n", DOMINANT * 100.0);
+
+        // Keep spaces only if the tree mostly has them
+        let keep_spaces = self.files > 0 && self.with_spaces as f64 >= self.files as f64 * DOMINANT;
+        let _ = writeln!(out, "# {} of {} names contain spaces", self.with_spaces, self.files);
+        let _ = writeln!(out, "replace_spaces = {}", !keep_spaces);
+
+        for (format, count) in self.dates.iter().filter(|(_, n)| *n > 0) {
+            let _ = writeln!(out, "# {} of {} names start with a {} date", count, self.files, format);
+        }
+
+        let overall = self.overall.dominant().map(|(style, _)| style);
+        for (ext, tally) in &self.extensions {
+            let Some((style, count)) = tally.dominant() else {
+                if tally.telling() >= MIN_EVIDENCE {
+                    let _ = writeln!(out, "\n# .{}: no single style ({})", ext, describe(tally));
+                }
+                continue;
+            };
+            // The catch-all below already covers it
+            if Some(style) == overall {
+                continue;
+            }
+            let _ = writeln!(out, "\n# .{}: {} of {} telling names are {}", ext, count, tally.telling(), STYLES[style]);
+            let _ = writeln!(out, "[[behaviors]]\npattern = \"*.{}\"\nstyle = \"{}\"", ext.replace('"', "\\\""), STYLES[style]);
+        }
+
+        if let Some((style, count)) = self.overall.dominant() {
+            let _ = writeln!(out, "\n# Everything else: {} of {} telling names are {}", count, self.overall.telling(), STYLES[style]);
+            let _ = writeln!(out, "[[behaviors]]\npattern = \"*\"\nstyle = \"{}\"", STYLES[style]);
+        } else if self.overall.telling() > 0 {
+            let _ = writeln!(out, "\n# No style dominates overall ({})", describe(&self.overall));
+        }
+        out
+    }
+}
+
+fn describe(tally: &Tally) -> String {
+    let mut parts: Vec<String> = STYLES
+        .iter()
+        .zip(tally.styles)
+        .filter(|(_, n)| *n > 0)
+        .map(|(style, n)| format!("{} {}", n, style))
+        .collect();
+    if tally.other > 0 {
+        parts.push(format!("{} other", tally.other));
+    }
+    parts.join(", ")
+}
+
+fn shape(stem: &str) -> Shape {
+    let has_upper = stem.chars().any(char::is_uppercase);
+    let underscores = stem.contains('_');
+    let dashes = stem.contains('-');
+    let first_lower = stem.chars().next().is_some_and(|c| !c.is_uppercase());
+
+    if stem.contains(' ') || (underscores && dashes) {
+        Shape::Other
+    } else if has_upper {
+        if first_lower && !underscores && !dashes {
+            Shape::Style(CAMEL)
+        } else {
+            Shape::Other
+        }
+    } else if underscores {
+        Shape::Style(SNAKE)
+    } else if dashes {
+        Shape::Style(KEBAB)
+    } else {
+        Shape::Neutral
+    }
+}
+
+/// True if `text` starts with `pattern` (`d` for a digit, other characters
+/// exactly) and no further digit follows.
+fn date_shape(text: &str, pattern: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= pattern.len()
+        && pattern.bytes().zip(bytes).all(|(p, &b)| if p == b'd' { b.is_ascii_digit() } else { p == b })
+        && !bytes.get(pattern.len()).is_some_and(u8::is_ascii_digit)
+        && text.get(..4).and_then(|y| y.parse::<u32>().ok()).is_some_and(|y| (1900..2100).contains(&y))
+}
+
+/// The stem with a leading date and the separators after it removed.
+fn strip_date(stem: &str) -> String {
+    stem.trim_start_matches(|c: char| c.is_ascii_digit() || c == '-' || c == '_').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conventions_need_a_dominant_style() {
+        let names = [
+            "build-site.sh", "deploy-app.sh", "run-tests.sh", "lint-all.sh", "release_notes.md", "user_guide.md",
+            "change_log.md", "Road Map.md", ".gitignore", "2024-01-31 trip-photos.txt",
+        ];
+        let files: Vec<PathBuf> = names.iter().map(|name| Path::new("/repo").join(name)).collect();
+        let learned = learn(Path::new("/repo"), &files);
+        assert_eq!((learned.files, learned.with_spaces), (9, 2));
+        let toml = learned.to_toml();
+        assert!(toml.contains("# .sh: 4 of 4 telling names are kebab-case\n[[behaviors]]\npattern = \"*.sh\"\nstyle = \"kebab-case\"\n"), "{}", toml);
+        // Three of four isn't enough to adopt a style
+        assert!(toml.contains("# .md: no single style (3 snake_case, 1 other)\n"), "{}", toml);
+        assert!(toml.contains("# No style dominates overall (3 snake_case, 4 kebab-case, 2 other)\n"), "{}", toml);
+        assert!(toml.contains("# 1 of 9 names start with a YYYY-MM-DD date\n"), "{}", toml);
+        assert!(toml.starts_with("# Learned by `namefmt learn` from /repo (9 files)\n"), "{}", toml);
+    }
+}
@@ -0,0 +1,3386 @@
+//! File name formatting as done by the `namefmt` command.
+//!
+//! [`Formatter`] is the way in for other tools:
+//!
+//! ```
+//! use namefmt::{Formatter, NamingStyle};
+//!
+//! let formatter = Formatter::builder().style("*.rs", NamingStyle::SnakeCase).build();
+//! assert_eq!(formatter.format("My Module.rs"), "my_module.rs");
+//! ```
+//!
+//! [`Config`] holds the complete rule set and deserializes from
+//! `namefmt.toml`; [`read_config`] loads one the way the command does. The
+//! modules are the pieces the command is built from (planning, the rename
+//! journal, undo) and change more freely than the crate root.
+
+pub mod cache;
+pub mod check;
+pub mod clashes;
+pub mod completions;
+pub mod configdiff;
+pub mod conflicts;
+pub mod csv;
+pub mod dates;
+pub mod diff;
+pub mod documents;
+pub mod dupes;
+pub mod edit;
+pub mod episodes;
+pub mod external;
+pub mod exif;
+pub mod guard;
+pub mod hash;
+pub mod history;
+pub mod http;
+pub mod ignore;
+pub mod interactive;
+pub mod journal;
+pub mod junk;
+pub mod layers;
+pub mod learn;
+pub mod log;
+pub mod logs;
+pub mod magic;
+pub mod mapping;
+pub mod media;
+pub mod metadata;
+pub mod metrics;
+pub mod name;
+pub mod notify;
+pub mod output;
+pub mod pairs;
+pub mod partial;
+pub mod pdf;
+pub mod pipe;
+pub mod plan;
+pub mod presets;
+pub mod preserve;
+pub mod references;
+pub mod registry;
+pub mod rename;
+pub mod report;
+pub mod resume;
+pub mod selection;
+pub mod separators;
+pub mod shard;
+pub mod template;
+pub mod timeparse;
+pub mod transaction;
+pub mod translit;
+pub mod tui;
+pub mod undo;
+pub mod watch;
+pub mod wizard;
+
+use cache::IdentityCache;
+use conflicts::{ConflictPolicy, SyncConflictRules};
+use dates::DateRules;
+use documents::DocumentRules;
+use dupes::DuplicatePolicy;
+use episodes::EpisodeRules;
+pub use name::{Platform, SanitizedName};
+use name::LengthUnit;
+use globset::{GlobBuilder, GlobMatcher};
+use guard::GuardRules;
+use ignore::Ignore;
+use junk::JunkRules;
+use media::MediaTokens;
+use metrics::MetricsRules;
+use notify::NotifyRules;
+use pairs::{PairRules, SubtitleRules};
+use partial::PartialDownloadRules;
+use plan::{Coverage, Plan, Rename};
+use preserve::PreserveRules;
+use rayon::prelude::*;
+use references::BuildReferenceRules;
+use regex::Regex;
+use selection::Filters;
+use separators::SeparatorRules;
+use serde::{Deserialize, Serialize};
+use shard::Shard;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::SystemTime;
+use template::{Field, Template};
+use walkdir::WalkDir;
+
+/// The complete rule set, as read from `namefmt.toml`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_replace_spaces")]
+    pub replace_spaces: bool,
+ 
+    #[serde(default)]
+    pub behaviors: Vec<Behavior>,
+
+    /// Hand the name each behavior makes on to the next behavior that
+    /// matches it, as if every behavior had `chain = true`
+    #[serde(default)]
+    pub chain: bool,
+
+    /// Add a timestamp (YYYY_MM_DD__ in front by default) to all filenames,
+    /// as if --timestamp were always given
+    #[serde(default)]
+    pub timestamp: bool,
+
+    /// How the timestamp is written and where it goes
+    #[serde(default, skip_serializing_if = "TimestampFormat::is_default")]
+    pub timestamp_format: TimestampFormat,
+
+    /// Where the timestamp prefix's date comes from: "now" (the default),
+    /// the file's "mtime", "ctime" or "created" time, or the "exif" capture
+    /// time of photos
+    #[serde(default, skip_serializing_if = "TimestampSource::is_now")]
+    pub timestamp_source: TimestampSource,
+
+    /// Longest name a rename may produce, e.g. 143 for eCryptfs. Longer
+    /// names are cut from the end, keeping the extension and timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+
+    /// What max_length counts: "bytes" (the default) or "chars"
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub max_length_unit: LengthUnit,
+
+    /// What happens to longer names: "truncate" them (the default), or
+    /// "error" and rename nothing
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub max_length_action: LengthAction,
+
+    /// Spell names in ASCII before styling them (é to e, Ж to Zh), as if
+    /// --ascii were always given
+    #[serde(default)]
+    pub transliterate: bool,
+
+    /// Words written exactly as listed whatever the style, matched
+    /// regardless of case and in the plural too: ["ID", "URL"] style
+    /// "user id urls.txt" as user_ID_URLs.txt and "UserId" as UserID
+    #[serde(default)]
+    pub acronyms: Vec<String>,
+
+    /// Versions, ISO dates and hashes that styling leaves as written
+    #[serde(default)]
+    pub preserve: PreserveRules,
+
+    /// Dates in names rewritten to one form
+    #[serde(default)]
+    pub dates: DateRules,
+
+    /// Copy markers, download numbers and site tags taken out of names
+    #[serde(default)]
+    pub junk: JunkRules,
+
+    /// Runs of separators written once, and separators trimmed from the ends
+    #[serde(default)]
+    pub separators: SeparatorRules,
+
+    /// Style every directory below the target too, as if --dirs were always given
+    #[serde(default, alias = "dirs")]
+    pub paths: bool,
+
+    /// Filesystems new names must be valid on: "posix" or "windows"
+    #[serde(default)]
+    pub platform: Platform,
+
+    #[serde(default)]
+    pub presets: Vec<String>,
+
+    /// File or directory names (globs) that are never renamed or descended into
+    #[serde(default)]
+    pub protected: Vec<String>,
+
+    /// Also protect the names conventions depend on: README*, LICENSE*,
+    /// Makefile, Dockerfile, Cargo.toml, package.json, .gitignore and the
+    /// like. --rename-protected turns this off for one run
+    #[serde(default = "default_protected")]
+    pub default_protected: bool,
+
+    /// Paths a run doesn't look at, in .gitignore syntax relative to the
+    /// target, e.g. ["target/", "vendor/", "*.log"]; added to by --exclude
+    #[serde(default)]
+    pub ignore: Vec<String>,
+
+    /// Also leave alone what .gitignore files ignore, as if --gitignore were always given
+    #[serde(default)]
+    pub gitignore: bool,
+
+    /// Rename files git tracks with `git mv`, so the renames are staged, as
+    /// if --git were always given
+    #[serde(default)]
+    pub git: bool,
+
+    /// How deep a run looks, whether it follows symbolic links and skips
+    /// hidden files
+    #[serde(default)]
+    pub traversal: TraversalRules,
+
+    /// Skip files already settled by a previous run (tracked by device and inode)
+    #[serde(default)]
+    pub cache: bool,
+
+    #[serde(default)]
+    pub detection: DetectionRules,
+
+    #[serde(default)]
+    pub sync_conflicts: SyncConflictRules,
+
+    /// What to do with files whose content another file has: "off" (the
+    /// default), "report" them, "skip" their renames, or "suffix" their
+    /// names with _dup1, _dup2, ...
+    #[serde(default)]
+    pub duplicates: DuplicatePolicy,
+
+    #[serde(default)]
+    pub partial_downloads: PartialDownloadRules,
+
+    #[serde(default)]
+    pub pairs: PairRules,
+
+    #[serde(default)]
+    pub subtitles: SubtitleRules,
+
+    #[serde(default)]
+    pub episodes: EpisodeRules,
+
+    /// Release-name tokens (`1080p`, `x265`) kept whole in media file names
+    #[serde(default)]
+    pub media_tokens: MediaTokens,
+
+    /// How every file's extension is written: lowercased, and aliases such
+    /// as `jpeg` or `yml` mapped to one spelling
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub extensions: ExtensionRules,
+
+    #[serde(default)]
+    pub documents: DocumentRules,
+
+    #[serde(default)]
+    pub build_references: BuildReferenceRules,
+
+    /// Extensions of files renamed together with their primary, e.g. [".xmp", ".srt"]
+    #[serde(default)]
+    pub sidecars: Vec<String>,
+
+    /// Named settings laid over the rest with --profile. They are applied
+    /// before the configuration is built, so this is only filled in when a
+    /// file is checked as written
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, Config>,
+
+    /// Where results are reported
+    #[serde(default)]
+    pub notify: NotifyRules,
+
+    /// Index of community preset packs for `namefmt preset`
+    #[serde(default)]
+    pub registry: Option<String>,
+
+    /// Most renames a single run may perform; larger runs stop before renaming anything
+    #[serde(default)]
+    pub max_renames: Option<usize>,
+
+    /// Safety checks for --inplace runs on very broad targets
+    #[serde(default)]
+    pub guardrails: GuardRules,
+
+    /// Where run metrics are pushed or served, for scheduled and daemon runs
+    #[serde(default)]
+    pub metrics: MetricsRules,
+
+    /// Set from --shard; only this worker's slice of the tree is planned
+    #[serde(skip)]
+    pub shard: Option<Shard>,
+
+    /// Set from --threads; planning uses one thread per CPU when unset
+    #[serde(skip)]
+    pub threads: Option<usize>,
+
+    /// Set from --ext, --min-size/--max-size and --newer-than/--older-than
+    #[serde(skip)]
+    pub filters: Filters,
+
+    /// Set from --suggest / --apply-suggestions
+    #[serde(skip)]
+    pub suggestions: Suggestions,
+
+    /// Set from --interactive: every rename is confirmed before it is made
+    #[serde(skip)]
+    pub interactive: bool,
+
+    /// Set from --edit: the renames are edited in $EDITOR before they are made
+    #[serde(skip)]
+    pub edit: bool,
+
+    /// Set from --keep-going: renames that fail are reported and the rest
+    /// go ahead, instead of the whole batch being rolled back
+    #[serde(skip)]
+    pub keep_going: bool,
+
+    #[serde(skip)]
+    root: RunRoot,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Behavior {
+    /// Glob on the name (`*`, `?`, `[a-z]`), or on the end of the path if
+    /// it has a `/` (`src/**/*.test.js`); with `regex` set as well, a name
+    /// must match both
+    #[serde(default)]
+    pub pattern: String,
+    
+    /// How `pattern` is read: "glob" (the default) or "regex"
+    #[serde(default, skip_serializing_if = "PatternType::is_glob")]
+    pub pattern_type: PatternType,
+    
+    /// MIME type the file's content must have as well, told from its
+    /// first bytes: "image/*", "application/pdf", "text/plain"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    
+    /// What `pattern` or `regex` is matched against: the "name" (the
+    /// default) or the "path" below the directory being renamed, so that
+    /// "src/**/*.rs" selects Rust files in its `src` and nowhere else.
+    /// Groups and replacements still work on the name
+    #[serde(default, rename = "match", skip_serializing_if = "MatchOn::is_name")]
+    pub match_on: MatchOn,
+    
+    /// Regular expression the name must match (and `pattern` too, if set), in the
+    /// syntax of the `regex` crate: Unicode classes (`\p{L}`), flags
+    /// (`(?i)`) and named groups, matched in time linear in the name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    
+    /// New name built from placeholders: the regex's groups by name or
+    /// number, plus {name} and {ext} (the current name's parts), {parent},
+    /// {counter} (the file's number among those this behavior selects in
+    /// the directory, see `counter`), {date} (today), {mtime} and {hash}
+    /// (SHA-256 of the content, {hash:12} for the first 12 digits),
+    /// {artist}, {album}, {track} and {title} from a music file's tags,
+    /// {created}, {duration}, {width}, {height} and {resolution} ("1080p")
+    /// from a video's headers and a PDF's {title}, e.g.
+    /// "{parent}_{counter:03}.{ext}" or "{artist} - {track} - {title}.{ext}"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    
+    /// Replacement for the part of the name the regex matched, with groups
+    /// as `$1` or `${name}`, e.g. "photo_$1" for "IMG_(\d+)"; write `${1}x`
+    /// for group 1 followed by "x", as `$1x` is the group named "1x"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replace: Option<String>,
+    
+    /// How {counter} counts, e.g. `counter = { start = 0, step = 10, width = 4, order = "mtime" }`
+    #[serde(default, skip_serializing_if = "CounterOptions::is_default")]
+    pub counter: CounterOptions,
+    
+    /// Applied after the template or replacement, if both are given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<NamingStyle>,
+    
+    /// Program that names files for `style = "external"`, with its
+    /// arguments: it is given the name on stdin and writes the new name to
+    /// stdout, e.g. "sed s/draft/final/"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    
+    /// What happens to the extension (`.jpg`, `.tar.gz`): "keep" it as it
+    /// is (the default), "lowercase" it, or "style" it with the rest
+    #[serde(default, skip_serializing_if = "ExtensionCase::is_keep")]
+    pub extension: ExtensionCase,
+    
+    /// Removed from the start of the name before styling, e.g. "DRAFT_"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strip_prefix: Option<String>,
+    
+    /// Removed from the end of the name, before the extension, before styling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strip_suffix: Option<String>,
+    
+    /// Put in front of the styled name as written
+    #[serde(default, alias = "prefix", skip_serializing_if = "Option::is_none")]
+    pub add_prefix: Option<String>,
+    
+    /// Put after the styled name, before the extension, as written
+    #[serde(default, alias = "suffix", skip_serializing_if = "Option::is_none")]
+    pub add_suffix: Option<String>,
+    
+    /// Timestamp the files this behavior renames (true) or leave them
+    /// without one (false), whatever the top-level `timestamp` and
+    /// --timestamp say; directories are never timestamped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<bool>,
+    
+    /// Hand the name this behavior makes on to the next behavior that
+    /// matches it, instead of stopping here; defaults to the top-level
+    /// `chain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<bool>,
+    
+    #[serde(skip)]
+    compiled: OnceLock<Result<Compiled, String>>,
+    
+    #[serde(skip)]
+    counters: Counters,
+    
+    #[serde(skip)]
+    commanded: Commanded,
+}
+
+/// True if `group` names a group of `regex`, or numbers one (0 is the
+/// whole match).
+fn has_group(regex: &Regex, group: &str) -> bool {
+    regex.capture_names().flatten().any(|name| name == group) || group.parse().is_ok_and(|i: usize| i < regex.captures_len())
+}
+
+/// The groups `replacement` refers to, read as `Regex::replace` reads
+/// them: `$1`, `$name`, `${name}`, with `$$` a literal `$`. A name runs as
+/// far as letters, digits and `_` do, so `$1a` is the group "1a".
+fn replacement_groups(replacement: &str) -> Vec<&str> {
+    let mut groups = Vec::new();
+    let mut rest = replacement;
+    while let Some(at) = rest.find('$') {
+        let after = &rest[at + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            rest = after;
+        } else if let Some(close) = after.strip_prefix('{').and_then(|a| a.find('}')) {
+            groups.push(&after[1..close + 1]);
+            rest = &after[close + 2..];
+        } else {
+            let length = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+            if length > 0 {
+                groups.push(&after[..length]);
+            }
+            rest = &after[length..];
+        }
+    }
+    groups
+}
+
+/// A behavior's regex and template, parsed.
+type Compiled = (Option<Regex>, Option<Template>);
+
+/// Placeholders every template may use besides regex groups.
+const TEMPLATE_FIELDS: &[&str] = &["name", "ext", "parent", "counter", "date", "mtime", "hash"];
+
+/// The file names a template behavior selects in each directory, in
+/// counter order, so `{counter}` is the same for a file however often it is asked for.
+#[derive(Debug, Default)]
+struct Counters(std::sync::Mutex<HashMap<PathBuf, Vec<String>>>);
+
+impl Clone for Counters {
+    fn clone(&self) -> Self {
+        Counters::default()
+    }
+}
+
+/// The names an external command gave, by the name it was given, so the
+/// command runs once per name however often the name is formatted.
+#[derive(Debug, Default)]
+struct Commanded(std::sync::Mutex<HashMap<String, String>>);
+
+impl Clone for Commanded {
+    fn clone(&self) -> Self {
+        Commanded::default()
+    }
+}
+
+impl Behavior {
+    /// Style every name matching the glob `pattern`.
+    pub fn styled(pattern: &str, style: NamingStyle) -> Behavior {
+        Behavior { style: Some(style), ..Behavior::selecting(pattern) }
+    }
+    
+    /// Rename every name matching the glob `pattern` to `template`.
+    pub fn templated(pattern: &str, template: &str) -> Behavior {
+        Behavior { template: Some(template.to_string()), ..Behavior::selecting(pattern) }
+    }
+    
+    /// Selects names matching the glob `pattern` and changes nothing yet.
+    fn selecting(pattern: &str) -> Behavior {
+        Behavior {
+            pattern: pattern.to_string(),
+            pattern_type: PatternType::Glob,
+            mime: None,
+            match_on: MatchOn::Name,
+            regex: None,
+            template: None,
+            replace: None,
+            counter: CounterOptions::default(),
+            style: None,
+            command: None,
+            extension: ExtensionCase::default(),
+            strip_prefix: None,
+            strip_suffix: None,
+            add_prefix: None,
+            add_suffix: None,
+            timestamp: None,
+            chain: None,
+            compiled: OnceLock::new(),
+            counters: Counters::default(),
+            commanded: Commanded::default(),
+        }
+    }
+
+    
+    /// The regex and template, parsed on first use.
+    fn compiled(&self) -> &Result<Compiled, String> {
+        self.compiled.get_or_init(|| {
+            if self.regex.is_some() && self.pattern_type == PatternType::Regex {
+                return Err(format!("behavior has both regex \"{}\" and a regex pattern \"{}\"; use one", self.regex.as_deref().unwrap_or_default(), self.pattern));
+            }
+            let regex = self.regex_source().map(Regex::new).transpose().map_err(|e| e.to_string())?;
+            let template = self.template.as_deref().map(Template::parse).transpose()?;
+            let known = |token: &str| TEMPLATE_FIELDS.contains(&token) || metadata::TOKENS.contains(&token) || regex.as_ref().is_some_and(|r| has_group(r, token));
+            if let Some(token) = template.iter().flat_map(|t| t.tokens()).find(|t| !known(t)) {
+                let groups = if regex.is_some() { ", or a group of the regex" } else { "" };
+                return Err(format!("template \"{}\" uses unknown '{{{}}}' (known: {}{})", template.as_ref().map(|t| t.to_string()).unwrap_or_default(), token, [TEMPLATE_FIELDS, metadata::TOKENS].concat().join(", "), groups));
+            }
+            if let (Some(regex), Some(replacement)) = (&regex, self.replace.as_deref()) {
+                if let Some(group) = replacement_groups(replacement).into_iter().find(|group| !has_group(regex, group)) {
+                    return Err(format!("replacement \"{}\" uses '{}', which is not a group of regex \"{}\"", replacement, group, self.regex_source().unwrap_or_default()));
+                }
+            }
+            Ok((regex, template))
+        })
+    }
+    
+    /// `regex`, or `pattern` when it is declared a regex.
+    fn regex_source(&self) -> Option<&str> {
+        match (&self.regex, self.pattern_type) {
+            (Some(regex), _) => Some(regex),
+            (None, PatternType::Regex) => Some(&self.pattern),
+            (None, PatternType::Glob) => None,
+        }
+    }
+    
+    /// The glob a name must match: `pattern`, unless it is a regex or left
+    /// empty beside `regex`.
+    fn glob(&self) -> Option<&str> {
+        let unset = self.pattern.is_empty() && self.regex.is_some();
+        (self.pattern_type == PatternType::Glob && !unset).then_some(self.pattern.as_str())
+    }
+    
+    /// Problems that stop this behavior from working as written.
+    fn problem(&self) -> Option<String> {
+        match self.compiled() {
+            Err(e) => Some(e.clone()),
+            Ok(_) if self.glob().is_some_and(|glob| GlobBuilder::new(glob).build().is_err()) => Some(format!("behavior '{}' has an invalid glob; it only selects names spelled exactly like it", self.pattern)),
+            Ok((None, _)) if self.replace.is_some() => Some(format!("behavior {} has a replacement but no regex; the replacement is ignored", self.label())),
+            _ if self.template.is_some() && self.replace.is_some() => Some(format!("behavior {} has both a template and a replacement; the replacement is ignored", self.label())),
+            _ if self.style == Some(NamingStyle::External) && self.command.is_none() => Some(format!("behavior {} has style external but no command; names are left as they are", self.label())),
+            _ if self.style != Some(NamingStyle::External) && self.command.is_some() => Some(format!("behavior {} has a command but its style isn't external; the command is ignored", self.label())),
+            _ if self.actions().is_empty() => Some(format!("behavior {} has no style, template or prefix/suffix operation and changes nothing", self.label())),
+            _ => None,
+        }
+    }
+    
+    /// Whether the behavior selects `name`, the name of the file at `path`.
+    fn matches(&self, name: &str, path: &Path, config: &Config) -> bool {
+        let relative = || config.relative_path(&path.with_file_name(name));
+        let globbed = || match (self.glob(), self.match_on) {
+            (None, _) => true,
+            (Some(glob), MatchOn::Name) => matches_pattern(name, path, glob),
+            (Some(glob), MatchOn::Path) => glob_match(glob, &relative()),
+        };
+        let selected = match (self.compiled(), self.match_on) {
+            (Ok((None, _)), _) => globbed(),
+            (Ok((Some(regex), _)), MatchOn::Name) => globbed() && regex.is_match(name),
+            (Ok((Some(regex), _)), MatchOn::Path) => globbed() && regex.is_match(&relative()),
+            (Err(_), _) => false,
+        };
+        // Content is only read for the files the pattern selects
+        selected && self.mime.as_deref().is_none_or(|mime| config.detection.mime_type(path).is_some_and(|found| glob_match(mime, found)))
+    }
+    
+    /// Rewrite a name this behavior matches: fill in the template from the
+    /// regex's groups (or replace the matched part), strip prefix and
+    /// suffix, apply the style, then add prefix and suffix.
+    /// `path` is the file being renamed, for the template's placeholders;
+    /// `tokens` are kept whole while the name is styled (see `media`), and
+    /// the config's acronyms and preserved text written as they are.
+    fn apply(&self, name: &str, path: &Path, tokens: &[String], config: &Config) -> String {
+        let mut result = name.to_string();
+        match self.compiled() {
+            Ok((regex, Some(template))) => {
+                let captures = regex.as_ref().and_then(|regex| regex.captures(name));
+                let (stem, extension) = name::split_extension(name);
+                // Tags are read once, and only for templates that use them
+                let tags = std::cell::OnceCell::new();
+                result = template.render(|token| {
+                    if let Some(captures) = &captures {
+                        let group = captures.name(token).or_else(|| token.parse().ok().and_then(|i| captures.get(i)));
+                        if let Some(value) = group {
+                            return Some(Field::Text(value.as_str().to_string()));
+                        }
+                    }
+                    match token {
+                        "name" => Some(Field::Text(stem.to_string())),
+                        "ext" => Some(Field::Text(extension.trim_start_matches('.').to_string())),
+                        "parent" => path.parent().and_then(Path::file_name).map(|p| Field::Text(p.to_string_lossy().to_string())),
+                        "counter" => Some(Field::Padded(self.counter(path, config), self.counter.width)),
+                        "date" => Some(Field::Date(chrono::Local::now().naive_local())),
+                        "hash" => hash::sha256_file(path).ok().map(Field::Digest),
+                        "mtime" => {
+                            let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+                            Some(Field::Date(chrono::DateTime::<chrono::Local>::from(modified).naive_local()))
+                        }
+                        _ if metadata::TOKENS.contains(&token) => tags.get_or_init(|| metadata::read(path)).field(token),
+                        _ => None,
+                    }
+                });
+            }
+            Ok((Some(regex), None)) => {
+                if let Some(replacement) = &self.replace {
+                    result = regex.replace(name, replacement.as_str()).into_owned();
+                }
+            }
+            _ => {}
+        }
+        let (mut stem, extension) = name::split_extension(&result);
+        if let Some(prefix) = self.strip_prefix.as_deref() {
+            stem = stem.strip_prefix(prefix).unwrap_or(stem);
+        }
+        if let Some(suffix) = self.strip_suffix.as_deref() {
+            stem = stem.strip_suffix(suffix).unwrap_or(stem);
+        }
+        let mut result = format!("{}{}", stem, extension);
+        if self.style == Some(NamingStyle::External) {
+            result = self.run_command(&result);
+        } else if let Some(style) = &self.style {
+            let styled = |preserve: bool| media::around(&result, tokens, |name| match self.extension {
+                ExtensionCase::Style => style_name(name, style, config, preserve),
+                ExtensionCase::Keep | ExtensionCase::Lowercase => {
+                    let (stem, extension) = name::split_extensions(name);
+                    format!("{}{}", style_name(stem, style, config, preserve), extension)
+                }
+            });
+            let preserved = styled(true);
+            result = if config.keeps_whole(&preserved) { preserved } else { styled(false) };
+        }
+        if self.extension == ExtensionCase::Lowercase {
+            let (stem, extension) = name::split_extensions(&result);
+            result = format!("{}{}", stem, extension.to_ascii_lowercase());
+        }
+        // Names that already carry them don't get them twice on a re-run
+        if self.add_prefix.is_some() || self.add_suffix.is_some() {
+            let (stem, extension) = name::split_extension(&result);
+            let prefix = self.add_prefix.as_deref().filter(|p| !stem.starts_with(p)).unwrap_or_default();
+            let suffix = self.add_suffix.as_deref().filter(|s| !stem.ends_with(s)).unwrap_or_default();
+            result = format!("{}{}{}{}", prefix, stem, suffix, extension);
+        }
+        result
+    }
+    
+    /// `name` as `command` rewrites it; as it is, with a warning, if the
+    /// command fails.
+    fn run_command(&self, name: &str) -> String {
+        let Some(command) = self.command.as_deref() else {
+            return name.to_string();
+        };
+        if let Some(new) = self.commanded.0.lock().unwrap_or_else(|e| e.into_inner()).get(name) {
+            return new.clone();
+        }
+        let new = external::transform(command, name).unwrap_or_else(|e| {
+            warn!("behavior {}: {}; leaving {} as it is", self.label(), e, name);
+            name.to_string()
+        });
+        self.commanded.0.lock().unwrap_or_else(|e| e.into_inner()).insert(name.to_string(), new.clone());
+        new
+    }
+    
+    /// The number of `path` among the files this behavior selects in its
+    /// directory, taken in `counter.order`; `counter.start` for a file
+    /// that isn't on disk.
+    fn counter(&self, path: &Path, config: &Config) -> u64 {
+        let options = &self.counter;
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return options.start;
+        };
+        let mut counters = self.counters.0.lock().unwrap_or_else(|e| e.into_inner());
+        let names = counters.entry(dir.to_path_buf()).or_insert_with(|| {
+            let mut files: Vec<(Option<SystemTime>, String)> = fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| self.matches(name, &dir.join(name), config))
+                .map(|name| {
+                    let modified = match options.order {
+                        CounterOrder::Name => None,
+                        CounterOrder::Mtime => fs::metadata(dir.join(&name)).and_then(|m| m.modified()).ok(),
+                    };
+                    (modified, name)
+                })
+                .collect();
+            // Files modified at the same time go by name
+            files.sort();
+            files.into_iter().map(|(_, name)| name).collect()
+        });
+        let name = name.to_string_lossy();
+        let index = names.iter().position(|n| *n == name).unwrap_or(0) as u64;
+        options.start.saturating_add(index.saturating_mul(options.step))
+    }
+    
+    /// How the behavior selects names, for messages.
+    fn label(&self) -> String {
+        match self.regex_source() {
+            Some(regex) => format!("regex '{}'", regex),
+            None => format!("'{}'", self.pattern),
+        }
+    }
+    
+    /// What the behavior does to the names it selects, in order.
+    fn actions(&self) -> Vec<String> {
+        let mut actions = Vec::new();
+        if let Some(template) = &self.template {
+            actions.push(format!("\"{}\"", template));
+        } else if let Some(replacement) = &self.replace {
+            actions.push(format!("replace with \"{}\"", replacement));
+        }
+        if let Some(prefix) = &self.strip_prefix {
+            actions.push(format!("strip prefix \"{}\"", prefix));
+        }
+        if let Some(suffix) = &self.strip_suffix {
+            actions.push(format!("strip suffix \"{}\"", suffix));
+        }
+        match (&self.style, &self.command) {
+            (Some(NamingStyle::External), Some(command)) => actions.push(format!("external \"{}\"", command)),
+            (Some(style), _) => actions.push(style.as_str().to_string()),
+            (None, _) => {}
+        }
+        if self.extension == ExtensionCase::Lowercase {
+            actions.push(String::from("lowercase extension"));
+        }
+        if let Some(prefix) = &self.add_prefix {
+            actions.push(format!("add prefix \"{}\"", prefix));
+        }
+        if let Some(suffix) = &self.add_suffix {
+            actions.push(format!("add suffix \"{}\"", suffix));
+        }
+        match self.timestamp {
+            Some(true) => actions.push(String::from("timestamp")),
+            Some(false) => actions.push(String::from("no timestamp")),
+            None => {}
+        }
+        actions
+    }
+    
+    /// What the behavior does, for messages.
+    fn action(&self) -> String {
+        let actions = self.actions();
+        if actions.is_empty() {
+            String::from("no change")
+        } else {
+            actions.join(", ")
+        }
+    }
+}
+
+/// How a behavior's {counter} numbers the files it selects in a directory.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CounterOptions {
+    /// Number of the first file
+    pub start: u64,
+    /// Added for each file after it
+    pub step: u64,
+    /// Digits the number is zero-padded to, unless the token gives a
+    /// width as in {counter:03}
+    pub width: usize,
+    /// Which file comes first
+    pub order: CounterOrder,
+}
+
+impl Default for CounterOptions {
+    fn default() -> Self {
+        CounterOptions { start: 1, step: 1, width: 0, order: CounterOrder::Name }
+    }
+}
+
+impl CounterOptions {
+    fn is_default(&self) -> bool {
+        *self == CounterOptions::default()
+    }
+}
+
+/// The order {counter} numbers files in.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CounterOrder {
+    /// By file name
+    #[default]
+    Name,
+    /// Oldest modification time first, as photos were taken
+    Mtime,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternType {
+    #[default]
+    Glob,
+    Regex,
+}
+
+impl PatternType {
+    fn is_glob(&self) -> bool {
+        *self == PatternType::Glob
+    }
+}
+
+/// What a behavior's pattern is matched against.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchOn {
+    #[default]
+    Name,
+    Path,
+}
+
+impl MatchOn {
+    fn is_name(&self) -> bool {
+        *self == MatchOn::Name
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtensionCase {
+    #[default]
+    Keep,
+    Lowercase,
+    Style,
+}
+
+/// The date a timestamp prefix is made of.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampSource {
+    /// Today's date
+    #[default]
+    Now,
+    /// When the file was last modified
+    Mtime,
+    /// When the file's metadata last changed (on Unix; else the mtime)
+    Ctime,
+    /// When the file was created, where the filesystem records it; else
+    /// the mtime
+    #[value(alias = "birthtime")]
+    #[serde(alias = "birthtime")]
+    Created,
+    /// When a photo was taken, from its EXIF data; the modification time
+    /// for files without any
+    Exif,
+}
+
+impl TimestampSource {
+    fn is_now(&self) -> bool {
+        *self == TimestampSource::Now
+    }
+}
+
+/// Extensions as they should be written, for every file.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtensionRules {
+    /// `.JPG` becomes `.jpg`
+    pub lowercase: bool,
+
+    /// Extensions (without the dot, matched regardless of case) and what
+    /// to write instead, e.g. { jpeg = "jpg", htm = "html", yml = "yaml" }
+    pub remap: BTreeMap<String, String>,
+}
+
+impl ExtensionRules {
+    /// `name` with its extension written as these rules say.
+    pub fn apply(&self, name: &str) -> String {
+        let (stem, extension) = name::split_extensions(name);
+        if extension.is_empty() || (!self.lowercase && self.remap.is_empty()) {
+            return name.to_string();
+        }
+        let bare = &extension[1..];
+        let remapped = self.remap.iter().find(|(from, _)| from.eq_ignore_ascii_case(bare)).map(|(_, to)| to.trim_start_matches('.'));
+        let mut extension = remapped.unwrap_or(bare).to_string();
+        if self.lowercase {
+            extension.make_ascii_lowercase();
+        }
+        format!("{}.{}", stem, extension)
+    }
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// What happens to names longer than `max_length`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthAction {
+    #[default]
+    Truncate,
+    Error,
+}
+
+const DEFAULT_TIMESTAMP_PATTERN: &str = "%Y_%m_%d";
+
+/// How a timestamp is written into names: `YYYY_MM_DD__name` by default.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimestampFormat {
+    /// strftime pattern, e.g. "%Y-%m-%d" or "%Y%m%dT%H%M%S"
+    pub pattern: String,
+
+    /// Between the timestamp and the rest of the name
+    pub separator: String,
+
+    /// "prefix" (the default) or "suffix", which goes before the extension
+    pub position: TimestampPosition,
+
+    /// "utc" (the default) or "local" time
+    pub timezone: Timezone,
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        TimestampFormat {
+            pattern: String::from(DEFAULT_TIMESTAMP_PATTERN),
+            separator: String::from("__"),
+            position: TimestampPosition::Prefix,
+            timezone: Timezone::Utc,
+        }
+    }
+}
+
+impl TimestampFormat {
+    fn is_default(&self) -> bool {
+        *self == TimestampFormat::default()
+    }
+
+    /// Why `pattern` can't be used, if it can't.
+    pub fn problem(&self) -> Option<String> {
+        let invalid = chrono::format::StrftimeItems::new(&self.pattern).any(|item| item == chrono::format::Item::Error);
+        invalid.then(|| format!("timestamp_format.pattern \"{}\" is not a valid strftime pattern; using \"{}\"", self.pattern, DEFAULT_TIMESTAMP_PATTERN))
+    }
+
+    fn write(&self, time: chrono::NaiveDateTime) -> String {
+        use std::fmt::Write as _;
+        let mut stamp = String::new();
+        if write!(stamp, "{}", time.format(&self.pattern)).is_err() {
+            return time.format(DEFAULT_TIMESTAMP_PATTERN).to_string();
+        }
+        stamp
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampPosition {
+    #[default]
+    Prefix,
+    Suffix,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Timezone {
+    #[default]
+    Utc,
+    Local,
+}
+
+impl ExtensionCase {
+    fn is_keep(&self) -> bool {
+        *self == ExtensionCase::Keep
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+pub enum NamingStyle {
+    #[serde(rename = "camelCase")]
+    CamelCase,
+    #[serde(rename = "snake_case")]
+    SnakeCase,
+    #[serde(rename = "kebab-case")]
+    KebabCase,
+    #[serde(rename = "PascalCase")]
+    PascalCase,
+    #[serde(rename = "SCREAMING_SNAKE_CASE")]
+    ScreamingSnakeCase,
+    /// Words separated by spaces, which replace_spaces leaves alone
+    #[serde(rename = "Title Case")]
+    TitleCase,
+    #[serde(rename = "dot.case")]
+    DotCase,
+    #[serde(rename = "lowercase")]
+    Lowercase,
+    #[serde(rename = "UPPERCASE")]
+    Uppercase,
+    /// The name as a behavior's `command` rewrites it; other styles are
+    /// applied in namefmt itself
+    #[serde(rename = "external")]
+    External,
+}
+
+impl NamingStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NamingStyle::CamelCase => "camelCase",
+            NamingStyle::SnakeCase => "snake_case",
+            NamingStyle::KebabCase => "kebab-case",
+            NamingStyle::PascalCase => "PascalCase",
+            NamingStyle::ScreamingSnakeCase => "SCREAMING_SNAKE_CASE",
+            NamingStyle::TitleCase => "Title Case",
+            NamingStyle::DotCase => "dot.case",
+            NamingStyle::Lowercase => "lowercase",
+            NamingStyle::Uppercase => "UPPERCASE",
+            NamingStyle::External => "external",
+        }
+    }
+    
+    /// Parse a style as written in the config, e.g. "snake_case".
+    pub fn from_name(name: &str) -> Option<NamingStyle> {
+        match name {
+            "camelCase" => Some(NamingStyle::CamelCase),
+            "snake_case" => Some(NamingStyle::SnakeCase),
+            "kebab-case" => Some(NamingStyle::KebabCase),
+            "PascalCase" => Some(NamingStyle::PascalCase),
+            "SCREAMING_SNAKE_CASE" => Some(NamingStyle::ScreamingSnakeCase),
+            "Title Case" => Some(NamingStyle::TitleCase),
+            "dot.case" => Some(NamingStyle::DotCase),
+            "lowercase" => Some(NamingStyle::Lowercase),
+            "UPPERCASE" => Some(NamingStyle::Uppercase),
+            "external" => Some(NamingStyle::External),
+            _ => None,
+        }
+    }
+}
+
+/// What a run does about files that no behavior matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Suggestions {
+    /// Only the defaults (replace_spaces) apply
+    #[default]
+    Off,
+    /// Print a suggested name next to the plan
+    Show,
+    /// Rename them to the suggested name
+    Apply,
+}
+
+/// How a run walks the target directory.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TraversalRules {
+    /// Deepest level looked at; files directly in the target are at depth 1
+    pub max_depth: Option<usize>,
+    /// Shallowest level renamed, e.g. 2 to leave the target's own files alone
+    pub min_depth: Option<usize>,
+    /// Follow symbolic links, renaming links to files and walking into
+    /// linked directories
+    pub follow_symlinks: bool,
+    /// Leave files and directories whose names start with a dot alone,
+    /// and don't walk into such directories (.git, .cache)
+    pub skip_hidden: bool,
+}
+
+impl TraversalRules {
+    fn skips(&self, entry: &walkdir::DirEntry) -> bool {
+        self.skip_hidden && entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.')
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DetectionRules {
+    #[serde(default = "default_exe_extensions")]
+    pub exe_extensions: Vec<String>,
+
+    #[serde(default = "default_package_dirs")]
+    pub package_dirs: Vec<String>,
+
+    /// Read the start of files to tell their type, so ELF, Mach-O and PE
+    /// binaries are named as executables whatever their extension
+    #[serde(default)]
+    pub sniff: bool,
+
+    /// Count files with an execute permission bit (on Unix) or a `#!`
+    /// line as executables, whatever their extension
+    #[serde(default)]
+    pub unix_executables: bool,
+
+    /// The style each class of detected file is named in
+    #[serde(default)]
+    pub styles: ClassStyles,
+
+    #[serde(skip)]
+    packages: PackageCache,
+
+    #[serde(skip)]
+    mime_types: MimeCache,
+}
+
+/// What detection found a file to be.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileClass {
+    /// A program: an `exe_extensions` file, a binary or a file marked executable
+    Executable,
+    /// A text file starting with a `#!` line
+    Script,
+    /// A directory holding one of `package_dirs`, or a file in one
+    Package,
+}
+
+impl FileClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileClass::Executable => "executable",
+            FileClass::Script => "script",
+            FileClass::Package => "package",
+        }
+    }
+}
+
+/// The style detected files are named in, by class; kebab-case for each
+/// unless set otherwise.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ClassStyles {
+    #[serde(default = "default_class_style")]
+    pub executable: NamingStyle,
+
+    #[serde(default = "default_class_style")]
+    pub script: NamingStyle,
+
+    #[serde(default = "default_class_style")]
+    pub package: NamingStyle,
+}
+
+impl ClassStyles {
+    pub fn of(&self, class: FileClass) -> NamingStyle {
+        match class {
+            FileClass::Executable => self.executable,
+            FileClass::Script => self.script,
+            FileClass::Package => self.package,
+        }
+    }
+}
+
+impl Default for ClassStyles {
+    fn default() -> Self {
+        ClassStyles {
+            executable: default_class_style(),
+            script: default_class_style(),
+            package: default_class_style(),
+        }
+    }
+}
+
+fn default_class_style() -> NamingStyle {
+    NamingStyle::KebabCase
+}
+
+/// Whether each directory looked at holds one of `package_dirs`, so the
+/// files of a large directory don't each look for the same package files.
+#[derive(Debug, Default)]
+struct PackageCache(std::sync::Mutex<HashMap<PathBuf, bool>>);
+
+impl Clone for PackageCache {
+    fn clone(&self) -> Self {
+        PackageCache::default()
+    }
+}
+
+/// The type of each file whose content was looked at, so behaviors
+/// selecting by type read a file once however many of them there are.
+#[derive(Debug, Default)]
+struct MimeCache(std::sync::Mutex<HashMap<PathBuf, Option<&'static str>>>);
+
+impl Clone for MimeCache {
+    fn clone(&self) -> Self {
+        MimeCache::default()
+    }
+}
+
+/// The directory the current run renames below, which `match = "path"`
+/// behaviors match paths relative to.
+#[derive(Debug, Default)]
+struct RunRoot(std::sync::RwLock<Option<PathBuf>>);
+
+impl Clone for RunRoot {
+    fn clone(&self) -> Self {
+        RunRoot(std::sync::RwLock::new(self.0.read().unwrap_or_else(|e| e.into_inner()).clone()))
+    }
+}
+
+impl DetectionRules {
+    fn known(&self, dir: &Path) -> Option<bool> {
+        self.packages.0.lock().unwrap_or_else(|e| e.into_inner()).get(dir).copied()
+    }
+    
+    fn is_package_dir(&self, dir: &Path) -> bool {
+        self.known(dir).unwrap_or_else(|| {
+            let package = self.package_dirs.iter().any(|file| dir.join(file).exists());
+            self.packages.0.lock().unwrap_or_else(|e| e.into_inner()).insert(dir.to_path_buf(), package);
+            package
+        })
+    }
+    
+    /// Learn from an entry met while walking, so walked directories need
+    /// no lookups of their own: a directory being walked into holds no
+    /// package files until one of its entries turns out to be one.
+    fn note(&self, entry: &walkdir::DirEntry, descends: bool) {
+        let mut known = self.packages.0.lock().unwrap_or_else(|e| e.into_inner());
+        if descends {
+            known.entry(entry.path().to_path_buf()).or_insert(false);
+        }
+        let name = entry.file_name().to_string_lossy();
+        if entry.depth() > 0 && self.package_dirs.iter().any(|file| *file == name) {
+            if let Some(parent) = entry.path().parent() {
+                known.insert(parent.to_path_buf(), true);
+            }
+        }
+    }
+    
+    /// The MIME type of the file at `path`, from its content.
+    fn mime_type(&self, path: &Path) -> Option<&'static str> {
+        if let Some(&mime) = self.mime_types.0.lock().unwrap_or_else(|e| e.into_inner()).get(path) {
+            return mime;
+        }
+        let mime = magic::mime_type(path);
+        self.mime_types.0.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf(), mime);
+        mime
+    }
+    
+    /// Detect by name alone, for names that needn't be files below the
+    /// current directory: only `exe_extensions` count, and no directory or
+    /// file is looked at.
+    pub fn names_only(&mut self) {
+        self.package_dirs.clear();
+        self.sniff = false;
+        self.unix_executables = false;
+    }
+    
+    /// Drop what was learned about directories and files, which may have
+    /// changed since, e.g. between the runs of `namefmt watch`.
+    fn forget(&self) {
+        self.packages.0.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.mime_types.0.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+impl Default for DetectionRules {
+    fn default() -> Self {
+        DetectionRules {
+            exe_extensions: default_exe_extensions(),
+            package_dirs: default_package_dirs(),
+            sniff: false,
+            unix_executables: false,
+            styles: ClassStyles::default(),
+            packages: PackageCache::default(),
+            mime_types: MimeCache::default(),
+        }
+    }
+}
+
+fn default_replace_spaces() -> bool {
+    true
+}
+
+fn default_protected() -> bool {
+    true
+}
+
+fn default_exe_extensions() -> Vec<String> {
+    vec!["exe".to_string(), "bin".to_string(), "app".to_string()]
+}
+
+fn default_package_dirs() -> Vec<String> {
+    vec!["package.json".to_string(), "Cargo.toml".to_string(), "pyproject.toml".to_string()]
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            replace_spaces: true,
+            behaviors: Vec::new(),
+            chain: false,
+            timestamp: false,
+            timestamp_source: TimestampSource::Now,
+            timestamp_format: TimestampFormat::default(),
+            transliterate: false,
+            acronyms: Vec::new(),
+            preserve: PreserveRules::default(),
+            dates: DateRules::default(),
+            junk: JunkRules::default(),
+            separators: SeparatorRules::default(),
+            max_length: None,
+            max_length_unit: LengthUnit::Bytes,
+            max_length_action: LengthAction::Truncate,
+            paths: false,
+            platform: Platform::default(),
+            presets: Vec::new(),
+            protected: Vec::new(),
+            default_protected: default_protected(),
+            ignore: Vec::new(),
+            gitignore: false,
+            git: false,
+            traversal: TraversalRules::default(),
+            cache: false,
+            detection: DetectionRules::default(),
+            sync_conflicts: SyncConflictRules::default(),
+            duplicates: DuplicatePolicy::default(),
+            partial_downloads: PartialDownloadRules::default(),
+            pairs: PairRules::default(),
+            subtitles: SubtitleRules::default(),
+            episodes: EpisodeRules::default(),
+            media_tokens: MediaTokens::default(),
+            extensions: ExtensionRules::default(),
+            documents: DocumentRules::default(),
+            build_references: BuildReferenceRules::default(),
+            sidecars: Vec::new(),
+            profile: BTreeMap::new(),
+            notify: NotifyRules::default(),
+            registry: None,
+            max_renames: None,
+            guardrails: GuardRules::default(),
+            metrics: MetricsRules::default(),
+            shard: None,
+            threads: None,
+            filters: Filters::default(),
+            suggestions: Suggestions::Off,
+            interactive: false,
+            edit: false,
+            keep_going: false,
+            root: RunRoot::default(),
+        }
+    }
+}
+
+/// Settings that don't decide what files are renamed to, left out of
+/// `Config::naming_rules`.
+const NOT_NAMING_RULES: &[&str] = &[
+    "ignore", "gitignore", "git", "traversal", "profile", "notify", "registry", "max_renames", "guardrails", "metrics",
+];
+
+impl Config {
+    /// The settings that decide names, which plans and the identity cache
+    /// are tied to (see `plan::config_hash`).
+    pub fn naming_rules(&self) -> toml::Table {
+        let mut rules = toml::Table::try_from(self).unwrap_or_default();
+        rules.retain(|key, _| !NOT_NAMING_RULES.contains(&key));
+        rules
+    }
+    
+    /// Settings a run can use but not as written: behaviors that can't
+    /// compile or do nothing, a timestamp pattern strftime rejects.
+    pub fn problems(&self) -> Vec<String> {
+        self.behaviors.iter().filter_map(Behavior::problem).chain(self.timestamp_format.problem()).chain(self.dates.problem()).chain(self.junk.problems()).collect()
+    }
+    
+    /// Whether `name` is short enough not to be cut down. Text is only
+    /// preserved in styled names that are: cut in half, a date or version
+    /// would be styled like any other text the next time.
+    fn keeps_whole(&self, name: &str) -> bool {
+        let max = self.max_length.filter(|_| self.max_length_action == LengthAction::Truncate);
+        name.len() <= name::MAX_NAME_BYTES && max.is_none_or(|max| name::length(name, self.max_length_unit) <= max)
+    }
+    
+    /// Record the directory a run renames below.
+    fn set_root(&self, root: &Path) {
+        *self.root.0.write().unwrap_or_else(|e| e.into_inner()) = Some(root.to_path_buf());
+    }
+    
+    /// `path` below the directory the run renames, with `/` separators;
+    /// outside a run, `path` as given.
+    fn relative_path(&self, path: &Path) -> String {
+        let root = self.root.0.read().unwrap_or_else(|e| e.into_inner());
+        let relative = root.as_deref().and_then(|root| path.strip_prefix(root).ok()).unwrap_or(path);
+        relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/")
+    }
+}
+
+/// The configuration written on first run and by `namefmt config init`:
+/// the defaults, with the most used settings shown commented out.
+pub fn get_default_config_toml() -> String {
+    r#"# namefmt configuration. `namefmt config validate` checks this file and
+# `namefmt config show` prints the settings in effect.
+
+# Replace spaces in names with underscores
+replace_spaces = true
+
+# Prefix names with the date: "now", or the file's "mtime", "ctime",
+# "created" or photo "exif" time
+# timestamp = true
+# timestamp_source = "mtime"
+
+# Spell names in ASCII (é to e, Ж to Zh)
+# transliterate = true
+
+# Versions (v1.2.3), ISO dates (2024-01-05) and hashes (3f2a9c1) are kept as
+# written when names are styled; turn any of them off here
+# [preserve]
+# hashes = false
+
+# Rewrite dates already in names (05-01-2024, Jan 5 2024, 20240105) to one
+# form; 05-01-2024 is read as 5 January unless month_first is set
+# [dates]
+# normalize = true
+# format = "%Y_%m_%d"
+
+# Take "Copy of ", " - Copy", " (1)", wget's ".1", "final_final" and
+# [www.site.com] tags out of names, plus anything the patterns match
+# [junk]
+# strip = true
+# patterns = ["^DRAFT[ _-]"]
+
+# Runs of separators (My__Report) are written once and separators trimmed
+# from the ends of names (Report_.txt); styles listed under except are left be
+# [separators]
+# trim = false
+# except = ["dot.case"]
+
+# Keep names valid on "posix" or "windows" filesystems
+# platform = "windows"
+
+# Cut names longer than this many bytes, keeping the extension
+# max_length = 255
+
+# Built-in presets: "scanner", "audiobook", "shards", or a convention pack
+# presets = ["rust"]
+
+# Names (globs) never renamed or descended into, besides README*, LICENSE*,
+# Makefile, Cargo.toml, .gitignore and other names conventions depend on
+# protected = ["node_modules", "*.lock"]
+# default_protected = false
+
+# Paths a run doesn't look at, in .gitignore syntax
+# ignore = ["target/", "*.log"]
+
+# How deep to look, and whether to follow symlinks and skip dotfiles
+# [traversal]
+# max_depth = 3
+# follow_symlinks = false
+# skip_hidden = true
+
+# Rules tried in order; the first one matching a name decides it, unless it
+# sets chain = true to hand its result on to the next one that matches
+# [[behaviors]]
+# pattern = "*.md"
+# style = "kebab-case"
+#
+# Matched against the path below the directory being renamed instead
+# [[behaviors]]
+# pattern = "src/**/*.rs"
+# match = "path"
+# style = "snake_case"
+#
+# Selecting files by what their content is
+# [[behaviors]]
+# mime = "image/*"
+# style = "snake_case"
+#
+# Music named from its tags
+# [[behaviors]]
+# mime = "audio/*"
+# template = "{artist} - {track} - {title}.{ext}"
+#
+# and videos from when they were recorded
+# [[behaviors]]
+# mime = "video/*"
+# template = "{created:%Y_%m_%d}_{resolution}.{ext}"
+#
+# Named by a program of your own, given the name on stdin
+# [[behaviors]]
+# pattern = "*.log"
+# style = "external"
+# command = "sed s/draft/final/"
+
+# Settings laid over the rest with --profile photos
+# [profile.photos]
+# timestamp = true
+# timestamp_source = "exif"
+
+[detection]
+exe_extensions = ["exe", "bin", "app"]
+package_dirs = ["package.json", "Cargo.toml", "pyproject.toml"]
+# Name ELF, Mach-O and PE binaries as executables whatever their extension,
+# and scripts and files marked executable too
+# sniff = true
+# unix_executables = true
+
+# The style each class of detected file is named in
+# [detection.styles]
+# executable = "kebab-case"
+# package = "snake_case"
+# script = "lowercase"
+"#.to_string()
+}
+
+/// Read a configuration file, failing instead of falling back to defaults.
+pub fn read_config(config_path: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))?;
+    parse_config(&content, config_path, &[], None, false).map_err(|e| e.into())
+}
+
+/// Check a configuration file with nothing to fall back on: syntax and
+/// type errors come back with the line and column they're at, and each
+/// `[profile.*]` table is also checked laid over the rest of the file. `Ok`
+/// holds the problems a run would only warn about.
+pub fn validate_config(config_path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))?;
+    let invalid = |e: toml::de::Error| format!("{} is not valid: {}", config_path.display(), layers::explain(&e));
+    let config: Config = toml::from_str(&content).map_err(invalid)?;
+    let mut problems = config.problems();
+    
+    let mut base: toml::Table = content.parse().map_err(invalid)?;
+    for (name, profile) in layers::take_profiles(&mut base) {
+        let toml::Value::Table(profile) = profile else {
+            continue;
+        };
+        let config: Config = toml::Value::Table(layers::overlay(base.clone(), profile)).try_into()
+            .map_err(|e| format!("{} is not valid: in [profile.{}]: {}", config_path.display(), name, layers::explain(&e)))?;
+        // The profile's own problems; the rest were reported above
+        let new: Vec<String> = config.problems().into_iter().filter(|problem| !problems.contains(problem)).collect();
+        problems.extend(new.into_iter().map(|problem| format!("[profile.{}]: {}", name, problem)));
+    }
+    Ok(problems)
+}
+
+/// Parse the user's configuration, with the project configurations (see
+/// `layers::read_projects`) and then `profile` laid over it, and the whole
+/// laid over the system configuration. Keys namefmt doesn't know are an
+/// error, or when `lenient` are dropped with a warning.
+fn parse_config(content: &str, config_path: &Path, projects: &[(PathBuf, toml::Table)], profile: Option<&str>, lenient: bool) -> Result<Config, String> {
+    if !lenient {
+        // The file as written first, so a mistake is reported at its line and column
+        toml::from_str::<Config>(content)
+            .map_err(|e| format!("Failed to parse {}: {}", config_path.display(), layers::explain(&e)))?;
+    }
+    let mut user = content.parse::<toml::Table>()
+        .map_err(|e| format!("Failed to parse {}: {}", config_path.display(), e))?;
+    for (_, project) in projects {
+        user = layers::overlay(user, project.clone());
+    }
+    let mut profiles = layers::take_profiles(&mut user);
+    if let Some(toml::Value::Table(profile)) = profile.and_then(|name| profiles.remove(name)) {
+        user = layers::overlay(user, profile);
+    }
+    let sources = std::iter::once(config_path).chain(projects.iter().map(|(path, _)| path.as_path()))
+        .map(|path| path.display().to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    let mut table = match layers::read_system() {
+        Ok(Some((system_path, system))) => {
+            let (merged, overridden) = layers::merge(system, user);
+            for key in overridden {
+                warn!("{} is enforced by {}; ignoring the value in {}", key, system_path.display(), sources);
+            }
+            merged
+        }
+        Ok(None) => user,
+        Err(e) => {
+            warn!("{}; ignoring the system configuration", e);
+            user
+        }
+    };
+    if lenient {
+        for key in layers::prune_unknown(&mut table) {
+            warn!("Ignoring unknown setting {} in {}", key, sources);
+        }
+    }
+    toml::Value::Table(table).try_into()
+        .map_err(|e| format!("Failed to parse {}: {}", sources, layers::explain(&e)))
+}
+
+/// Read a configuration file, writing the default one if there is none,
+/// ignoring settings namefmt doesn't know and falling back to defaults
+/// (with a warning) if it can't be used.
+pub fn load_config(config_path: &Path) -> Config {
+    load_layered(config_path, &[], None, true).unwrap_or_default()
+}
+
+/// The configuration for a run on `target`: the user's configuration, with
+/// the `.namefmt.toml` files in and above `target` laid over it, nearest
+/// last, and then the named `[profile.<name>]` table. A profile none of
+/// them defines is an error, as is a file namefmt can't make sense of
+/// unless `lenient`, which behaves like `load_config`.
+pub fn load_config_for(config_path: &Path, target: &Path, profile: Option<&str>, lenient: bool) -> Result<Config, Box<dyn std::error::Error>> {
+    let projects = layers::read_projects(target, lenient)?;
+    if let Some(name) = profile {
+        let mut sources = vec![config_path.to_path_buf()];
+        sources.extend(projects.iter().map(|(path, _)| path.clone()));
+        let mut defined: Vec<String> = fs::read_to_string(config_path).ok()
+            .and_then(|content| content.parse::<toml::Table>().ok())
+            .into_iter()
+            .chain(projects.iter().map(|(_, table)| table.clone()))
+            .flat_map(|mut table| layers::take_profiles(&mut table).into_iter().map(|(name, _)| name))
+            .collect();
+        defined.sort();
+        defined.dedup();
+        if !defined.iter().any(|defined| defined == name) {
+            let sources: Vec<String> = sources.iter().map(|path| path.display().to_string()).collect();
+            let known = if defined.is_empty() { String::from("none are defined") } else { format!("defined: {}", defined.join(", ")) };
+            return Err(format!("No profile \"{}\" in {} ({})", name, sources.join(", "), known).into());
+        }
+    }
+    Ok(load_layered(config_path, &projects, profile, lenient)?)
+}
+
+fn load_layered(config_path: &Path, projects: &[(PathBuf, toml::Table)], profile: Option<&str>, lenient: bool) -> Result<Config, String> {
+    if !config_path.exists() {
+        // Create parent directory if it doesn't exist
+        if let Some(parent) = config_path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                warn!("Failed to create config directory {}: {}", parent.display(), e);
+                note!("Using default configuration");
+                return Ok(fallback_config(projects, profile));
+            }
+        }
+        
+        // Write default config
+        let default_config = get_default_config_toml();
+        if let Err(e) = fs::write(config_path, &default_config) {
+            warn!("Failed to write default config to {}: {}", config_path.display(), e);
+            note!("Using default configuration");
+            return Ok(fallback_config(projects, profile));
+        }
+    }
+    
+    let parsed = fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))
+        .and_then(|content| parse_config(&content, config_path, projects, profile, lenient));
+    match parsed {
+        Ok(config) => Ok(config),
+        Err(e) if lenient => {
+            warn!("{}", e);
+            note!("Using default configuration");
+            Ok(fallback_config(projects, profile))
+        }
+        Err(e) => Err(format!("{}\n(--lenient ignores unknown settings and falls back to the defaults on other errors)", e)),
+    }
+}
+
+/// Defaults laid under the system-wide configuration, for when the user's
+/// configuration can't be used.
+fn fallback_config(projects: &[(PathBuf, toml::Table)], profile: Option<&str>) -> Config {
+    let defaults = Path::new("the default configuration");
+    parse_config("", defaults, projects, profile, true).or_else(|_| parse_config("", defaults, &[], None, true)).unwrap_or_default()
+}
+
+/// The timestamp for the file at `path`, in `format`'s pattern and zone.
+/// EXIF times carry no zone and are written as recorded.
+fn timestamp_for(path: &Path, source: TimestampSource, format: &TimestampFormat) -> String {
+    let metadata = fs::metadata(path).ok();
+    let modified = || metadata.as_ref()?.modified().ok().map(chrono::DateTime::<chrono::Utc>::from);
+    let time = match source {
+        TimestampSource::Now => None,
+        TimestampSource::Mtime => modified(),
+        TimestampSource::Ctime => changed(metadata.as_ref()).or_else(modified),
+        TimestampSource::Created => metadata.as_ref().and_then(|m| m.created().ok()).map(chrono::DateTime::from).or_else(modified),
+        TimestampSource::Exif => match exif::capture_time(path) {
+            Some(taken) => return format.write(taken),
+            None => modified(),
+        },
+    };
+    let time = time.unwrap_or_else(chrono::Utc::now);
+    format.write(match format.timezone {
+        Timezone::Utc => time.naive_utc(),
+        Timezone::Local => time.with_timezone(&chrono::Local).naive_local(),
+    })
+}
+
+/// Whether `path` is a file with one of its execute permission bits set.
+#[cfg(unix)]
+fn is_marked_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_marked_executable(_path: &Path) -> bool {
+    false
+}
+
+/// When the inode last changed.
+#[cfg(unix)]
+fn changed(metadata: Option<&fs::Metadata>) -> Option<chrono::DateTime<chrono::Utc>> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = metadata?;
+    chrono::DateTime::from_timestamp(metadata.ctime(), metadata.ctime_nsec() as u32)
+}
+
+#[cfg(not(unix))]
+fn changed(_metadata: Option<&fs::Metadata>) -> Option<chrono::DateTime<chrono::Utc>> {
+    None
+}
+
+/// The new name for the file at `path`, currently called `name`, or `None`
+/// if it keeps its name.
+pub fn format_filename(name: &str, config: &Config, path: &Path, timestamp: bool) -> Option<SanitizedName> {
+    let (result, _) = format_filename_with_rule(name, config, path, timestamp);
+    
+    if result != name {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+/// Format a name and describe which rule decided the result.
+pub fn format_filename_with_rule(name: &str, config: &Config, path: &Path, timestamp: bool) -> (SanitizedName, String) {
+    // Characters the platform can't store are replaced before styling, so
+    // styling the result again changes nothing
+    let sanitized = SanitizedName::new(name, config.platform);
+    let folded = config.transliterate.then(|| translit::to_ascii(sanitized.as_str()));
+    let name = folded.as_deref().unwrap_or(sanitized.as_str());
+    
+    // Rotation suffixes (`.log.1`, `.2024-01-31.log.gz`) are left exactly as written
+    let (name, rotation) = logs::split_rotation(name).unwrap_or((name, ""));
+    // Junk is taken out and dates put in one form before behaviors see the name
+    let stripped = config.junk.apply(name);
+    let dated = config.dates.apply(&stripped);
+    let name = dated.as_str();
+    // Separators are tidied before styling as well as after, so styling
+    // sees the words and extension it will see in the name it makes
+    let detected = file_class(path, config).map(|class| (class, config.detection.styles.of(class)));
+    let planned = match detected {
+        Some((_, style)) => Some(style),
+        None => config.behaviors.iter().find(|b| b.matches(name, path, config)).and_then(|b| b.style),
+    };
+    let mut result = config.separators.apply(name, planned);
+    let mut rule = String::from("replace_spaces");
+    let mut style = None;
+    let mut stamped = None;
+    
+    // Executables, scripts and packages are named in their class's style
+    if let Some((class, class_style)) = detected {
+        let styled = |preserve: bool| style_name(&result, &class_style, config, preserve);
+        let preserved = styled(true);
+        result = if config.keeps_whole(&preserved) { preserved } else { styled(false) };
+        if config.replace_spaces && class_style != NamingStyle::TitleCase {
+            result = result.replace(' ', "_");
+        }
+        rule = format!("{} ({})", class.as_str(), class_style.as_str());
+        style = Some(class_style);
+    } else {
+        let tokens = config.media_tokens.for_file(path);
+        // Apply pattern-based behaviors: the first that matches, and the
+        // ones after it that match its result for as long as they chain
+        let mut keeps_spaces = false;
+        let mut applied = Vec::new();
+        for behavior in &config.behaviors {
+            if behavior.matches(&result, path, config) {
+                result = behavior.apply(&result, path, tokens, config);
+                applied.push(format!("behavior {}", behavior.label()));
+                // A behavior without a style leaves the one before it in effect
+                if behavior.style.is_some() {
+                    // Title Case names and what a command wrote keep their spaces
+                    keeps_spaces = matches!(behavior.style, Some(NamingStyle::TitleCase | NamingStyle::External));
+                    style = behavior.style;
+                }
+                stamped = behavior.timestamp.or(stamped);
+                if !behavior.chain.unwrap_or(config.chain) {
+                    break;
+                }
+            }
+        }
+        if !applied.is_empty() {
+            rule = applied.join(" + ");
+        }
+        
+        // Default: replace spaces with underscores
+        if config.replace_spaces && !keeps_spaces {
+            result = result.replace(' ', "_");
+        }
+        result = media::normalize(&result, tokens);
+    }
+    
+    // A name made only of separators styles to nothing; keep it instead
+    if result.is_empty() {
+        result = name.to_string();
+    }
+    result = config.extensions.apply(&result);
+    let format = &config.timestamp_format;
+    let timestamp = stamped.map_or(timestamp, |stamped| stamped && !path.is_dir());
+    let stamp = timestamp.then(|| timestamp_for(path, config.timestamp_source, format));
+    // Room for the timestamp and rotation suffix comes out of the stem
+    if let Some(max) = config.max_length.filter(|_| config.max_length_action == LengthAction::Truncate) {
+        let unit = config.max_length_unit;
+        let stamped = stamp.as_ref().map_or(0, |stamp| name::length(stamp, unit) + name::length(&format.separator, unit));
+        result = name::shorten(&result, max.saturating_sub(stamped + name::length(rotation, unit)), unit);
+    }
+    // Cut names too long for any filesystem here rather than when they are
+    // sanitized, which would leave the separator at the cut for trimming
+    let room = name::MAX_NAME_BYTES.saturating_sub(stamp.as_ref().map_or(0, |stamp| stamp.len() + format.separator.len()) + rotation.len());
+    if config.separators.trim && result.len() > room {
+        result = name::shorten(&result, room, LengthUnit::Bytes);
+    }
+    // and tidy what a cut leaves as the next run would see it, with what
+    // now reads as an extension or rotation suffix split off
+    let (base, suffix) = logs::split_rotation(&result).unwrap_or((&result, ""));
+    result = config.separators.apply(base, style) + suffix;
+    // A suffix goes before the extension, and before any rotation suffix
+    if let Some(stamp) = stamp.as_ref().filter(|_| format.position == TimestampPosition::Suffix) {
+        let (stem, extension) = name::split_extensions(&result);
+        result = format!("{}{}{}{}", stem, format.separator, stamp, extension);
+    }
+    
+    // Shorten the base rather than cut into the rotation suffix
+    if !rotation.is_empty() {
+        name::truncate_end(&mut result, name::MAX_NAME_BYTES.saturating_sub(rotation.len()));
+    }
+    result.push_str(rotation);
+    
+    // A prefix goes on last
+    if let Some(stamp) = stamp {
+        if format.position == TimestampPosition::Prefix {
+            result = format!("{}{}{}", stamp, format.separator, result);
+        }
+        rule.push_str(" + timestamp");
+    }
+    
+    (SanitizedName::new(&result, config.platform), rule)
+}
+
+/// Hold every new name to `max_length`: shorten the ones that are longer,
+/// or refuse the whole plan if `max_length_action` is "error".
+fn limit_lengths(renames: Vec<Rename>, config: &Config) -> Result<Vec<Rename>, Box<dyn std::error::Error>> {
+    let Some(max) = config.max_length else {
+        return Ok(renames);
+    };
+    let unit = config.max_length_unit;
+    let name_of = |path: &Path| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    if config.max_length_action == LengthAction::Error {
+        let long: Vec<String> = renames.iter()
+            .map(|r| name_of(&r.target))
+            .filter(|name| name::length(name, unit) > max)
+            .collect();
+        if !long.is_empty() {
+            let unit = if unit == LengthUnit::Chars { "characters" } else { "bytes" };
+            let examples: String = long.iter().take(5).map(|name| format!("\n    {}", name)).collect();
+            return Err(format!("{} new name(s) longer than max_length ({} {}), nothing was renamed:{}", long.len(), max, unit, examples).into());
+        }
+        return Ok(renames);
+    }
+    Ok(renames.into_iter()
+        .filter_map(|mut rename| {
+            let name = name_of(&rename.target);
+            let shortened = SanitizedName::new(&name::shorten(&name, max, unit), config.platform);
+            if shortened.as_str() != name {
+                rename.target.set_file_name(shortened.as_str());
+            }
+            (rename.target != rename.source).then_some(rename)
+        })
+        .collect())
+}
+
+/// The style that decides the name of the file at `path`, if a style does:
+/// the style of its class for executables, scripts and packages, else the
+/// style of the first behavior that matches.
+pub fn style_for(path: &Path, config: &Config) -> Option<NamingStyle> {
+    if let Some(class) = file_class(path, config) {
+        return Some(config.detection.styles.of(class));
+    }
+    let name = path.file_name()?.to_string_lossy();
+    let mut sanitized = SanitizedName::new(&name, config.platform).into_string();
+    if config.transliterate {
+        sanitized = translit::to_ascii(&sanitized);
+    }
+    let (name, _) = logs::split_rotation(&sanitized).unwrap_or((&sanitized, ""));
+    config.behaviors.iter().find(|b| b.matches(name, path, config)).and_then(|b| b.style)
+}
+
+/// What detection makes of the file at `path`, if anything.
+fn file_class(path: &Path, config: &Config) -> Option<FileClass> {
+    // Check if file has exe extension
+    if let Some(ext) = path.extension() {
+        let ext_str = ext.to_string_lossy().to_lowercase();
+        if config.detection.exe_extensions.iter().any(|e| e.to_lowercase() == ext_str) {
+            return Some(FileClass::Executable);
+        }
+    }
+    // or is a binary, whatever its extension
+    if config.detection.sniff && config.detection.mime_type(path).is_some_and(magic::is_executable) {
+        return Some(FileClass::Executable);
+    }
+    // or may be run: a script, or anything marked executable
+    if config.detection.unix_executables {
+        if config.detection.mime_type(path) == Some(magic::SCRIPT) {
+            return Some(FileClass::Script);
+        }
+        if is_marked_executable(path) {
+            return Some(FileClass::Executable);
+        }
+    }
+    
+    // Check if directory contains package files; a walked directory is
+    // already known to be one
+    let package = if let Some(package) = config.detection.known(path) {
+        package
+    } else if path.is_dir() {
+        config.detection.is_package_dir(path)
+    } else {
+        path.parent().is_some_and(|parent| config.detection.is_package_dir(parent))
+    };
+    package.then_some(FileClass::Package)
+}
+
+/// Shell-style glob matching by globset: `*` and `?` never cross a `/`,
+/// `**` matches any number of whole path segments, `[a-z]` / `[!0-9]` are
+/// classes and `{jpg,png}` alternatives. Each pattern is compiled once; one
+/// globset rejects only matches itself, spelled out exactly.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    static COMPILED: LazyLock<Mutex<HashMap<String, Option<GlobMatcher>>>> = LazyLock::new(Mutex::default);
+    let matcher = COMPILED.lock().unwrap_or_else(|e| e.into_inner())
+        .entry(pattern.to_string())
+        .or_insert_with(|| GlobBuilder::new(pattern).literal_separator(true).build().ok().map(|glob| glob.compile_matcher()))
+        .clone();
+    match matcher {
+        Some(matcher) => matcher.is_match(text),
+        None => pattern == text,
+    }
+}
+
+/// Whether the glob `pattern` selects `name`, the name being given to the
+/// file at `path`. Patterns with a `/` match the end of the path, so
+/// `src/**/*.rs` selects Rust files anywhere under a `src` directory;
+/// other patterns match the name alone, and a pattern without wildcards
+/// selects every name containing it.
+fn matches_pattern(name: &str, path: &Path, pattern: &str) -> bool {
+    if pattern.contains('/') {
+        let full = path.with_file_name(name).to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
+        if pattern.starts_with('/') || pattern.starts_with("**/") {
+            glob_match(pattern, &full)
+        } else {
+            glob_match(&format!("**/{}", pattern), &full)
+        }
+    } else if pattern.contains(['*', '?', '[', '{']) {
+        glob_match(pattern, name)
+    } else {
+        name.contains(pattern)
+    }
+}
+
+/// Convert `name` to `style`, extension included.
+pub fn apply_style(name: &str, style: &NamingStyle) -> String {
+    match style {
+        NamingStyle::CamelCase => to_camel_case(name),
+        NamingStyle::SnakeCase => to_snake_case(name),
+        NamingStyle::KebabCase => to_kebab_case(name),
+        NamingStyle::PascalCase => to_pascal_case(name),
+        NamingStyle::ScreamingSnakeCase => restyle(name, |words| words.join("_").to_uppercase()),
+        NamingStyle::TitleCase => to_title_case(name),
+        NamingStyle::DotCase => restyle(name, |words| words.join(".").to_lowercase()),
+        NamingStyle::Lowercase => name.to_lowercase(),
+        NamingStyle::Uppercase => name.to_uppercase(),
+        // Only a behavior knows the command to run
+        NamingStyle::External => name.to_string(),
+    }
+}
+
+/// First private-use character; the configured acronym `i` stands in as
+/// `ACRONYM + 2 * i` (`+ 1` in the plural) while a name is being styled.
+const ACRONYM: u32 = 0xF000;
+
+/// `apply_style`, with every word of the name that is one of `acronyms`,
+/// or one in the plural (`IDs`), written exactly as listed.
+pub fn apply_style_with(name: &str, style: &NamingStyle, acronyms: &[String]) -> String {
+    if acronyms.is_empty() {
+        return apply_style(name, style);
+    }
+    let find = |word: &str| acronyms.iter().position(|acronym| acronym.eq_ignore_ascii_case(word));
+    let mut hidden = String::new();
+    let mut at = 0;
+    for range in word_ranges(name) {
+        let word = &name[range.clone()];
+        let found = find(word).map(|i| 2 * i).or_else(|| find(word.strip_suffix('s')?).map(|i| 2 * i + 1));
+        if let Some(placeholder) = found.and_then(|i| char::from_u32(ACRONYM + i as u32)) {
+            hidden.push_str(&name[at..range.start]);
+            hidden.push(placeholder);
+            at = range.end;
+        }
+    }
+    hidden.push_str(&name[at..]);
+    let mut styled = String::new();
+    for c in apply_style(&hidden, style).chars() {
+        let i = (c as u32).checked_sub(ACRONYM).map(|i| i as usize);
+        match i.and_then(|i| Some((acronyms.get(i / 2)?, i % 2 == 1))) {
+            Some((acronym, plural)) => {
+                styled.push_str(acronym);
+                if plural {
+                    styled.push('s');
+                }
+            }
+            None => styled.push(c),
+        }
+    }
+    styled
+}
+
+/// `apply_style_with` the config's acronyms, and when `preserve` leaving
+/// the versions, dates and hashes it preserves as written.
+fn style_name(name: &str, style: &NamingStyle, config: &Config, preserve: bool) -> String {
+    let styled = |name: &str| apply_style_with(name, style, &config.acronyms);
+    if preserve { config.preserve.around(name, styled) } else { styled(name) }
+}
+
+/// Characters standing in for acronyms and media tokens while a name is
+/// styled; each is a word of its own.
+fn is_placeholder(c: char) -> bool {
+    ('\u{E000}'..='\u{F8FF}').contains(&c) && !preserve::is_placeholder(c)
+}
+
+/// Words of a name: split at spaces, `_`, `-` and where a lowercase letter
+/// is followed by an uppercase one (`myFile` -> `my`, `File`). Acronyms
+/// stay whole, and end before the capital of a word that follows them
+/// (`HTTPServer` -> `HTTP`, `Server`) unless all that follows is a plural
+/// `s` (`PDFs`). Letters without the other case don't count, or restyling
+/// would find new words in its own output.
+fn words(s: &str) -> Vec<&str> {
+    word_ranges(s).into_iter().map(|range| &s[range]).collect()
+}
+
+fn word_ranges(s: &str) -> Vec<Range<usize>> {
+    let lower = |c: char| c.is_lowercase() && !c.to_uppercase().eq([c]);
+    let upper = |c: char| c.is_uppercase() && !c.to_lowercase().eq([c]);
+    let is_separator = |c: char| matches!(c, ' ' | '_' | '-');
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let mut ranges = Vec::new();
+    let mut start: Option<usize> = None;
+    for (k, &(i, c)) in chars.iter().enumerate() {
+        if is_separator(c) {
+            ranges.extend(start.take().map(|start| start..i));
+            continue;
+        }
+        let next = chars.get(k + 1).map(|&(_, c)| c);
+        let after = chars.get(k + 2).map(|&(_, c)| c);
+        let plural = next == Some('s') && !after.is_some_and(lower);
+        let boundary = start.is_some() && k > 0 && {
+            let previous = chars[k - 1].1;
+            (upper(c) && lower(previous))
+                // `Version2Final`, but not `3D`
+                || (upper(c) && (previous.is_ascii_digit() || preserve::is_placeholder(previous)) && next.is_some_and(lower))
+                || (upper(c) && upper(previous) && next.is_some_and(lower) && !plural)
+                || is_placeholder(c)
+                || is_placeholder(previous)
+        };
+        if boundary {
+            ranges.extend(start.map(|start| start..i));
+            start = Some(i);
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    ranges.extend(start.map(|start| start..s.len()));
+    ranges
+}
+
+/// Rebuild the words of `s` with `join`, keeping leading and trailing
+/// separators as they are (`__init__`, or a name cut short at a `_`).
+fn restyle(s: &str, join: impl Fn(Vec<&str>) -> String) -> String {
+    let is_separator = |c: char| matches!(c, ' ' | '_' | '-');
+    let core = s.trim_matches(is_separator);
+    if core.is_empty() {
+        return s.to_string();
+    }
+    let start = s.len() - s.trim_start_matches(is_separator).len();
+    let end = start + core.len();
+    format!("{}{}{}", &s[..start], join(words(core)), &s[end..])
+}
+
+/// `word` with its first letter in title case: `ß` becomes `Ss`, not an
+/// `SS` that would read as an acronym, and a title case letter such as
+/// `ǅ` stays as it is.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) if !first.is_lowercase() && !first.is_uppercase() => word.to_string(),
+        Some(first) => {
+            let mut upper = first.to_uppercase();
+            upper.next().into_iter().chain(upper.flat_map(char::to_lowercase)).chain(chars).collect()
+        }
+        None => String::new(),
+    }
+}
+
+/// `my file-name` -> `MyFileName`
+pub fn to_pascal_case(s: &str) -> String {
+    restyle(s, |words| words.into_iter().map(capitalize).collect())
+}
+
+/// Short words left lowercase inside a title.
+const TITLE_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "nor", "of", "on", "or", "the", "to", "vs", "with",
+];
+
+/// `the lord_of-the rings` -> `The Lord of the Rings`: words capitalized
+/// and separated by spaces, short stop words lowercase except first and last.
+pub fn to_title_case(s: &str) -> String {
+    restyle(s, |words| {
+        let last = words.len().saturating_sub(1);
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                let lower = word.to_lowercase();
+                if i != 0 && i != last && TITLE_STOP_WORDS.contains(&lower.as_str()) {
+                    lower
+                } else {
+                    capitalize(word)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    })
+}
+
+/// `My File-name` -> `myFileName`; names already in camelCase are kept.
+pub fn to_camel_case(s: &str) -> String {
+    // Already camelCase: lowercasing the first word again would flatten it
+    let trimmed = s.trim_start_matches([' ', '_', '-']);
+    if !trimmed.contains([' ', '_', '-']) && !trimmed.chars().next().is_some_and(has_lowercase) {
+        return trimmed.to_string();
+    }
+    
+    let mut words = words(s).into_iter();
+    let first = words.next().map(str::to_lowercase).unwrap_or_default();
+    words.fold(first, |result, word| result + &capitalize(word))
+}
+
+/// True for letters that change when lowercased.
+fn has_lowercase(ch: char) -> bool {
+    ch.to_lowercase().next() != Some(ch)
+}
+
+/// `My File-name` -> `my_file_name`, `HTTPServer` -> `http_server`
+pub fn to_snake_case(s: &str) -> String {
+    restyle(s, |words| words.join("_").to_lowercase())
+}
+
+/// `My File_name` -> `my-file-name`
+pub fn to_kebab_case(s: &str) -> String {
+    restyle(s, |words| words.join("-").to_lowercase())
+}
+
+/// Formats names with a fixed configuration; see [`FormatterBuilder`].
+#[derive(Debug, Clone)]
+pub struct Formatter {
+    config: Config,
+    /// `config` without package detection, for names that aren't files on disk
+    detached: Config,
+}
+
+impl Formatter {
+    pub fn builder() -> FormatterBuilder {
+        FormatterBuilder { config: Config::default() }
+    }
+
+    /// A formatter for a full configuration, e.g. one from [`read_config`].
+    /// Its presets are expanded here.
+    pub fn new(config: Config) -> Formatter {
+        let config = expand_presets(config);
+        let mut detached = config.clone();
+        detached.detection.package_dirs.clear();
+        Formatter { config, detached }
+    }
+
+    /// Format a bare file name; protected names come back unchanged.
+    /// Package detection, which looks at the file's directory, only applies
+    /// through [`Formatter::format_path`].
+    pub fn format(&self, name: &str) -> SanitizedName {
+        self.format_with_rule(name).0
+    }
+
+    /// Format a bare file name and describe which rule decided the result.
+    pub fn format_with_rule(&self, name: &str) -> (SanitizedName, String) {
+        if is_protected(Path::new(name), &self.config) {
+            return (SanitizedName::new(name, self.config.platform), String::from("protected name"));
+        }
+        format_filename_with_rule(name, &self.detached, Path::new(name), self.config.timestamp)
+    }
+
+    /// The new name for the file at `path`, or `None` if it keeps its name.
+    pub fn format_path(&self, path: &Path) -> Option<SanitizedName> {
+        if is_protected(path, &self.config) {
+            return None;
+        }
+        let name = path.file_name()?.to_string_lossy();
+        format_filename(&name, &self.config, path, self.config.timestamp)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+/// Builds a [`Formatter`], starting from the default configuration.
+///
+/// ```
+/// use namefmt::{Formatter, NamingStyle};
+///
+/// let formatter = Formatter::builder()
+///     .style("*.md", NamingStyle::KebabCase)
+///     .protect("README*")
+///     .build();
+/// assert_eq!(formatter.format("Release Notes.md"), "release-notes.md");
+/// assert_eq!(formatter.format("README.md"), "README.md");
+/// assert_eq!(formatter.format("Draft notes.txt"), "Draft_notes.txt");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FormatterBuilder {
+    config: Config,
+}
+
+impl FormatterBuilder {
+    /// Start from `config` instead of the defaults.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Add a behavior; the first one matching a name decides its style.
+    pub fn behavior(mut self, behavior: Behavior) -> Self {
+        self.config.behaviors.push(behavior);
+        self
+    }
+
+    /// Style names matching the glob `pattern`.
+    pub fn style(self, pattern: &str, style: NamingStyle) -> Self {
+        self.behavior(Behavior::styled(pattern, style))
+    }
+
+    /// Leave names matching the glob `pattern` alone.
+    pub fn protect(mut self, pattern: &str) -> Self {
+        self.config.protected.push(pattern.to_string());
+        self
+    }
+
+    /// Enable a built-in preset or convention pack, e.g. "rust".
+    pub fn preset(mut self, name: &str) -> Self {
+        self.config.presets.push(name.to_string());
+        self
+    }
+
+    pub fn replace_spaces(mut self, replace_spaces: bool) -> Self {
+        self.config.replace_spaces = replace_spaces;
+        self
+    }
+
+    /// The platform whose file name rules results must follow.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.config.platform = platform;
+        self
+    }
+
+    /// Prefix names with today's date.
+    pub fn timestamp(mut self, timestamp: bool) -> Self {
+        self.config.timestamp = timestamp;
+        self
+    }
+
+    pub fn build(self) -> Formatter {
+        Formatter::new(self.config)
+    }
+}
+
+/// Resolve the root directory and the files to consider for a target path.
+pub fn collect_target<'a>(path: &'a Path, config: &Config) -> Result<(&'a Path, Vec<PathBuf>), Box<dyn std::error::Error>> {
+    if path.is_file() {
+        let files = if config.filters.matches(path) { vec![path.to_path_buf()] } else { Vec::new() };
+        Ok((path.parent().unwrap_or(Path::new(".")), files))
+    } else if path.is_dir() {
+        Ok((path, collect_files(path, config)?))
+    } else {
+        Err(format!("Path does not exist: {}", path.display()).into())
+    }
+}
+
+/// Add the rules of the presets `config` enables: built-in convention packs,
+/// then packs installed with `namefmt preset install`. They come after the
+/// configured behaviors, so those still win.
+pub fn expand_presets(mut config: Config) -> Config {
+    // The names every pack protects are protected by default already
+    let packs: Vec<presets::Pack> = config.presets.iter().filter_map(|name| presets::pack(name)).collect();
+    for pack in packs {
+        config.protected.extend(pack.protected.iter().map(|p| p.to_string()));
+        for (pattern, style) in pack.behaviors {
+            let style = NamingStyle::from_name(style).unwrap_or(NamingStyle::KebabCase);
+            config.behaviors.push(Behavior::styled(pattern, style));
+        }
+    }
+    // Installed packs come last, after the built-in ones
+    let installed: Vec<String> = config.presets.iter().filter(|name| presets::pack(name).is_none()).cloned().collect();
+    for name in installed {
+        let pack = match registry::load(&name) {
+            Some(Ok(content)) => toml::from_str::<InstalledPack>(&content).map_err(|e| e.to_string()),
+            Some(Err(e)) => Err(e),
+            None => continue,
+        };
+        match pack {
+            Ok(pack) => {
+                config.protected.extend(pack.protected);
+                config.behaviors.extend(pack.behaviors);
+            }
+            Err(e) => warn!("Ignoring preset '{}': {}", name, e),
+        }
+    }
+    config
+}
+
+/// A preset pack installed with `namefmt preset install`: behaviors and
+/// protected names, like a built-in convention pack.
+#[derive(Debug, Deserialize)]
+pub struct InstalledPack {
+    #[serde(default)]
+    pub behaviors: Vec<Behavior>,
+    #[serde(default)]
+    pub protected: Vec<String>,
+}
+
+fn is_protected(path: &Path, config: &Config) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        // namefmt's own files in the tree have to keep their names to work
+        .is_some_and(|name| name == layers::PROJECT_CONFIG
+            || name == ignore::IGNORE_FILE
+            || config.protected.iter().any(|p| glob_match(p, &name))
+            || (config.default_protected && presets::common_protected().iter().any(|p| glob_match(p, &name))))
+}
+
+pub fn build_plan(path: &Path, config: &Config, timestamp: bool, cache: Option<&IdentityCache>) -> Result<Plan, Box<dyn std::error::Error>> {
+    config.detection.forget();
+    let started = std::time::Instant::now();
+    let (root, mut files) = collect_target(path, config)?;
+    config.set_root(root);
+    if let Some(shard) = config.shard {
+        files.retain(|file| shard.contains(root, file));
+    }
+    debug!("Found {} file(s) under {} in {:.2?}", files.len(), root.display(), started.elapsed());
+    for template in [config.episodes.compile(), config.documents.compile()] {
+        if let Some(Err(e)) = template {
+            warn!("{}", e);
+        }
+    }
+    for problem in config.problems() {
+        warn!("{}", problem);
+    }
+    // --threads 0 or unset leaves the pool at one thread per CPU
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(config.threads.unwrap_or(0)).build()?;
+    let threads = pool.current_num_threads();
+    let started = std::time::Instant::now();
+    
+    // Files settled by an earlier run with the same rules need no decision
+    let mut skipped = Vec::new();
+    let mut candidates = Vec::new();
+    for file in &files {
+        if is_protected(file, config) {
+            skipped.push((file.clone(), String::from("protected name")));
+        } else if cache.is_some_and(|c| c.is_settled(file)) {
+            skipped.push((file.clone(), String::from("unchanged since last run")));
+        } else if config.partial_downloads.is_partial(file) && config.partial_downloads.completed_name(file).is_none() {
+            skipped.push((file.clone(), String::from("download in progress")));
+        } else {
+            candidates.push(file.clone());
+        }
+    }
+    
+    let preset_renames = presets::plan(&config.presets, &candidates);
+    let document_renames = config.documents.plan(&candidates);
+    
+    // Each file is decided on its own, so they are spread over threads;
+    // the results keep the order of the files
+    let renames: Vec<Option<Rename>> = pool.install(|| candidates.par_iter().map(|file| {
+        match (preset_renames.get(file), document_renames.get(file)) {
+            (Some((new_name, preset)), _) => Some(Rename {
+                source: file.clone(),
+                target: file.with_file_name(new_name),
+                rule: format!("preset '{}'", preset),
+            }),
+            (None, Some(new_name)) => Some(Rename {
+                source: file.clone(),
+                target: file.with_file_name(new_name),
+                rule: String::from("document content"),
+            }),
+            (None, None) => plan_file(root, file, config, timestamp),
+        }
+    }).collect());
+    let mut renames: Vec<Rename> = renames.into_iter().flatten().collect();
+    
+    let reaches: Vec<bool> = pool.install(|| candidates.par_iter().map(|f| {
+        !preset_renames.contains_key(f) && !document_renames.contains_key(f) && reaches_behaviors(root, f, config, timestamp)
+    }).collect());
+    let styled: Vec<&PathBuf> = candidates.iter().zip(reaches).filter(|(_, reaches)| *reaches).map(|(f, _)| f).collect();
+    let mut suggestions = match config.suggestions {
+        Suggestions::Off => Vec::new(),
+        Suggestions::Show | Suggestions::Apply => suggest_renames(&files, &styled, &renames, config, timestamp),
+    };
+    if config.suggestions == Suggestions::Apply {
+        let suggested: HashSet<PathBuf> = suggestions.iter().map(|s| s.source.clone()).collect();
+        renames.retain(|r| !suggested.contains(&r.source));
+        renames.append(&mut suggestions);
+        renames.sort_by(|a, b| a.source.cmp(&b.source));
+    }
+    
+    let (renames, unpaired) = pairs::link(&config.pairs, &candidates, renames);
+    skipped.extend(unpaired);
+    let (renames, orphaned) = pairs::follow_sidecars(&config.sidecars, &candidates, renames);
+    skipped.extend(orphaned);
+    let (renames, unmatched) = pairs::follow_subtitles(&config.subtitles, &candidates, renames);
+    skipped.extend(unmatched);
+    
+    let renames = if config.duplicates == DuplicatePolicy::Off {
+        renames
+    } else {
+        let compared = std::time::Instant::now();
+        let sets = pool.install(|| dupes::find(&candidates));
+        debug!("Found {} set(s) of duplicates in {:.2?}", sets.len(), compared.elapsed());
+        dupes::apply(config.duplicates, &sets, renames, &mut skipped)
+    };
+    
+    let mut renames = sanitize_targets(renames, config.platform);
+    if config.paths {
+        let dirs = if path.is_dir() { collect_dirs(path, config)? } else { Vec::new() };
+        renames.extend(plan_dirs(root, &files, &dirs, config));
+    }
+    
+    let renames = limit_lengths(renames, config)?;
+    
+    let sources: Vec<&Path> = renames.iter().map(|r| r.source.as_path()).collect();
+    config.build_references.check(root, &files, &sources);
+    
+    let coverage = behavior_coverage(&styled, config);
+    let suggestions = sanitize_targets(suggestions, config.platform);
+    debug!("Planned {} rename(s) on {} thread(s) in {:.2?}", renames.len(), threads, started.elapsed());
+    
+    Ok(Plan { root: root.to_path_buf(), files, renames, skipped, coverage, suggestions })
+}
+
+/// Names for files that reach the behaviors but match none of them: styled
+/// the way most other files in the same directory are or, when no style
+/// clearly dominates there, snake_case for names written in no style at
+/// all. Files the plan already gives the suggested name get no suggestion.
+fn suggest_renames(files: &[PathBuf], styled: &[&PathBuf], renames: &[Rename], config: &Config, timestamp: bool) -> Vec<Rename> {
+    let mut by_dir: HashMap<&Path, Vec<PathBuf>> = HashMap::new();
+    for file in files {
+        by_dir.entry(file.parent().unwrap_or(Path::new(""))).or_default().push(file.clone());
+    }
+    let planned: HashMap<&Path, &Path> = renames.iter().map(|r| (r.source.as_path(), r.target.as_path())).collect();
+    let mut siblings: HashMap<&Path, learn::Siblings> = HashMap::new();
+    // The suggested style becomes the only behavior, so everything else
+    // (rotation suffixes, timestamp, sanitizing) works as in a real run
+    let mut restyle = Config { behaviors: Vec::new(), ..config.clone() };
+    
+    let mut suggestions = Vec::new();
+    for file in styled {
+        let Some(name) = file.file_name().map(|n| n.to_string_lossy()) else {
+            continue;
+        };
+        let (base, _) = logs::split_rotation(&name).unwrap_or((&name, ""));
+        if config.behaviors.iter().any(|b| b.matches(base, file, config)) {
+            continue;
+        }
+        let dir = file.parent().unwrap_or(Path::new(""));
+        let around = siblings.entry(dir).or_insert_with(|| learn::Siblings::new(&by_dir[dir])).style_around(file);
+        let (style, reason) = match around.and_then(NamingStyle::from_name) {
+            Some(style) => (style, "like most files in its directory"),
+            // A name already written in some style is left to the user
+            None if !learn::unstyled(file) => continue,
+            None => (NamingStyle::SnakeCase, "the default"),
+        };
+        let rule = format!("suggestion: {}, {}", style.as_str(), reason);
+        restyle.behaviors = vec![Behavior::styled("*", style)];
+        let (new_name, _) = format_filename_with_rule(&name, &restyle, file, timestamp);
+        let target = file.with_file_name(new_name.as_str());
+        if target == **file || planned.get(file.as_path()) == Some(&target.as_path()) {
+            continue;
+        }
+        suggestions.push(Rename { source: file.to_path_buf(), target, rule });
+    }
+    suggestions
+}
+
+/// Renames for `dirs` and the directories between `root` and `files`,
+/// deepest first. Each is planned under its parent's current name, so
+/// applying them in order after the files inside have been renamed works
+/// bottom-up. The timestamp prefix is for files only.
+fn plan_dirs(root: &Path, files: &[PathBuf], dirs: &[PathBuf], config: &Config) -> Vec<Rename> {
+    let mut dirs: BTreeSet<&Path> = dirs.iter().map(PathBuf::as_path).collect();
+    for file in files {
+        dirs.extend(file.ancestors().skip(1).take_while(|dir| *dir != root && dir.starts_with(root)));
+    }
+    let mut dirs: Vec<&Path> = dirs.into_iter().filter(|dir| !is_protected(dir, config)).collect();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    
+    dirs.into_iter()
+        .filter_map(|dir| {
+            let name = dir.file_name()?.to_string_lossy();
+            let (new_name, rule) = format_filename_with_rule(&name, config, dir, false);
+            (new_name != *name).then(|| Rename {
+                source: dir.to_path_buf(),
+                target: dir.with_file_name(new_name.as_str()),
+                rule: format!("{} (directory)", rule),
+            })
+        })
+        .collect()
+}
+
+/// Make every target name valid for `platform`, whichever rule produced
+/// it. Renames that sanitizing turns back into the original are dropped.
+fn sanitize_targets(renames: Vec<Rename>, platform: Platform) -> Vec<Rename> {
+    renames.into_iter()
+        .filter_map(|mut rename| {
+            let name = rename.target.file_name()?.to_string_lossy();
+            let sanitized = SanitizedName::new(&name, platform);
+            if sanitized.as_str() != name {
+                rename.target.set_file_name(sanitized.as_str());
+            }
+            (rename.target != rename.source).then_some(rename)
+        })
+        .collect()
+}
+
+/// True if `plan_file` gets as far as the configured behaviors for this
+/// file rather than settling it with a more specific rule first.
+fn reaches_behaviors(root: &Path, file_path: &Path, config: &Config, timestamp: bool) -> bool {
+    let Some(name) = file_path.file_name().map(|n| n.to_string_lossy()) else {
+        return false;
+    };
+    !config.partial_downloads.is_partial(file_path)
+        && sync_conflict_target(root, file_path, &name, config, timestamp).is_none()
+        && config.episodes.rename(file_path).is_none()
+        && file_class(file_path, config).is_none()
+}
+
+fn behavior_coverage(files: &[&PathBuf], config: &Config) -> Vec<Coverage> {
+    let mut coverage: Vec<Coverage> = config.behaviors.iter()
+        .map(|b| Coverage { rule: format!("behavior {} ({})", b.label(), b.action()), decided: 0, shadowed: 0 })
+        .collect();
+    for file in files {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let (name, _) = logs::split_rotation(&name).unwrap_or((&name, ""));
+        let mut matching = config.behaviors.iter().enumerate()
+            .filter(|(_, b)| b.matches(name, file, config))
+            .map(|(i, _)| i);
+        if let Some(first) = matching.next() {
+            coverage[first].decided += 1;
+        }
+        for later in matching {
+            coverage[later].shadowed += 1;
+        }
+    }
+    coverage
+}
+
+pub fn collect_files(path: &Path, config: &Config) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    for entry in walk(path, config) {
+        let Some(entry) = walked(entry)? else {
+            continue;
+        };
+        if entry.file_type().is_file() && config.filters.matches(entry.path()) {
+            files.push(entry.into_path());
+        }
+        log::progress("Scanning", files.len(), None);
+    }
+    log::finish_progress();
+    Ok(files)
+}
+
+/// The directories below `path`, including empty ones, for --dirs.
+fn collect_dirs(path: &Path, config: &Config) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut dirs = Vec::new();
+    for entry in walk(path, config) {
+        let Some(entry) = walked(entry)? else {
+            continue;
+        };
+        if entry.file_type().is_dir() && entry.depth() > 0 {
+            dirs.push(entry.into_path());
+        }
+    }
+    Ok(dirs)
+}
+
+/// An entry of `walk`, or `None` for a symbolic link leading back up the
+/// tree, which is skipped with a warning rather than ending the run.
+fn walked(entry: walkdir::Result<walkdir::DirEntry>) -> Result<Option<walkdir::DirEntry>, walkdir::Error> {
+    match entry {
+        Ok(entry) => Ok(Some(entry)),
+        Err(e) if e.loop_ancestor().is_some() => {
+            warn!("{}", e);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Everything below `path` a run looks at, sorted by name.
+fn walk<'a>(path: &Path, config: &'a Config) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + 'a {
+    let quarantine = config.sync_conflicts.quarantine_root(path);
+    let mut ignore = Ignore::new(path, &config.ignore, config.gitignore);
+    let traversal = &config.traversal;
+    let mut walker = WalkDir::new(path).sort_by_file_name().follow_links(traversal.follow_symlinks);
+    if let Some(depth) = traversal.max_depth {
+        walker = walker.max_depth(depth);
+    }
+    // Safari's .download bundles are directories; never descend into them
+    walker.into_iter()
+        .filter_entry(move |e| {
+            let keep = e.path() != quarantine
+                && !traversal.skips(e)
+                && !(e.file_type().is_dir() && config.partial_downloads.is_partial(e.path()))
+                && !(e.file_type().is_dir() && e.depth() > 0 && is_protected(e.path(), config))
+                && !(e.depth() > 0 && ignore.is_ignored(e.path(), e.file_type().is_dir()));
+            // Every entry of a directory passes through here, skipped or not
+            let descends = keep && e.file_type().is_dir() && traversal.max_depth.is_none_or(|max| e.depth() < max);
+            config.detection.note(e, descends);
+            keep
+        })
+        // Not walkdir's min_depth, which hides the levels above it from
+        // the filter, so hidden, ignored and protected directories there
+        // would be walked into
+        .filter(move |entry| entry.as_ref().map_or(true, |e| traversal.min_depth.is_none_or(|min| e.depth() >= min)))
+}
+
+fn plan_file(root: &Path, file_path: &Path, config: &Config, timestamp: bool) -> Option<Rename> {
+    let name = file_path.file_name()?.to_string_lossy();
+    let parent = file_path.parent()?;
+    let rename = |target: PathBuf, rule: String| Some(Rename { source: file_path.to_path_buf(), target, rule });
+    
+    if config.partial_downloads.is_partial(file_path) {
+        // Still downloading (or not yet settled): leave it alone
+        let completed = config.partial_downloads.completed_name(file_path)?;
+        let new_name = format_filename(&completed, config, file_path, timestamp).map_or(completed, SanitizedName::into_string);
+        return rename(parent.join(new_name), String::from("partial download cleanup"));
+    }
+    
+    if let Some(target) = sync_conflict_target(root, file_path, &name, config, timestamp) {
+        return rename(target, String::from("sync conflict"));
+    }
+    
+    // The configured episode scheme is final; no further formatting
+    if let Some(new_name) = config.episodes.rename(file_path) {
+        if new_name == name {
+            return None;
+        }
+        return rename(parent.join(new_name), String::from("episode template"));
+    }
+    
+    let (new_name, rule) = format_filename_with_rule(&name, config, file_path, timestamp);
+    if new_name == *name {
+        return None;
+    }
+    rename(parent.join(new_name.as_str()), rule)
+}
+
+fn sync_conflict_target(root: &Path, file_path: &Path, name: &str, config: &Config, timestamp: bool) -> Option<PathBuf> {
+    let policy = config.sync_conflicts.policy;
+    if policy == ConflictPolicy::Ignore {
+        return None;
+    }
+    let conflict = conflicts::detect(name)?;
+    
+    match policy {
+        ConflictPolicy::Rename => {
+            let renamed = conflict.renamed();
+            let new_name = format_filename(&renamed, config, file_path, timestamp).map_or(renamed, SanitizedName::into_string);
+            Some(file_path.with_file_name(new_name))
+        }
+        ConflictPolicy::Quarantine => {
+            // Keep the relative layout so same-named conflicts from different folders don't collide
+            let relative = file_path.strip_prefix(root).unwrap_or(Path::new(name));
+            Some(config.sync_conflicts.quarantine_root(root).join(relative))
+        }
+        ConflictPolicy::Ignore => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use name::tests::{assert_valid, names};
+    use proptest::prelude::*;
+
+    fn configs() -> Vec<Config> {
+        let mut configs = Vec::new();
+        for platform in [Platform::Posix, Platform::Windows] {
+            for replace_spaces in [true, false] {
+                let styles = [
+                    None, Some(NamingStyle::CamelCase), Some(NamingStyle::SnakeCase), Some(NamingStyle::KebabCase),
+                    Some(NamingStyle::PascalCase), Some(NamingStyle::ScreamingSnakeCase), Some(NamingStyle::TitleCase),
+                    Some(NamingStyle::DotCase), Some(NamingStyle::Lowercase), Some(NamingStyle::Uppercase),
+                ];
+                for style in styles {
+                    let behaviors = style.into_iter().map(|style| Behavior::styled("*", style)).collect();
+                    configs.push(Config { platform, replace_spaces, behaviors, ..Config::default() });
+                }
+            }
+        }
+        configs
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(20_000))]
+
+        #[test]
+        fn formatted_names_are_valid_and_stable(input in names(), config in proptest::sample::select(configs())) {
+            let path = Path::new("/nonexistent/namefmt-test/file");
+            let (once, rule) = format_filename_with_rule(&input, &config, path, false);
+            assert_valid(once.as_str(), config.platform, &input);
+            let (twice, _) = format_filename_with_rule(once.as_str(), &config, path, false);
+            prop_assert_eq!(twice, once, "{:?} via {} on {:?}", input, rule, config.platform);
+        }
+    }
+
+    #[test]
+    fn validation_points_at_the_offending_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.toml");
+        fs::write(&file, get_default_config_toml()).unwrap();
+        assert_eq!(validate_config(&file).unwrap(), Vec::<String>::new());
+        
+        fs::write(&file, "replace_spaces = true\n\n[profile.photos]\nmax_length = \"long\"\n").unwrap();
+        let error = validate_config(&file).unwrap_err().to_string();
+        assert!(error.contains("line 4, column 14"), "{}", error);
+    }
+    
+    #[test]
+    fn unknown_keys_are_named_or_dropped_when_lenient() {
+        let written = "behaviours = []\n\n[[behaviors]]\npattern = \"*\"\nstyel = \"snake_case\"\n";
+        let error = layers::explain(&toml::from_str::<Config>(written).unwrap_err());
+        assert!(error.contains("line 1, column 1") && error.contains("did you mean `behaviors`?"), "{}", error);
+        
+        let mut table: toml::Table = written.parse().unwrap();
+        assert_eq!(layers::prune_unknown(&mut table), ["behaviors.styel", "behaviours"]);
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.behaviors.len(), 1);
+    }
+    
+    #[test]
+    fn walked_directories_know_their_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("tool")).unwrap();
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::write(root.join("tool/Cargo.toml"), "").unwrap();
+        fs::write(root.join("notes/Read Me.txt"), "").unwrap();
+        let config = Config { ignore: vec!["Cargo.toml".to_string()], ..Config::default() };
+        config.detection.forget();
+        let files = collect_files(root, &config).unwrap();
+        let known = (config.detection.known(&root.join("tool")), config.detection.known(&root.join("notes")));
+        assert_eq!(files, [root.join("notes/Read Me.txt")]);
+        assert_eq!(known, (Some(true), Some(false)));
+    }
+
+    #[test]
+    fn detected_classes_take_their_configured_styles() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("My Tool")).unwrap();
+        fs::write(root.join("My Tool/package.json"), "{}").unwrap();
+        fs::write(root.join("Build Site"), "#!/bin/sh\n").unwrap();
+        let config: Config = toml::from_str(r#"
+            [detection]
+            unix_executables = true
+            styles = { package = "snake_case", script = "SCREAMING_SNAKE_CASE" }
+        "#).unwrap();
+        let format = |name: &str| {
+            let (name, rule) = format_filename_with_rule(name, &config, &root.join(name), false);
+            (name.into_string(), rule)
+        };
+        let (package, script, exe) = (format("My Tool"), format("Build Site"), format("Setup Tool.exe"));
+        assert_eq!(package, ("my_tool".to_string(), "package (snake_case)".to_string()));
+        assert_eq!(script, ("BUILD_SITE".to_string(), "script (SCREAMING_SNAKE_CASE)".to_string()));
+        assert_eq!(exe, ("setup-tool.exe".to_string(), "executable (kebab-case)".to_string()));
+    }
+
+    #[test]
+    fn styles_split_words_at_separators_and_case() {
+        assert_eq!(to_pascal_case("my file-name"), "MyFileName");
+        assert_eq!(apply_style("myFile name", &NamingStyle::ScreamingSnakeCase), "MY_FILE_NAME");
+        assert_eq!(to_title_case("the lord_of-the rings"), "The Lord of the Rings");
+        assert_eq!(to_title_case("what it's for"), "What It's For");
+        assert_eq!(apply_style("Quarterly Report-final", &NamingStyle::DotCase), "quarterly.report.final");
+        assert_eq!(apply_style("__init__", &NamingStyle::PascalCase), "__Init__");
+        assert_eq!(to_snake_case("HTTPServer.rs"), "http_server.rs");
+        assert_eq!(to_kebab_case("Version2Final PDFs 3D"), "version2-final-pdfs-3d");
+        assert_eq!(to_camel_case("XML HttpRequest"), "xmlHttpRequest");
+        let acronyms = ["ID".to_string(), "URL".to_string()];
+        assert_eq!(apply_style_with("user id urls", &NamingStyle::SnakeCase, &acronyms), "user_ID_URLs");
+        assert_eq!(apply_style_with("parseUrlId", &NamingStyle::PascalCase, &acronyms), "ParseURLID");
+    }
+
+    #[test]
+    fn conventional_names_are_protected_unless_turned_off() {
+        let config = Config::default();
+        for name in ["Makefile", "README.md", "Cargo.toml", ".gitignore", "requirements-dev.txt"] {
+            assert!(is_protected(&Path::new("/project").join(name), &config), "{}", name);
+        }
+        assert!(!is_protected(Path::new("/project/My Notes.txt"), &config));
+        let config = Config { default_protected: false, ..Config::default() };
+        assert!(!is_protected(Path::new("/project/Makefile"), &config));
+    }
+
+    #[test]
+    fn styles_leave_the_extension_alone() {
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let format = |name: &str, extension: ExtensionCase| {
+            let behavior = Behavior { extension, ..Behavior::styled("*", NamingStyle::SnakeCase) };
+            let config = Config { behaviors: vec![behavior], ..Config::default() };
+            format_filename_with_rule(name, &config, path, false).0
+        };
+        assert_eq!(format("MyPhoto.JPG", ExtensionCase::Keep), "my_photo.JPG");
+        assert_eq!(format("MyPhoto.JPG", ExtensionCase::Lowercase), "my_photo.jpg");
+        assert_eq!(format("MyPhoto.JPG", ExtensionCase::Style), "my_photo.jpg");
+        assert_eq!(format("Site Backup.TAR.GZ", ExtensionCase::Lowercase), "site_backup.tar.gz");
+        assert_eq!(format("v1.2 Release Notes", ExtensionCase::Keep), "v1.2_release_notes");
+    }
+
+    #[test]
+    fn media_tokens_survive_styling() {
+        let path = Path::new("/nonexistent/namefmt-test/movie.mkv");
+        let config = Config { behaviors: vec![Behavior::styled("*", NamingStyle::SnakeCase)], ..Config::default() };
+        let (once, _) = format_filename_with_rule("Some Movie 4k x265 HDR10 WEB-DL.mkv", &config, path, false);
+        assert_eq!(once, "some_movie_4K_x265_HDR10_WEB-DL.mkv");
+        assert_eq!(format_filename_with_rule(once.as_str(), &config, path, false).0, once);
+        // Only whole tokens count, and never the extension
+        let (name, _) = format_filename_with_rule("Hdrive mp3.mp3", &config, Path::new("/nonexistent/a.mp3"), false);
+        assert_eq!(name, "hdrive_MP3.mp3");
+    }
+
+    #[test]
+    fn behavior_patterns_are_globs() {
+        let path = Path::new("/home/me/project/src/ui/widget.test.js");
+        let cases = [
+            ("*.test.*.js", "a.test.unit.js", true),
+            ("*.test.*.js", "a.test.js", false),
+            ("file?.txt", "file1.txt", true),
+            ("[a-c]*", "beta", true),
+            ("[a-c]*", "delta", false),
+            ("draft", "my draft copy", true),
+            ("src/**/*.test.js", "widget.test.js", true),
+            ("src/*.test.js", "widget.test.js", false),
+            ("/home/**/ui/*", "widget.test.js", true),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(matches_pattern(name, path, pattern), expected, "{} on {}", pattern, name);
+        }
+    }
+
+    #[test]
+    fn templates_fill_in_file_placeholders() {
+        let path = Path::new("/nonexistent/Trip/IMG 1.JPG");
+        let config = Config { behaviors: vec![Behavior::templated("*", "{parent}-{counter:02}-{name}.{ext}")], ..Config::default() };
+        assert_eq!(format_filename_with_rule("IMG 1.JPG", &config, path, false).0, "Trip-01-IMG_1.JPG");
+        // Without an extension the dangling dot is dropped
+        assert_eq!(format_filename_with_rule("README", &config, &path.with_file_name("README"), false).0, "Trip-01-README");
+    }
+
+    #[test]
+    fn counters_number_files_in_the_configured_order() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        for (i, name) in ["b.jpg", "c.jpg", "a.jpg"].iter().enumerate() {
+            let file = fs::File::create(dir.join(name)).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000 * i as u64)).unwrap();
+        }
+        let config: Config = toml::from_str(r#"
+            [[behaviors]]
+            pattern = "*.jpg"
+            template = "trip_{counter}.{ext}"
+            counter = { start = 0, step = 10, width = 3, order = "mtime" }
+        "#).unwrap();
+        let names: Vec<String> = ["a.jpg", "b.jpg", "c.jpg"].iter()
+            .map(|name| format_filename_with_rule(name, &config, &dir.join(name), false).0.into_string())
+            .collect();
+        assert_eq!(names, ["trip_020.jpg", "trip_000.jpg", "trip_010.jpg"]);
+    }
+
+    #[test]
+    fn hash_placeholder_keeps_as_many_digits_as_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("style.css");
+        fs::write(&file, "abc").unwrap();
+        let name = file.file_name().unwrap().to_string_lossy().to_string();
+        let config = Config { behaviors: vec![Behavior::templated("*", "site.{hash:8}.{ext}")], ..Config::default() };
+        let renamed = format_filename_with_rule(&name, &config, &file, false).0;
+        assert_eq!(renamed, "site.ba7816bf.css");
+    }
+
+    #[test]
+    fn chained_behaviors_apply_in_order() {
+        let mut config: Config = toml::from_str(r#"
+            [[behaviors]]
+            pattern = "DRAFT*"
+            strip_prefix = "DRAFT "
+            chain = true
+
+            [[behaviors]]
+            pattern = "*.md"
+            style = "snake_case"
+
+            [[behaviors]]
+            pattern = "*"
+            add_suffix = "_v1"
+        "#).unwrap();
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let (name, rule) = format_filename_with_rule("DRAFT Release Notes.md", &config, path, false);
+        assert_eq!((name.as_str(), rule.as_str()), ("release_notes.md", "behavior 'DRAFT*' + behavior '*.md'"));
+        // Chained everywhere, the suffix goes on too and styling stays in effect
+        config.chain = true;
+        assert_eq!(format_filename_with_rule("DRAFT Release Notes.md", &config, path, false).0, "release_notes_v1.md");
+        assert_eq!(format_filename_with_rule("Notes Only.md", &config, path, false).0, "notes_only_v1.md");
+    }
+
+    #[test]
+    fn behaviors_decide_their_own_timestamps() {
+        let config: Config = toml::from_str(r#"
+            timestamp_format = { pattern = "stamp", separator = "_" }
+
+            [[behaviors]]
+            pattern = "*invoice*"
+            prefix = "acme_"
+            style = "snake_case"
+            timestamp = true
+
+            [[behaviors]]
+            pattern = "*.rs"
+            style = "snake_case"
+            timestamp = false
+        "#).unwrap();
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let format = |name: &str, timestamp: bool| format_filename_with_rule(name, &config, path, timestamp).0;
+        assert_eq!(format("March invoice.pdf", false), "stamp_acme_march_invoice.pdf");
+        assert_eq!(format("Http Client.rs", true), "http_client.rs");
+        assert_eq!(format("Notes.txt", true), "stamp_Notes.txt");
+    }
+
+    #[test]
+    fn path_behaviors_match_below_the_run_root() {
+        let config: Config = toml::from_str(r#"
+            [[behaviors]]
+            pattern = "src/**/*.rs"
+            match = "path"
+            style = "snake_case"
+
+            [[behaviors]]
+            pattern = "^docs/"
+            pattern_type = "regex"
+            match = "path"
+            style = "kebab-case"
+        "#).unwrap();
+        config.set_root(Path::new("/project"));
+        let format = |path: &str| {
+            let path = Path::new(path);
+            format_filename_with_rule(&path.file_name().unwrap().to_string_lossy(), &config, path, false).0
+        };
+        assert_eq!(format("/project/src/net/Http Client.rs"), "http_client.rs");
+        assert_eq!(format("/project/docs/Http Client.md"), "http-client.md");
+        assert_eq!(format("/project/vendor/src/Http Client.rs"), "Http_Client.rs");
+    }
+
+    #[test]
+    fn nested_repeats_match_in_linear_time() {
+        let config: Config = toml::from_str(r#"
+            [[behaviors]]
+            pattern = "(a+)+b"
+            pattern_type = "regex"
+            style = "UPPERCASE"
+        "#).unwrap();
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let name = format!("{}.txt", "a".repeat(200));
+        let started = std::time::Instant::now();
+        assert_eq!(format_filename_with_rule(&name, &config, path, false).0, name);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(format_filename_with_rule("aab.txt", &config, path, false).0, "AAB.txt");
+    }
+
+    #[test]
+    fn regex_replacements_fill_in_groups() {
+        let config: Config = toml::from_str(r#"
+            replace_spaces = false
+
+            [[behaviors]]
+            regex = 'IMG_(\d+)'
+            replace = "photo_$1"
+
+            [[behaviors]]
+            regex = '(?i)^(?<word>\p{L}+) draft'
+            replace = "${word}_${1}x final"
+        "#).unwrap();
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let format = |name: &str| format_filename_with_rule(name, &config, path, false).0;
+        assert_eq!(format("IMG_0042.jpg"), "photo_0042.jpg");
+        assert_eq!(format("Trip IMG_7.JPG"), "Trip photo_7.JPG");
+        assert_eq!(format("Übersicht DRAFT.md"), "Übersicht_Übersichtx final.md");
+        assert_eq!(format("notes.txt"), "notes.txt");
+        assert!(config.problems().is_empty());
+
+        let unknown: Config = toml::from_str("[[behaviors]]\nregex = 'IMG_(\\d+)'\nreplace = \"photo_$1x\"\n").unwrap();
+        assert_eq!(unknown.problems(), ["replacement \"photo_$1x\" uses '1x', which is not a group of regex \"IMG_(\\d+)\""]);
+    }
+
+    #[test]
+    fn patterns_and_regexes_must_both_match() {
+        let config: Config = toml::from_str(r#"
+            [[behaviors]]
+            pattern = "*.jpg"
+            regex = 'IMG_(\d+)'
+            replace = "photo_$1"
+        "#).unwrap();
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let format = |name: &str| format_filename_with_rule(name, &config, path, false).0;
+        assert_eq!(format("IMG_0042.jpg"), "photo_0042.jpg");
+        assert_eq!(format("IMG_0042.png"), "IMG_0042.png");
+        assert_eq!(format("holiday.jpg"), "holiday.jpg");
+
+        let both: Config = toml::from_str("replace_spaces = false\n[[behaviors]]\npattern = 'a+'\npattern_type = \"regex\"\nregex = 'b+'\nstyle = \"snake_case\"\n").unwrap();
+        assert_eq!(both.problems(), ["behavior has both regex \"b+\" and a regex pattern \"a+\"; use one"]);
+        assert_eq!(format_filename_with_rule("aa bb", &both, path, false).0, "aa bb");
+    }
+
+    #[test]
+    fn config_hashes_only_follow_naming_rules() {
+        let config = |text: &str| toml::from_str::<Config>(text).unwrap();
+        let base = plan::config_hash(&config(""));
+        assert_eq!(plan::config_hash(&config("max_renames = 10\n[notify]\nwebhook = \"https://example.com/hook\"\n")), base);
+        assert_ne!(plan::config_hash(&config("replace_spaces = false\n")), base);
+
+        // Everything else is still shown
+        let shown = toml::to_string_pretty(&config("max_renames = 10\n[notify]\nwebhook = \"https://example.com/hook\"\n")).unwrap();
+        assert!(shown.contains("max_renames = 10"));
+        assert!(shown.contains("webhook = \"https://example.com/hook\""));
+    }
+
+    #[test]
+    fn pairs_and_sidecars_follow_their_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for name in ["IMG_1.CR3", "IMG_1.xmp", "IMG_2.CR3", "IMG_2.JPG"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+        // Only the RAW files match the rule
+        let rules = r#"
+            [[behaviors]]
+            pattern = "*.CR3"
+            regex = 'IMG_(\d+)'
+            replace = "photo_$1"
+        "#;
+        let plan = |config: &str| {
+            let config: Config = toml::from_str(config).unwrap();
+            let renames = build_plan(root, &config, false, None).unwrap().renames;
+            renames.into_iter().map(|r| (r.source, r.target)).collect::<Vec<(PathBuf, PathBuf)>>()
+        };
+        let renamed = |from: &str, to: &str| (root.join(from), root.join(to));
+
+        assert_eq!(plan(&format!("pairs.enabled = false\n{}", rules)), [
+            renamed("IMG_1.CR3", "photo_1.CR3"),
+            renamed("IMG_2.CR3", "photo_2.CR3"),
+        ]);
+        // The JPEG is renamed with its RAW file, the sidecar with IMG_1.CR3
+        assert_eq!(plan(&format!("sidecars = [\"xmp\"]\n{}", rules)), [
+            renamed("IMG_1.CR3", "photo_1.CR3"),
+            renamed("IMG_1.xmp", "photo_1.xmp"),
+            renamed("IMG_2.CR3", "photo_2.CR3"),
+            renamed("IMG_2.JPG", "photo_2.JPG"),
+        ]);
+    }
+
+    #[test]
+    fn globs_take_alternatives_and_match_in_linear_time() {
+        assert!(glob_match("*.{JPG,CR3}", "IMG_1.CR3"));
+        assert!(glob_match("*.{JPG,CR3}", "IMG_1.JPG"));
+        assert!(!glob_match("*.{JPG,CR3}", "IMG_1.xmp"));
+        assert!(glob_match("src/**/*.{rs,toml}", "src/a/b/lib.rs"));
+        assert!(glob_match("src/**/a.rs", "src/a.rs"));
+        assert!(!glob_match("*.rs", "src/a.rs"));
+        assert!(glob_match("[!0-9]?.txt", "ab.txt"));
+        assert!(glob_match("notes[", "notes["));
+
+        let started = std::time::Instant::now();
+        assert!(!glob_match("*a*a*a*a*a*a*a*a*a*a*b", &"a".repeat(60)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        let config: Config = toml::from_str("[[behaviors]]\npattern = \"*.{jpg\"\nstyle = \"snake_case\"\n").unwrap();
+        assert_eq!(config.problems(), ["behavior '*.{jpg' has an invalid glob; it only selects names spelled exactly like it"]);
+    }
+
+    #[test]
+    fn coverage_finds_dead_and_shadowed_behaviors() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Release Notes.md", "Todo List.md", "logo.png"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let config: Config = toml::from_str(r#"
+            [[behaviors]]
+            pattern = "*.md"
+            style = "snake_case"
+
+            [[behaviors]]
+            pattern = "Todo*"
+            style = "kebab-case"
+
+            [[behaviors]]
+            pattern = "*.txt"
+            style = "kebab-case"
+        "#).unwrap();
+        let coverage = build_plan(dir.path(), &config, false, None).unwrap().coverage;
+        let counts: Vec<_> = coverage.iter().map(|rule| (rule.decided, rule.shadowed, rule.problem())).collect();
+        assert_eq!(counts, [(2, 0, None), (0, 1, Some("shadowed by earlier behaviors")), (0, 0, Some("matched no files"))]);
+    }
+
+    #[test]
+    fn unmatched_files_get_their_neighbors_style() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("notes")).unwrap();
+        for name in ["build-site.sh", "deploy-app.sh", "run-tests.sh", "Clean Cache.sh", "notes/Loose Ends.txt", "notes/oddOne.txt", "keep.md"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let config = Config {
+            behaviors: vec![Behavior::styled("*.md", NamingStyle::Uppercase)],
+            replace_spaces: false,
+            suggestions: Suggestions::Show,
+            ..Config::default()
+        };
+        let plan = build_plan(root, &config, false, None).unwrap();
+        let suggested: Vec<_> = plan.suggestions.iter()
+            .map(|s| (s.source.strip_prefix(root).unwrap(), s.target.strip_prefix(root).unwrap(), s.rule.as_str()))
+            .collect();
+        // camelCase is a style already, and keep.md matches a behavior
+        assert_eq!(suggested, [
+            (Path::new("Clean Cache.sh"), Path::new("clean-cache.sh"), "suggestion: kebab-case, like most files in its directory"),
+            (Path::new("notes/Loose Ends.txt"), Path::new("notes/loose_ends.txt"), "suggestion: snake_case, the default"),
+        ]);
+        // Suggestions are only shown, not planned
+        let renamed: Vec<_> = plan.renames.iter().map(|r| r.target.strip_prefix(root).unwrap()).collect();
+        assert_eq!(renamed, [Path::new("KEEP.md")]);
+    }
+
+    #[test]
+    fn packs_bring_their_styles_and_protected_names() {
+        let config = Config { presets: vec![String::from("python"), String::from("node")], default_protected: false, ..Config::default() };
+        let formatter = Formatter::new(config);
+        assert_eq!(formatter.format("My Module.py"), "my_module.py");
+        assert_eq!(formatter.format("App Shell.tsx"), "app-shell.tsx");
+        assert_eq!(formatter.format("__init__.py"), "__init__.py");
+        assert_eq!(formatter.format_with_rule("tsconfig.Base.json").1, "protected name");
+        // Without the pack only the defaults apply
+        assert_eq!(Formatter::new(Config::default()).format("App Shell.tsx"), "App_Shell.tsx");
+    }
+
+    #[test]
+    fn whole_relative_paths_are_styled_bottom_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("My Docs/Tax Year")).unwrap();
+        fs::write(root.join("My Docs/Tax Year/Final Return.pdf"), "").unwrap();
+        let config = Config { paths: true, behaviors: vec![Behavior::styled("*", NamingStyle::KebabCase)], ..Config::default() };
+        let renames = build_plan(root, &config, false, None).unwrap().renames;
+        let planned: Vec<_> = renames.iter()
+            .map(|r| (r.source.strip_prefix(root).unwrap(), r.target.strip_prefix(root).unwrap()))
+            .collect();
+        // The file first, then each directory under its parent's current name
+        assert_eq!(planned, [
+            (Path::new("My Docs/Tax Year/Final Return.pdf"), Path::new("My Docs/Tax Year/final-return.pdf")),
+            (Path::new("My Docs/Tax Year"), Path::new("My Docs/tax-year")),
+            (Path::new("My Docs"), Path::new("my-docs")),
+        ]);
+        assert!(renames[1].rule.ends_with(" (directory)"), "{}", renames[1].rule);
+        for rename in &renames {
+            fs::rename(&rename.source, &rename.target).unwrap();
+        }
+        assert!(root.join("my-docs/tax-year/final-return.pdf").is_file());
+    }
+
+    #[test]
+    fn empty_directories_are_renamed_but_protected_ones_are_not_entered() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for sub in ["Empty Dir", "Old Stuff/Nested Empty", "node_modules/Some Package"] {
+            fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        let config = Config { paths: true, protected: vec![String::from("node_modules")], ..Config::default() };
+        let renames = build_plan(root, &config, false, None).unwrap().renames;
+        let planned: Vec<_> = renames.iter().map(|r| r.target.strip_prefix(root).unwrap()).collect();
+        assert_eq!(planned, [Path::new("Old Stuff/Nested_Empty"), Path::new("Empty_Dir"), Path::new("Old_Stuff")]);
+    }
+
+    #[test]
+    fn prefixes_and_suffixes_are_stripped_before_styling_and_added_once() {
+        let config: Config = toml::from_str(r#"
+            [[behaviors]]
+            pattern = "*"
+            strip_prefix = "DRAFT "
+            strip_suffix = " (copy)"
+            style = "snake_case"
+            add_prefix = "acme_"
+            add_suffix = "_v2"
+        "#).unwrap();
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let format = |name: &str| format_filename_with_rule(name, &config, path, false).0;
+        assert_eq!(format("DRAFT Release Notes (copy).md"), "acme_release_notes_v2.md");
+        assert_eq!(format("Release Notes.md"), "acme_release_notes_v2.md");
+        // A name that already has them is left as it is
+        assert_eq!(format("acme_release_notes_v2.md"), "acme_release_notes_v2.md");
+        // Nothing to strip or add, and no style: the behavior is reported
+        let idle = Config { behaviors: vec![Behavior::selecting("*.tmp")], ..Config::default() };
+        assert_eq!(idle.problems(), ["behavior '*.tmp' has no style, template or prefix/suffix operation and changes nothing"]);
+    }
+
+    #[test]
+    fn timestamps_come_from_the_chosen_file_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("scan.pdf");
+        fs::write(&file, "scan").unwrap();
+        let taken = chrono::DateTime::parse_from_rfc3339("2001-02-03T12:00:00Z").unwrap();
+        fs::File::options().write(true).open(&file).unwrap().set_modified(taken.into()).unwrap();
+        let format = TimestampFormat::default();
+        let today = chrono::Utc::now().format("%Y_%m_%d").to_string();
+
+        assert_eq!(timestamp_for(&file, TimestampSource::Mtime, &format), "2001_02_03");
+        assert_eq!(timestamp_for(&file, TimestampSource::Now, &format), today);
+        // Setting the mtime changed the inode just now
+        assert_eq!(timestamp_for(&file, TimestampSource::Ctime, &format), today);
+        let created = if fs::metadata(&file).unwrap().created().is_ok() { today.as_str() } else { "2001_02_03" };
+        assert_eq!(timestamp_for(&file, TimestampSource::Created, &format), created);
+        // Without a file to ask, or an EXIF time to read, the fallbacks apply
+        assert_eq!(timestamp_for(&dir.path().join("missing.pdf"), TimestampSource::Mtime, &format), today);
+        assert_eq!(timestamp_for(&file, TimestampSource::Exif, &format), "2001_02_03");
+    }
+
+    #[test]
+    fn timestamps_are_written_as_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("Q1 Report.tar.gz");
+        fs::write(&file, "report").unwrap();
+        let taken = chrono::DateTime::parse_from_rfc3339("2001-02-03T23:30:00Z").unwrap();
+        fs::File::options().write(true).open(&file).unwrap().set_modified(taken.into()).unwrap();
+        let format = |timestamp_format: &str| {
+            let config: Config = toml::from_str(&format!("timestamp_source = \"mtime\"\ntimestamp_format = {}", timestamp_format)).unwrap();
+            format_filename_with_rule("Q1 Report.tar.gz", &config, &file, true).0.into_string()
+        };
+        assert_eq!(format("{}"), "2001_02_03__Q1_Report.tar.gz");
+        assert_eq!(format("{ pattern = \"%Y%m%dT%H%M\", separator = \"-\", position = \"suffix\" }"), "Q1_Report-20010203T2330.tar.gz");
+        let local = taken.with_timezone(&chrono::Local).format("%Y-%m-%d %H").to_string();
+        assert_eq!(format("{ pattern = \"%Y-%m-%d %H\", separator = \" \", timezone = \"local\" }"), format!("{} Q1_Report.tar.gz", local));
+
+        let broken = TimestampFormat { pattern: String::from("%Y_%Q"), ..TimestampFormat::default() };
+        assert_eq!(broken.problem().unwrap(), "timestamp_format.pattern \"%Y_%Q\" is not a valid strftime pattern; using \"%Y_%m_%d\"");
+        assert_eq!(broken.write(taken.naive_utc()), "2001_02_03");
+    }
+
+    #[test]
+    fn extensions_are_lowercased_and_remapped() {
+        let config: Config = toml::from_str(r#"
+            [extensions]
+            lowercase = true
+            remap = { jpeg = "jpg", YML = ".yaml" }
+        "#).unwrap();
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        let format = |name: &str| format_filename_with_rule(name, &config, path, false).0;
+        assert_eq!(format("Beach Day.JPEG"), "Beach_Day.jpg");
+        assert_eq!(format("docker-compose.yml"), "docker-compose.yaml");
+        assert_eq!(format("Backup.TAR.GZ"), "Backup.tar.gz");
+        assert_eq!(format("Makefile"), "Makefile");
+        // Without rules extensions are left as written
+        assert_eq!(format_filename_with_rule("Beach Day.JPEG", &Config::default(), path, false).0, "Beach_Day.JPEG");
+    }
+
+    #[test]
+    fn project_configs_are_laid_over_the_users_nearest_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let (user, repo) = (dir.path().join("config.toml"), dir.path().join("repo"));
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(&user, "max_length = 100\nprotected = [\"LICENSE\"]\n").unwrap();
+        fs::write(repo.join(layers::PROJECT_CONFIG), "max_length = 80\nprotected = [\"Makefile\"]\n").unwrap();
+        fs::write(repo.join("docs").join(layers::PROJECT_CONFIG), "replace_spaces = false\n").unwrap();
+
+        let config = load_config_for(&user, &repo.join("docs"), None, false).unwrap();
+        assert_eq!((config.max_length, config.replace_spaces), (Some(80), false));
+        assert_eq!(config.protected, ["Makefile", "LICENSE"]);
+        let config = load_config_for(&user, &repo, None, false).unwrap();
+        assert_eq!((config.max_length, config.replace_spaces), (Some(80), true));
+
+        // A broken project file stops the run, unless lenient
+        fs::write(repo.join(layers::PROJECT_CONFIG), "max_length = \"long\"\n").unwrap();
+        let error = load_config_for(&user, &repo, None, false).unwrap_err().to_string();
+        assert!(error.starts_with(&format!("Failed to parse {}", repo.join(layers::PROJECT_CONFIG).display())), "{}", error);
+        assert_eq!(load_config_for(&user, &repo, None, true).unwrap().max_length, Some(100));
+    }
+
+    #[test]
+    fn profiles_are_laid_over_everything_else() {
+        let dir = tempfile::tempdir().unwrap();
+        let (user, repo) = (dir.path().join("config.toml"), dir.path().join("repo"));
+        fs::create_dir(&repo).unwrap();
+        fs::write(&user, "max_length = 100\n\n[profile.photos]\nmax_length = 60\ntimestamp = true\n").unwrap();
+        fs::write(repo.join(layers::PROJECT_CONFIG), "max_length = 80\n\n[profile.music]\nreplace_spaces = false\n").unwrap();
+
+        let config = load_config_for(&user, &repo, Some("photos"), false).unwrap();
+        assert_eq!((config.max_length, config.timestamp), (Some(60), true));
+        let config = load_config_for(&user, &repo, Some("music"), false).unwrap();
+        assert_eq!((config.max_length, config.replace_spaces, config.timestamp), (Some(80), false, false));
+        assert!(config.profile.is_empty());
+
+        let error = load_config_for(&user, &repo, Some("video"), false).unwrap_err().to_string();
+        assert_eq!(error, format!("No profile \"video\" in {}, {} (defined: music, photos)", user.display(), repo.join(layers::PROJECT_CONFIG).display()));
+    }
+
+    #[test]
+    fn traversal_keeps_to_its_depth_and_skips_hidden_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir(root.join(".cache")).unwrap();
+        for file in ["top.txt", ".hidden.txt", "a/mid.txt", "a/b/deep.txt", ".cache/entry.txt"] {
+            fs::write(root.join(file), file).unwrap();
+        }
+        let found = |traversal: TraversalRules| {
+            let config = Config { traversal, ..Config::default() };
+            collect_files(root, &config).unwrap().iter().map(|f| f.strip_prefix(root).unwrap().to_string_lossy().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(found(TraversalRules::default()), [".cache/entry.txt", ".hidden.txt", "a/b/deep.txt", "a/mid.txt", "top.txt"]);
+        assert_eq!(found(TraversalRules { skip_hidden: true, ..TraversalRules::default() }), ["a/b/deep.txt", "a/mid.txt", "top.txt"]);
+        assert_eq!(found(TraversalRules { max_depth: Some(2), skip_hidden: true, ..TraversalRules::default() }), ["a/mid.txt", "top.txt"]);
+        assert_eq!(found(TraversalRules { min_depth: Some(2), skip_hidden: true, ..TraversalRules::default() }), ["a/b/deep.txt", "a/mid.txt"]);
+
+        // Linked directories are walked into when asked, and links back up the tree skipped
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("a/b"), root.join("linked")).unwrap();
+            std::os::unix::fs::symlink(root.join("a"), root.join("a/b/up")).unwrap();
+            let followed = found(TraversalRules { follow_symlinks: true, skip_hidden: true, ..TraversalRules::default() });
+            assert_eq!(followed, ["a/b/deep.txt", "a/mid.txt", "linked/deep.txt", "linked/up/b/deep.txt", "linked/up/mid.txt", "top.txt"]);
+            assert_eq!(found(TraversalRules { skip_hidden: true, ..TraversalRules::default() }), ["a/b/deep.txt", "a/mid.txt", "top.txt"]);
+        }
+    }
+
+    #[test]
+    fn scripts_and_files_marked_executable_count_only_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("deploy site"), "#!/usr/bin/env python3\n").unwrap();
+        fs::write(root.join("notes draft"), "# not a script\n").unwrap();
+        let mut config: Config = toml::from_str("[detection]\nunix_executables = true\n").unwrap();
+        assert_eq!(file_class(&root.join("deploy site"), &config), Some(FileClass::Script));
+        assert_eq!(file_class(&root.join("notes draft"), &config), None);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::write(root.join("run tool"), [0x00, 0x01]).unwrap();
+            fs::set_permissions(root.join("run tool"), fs::Permissions::from_mode(0o755)).unwrap();
+            assert_eq!(file_class(&root.join("run tool"), &config), Some(FileClass::Executable));
+        }
+        config.detection.unix_executables = false;
+        assert_eq!(file_class(&root.join("deploy site"), &config), None);
+    }
+}
@@ -0,0 +1,159 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+/// How much namefmt says while it works, from -q, -v and -vv. What a
+/// command is asked for (a report, a completion script) is printed
+/// whatever the level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Errors only
+    Quiet,
+    /// Renames, warnings and the progress bar
+    Normal,
+    /// Also files left alone and the rule behind each rename
+    Verbose,
+    /// Also how long each step took
+    Debug,
+}
+
+impl Level {
+    pub fn from_flags(quiet: bool, verbose: u8) -> Level {
+        match (quiet, verbose) {
+            (true, _) => Level::Quiet,
+            (false, 0) => Level::Normal,
+            (false, 1) => Level::Verbose,
+            (false, _) => Level::Debug,
+        }
+    }
+}
+
+/// Where a message is written.
+#[derive(Debug, Clone, Copy)]
+pub enum Stream {
+    Out,
+    Err,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Level::Quiet,
+        1 => Level::Normal,
+        2 => Level::Verbose,
+        _ => Level::Debug,
+    }
+}
+
+pub fn enabled(level: Level) -> bool {
+    self::level() >= level
+}
+
+/// The progress bar at the bottom of the terminal, if one is showing.
+/// Messages are written above it.
+static BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+static PROGRESS: AtomicBool = AtomicBool::new(false);
+
+const BAR_TEMPLATE: &str = "{msg} [{bar:30}] {pos}/{len}";
+const COUNT_TEMPLATE: &str = "{msg}: {pos} file(s)";
+
+/// Write a message at `level`, keeping the progress bar below it.
+#[doc(hidden)]
+pub fn write(level: Level, stream: Stream, message: Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let print = || match stream {
+        Stream::Out => println!("{}", message),
+        Stream::Err => eprintln!("{}", message),
+    };
+    match &*BAR.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(bar) => bar.suspend(print),
+        None => print(),
+    }
+}
+
+/// Show a progress bar from now on, unless namefmt is quiet. indicatif
+/// draws nothing when stderr isn't a terminal.
+pub fn show_progress() {
+    PROGRESS.store(enabled(Level::Normal), Ordering::Relaxed);
+}
+
+/// Update the progress bar: `done` of `total` with a bar, or a running
+/// count when the total isn't known yet.
+pub fn progress(what: &str, done: usize, total: Option<usize>) {
+    if !PROGRESS.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut bar = BAR.lock().unwrap_or_else(|e| e.into_inner());
+    let bar = bar.get_or_insert_with(|| {
+        let (bar, template) = match total {
+            Some(total) => (ProgressBar::new(total as u64), BAR_TEMPLATE),
+            None => (ProgressBar::no_length(), COUNT_TEMPLATE),
+        };
+        let style = ProgressStyle::with_template(template).unwrap_or_else(|_| ProgressStyle::default_bar()).progress_chars("#-");
+        bar.with_style(style).with_message(what.to_string())
+    });
+    bar.set_position(done as u64);
+}
+
+/// Take the progress bar away, at the end of a step.
+pub fn finish_progress() {
+    if let Some(bar) = BAR.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        bar.finish_and_clear();
+    }
+}
+
+/// `Error: ...` on stderr, even when quiet.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Quiet, $crate::log::Stream::Err, format_args!("Error: {}", format_args!($($arg)*)))
+    };
+}
+
+/// `Warning: ...` on stderr.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Normal, $crate::log::Stream::Err, format_args!("Warning: {}", format_args!($($arg)*)))
+    };
+}
+
+/// A message about the run on stderr, such as a file being skipped.
+#[macro_export]
+macro_rules! note {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Normal, $crate::log::Stream::Err, format_args!($($arg)*))
+    };
+}
+
+/// What the run does, on stdout: `Renamed: ...`.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Normal, $crate::log::Stream::Out, format_args!($($arg)*))
+    };
+}
+
+/// Details on stdout with -v.
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Verbose, $crate::log::Stream::Out, format_args!($($arg)*))
+    };
+}
+
+/// Timings and internals on stderr with -vv.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Debug, $crate::log::Stream::Err, format_args!("Debug: {}", format_args!($($arg)*)))
+    };
+}
@@ -0,0 +1,95 @@
+/// Compression suffixes logrotate and friends append to rotated files.
+const COMPRESSION: &[&str] = &["gz", "bz2", "xz", "zst", "z", "lz4"];
+
+/// Split a rotated log name into the base name and the rotation suffix:
+/// `app.log.1` -> (`app`, `.log.1`), `App Server.2024-01-31.log.gz` ->
+/// (`App Server`, `.2024-01-31.log.gz`), `app.log-20240131` -> (`app`,
+/// `.log-20240131`). The suffix must be kept verbatim so rotated files keep
+/// sorting and matching the way the rotation tool expects.
+pub fn split_rotation(name: &str) -> Option<(&str, &str)> {
+    let mut rest = name;
+    if let Some((before, ext)) = rest.rsplit_once('.') {
+        if COMPRESSION.iter().any(|c| c.eq_ignore_ascii_case(ext)) {
+            rest = before;
+        }
+    }
+
+    // `app.log` followed by a rotation segment: `.1`, `-20240131`, `.2024-01-31`
+    if let Some((before, _)) = split_segment(rest) {
+        if ends_with_log(before) {
+            return split_at(name, before.len() - 4);
+        }
+    }
+
+    // Rotation segment before the extension: `app.2024-01-31.log`, `app.1.log`
+    if ends_with_log(rest) {
+        let before_log = &rest[..rest.len() - 4];
+        if let Some((base, _)) = split_segment(before_log) {
+            return split_at(name, base.len());
+        }
+        // Plain `app.log` only counts once it has been rotated
+        if rest.len() < name.len() {
+            return split_at(name, before_log.len());
+        }
+    }
+    None
+}
+
+/// `(base, segment)` if `text` ends in a separator followed by a rotation
+/// index (`1`, `20240131`) or a dashed date (`2024-01-31`).
+fn split_segment(text: &str) -> Option<(&str, &str)> {
+    let is_separator = |at: usize| matches!(text.as_bytes().get(at), Some(b'.' | b'-' | b'_'));
+
+    let at = text.len().saturating_sub(10);
+    if text.get(at..).is_some_and(is_dashed_date) {
+        // A date run into the name, or making up all of it, is part of it,
+        // not a rotation index of `31`
+        return (at > 0 && is_separator(at - 1)).then(|| (&text[..at - 1], &text[at..]));
+    }
+    let digits = text.len() - text.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 || digits > 14 || digits == text.len() || !is_separator(text.len() - digits - 1) {
+        return None;
+    }
+    Some((&text[..text.len() - digits - 1], &text[text.len() - digits..]))
+}
+
+fn is_dashed_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 10
+        && bytes[4] == bytes[7]
+        && matches!(bytes[4], b'-' | b'_')
+        && bytes.iter().enumerate().all(|(i, b)| i == 4 || i == 7 || b.is_ascii_digit())
+}
+
+fn ends_with_log(text: &str) -> bool {
+    text.len() > 4 && text.get(text.len() - 4..).is_some_and(|ext| ext.eq_ignore_ascii_case(".log"))
+}
+
+fn split_at(name: &str, at: usize) -> Option<(&str, &str)> {
+    (at > 0 && name.is_char_boundary(at)).then(|| name.split_at(at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_suffixes_are_split_off_verbatim() {
+        let cases = [
+            ("app.log.1", Some(("app", ".log.1"))),
+            ("App Server.2024-01-31.log.gz", Some(("App Server", ".2024-01-31.log.gz"))),
+            ("app.log-20240131", Some(("app", ".log-20240131"))),
+            ("Web_Access.3.log", Some(("Web_Access", ".3.log"))),
+            ("error.LOG.2.bz2", Some(("error", ".LOG.2.bz2"))),
+            ("app.log.gz", Some(("app", ".log.gz"))),
+            // Not rotated (yet), or not a log
+            ("app.log", None),
+            ("report.2024-01-31.pdf", None),
+            ("build1.log", None),
+            ("2024-01-31.log", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(split_rotation(name), expected, "{}", name);
+        }
+    }
+}
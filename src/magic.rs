@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// How much of a file is read to tell its type: enough for a tar header
+/// and for the PE header of most Windows binaries.
+const READ_LIMIT: u64 = 1024;
+
+/// Types whose content is machine code: ELF, Mach-O and PE binaries.
+const EXECUTABLES: &[&str] = &["application/x-executable", "application/x-mach-binary", "application/vnd.microsoft.portable-executable"];
+
+/// Fixed bytes at an offset, and the type of the files that start so.
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x7fELF", "application/x-executable"),
+    (0, b"\xfe\xed\xfa\xce", "application/x-mach-binary"),
+    (0, b"\xfe\xed\xfa\xcf", "application/x-mach-binary"),
+    (0, b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"II*\0", "image/tiff"),
+    (0, b"MM\0*", "image/tiff"),
+    (0, b"\0\0\x01\0", "image/x-icon"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"\xff\xfb", "audio/mpeg"),
+    (0, b"\xff\xf3", "audio/mpeg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"\xfd7zXZ\0", "application/x-xz"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (0, b"Rar!\x1a\x07", "application/vnd.rar"),
+    (0, b"SQLite format 3\0", "application/vnd.sqlite3"),
+    (257, b"ustar", "application/x-tar"),
+    (0, b"#!", SCRIPT),
+];
+
+/// Text files that start with a `#!` line naming their interpreter.
+pub const SCRIPT: &str = "text/x-script";
+
+/// The MIME type of the file at `path`, told from its first bytes; `None`
+/// for files that can't be read, empty ones and unknown binary formats.
+pub fn mime_type(path: &Path) -> Option<&'static str> {
+    let mut data = Vec::new();
+    File::open(path).ok()?.take(READ_LIMIT).read_to_end(&mut data).ok()?;
+    sniff(&data)
+}
+
+/// The MIME type of a file starting with `data`.
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    if data.is_empty() {
+        return None;
+    }
+    let at = |offset: usize, bytes: &[u8]| data.get(offset..offset + bytes.len()) == Some(bytes);
+    if let Some(&(_, _, mime)) = SIGNATURES.iter().find(|(offset, bytes, _)| at(*offset, bytes)) {
+        return Some(mime);
+    }
+    // Two or three letters alone could start a text file too
+    let word = |offset: usize| data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    if at(0, b"MZ") && word(0x3c).is_some_and(|header| at(header as usize, b"PE\0\0")) {
+        // The DOS stub says where the PE header is
+        return Some("application/vnd.microsoft.portable-executable");
+    }
+    if at(0, b"BM") && word(6) == Some(0) {
+        return Some("image/bmp");
+    }
+    if at(0, b"BZh") && data.get(3).is_some_and(|c| (b'1'..=b'9').contains(c)) {
+        return Some("application/x-bzip2");
+    }
+    if at(0, b"RIFF") {
+        return match data.get(8..12)? {
+            b"WEBP" => Some("image/webp"),
+            b"WAVE" => Some("audio/wav"),
+            b"AVI " => Some("video/x-msvideo"),
+            _ => None,
+        };
+    }
+    if at(4, b"ftyp") {
+        return Some(match data.get(8..12)? {
+            b"heic" | b"heix" | b"mif1" | b"msf1" => "image/heic",
+            b"avif" => "image/avif",
+            b"qt  " => "video/quicktime",
+            b"M4A " => "audio/mp4",
+            b"3gp4" | b"3gp5" => "video/3gpp",
+            _ => "video/mp4",
+        });
+    }
+    if at(0, b"\x1a\x45\xdf\xa3") {
+        let webm = data.windows(4).any(|window| window == b"webm");
+        return Some(if webm { "video/webm" } else { "video/x-matroska" });
+    }
+    is_text(data).then_some("text/plain")
+}
+
+/// Whether files of type `mime` are programs to run.
+pub fn is_executable(mime: &str) -> bool {
+    EXECUTABLES.contains(&mime)
+}
+
+/// UTF-8 without NULs, allowing for a character cut off at the end.
+fn is_text(data: &[u8]) -> bool {
+    if data.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(data) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && data.len() as u64 == READ_LIMIT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binaries_images_and_text_are_told_apart() {
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x80, 0);
+        pe[0x3c] = 0x40;
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        assert_eq!(sniff(&pe), Some("application/vnd.microsoft.portable-executable"));
+        assert_eq!(sniff(b"MZ is how the notes start"), Some("text/plain"));
+        assert_eq!(sniff(b"\x7fELF\x02\x01\x01"), Some("application/x-executable"));
+        assert!(is_executable(sniff(b"\xcf\xfa\xed\xfe\x07\0\0\x01").unwrap()));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"\0\0\0\x18ftypheic"), Some("image/heic"));
+        assert_eq!(sniff("# Notes — draft\n".as_bytes()), Some("text/plain"));
+        assert_eq!(sniff(b"#!/bin/sh\necho hi\n"), Some(SCRIPT));
+        assert_eq!(sniff(b"\x00\x01\x02"), None);
+    }
+}
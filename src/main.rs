@@ -1,104 +1,625 @@
-use clap::Parser;
-use serde::{Deserialize, Serialize};
+use clap::{Args as _, CommandFactory, FromArgMatches, Parser, Subcommand};
+use namefmt::cache::IdentityCache;
+use namefmt::dupes::DuplicatePolicy;
+use namefmt::completions::Shell;
+use namefmt::journal::{ExportFormat, Journal, Run};
+use namefmt::mapping::MapFormat;
+use namefmt::name::Platform;
+use namefmt::metrics::{Metrics, RunStats};
+use namefmt::output::{ColorChoice, Output, OutputFormat};
+use namefmt::plan::{Coverage, Plan, Rename};
+use namefmt::shard::Shard;
+use namefmt::transaction::Transaction;
+use namefmt::{
+    check, clashes, configdiff, diff, edit, hash, history, interactive, journal, learn, log, mapping, metrics, notify, pipe, plan, presets, registry, report, resume,
+    selection, timeparse, transaction, tui, undo, watch, wizard,
+};
+use namefmt::{debug, error, info, note, verbose, warn};
+use namefmt::{
+    build_plan, collect_files, collect_target, get_default_config_toml, load_config, load_config_for, read_config, validate_config, Behavior, Config,
+    CounterOrder, InstalledPack, Suggestions, TimestampPosition, TimestampSource, Timezone,
+};
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
 #[command(name = "namefmt")]
 #[command(about = "Format filenames according to configuration")]
+#[command(override_usage = "namefmt [OPTIONS] [PATH]\n       namefmt [OPTIONS] <COMMAND>")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Override config file location
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+    /// Ignore settings namefmt doesn't know and fall back to the defaults
+    /// if a configuration file can't be read, instead of stopping
+    #[arg(long, global = true)]
+    lenient: bool,
+    /// Say more: -v lists files left alone and the rule behind each rename,
+    /// -vv adds how long each step took
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Print only errors; `check` prints nothing at all, its exit status
+    /// tells the result
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    // Without a subcommand, namefmt runs `fmt`
+    #[command(flatten)]
+    run: RunOptions,
+}
+
+/// Options of a formatting run: `namefmt fmt`, or `namefmt` on its own.
+#[derive(clap::Args, Debug, Clone, Default)]
+struct RunOptions {
     /// Path or file to process
     path: Option<PathBuf>,
     /// Actually perform renames (default: dry-run mode)
     #[arg(short, long)]
     inplace: bool,
-    /// Override config file location
+    /// Re-evaluate every file even if the identity cache is enabled
+    #[arg(long)]
+    no_cache: bool,
+    /// After the run, report how many files each behavior decided
+    #[arg(long)]
+    coverage: bool,
+    /// Refuse to rename more than N files in one run (overrides max_renames)
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+    /// Go ahead even if the run would exceed max_renames / --limit
+    #[arg(long)]
+    force: bool,
+    /// Allow --inplace on /, the home directory or a configured protected root
+    #[arg(long)]
+    allow_root: bool,
+    /// Don't ask for confirmation when renames span many top-level directories
     #[arg(short, long)]
-    config: Option<PathBuf>,
-    /// Prefix YYYY_MM_DD__ to all filenames
+    yes: bool,
+    /// Only process slice K of N (e.g. 2/8), for splitting a huge tree between workers.
+    /// Files are sliced by parent directory; can't be combined with --paths. Workers
+    /// share one journal, appending under its lock, so `undo` covers every slice
+    #[arg(long, value_name = "K/N", conflicts_with = "paths")]
+    shard: Option<Shard>,
+    /// For files no behavior matches, suggest a name styled like their siblings
     #[arg(long)]
-    timestamp: bool,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct Config {
-    #[serde(default = "default_replace_spaces")]
-    replace_spaces: bool,
- 
-    #[serde(default)]
-    behaviors: Vec<Behavior>,
-
-    #[serde(default)]
-    detection: DetectionRules,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct Behavior {
-    pattern: String,
-    style: NamingStyle,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-enum NamingStyle {
-    #[serde(rename = "camelCase")]
-    CamelCase,
-    #[serde(rename = "snake_case")]
-    SnakeCase,
-    #[serde(rename = "kebab-case")]
-    KebabCase,
+    suggest: bool,
+    /// Rename files no behavior matches to the suggested name (implies --suggest)
+    #[arg(long)]
+    apply_suggestions: bool,
+    /// Print records for scripts instead of text: a JSON array, or one JSON object per line
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputFormat,
+    /// Color what a dry run would change in each name
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t)]
+    color: ColorChoice,
+    /// Ask before each rename: [y]es / [n]o / [a]ll / [e]dit the new name / [q]uit (implies --inplace)
+    #[arg(long, conflicts_with = "edit")]
+    interactive: bool,
+    /// Edit the renames in $EDITOR first, one per line: change a new name, or
+    /// delete the line to leave the file alone (implies --inplace)
+    #[arg(long)]
+    edit: bool,
+    /// Go on past renames that fail and sum them up at the end, instead of
+    /// rolling back the whole batch; exits with 1 if any failed
+    #[arg(long)]
+    keep_going: bool,
+    #[command(flatten)]
+    format: FormatOptions,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct DetectionRules {
-    #[serde(default = "default_exe_extensions")]
-    exe_extensions: Vec<String>,
-
-    #[serde(default = "default_package_dirs")]
-    package_dirs: Vec<String>,
+/// Options that influence how names are formatted.
+#[derive(clap::Args, Debug, Clone, Default)]
+struct FormatOptions {
+    /// Prefix YYYY_MM_DD__ to all filenames; the --timestamp-* options and
+    /// --timezone imply it
+    #[arg(long)]
+    timestamp: bool,
+    /// Date the timestamp from the file's own mtime, ctime or creation time,
+    /// or a photo's EXIF capture time, instead of now
+    #[arg(long, value_enum, value_name = "SOURCE")]
+    timestamp_source: Option<TimestampSource>,
+    /// strftime pattern of the timestamp, e.g. "%Y-%m-%d" (default "%Y_%m_%d")
+    #[arg(long, value_name = "PATTERN")]
+    timestamp_format: Option<String>,
+    /// Text between the timestamp and the name (default "__")
+    #[arg(long, value_name = "SEP")]
+    timestamp_separator: Option<String>,
+    /// Put the timestamp before the name, or after it and before the extension
+    #[arg(long, value_enum, value_name = "POSITION")]
+    timestamp_position: Option<TimestampPosition>,
+    /// Write timestamps in UTC (the default) or local time
+    #[arg(long, value_enum)]
+    timezone: Option<Timezone>,
+    /// Also style directory names below the target, empty ones included, renaming them bottom-up
+    #[arg(long, visible_alias = "dirs")]
+    paths: bool,
+    /// Enable a built-in preset (repeatable): "scanner", "audiobook", "shards", or a
+    /// convention pack: "rust", "python", "node", "go", "web-assets", or a pack
+    /// installed with `namefmt preset install`
+    #[arg(long = "preset")]
+    presets: Vec<String>,
+    
+    /// Lay the config's [profile.NAME] table over the rest of it, e.g. to
+    /// keep "photos" and "code" conventions in one file
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+    
+    /// Rename every file to this template, before the configured behaviors,
+    /// e.g. "{parent}_{counter:03}.{ext}" or "{mtime:%Y%m%d}_{name}.{ext}"
+    #[arg(long)]
+    template: Option<String>,
+    
+    /// Number the files in each directory instead: NAME_001.jpg, NAME_002.jpg, ...
+    #[arg(long, value_name = "NAME", conflicts_with = "template")]
+    counter: Option<String>,
+    
+    /// Rename every file to a digest of its content instead, keeping the
+    /// extension: "sha256", or "sha256:12" for the first 12 hex digits
+    #[arg(long, value_name = "ALGO[:LEN]", value_parser = hash::parse_spec, conflicts_with_all = ["template", "counter"])]
+    hash: Option<usize>,
+    
+    /// With --hash, keep the name and put the digest before the extension,
+    /// replacing one put there before: style.3f2a9c1b4e5d.css
+    #[arg(long, requires = "hash")]
+    append_hash: bool,
+    
+    /// Compare file contents and "report" files with the same content (the
+    /// default), "skip" their renames, or "suffix" their names with _dup1,
+    /// _dup2, ... ("off" overrides the config's duplicates)
+    #[arg(long, value_enum, value_name = "ACTION", num_args = 0..=1, require_equals = true, default_missing_value = "report")]
+    detect_dupes: Option<DuplicatePolicy>,
+    
+    /// Rewrite dates already in names (05-01-2024, Jan 5 2024, 20240105) to
+    /// one form: FORMAT, e.g. "%Y_%m_%d", or the config's dates.format
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true)]
+    normalize_dates: Option<Option<String>>,
+    
+    /// Take "Copy of ", " - Copy", " (1)", wget's ".1", "final_final" and
+    /// [www.site.com] tags out of names before styling them
+    #[arg(long)]
+    strip_junk: bool,
+    
+    /// Number {counter} from N (default 1)
+    #[arg(long, value_name = "N")]
+    counter_start: Option<u64>,
+    
+    /// Count {counter} up by N from file to file (default 1)
+    #[arg(long, value_name = "N")]
+    counter_step: Option<u64>,
+    
+    /// Zero-pad {counter} to N digits (default 3 with --counter, else none)
+    #[arg(long, value_name = "N")]
+    counter_width: Option<usize>,
+    
+    /// Number files by name or oldest modification time first (default name)
+    #[arg(long, value_enum, value_name = "ORDER")]
+    counter_order: Option<CounterOrder>,
+    
+    /// Leave paths matching this .gitignore-style pattern alone (repeatable),
+    /// e.g. "target/" or "*.log"
+    #[arg(long = "exclude", value_name = "GLOB")]
+    excludes: Vec<String>,
+    
+    /// Also rename README*, Makefile, Cargo.toml, .gitignore and the other
+    /// names protected by default (names in `protected` stay protected)
+    #[arg(long)]
+    rename_protected: bool,
+    
+    /// Only rename files with these extensions, e.g. "jpg,png" (repeatable)
+    #[arg(long = "ext", value_name = "EXT", value_delimiter = ',')]
+    extensions: Vec<String>,
+    
+    /// Only rename files of at least this size, e.g. "500k" or "10M"
+    #[arg(long, value_name = "SIZE", value_parser = selection::parse_size)]
+    min_size: Option<u64>,
+    
+    /// Only rename files of at most this size
+    #[arg(long, value_name = "SIZE", value_parser = selection::parse_size)]
+    max_size: Option<u64>,
+    
+    /// Only rename files modified after this time, e.g. "7d", "2 hours ago" or "2024-06-01"
+    #[arg(long, value_name = "TIME", value_parser = timeparse::parse_point_in_time)]
+    newer_than: Option<chrono::DateTime<chrono::Utc>>,
+    
+    /// Only rename files modified before this time
+    #[arg(long, value_name = "TIME", value_parser = timeparse::parse_point_in_time)]
+    older_than: Option<chrono::DateTime<chrono::Utc>>,
+    
+    /// Look no deeper than N levels below the target; files directly in it are at level 1
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    
+    /// Only rename what is at least N levels below the target
+    #[arg(long, value_name = "N")]
+    min_depth: Option<usize>,
+    
+    /// Follow symbolic links, renaming links to files and walking into linked directories
+    #[arg(long)]
+    follow_symlinks: bool,
+    
+    /// Leave files and directories whose names start with a dot alone, and don't walk into them
+    #[arg(long)]
+    skip_hidden: bool,
+    
+    /// Plan with N threads (default: one per CPU); renames are still made one at a time, in order
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+    
+    /// Keep names valid on these filesystems: "windows" also replaces
+    /// <>:"\|?* and control characters, trims trailing dots and spaces and
+    /// prefixes reserved device names such as CON and NUL, for files synced
+    /// to Windows (overrides platform)
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+    
+    /// Cut new names longer than N bytes (see max_length_unit), keeping the
+    /// extension and timestamp
+    #[arg(long, value_name = "N")]
+    max_length: Option<usize>,
+    
+    /// Spell names in ASCII before styling them: é to e, ü to u, Cyrillic
+    /// and Greek to Latin letters
+    #[arg(long)]
+    ascii: bool,
+    
+    /// Also leave alone what .gitignore files ignore
+    #[arg(long)]
+    gitignore: bool,
+    
+    /// Rename files git tracks with `git mv`, so the renames are staged
+    /// and history follows them
+    #[arg(long)]
+    git: bool,
 }
 
-impl Default for DetectionRules {
-    fn default() -> Self {
-        DetectionRules {
-            exe_extensions: default_exe_extensions(),
-            package_dirs: default_package_dirs(),
-        }
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Rename files to the configured format (what namefmt does without a subcommand)
+    Fmt(RunOptions),
+    /// List files that don't follow the configured format, for CI: exits with
+    /// status 1 if there are any, 2 if the check couldn't run
+    Check {
+        /// Path or file to check
+        path: Option<PathBuf>,
+        #[command(flatten)]
+        format: FormatOptions,
+    },
+    /// Revert renames recorded in the journal
+    #[command(group = clap::ArgGroup::new("selection").required(true).args(["run_id", "since", "last"]))]
+    Undo {
+        /// Run ID printed at the end of an --inplace run
+        run_id: Option<String>,
+        /// Only revert renames whose path relative to the run root matches this glob
+        #[arg(long, requires = "run_id")]
+        only: Option<String>,
+        /// Revert every rename performed after this point in time, e.g. "2 hours ago" or "2024-01-31 14:00"
+        #[arg(long, conflicts_with = "run_id")]
+        since: Option<String>,
+        /// Revert the most recent run that hasn't been undone yet
+        #[arg(long, conflicts_with_all = ["run_id", "since"])]
+        last: bool,
+        /// Show what would be restored without touching any files
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List renames recorded in the journal, newest last, or follow one
+    /// file through every name it had
+    History {
+        /// Only renames of this file or of files under this directory, to or
+        /// from it; a bare name like report.docx matches it in any directory
+        path: Option<PathBuf>,
+        /// Show the last N renames, 0 for all
+        #[arg(short = 'n', long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+        /// Only renames after this point in time, e.g. "7d" or "2024-06-01"
+        #[arg(long, value_name = "TIME", value_parser = timeparse::parse_point_in_time)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        /// Only renames before this point in time
+        #[arg(long, value_name = "TIME", value_parser = timeparse::parse_point_in_time)]
+        until: Option<chrono::DateTime<chrono::Utc>>,
+        /// Follow the file at PATH (or that last left it) back through the
+        /// names it had and on to where it went
+        #[arg(long, requires = "path")]
+        trace: bool,
+        /// Print the entries as JSON or CSV, as `journal export` does
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+    },
+    /// Archive or transfer the rename journal
+    #[command(subcommand)]
+    Journal(JournalCommand),
+    /// Write the proposed renames to a plan file for review; its targets
+    /// can be edited before `namefmt apply` performs exactly those renames
+    Plan {
+        /// Path or file to process
+        path: Option<PathBuf>,
+        /// Where to write the plan
+        #[arg(short, long)]
+        output: PathBuf,
+        #[command(flatten)]
+        format: FormatOptions,
+    },
+    /// Review the proposed renames full-screen: toggle and edit them, then
+    /// perform the selected ones
+    Tui {
+        /// Path or file to process
+        path: Option<PathBuf>,
+        /// Allow running on /, the home directory or a protected root
+        #[arg(long)]
+        allow_root: bool,
+        #[command(flatten)]
+        format: FormatOptions,
+    },
+    /// Keep formatting a directory as files arrive in it, e.g. downloads or scans
+    Watch {
+        /// Directory to watch
+        path: PathBuf,
+        /// Actually perform renames (default: dry-run mode)
+        #[arg(short, long)]
+        inplace: bool,
+        /// Wait until nothing has changed for this long before a run, so
+        /// files still being written are left alone
+        #[arg(long, value_name = "MS", default_value_t = 1000)]
+        debounce: u64,
+        /// Allow --inplace on /, the home directory or a protected root
+        #[arg(long)]
+        allow_root: bool,
+        #[command(flatten)]
+        format: FormatOptions,
+    },
+    /// Finish an --inplace run that was interrupted
+    Resume {
+        /// Run to resume (default: the most recent unfinished run)
+        run_id: Option<String>,
+        /// Show the remaining renames without performing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Rename files according to an old -> new table (CSV, TSV or JSON)
+    ApplyMap {
+        /// Mapping file; paths in it are relative to --root
+        mapping: PathBuf,
+        /// Directory the mapping's paths are relative to
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+        /// Input format (default: guessed from the file extension)
+        #[arg(long, value_enum)]
+        format: Option<MapFormat>,
+        /// Actually perform renames (default: dry-run mode)
+        #[arg(short, long)]
+        inplace: bool,
+    },
+    /// Perform the renames in a reviewed (and perhaps edited) plan file
+    Apply {
+        plan: PathBuf,
+        /// Apply even if the configuration or files changed since planning
+        #[arg(long)]
+        force: bool,
+        /// Check the plan and show the renames without performing them
+        #[arg(long)]
+        dry_run: bool,
+        /// Allow a plan made for /, the home directory or a protected root
+        #[arg(long)]
+        allow_root: bool,
+        /// The profile the plan was made with
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+    },
+    /// Write a reviewable report of the renames a run would perform
+    Report {
+        /// Path or file to process
+        path: Option<PathBuf>,
+        #[arg(long, value_enum, default_value = "html")]
+        format: report::ReportFormat,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        options: FormatOptions,
+    },
+    /// Find files in different directories that would end up with the same name
+    Clashes {
+        /// Path or file to process
+        path: Option<PathBuf>,
+        /// Treat names that differ only in case as the same
+        #[arg(long)]
+        ignore_case: bool,
+        /// Add a suffix made of parent directory names to every clashing name
+        #[arg(long)]
+        disambiguate: bool,
+        /// Actually perform the renames, including the suffixes (default: dry-run mode)
+        #[arg(short, long, requires = "disambiguate")]
+        inplace: bool,
+        #[command(flatten)]
+        options: FormatOptions,
+    },
+    /// Write a starting configuration file (same as `config init`)
+    #[command(hide = true)]
+    Init {
+        /// Answer a few questions and preview the result instead of writing the defaults
+        #[arg(long)]
+        wizard: bool,
+        /// Replace an existing configuration file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Infer the naming conventions of a well-kept tree and print them as a config
+    Learn {
+        /// Directory to learn from
+        path: PathBuf,
+        /// Write the config to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Show which files two configurations would rename differently
+    DiffConfig {
+        /// Current configuration
+        old: PathBuf,
+        /// Proposed configuration
+        new: PathBuf,
+        /// Path or file to process
+        path: Option<PathBuf>,
+        /// Exit with status 1 if the configurations disagree about any file
+        #[arg(long)]
+        exit_code: bool,
+        #[command(flatten)]
+        options: FormatOptions,
+    },
+    /// List and install preset packs shared through a registry or git
+    #[command(subcommand)]
+    Preset(PresetCommand),
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Print a shell completion script
+    ///
+    /// e.g. `namefmt completions bash > ~/.local/share/bash-completion/completions/namefmt`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Format names or paths read from stdin, one per line, and print the
+    /// results; nothing is renamed
+    ///
+    /// e.g. `find . -name '*.txt' -print0 | namefmt filter -0 | xargs -0 ...`
+    Filter {
+        /// Names are separated by NUL characters, as `find -print0` and
+        /// `fd -0` write them, instead of newlines; so are the results
+        #[arg(short = '0', long)]
+        null: bool,
+        /// Look at the files the names refer to, relative to the current
+        /// directory, to detect packages, scripts and executables; without
+        /// it names are styled by what they say alone
+        #[arg(long)]
+        detect: bool,
+        #[command(flatten)]
+        format: FormatOptions,
+    },
 }
 
-fn default_replace_spaces() -> bool {
-    true
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Write a commented starting configuration file
+    Init {
+        /// Answer a few questions and preview the result instead of writing the defaults
+        #[arg(long)]
+        wizard: bool,
+        /// Replace an existing configuration file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the naming rules in effect for PATH, after the system, user
+    /// and project configurations and the profile are layered
+    Show {
+        /// Path the rules are for (default: the current directory)
+        path: Option<PathBuf>,
+        /// Lay this [profile.NAME] table over the rest
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Check a configuration file, reporting errors with their line and column
+    Validate {
+        /// File to check (default: the configuration file in use)
+        file: Option<PathBuf>,
+    },
+    /// Print where the configuration file is read from
+    Path,
+    /// Open the configuration file in $VISUAL or $EDITOR, then validate it
+    Edit,
 }
 
-fn default_exe_extensions() -> Vec<String> {
-    vec!["exe".to_string(), "bin".to_string(), "app".to_string()]
+#[derive(Subcommand, Debug)]
+enum PresetCommand {
+    /// Show built-in presets, installed packs and what the registry offers
+    List {
+        /// Registry index URL (default: `registry` from the config)
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    /// Install a pack: a registry name (pinned with name@version), a
+    /// git+URL[#tag] or *.git repository, or a URL or path to a pack file
+    Install {
+        spec: String,
+        /// Registry index URL (default: `registry` from the config)
+        #[arg(long)]
+        registry: Option<String>,
+        /// Install under this name instead of the registry or file name
+        #[arg(long)]
+        name: Option<String>,
+        /// Expected SHA-256 of a pack fetched outside the registry
+        #[arg(long)]
+        sha256: Option<String>,
+    },
 }
 
-fn default_package_dirs() -> Vec<String> {
-    vec!["package.json".to_string(), "Cargo.toml".to_string(), "pyproject.toml".to_string()]
+#[derive(Subcommand, Debug)]
+enum JournalCommand {
+    /// Write the journal as JSON or CSV
+    Export {
+        #[arg(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Append entries from an exported journal, skipping ones already present
+    Import {
+        file: PathBuf,
+        /// Input format (default: guessed from the file extension)
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+        /// Rewrite paths under FROM to TO, e.g. /old/share=/mnt/share
+        #[arg(long, value_name = "FROM=TO")]
+        relocate: Option<String>,
+    },
+    /// Check the journal's hash chain for edited, removed or reordered entries
+    /// and print its head hash
+    Verify {
+        /// A head printed by an earlier verify; fails if the journal no longer reaches it
+        #[arg(long, value_name = "HASH")]
+        head: Option<String>,
+    },
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            replace_spaces: true,
-            behaviors: Vec::new(),
-            detection: DetectionRules {
-                exe_extensions: default_exe_extensions(),
-                package_dirs: default_package_dirs(),
-            },
+/// Write the default configuration, or the one the wizard builds, to `config_path`.
+fn init_config(config_path: &Path, wizard: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if config_path.exists() && !force {
+        return Err(format!("{} already exists; pass --force to replace it", config_path.display()).into());
+    }
+    let content = if wizard {
+        let dir = std::env::current_dir()?;
+        let preview = |answers: &wizard::Answers| -> wizard::Preview {
+            let config = with_format_options(toml::from_str(&answers.to_toml())?, &FormatOptions::default());
+            let planned = build_plan(&dir, &config, config.timestamp, None)?;
+            Ok((planned.files.len(), planned.renames.into_iter().map(|r| (r.source, r.target)).collect()))
+        };
+        match wizard::run(&mut std::io::stdin().lock(), &mut std::io::stdout(), &dir, preview)? {
+            Some(answers) => answers.to_toml(),
+            None => return Err("Setup cancelled; nothing was written".into()),
         }
+    } else {
+        get_default_config_toml()
+    };
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
     }
+    fs::write(config_path, content)?;
+    info!("Wrote {}", config_path.display());
+    Ok(())
 }
 
-fn get_default_config_toml() -> String {
-    r#"replace_spaces = true
-
-[detection]
-exe_extensions = ["exe", "bin", "app"]
-package_dirs = ["package.json", "Cargo.toml", "pyproject.toml"]
-"#.to_string()
+/// Report what `validate_config` finds in `file`; an error if it can't be used.
+fn validate_file(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let problems = validate_config(file)?;
+    for problem in &problems {
+        warn!("{}", problem);
+    }
+    println!("{} is valid{}", file.display(), if problems.is_empty() { String::new() } else { format!(" ({} warning(s))", problems.len()) });
+    Ok(())
 }
 
 fn get_config_path(custom_path: Option<&PathBuf>) -> Result<PathBuf, Box<dyn std::error::Error>> {
@@ -111,260 +632,1080 @@ fn get_config_path(custom_path: Option<&PathBuf>) -> Result<PathBuf, Box<dyn std
     Ok(config_dir.join("namefmt").join("namefmt.toml"))
 }
 
-fn load_config(config_path: &Path) -> Config {
-    if !config_path.exists() {
-        // Create parent directory if it doesn't exist
-        if let Some(parent) = config_path.parent() {
-            if let Err(e) = fs::create_dir_all(parent) {
-                eprintln!("Warning: Failed to create config directory {}: {}", parent.display(), e);
-                eprintln!("Using default configuration");
-                return Config::default();
-            }
-        }
-        
-        // Write default config
-        let default_config = get_default_config_toml();
-        if let Err(e) = fs::write(config_path, &default_config) {
-            eprintln!("Warning: Failed to write default config to {}: {}", config_path.display(), e);
-            eprintln!("Using default configuration");
-            return Config::default();
-        }
-    }
-    
-    match fs::read_to_string(config_path) {
-        Ok(content) => {
-            match toml::from_str(&content) {
-                Ok(config) => config,
-                Err(e) => {
-                    eprintln!("Warning: Failed to parse {}: {}", config_path.display(), e);
-                    eprintln!("Using default configuration");
-                    Config::default()
-                }
-            }
+fn with_format_options(mut config: Config, options: &FormatOptions) -> Config {
+    config.timestamp |= options.timestamp;
+    let stamp = &mut config.timestamp_format;
+    if let Some(source) = options.timestamp_source {
+        config.timestamp_source = source;
+    }
+    if let Some(pattern) = &options.timestamp_format {
+        stamp.pattern = pattern.clone();
+    }
+    if let Some(separator) = &options.timestamp_separator {
+        stamp.separator = separator.clone();
+    }
+    if let Some(position) = options.timestamp_position {
+        stamp.position = position;
+    }
+    if let Some(timezone) = options.timezone {
+        stamp.timezone = timezone;
+    }
+    config.timestamp |= options.timestamp_source.is_some()
+        || options.timestamp_format.is_some()
+        || options.timestamp_separator.is_some()
+        || options.timestamp_position.is_some()
+        || options.timezone.is_some();
+    config.paths |= options.paths;
+    config.presets.extend(options.presets.iter().cloned());
+    config.ignore.extend(options.excludes.iter().cloned());
+    config.threads = options.threads.or(config.threads);
+    let traversal = &mut config.traversal;
+    traversal.max_depth = options.max_depth.or(traversal.max_depth);
+    traversal.min_depth = options.min_depth.or(traversal.min_depth);
+    traversal.follow_symlinks |= options.follow_symlinks;
+    traversal.skip_hidden |= options.skip_hidden;
+    let filters = &mut config.filters;
+    filters.extensions.extend(options.extensions.iter().map(|e| e.trim_start_matches('.').to_lowercase()));
+    filters.min_size = options.min_size.or(filters.min_size);
+    filters.max_size = options.max_size.or(filters.max_size);
+    filters.newer_than = options.newer_than.or(filters.newer_than);
+    filters.older_than = options.older_than.or(filters.older_than);
+    config.transliterate |= options.ascii;
+    config.max_length = options.max_length.or(config.max_length);
+    config.platform = options.platform.unwrap_or(config.platform);
+    config.gitignore |= options.gitignore;
+    config.git |= options.git;
+    config.default_protected &= !options.rename_protected;
+    config.duplicates = options.detect_dupes.unwrap_or(config.duplicates);
+    config.junk.strip |= options.strip_junk;
+    if let Some(format) = &options.normalize_dates {
+        config.dates.normalize = true;
+        config.dates.format = format.clone().unwrap_or(config.dates.format);
+    }
+    if let Some(template) = &options.template {
+        config.behaviors.insert(0, Behavior::templated("*", template));
+    }
+    if let Some(name) = &options.counter {
+        let template = format!("{}_{{counter}}.{{ext}}", name.replace('{', "{{").replace('}', "}}"));
+        let mut behavior = Behavior::templated("*", &template);
+        behavior.counter.width = 3;
+        config.behaviors.insert(0, behavior);
+    }
+    if let Some(length) = options.hash {
+        let behavior = if options.append_hash {
+            let mut behavior = Behavior::templated("*", &format!("{{stem}}.{{hash:{}}}{{extension}}", length));
+            // A digest of the same length before the extension is replaced,
+            // so files renamed before keep their name while unchanged
+            behavior.regex = Some(format!(r"^(?P<stem>.+?)(?:\.[0-9a-f]{{{}}})?(?P<extension>\.[^.]*)?$", length));
+            behavior
+        } else {
+            Behavior::templated("*", &format!("{{hash:{}}}.{{ext}}", length))
+        };
+        config.behaviors.insert(0, behavior);
+    }
+    for behavior in &mut config.behaviors {
+        let counter = &mut behavior.counter;
+        counter.start = options.counter_start.unwrap_or(counter.start);
+        counter.step = options.counter_step.unwrap_or(counter.step);
+        counter.width = options.counter_width.unwrap_or(counter.width);
+        counter.order = options.counter_order.unwrap_or(counter.order);
+    }
+    namefmt::expand_presets(config)
+}
+
+fn print_coverage(coverage: &[Coverage]) {
+    if coverage.is_empty() {
+        return;
+    }
+    println!("Rule coverage:");
+    for rule in coverage {
+        match rule.shadowed {
+            0 => println!("  {}: {} file(s)", rule.rule, rule.decided),
+            n => println!("  {}: {} file(s), {} more taken by earlier behaviors", rule.rule, rule.decided, n),
         }
-        Err(e) => {
-            eprintln!("Warning: Failed to read {}: {}", config_path.display(), e);
-            eprintln!("Using default configuration");
-            Config::default()
+    }
+    for rule in coverage {
+        if let Some(problem) = rule.problem() {
+            warn!("{} {}", rule.rule, problem);
         }
     }
 }
 
-fn get_timestamp_prefix() -> String {
-    let now = chrono::Utc::now();
-    format!("{}__", now.format("%Y_%m_%d"))
+/// What became of the files of one run.
+#[derive(Debug, Default)]
+struct Tally {
+    scanned: usize,
+    /// Renamed, or to be renamed in a dry run
+    renamed: usize,
+    /// Left alone, with the reason
+    skipped: Vec<(PathBuf, String)>,
+    /// Renames that failed under --keep-going, with the error
+    failed: Vec<(PathBuf, String)>,
 }
 
-fn format_filename(name: &str, config: &Config, path: &Path, timestamp: bool) -> Option<String> {
-    let mut result = name.to_string();
-    
-    // Check if this is an exe or package (use kebab-case)
-    if is_exe_or_package(path, config) {
-        result = to_kebab_case(&result);
-    } else {
-        // Apply pattern-based behaviors
-        for behavior in &config.behaviors {
-            if matches_pattern(&result, &behavior.pattern) {
-                result = apply_style(&result, &behavior.style);
-                break;
+impl Tally {
+    /// Counts, how many were skipped for each reason, and every failure.
+    fn print(&self, inplace: bool) {
+        let (renamed, failed) = if inplace { ("renamed", "failed") } else { ("to rename", "would fail") };
+        info!("Summary: {} {}, {} skipped, {} {}", self.renamed, renamed, self.skipped.len(), self.failed.len(), failed);
+        let mut reasons: Vec<(&str, usize)> = Vec::new();
+        for (_, reason) in &self.skipped {
+            match reasons.iter_mut().find(|(known, _)| known == reason) {
+                Some((_, n)) => *n += 1,
+                None => reasons.push((reason, 1)),
             }
         }
-        
-        // Default: replace spaces with underscores
-        if config.replace_spaces {
-            result = result.replace(' ', "_");
+        for (reason, n) in reasons {
+            info!("  Skipped ({}): {}", reason, n);
+        }
+        for (file, error) in &self.failed {
+            info!("  Failed: {}: {}", file.display(), error);
         }
-    }
-    
-    // Apply timestamp prefix last if requested
-    if timestamp {
-        let prefix = get_timestamp_prefix();
-        result = format!("{}{}", prefix, result);
-    }
-    
-    if result != name {
-        Some(result)
-    } else {
-        None
     }
 }
 
-fn is_exe_or_package(path: &Path, config: &Config) -> bool {
-    // Check if file has exe extension
-    if let Some(ext) = path.extension() {
-        let ext_str = ext.to_string_lossy().to_lowercase();
-        if config.detection.exe_extensions.iter().any(|e| e.to_lowercase() == ext_str) {
-            return true;
+fn process_path(path: &Path, config: &Config, inplace: bool, coverage: bool, run: &Run, cache: Option<&mut IdentityCache>, output: &mut Output) -> Result<Tally, Box<dyn std::error::Error>> {
+    let plan = build_plan(path, config, config.timestamp, cache.as_deref())?;
+    if let Some(limit) = config.max_renames.filter(|&limit| plan.renames.len() > limit) {
+        if inplace {
+            return Err(volume_summary(&plan, limit).into());
         }
+        warn!("{} renames exceed the limit of {}; an --inplace run would stop before renaming anything", plan.renames.len(), limit);
+    }
+    let sources = || plan.renames.iter().map(|r| r.source.as_path());
+    if inplace {
+        config.guardrails.confirm_spread(&plan.root, sources())?;
+    } else if let Some(warning) = config.guardrails.spread_warning(&plan.root, sources()) {
+        warn!("{}", warning);
     }
+    let pairs: Vec<(PathBuf, PathBuf)> = if config.interactive {
+        interactive::review(&plan.renames, config.platform, &mut io::stdin().lock(), &mut io::stderr())?
+    } else if config.edit {
+        edit::review(&plan.renames, config.platform)?
+    } else {
+        plan.renames.iter().map(|r| (r.source.clone(), r.target.clone())).collect()
+    };
+    for (file, reason) in &plan.skipped {
+        if output.is_text() {
+            verbose!("Skipped: {} ({})", file.display(), reason);
+        }
+        output.skipped(file, reason);
+    }
+    output.explain(&plan.renames, |rename| {
+        let styled = rename.rule.starts_with("behavior") || rename.rule.starts_with("executable");
+        styled.then(|| namefmt::style_for(&rename.source, config)).flatten().map(|style| style.as_str().to_string())
+    });
+    let failed = apply_renames(&pairs, inplace, config.git, config.keep_going, run, output)?;
     
-    // Check if directory contains package files
-    if path.is_dir() {
-        for package_file in &config.detection.package_dirs {
-            if path.join(package_file).exists() {
-                return true;
-            }
+    if let Some(cache) = cache {
+        // Renames turned down at the prompt are asked about again next time
+        let planned: HashSet<&PathBuf> = plan.renames.iter().map(|r| &r.source).collect();
+        for file in plan.files.iter().filter(|f| !planned.contains(f)) {
+            cache.remember(file);
         }
-    } else if let Some(parent) = path.parent() {
-        for package_file in &config.detection.package_dirs {
-            if parent.join(package_file).exists() {
-                return true;
+        if inplace {
+            for (_, target) in &pairs {
+                cache.remember(target);
             }
         }
+        if let Err(e) = cache.save() {
+            warn!("Failed to write identity cache: {}", e);
+        }
     }
     
-    false
-}
-
-fn matches_pattern(name: &str, pattern: &str) -> bool {
-    // Simple glob-like pattern matching
-    // Supports * for any characters
-    if pattern.contains('*') {
-        let parts: Vec<&str> = pattern.split('*').collect();
-        if parts.len() == 2 {
-            name.starts_with(parts[0]) && name.ends_with(parts[1])
-        } else if parts.len() == 1 {
-            name.contains(parts[0])
-        } else {
-            false
+    for suggestion in &plan.suggestions {
+        let reason = suggestion.rule.trim_start_matches("suggestion: ");
+        if output.is_text() {
+            info!("Suggestion: {} -> {} ({})", suggestion.source.display(), suggestion.target.display(), reason);
         }
-    } else {
-        name.contains(pattern)
+        output.suggested(suggestion, reason.split(',').next().map(str::to_string));
+    }
+    if !plan.suggestions.is_empty() && output.is_text() {
+        info!("{} suggestion(s) not applied; re-run with --apply-suggestions to accept them, or add behaviors to the config", plan.suggestions.len());
+    }
+    
+    if coverage && output.is_text() {
+        print_coverage(&plan.coverage);
     }
+    
+    let tally = Tally {
+        scanned: plan.files.len(),
+        renamed: pairs.len() - failed.len(),
+        skipped: plan.skipped,
+        failed,
+    };
+    if config.keep_going && output.is_text() {
+        tally.print(inplace);
+    }
+    Ok(tally)
 }
 
-fn apply_style(name: &str, style: &NamingStyle) -> String {
-    match style {
-        NamingStyle::CamelCase => to_camel_case(name),
-        NamingStyle::SnakeCase => to_snake_case(name),
-        NamingStyle::KebabCase => to_kebab_case(name),
+/// Explain a run that was stopped for renaming too many files: where the
+/// renames are and which rules are behind them.
+fn volume_summary(plan: &Plan, limit: usize) -> String {
+    let mut by_dir: HashMap<&Path, usize> = HashMap::new();
+    let mut by_rule: HashMap<&str, usize> = HashMap::new();
+    for rename in &plan.renames {
+        *by_dir.entry(rename.source.parent().unwrap_or(&plan.root)).or_default() += 1;
+        *by_rule.entry(&rename.rule).or_default() += 1;
     }
+    let top = |counts: HashMap<&str, usize>| {
+        let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts.iter().take(5).map(|(what, n)| format!("\n    {:>8}  {}", n, what)).collect::<String>()
+    };
+    let dirs: Vec<(String, usize)> = by_dir.into_iter()
+        .map(|(dir, n)| (dir.strip_prefix(&plan.root).unwrap_or(dir).display().to_string(), n))
+        .collect();
+    format!(
+        "Refusing to rename {} files, more than the limit of {} (max_renames / --limit). Nothing was renamed.\n  Directories:{}\n  Rules:{}\nRe-run with --force to go ahead anyway.",
+        plan.renames.len(),
+        limit,
+        top(dirs.iter().map(|(dir, n)| (if dir.is_empty() { "." } else { dir.as_str() }, *n)).collect()),
+        top(by_rule),
+    )
 }
 
-fn to_camel_case(s: &str) -> String {
-    let words: Vec<&str> = s.split(|c: char| c == ' ' || c == '_' || c == '-').collect();
-    let mut result = String::new();
-    
-    for (i, word) in words.iter().enumerate() {
-        if word.is_empty() {
-            continue;
+/// Perform a batch of renames, checkpointing it in the journal first so an
+/// interrupted run can be finished with `namefmt resume`. The batch goes
+/// through as a whole: it is checked up front, and when a rename fails
+/// anyway, the ones already made are put back. With `keep_going`, renames
+/// that fail are left out instead and returned with the error.
+fn apply_renames(renames: &[(PathBuf, PathBuf)], inplace: bool, git: bool, keep_going: bool, run: &Run, output: &Output) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let problems = transaction::preflight(renames);
+    debug!("Checked {} rename(s) in {:.2?}", renames.len(), started.elapsed());
+    let mut failed = Vec::new();
+    if !problems.is_empty() {
+        if inplace && !keep_going {
+            return Err(transaction::refusal(renames, &problems).into());
         }
-        if i == 0 {
-            result.push_str(&word.to_lowercase());
-        } else {
-            let mut chars: Vec<char> = word.chars().collect();
-            if !chars.is_empty() {
-                chars[0] = chars[0].to_uppercase().next().unwrap_or(chars[0]);
-                result.push_str(&chars.iter().collect::<String>());
+        for (i, problem) in &problems {
+            let (source, target) = &renames[*i];
+            // Listed in the summary at the end
+            if keep_going {
+                output.error(Some(source), Some(target), problem);
+                failed.push((source.clone(), problem.clone()));
+            } else if output.is_text() {
+                warn!("{}: {}", source.display(), problem);
             }
         }
+        if !keep_going {
+            warn!("{} rename(s) would fail; an --inplace run would stop before renaming anything", problems.len());
+        }
     }
+    let doomed: HashSet<usize> = problems.iter().map(|(i, _)| *i).collect();
+    let renames: Vec<&(PathBuf, PathBuf)> = renames.iter().enumerate().filter(|(i, _)| !doomed.contains(i)).map(|(_, rename)| rename).collect();
     
-    result
-}
-
-fn to_snake_case(s: &str) -> String {
-    let mut result = String::new();
-    let mut chars = s.chars().peekable();
+    if inplace && !renames.is_empty() {
+        let checkpoint: Vec<(PathBuf, PathBuf)> = renames.iter().map(|&rename| rename.clone()).collect();
+        if let Err(e) = run.begin(&checkpoint) {
+            warn!("Failed to checkpoint run in journal: {}", e);
+        }
+    }
     
-    while let Some(ch) = chars.next() {
-        if ch.is_uppercase() {
-            if !result.is_empty() && !result.ends_with('_') {
-                result.push('_');
+    let mut transaction = Transaction::new(run, git);
+    for (done, (source, target)) in renames.iter().copied().enumerate() {
+        if inplace {
+            log::progress("Renaming", done, Some(renames.len()));
+        }
+        if let Err(e) = apply_rename(source, target, inplace, &mut transaction, output) {
+            output.error(Some(source), Some(target), &e.to_string());
+            if keep_going {
+                error!("Renaming {} failed: {}", source.display(), e);
+                failed.push((source.clone(), e.to_string()));
+                continue;
             }
-            result.push(ch.to_lowercase().next().unwrap_or(ch));
-        } else if ch == ' ' || ch == '-' {
-            if !result.is_empty() && !result.ends_with('_') {
-                result.push('_');
+            if !inplace {
+                return Err(e);
             }
-        } else {
-            result.push(ch);
+            log::finish_progress();
+            return Err(roll_back(transaction, source, e, run, output).into());
         }
     }
+    log::finish_progress();
     
-    result
+    if inplace && !renames.is_empty() {
+        if let Err(e) = run.complete() {
+            warn!("Failed to write journal entry: {}", e);
+        }
+    }
+    
+    Ok(failed)
 }
 
-fn to_kebab_case(s: &str) -> String {
-    let mut result = String::new();
-    let mut chars = s.chars().peekable();
-    
-    while let Some(ch) = chars.next() {
-        if ch.is_uppercase() {
-            if !result.is_empty() && !result.ends_with('-') {
-                result.push('-');
-            }
-            result.push(ch.to_lowercase().next().unwrap_or(ch));
-        } else if ch == ' ' || ch == '_' {
-            if !result.is_empty() && !result.ends_with('-') {
-                result.push('-');
-            }
+/// Undo the batch after renaming `failed` went wrong, and say how that went.
+fn roll_back(transaction: Transaction, failed: &Path, error: Box<dyn std::error::Error>, run: &Run, output: &Output) -> String {
+    let rollback = transaction.rollback();
+    for (source, target) in &rollback.restored {
+        if output.is_text() {
+            info!("Rolled back: {} -> {}", target.display(), source.display());
         } else {
-            result.push(ch);
+            output.rolled_back(source, target);
         }
     }
-    
-    result
+    for (source, target, reason) in &rollback.stuck {
+        error!("Could not put back {} -> {}: {}", target.display(), source.display(), reason);
+    }
+    let summary = format!("Renaming {} failed: {}", failed.display(), error);
+    match rollback.stuck.len() {
+        0 => format!("{}; rolled back {} rename(s), nothing was changed", summary, rollback.restored.len()),
+        n => format!(
+            "{}; rolled back {} rename(s), but {} could not be put back. Once the cause is fixed, `namefmt undo {}` reverts them.",
+            summary,
+            rollback.restored.len(),
+            n,
+            run.id
+        ),
+    }
 }
 
-fn process_path(path: &Path, config: &Config, inplace: bool, timestamp: bool) -> Result<(), Box<dyn std::error::Error>> {
-    if path.is_file() {
-        process_file(path, config, inplace, timestamp)?;
-    } else if path.is_dir() {
-        for entry in WalkDir::new(path) {
-            let entry = entry?;
-            if entry.file_type().is_file() {
-                process_file(entry.path(), config, inplace, timestamp)?;
-            }
+fn apply_rename(file_path: &Path, new_path: &Path, inplace: bool, transaction: &mut Transaction, output: &Output) -> Result<(), Box<dyn std::error::Error>> {
+    // The rule behind it, with -v
+    let rule = output.reason(file_path)
+        .filter(|rule| !rule.is_empty() && log::enabled(log::Level::Verbose))
+        .map(|rule| format!(" [{}]", rule))
+        .unwrap_or_default();
+    if inplace {
+        let moved = transaction.rename(file_path, new_path)?;
+        match &moved.target_sha256 {
+            _ if !output.is_text() => output.rename(file_path, new_path, Some(transaction.run_id())),
+            Some(hash) => info!("Renamed: {} -> {} (copied, sha256 {} verified){}", file_path.display(), new_path.display(), hash, rule),
+            None => info!("Renamed: {} -> {}{}", file_path.display(), new_path.display(), rule),
         }
+    } else if output.is_colored() {
+        let (from, to) = diff::highlight(&file_path.display().to_string(), &new_path.display().to_string());
+        info!("Would rename: {} -> {}{}", from, to, rule);
+    } else if output.is_text() {
+        info!("Would rename: {} -> {}{}", file_path.display(), new_path.display(), rule);
     } else {
-        return Err(format!("Path does not exist: {}", path.display()).into());
+        output.rename(file_path, new_path, None);
     }
     
     Ok(())
 }
 
-fn process_file(file_path: &Path, config: &Config, inplace: bool, timestamp: bool) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(file_name) = file_path.file_name() {
-        let name = file_name.to_string_lossy();
-        
-        if let Some(new_name) = format_filename(&name, config, file_path, timestamp) {
-            let new_path = file_path.parent().unwrap().join(&new_name);
+fn run_command(command: &Command, config_path: &Path, lenient: bool, journal: &Journal) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Plan { path, output, format } => {
+            let path = path.as_deref().unwrap_or(Path::new("."));
+            let config = load_config_for(config_path, path, format.profile.as_deref(), lenient)?;
+            let config_hash = plan::config_hash(&config);
+            let config = with_format_options(config, format);
+            let path = std::path::absolute(path)?;
+            let planned = build_plan(&path, &config, config.timestamp, None)?;
+            
+            let mut renames = Vec::new();
+            for rename in &planned.renames {
+                let (size, mtime) = journal::file_fingerprint(&rename.source)?;
+                let relative = |p: &Path| p.strip_prefix(&planned.root).unwrap_or(p).to_path_buf();
+                renames.push(plan::PlannedRename {
+                    source: relative(&rename.source),
+                    target: relative(&rename.target),
+                    rule: rename.rule.clone(),
+                    size,
+                    mtime,
+                });
+            }
             
-            if inplace {
-                fs::rename(file_path, &new_path)?;
-                println!("Renamed: {} -> {}", file_path.display(), new_path.display());
-            } else {
-                println!("Would rename: {} -> {}", file_path.display(), new_path.display());
+            let plan_file = plan::PlanFile {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                created: chrono::Utc::now().to_rfc3339(),
+                config_hash,
+                files_hash: plan::files_hash(&planned.root, &planned.files),
+                file_count: planned.files.len(),
+                path: path.clone(),
+                root: planned.root.clone(),
+                timestamp: config.timestamp,
+                presets: format.presets.clone(),
+                renames,
+            };
+            plan_file.save(output)?;
+            info!("Wrote plan with {} rename(s) to {}", plan_file.renames.len(), output.display());
+            info!("Edit the targets if need be, then run `namefmt apply {}`", output.display());
+            Ok(())
+        }
+        Command::Fmt(_) => unreachable!("fmt runs in main"),
+        Command::Check { path, format } => {
+            let quiet = log::level() == log::Level::Quiet;
+            let path = path.as_deref().unwrap_or(Path::new("."));
+            let planned = load_config_for(config_path, path, format.profile.as_deref(), lenient).and_then(|config| {
+                let config = with_format_options(config, format);
+                build_plan(path, &config, config.timestamp, None)
+            });
+            let planned = planned.unwrap_or_else(|e| {
+                // A distinct status, so CI can tell a broken setup from badly named files
+                if !quiet {
+                    error!("{}", e);
+                }
+                std::process::exit(check::FAILED);
+            });
+            if !quiet {
+                print!("{}", check::render(&planned));
             }
+            std::process::exit(if planned.renames.is_empty() { check::CLEAN } else { check::VIOLATIONS });
+        }
+        Command::Config(ConfigCommand::Init { wizard, force }) => init_config(config_path, *wizard, *force),
+        Command::Config(ConfigCommand::Show { path, profile }) => {
+            let path = path.as_deref().unwrap_or(Path::new("."));
+            let config = load_config_for(config_path, path, profile.as_deref(), lenient)?;
+            print!("{}", toml::to_string_pretty(&config)?);
+            Ok(())
+        }
+        Command::Config(ConfigCommand::Validate { file }) => validate_file(file.as_deref().unwrap_or(config_path)),
+        Command::Config(ConfigCommand::Edit) => {
+            if !config_path.exists() {
+                init_config(config_path, false, false)?;
+            }
+            edit::open_editor(config_path)?;
+            validate_file(config_path)
+        }
+        Command::Config(ConfigCommand::Path) => {
+            println!("{}", config_path.display());
+            Ok(())
+        }
+        Command::Completions { shell } => {
+            print!("{}", shell.script(&Args::command()));
+            Ok(())
+        }
+        Command::Filter { null, detect, format } => {
+            let config = load_config_for(config_path, Path::new("."), format.profile.as_deref(), lenient)?;
+            let mut config = with_format_options(config, format);
+            // The same names give the same results wherever they're piped in
+            if !*detect {
+                config.detection.names_only();
+            }
+            let delimiter = if *null { b'\0' } else { b'\n' };
+            match pipe::filter(io::stdin().lock(), io::stdout().lock(), &config, delimiter) {
+                Ok(changed) => debug!("Formatted {} name(s)", changed),
+                // The reader stopped early, as `head` does
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+                Err(e) => return Err(e.into()),
+            }
+            Ok(())
+        }
+        Command::Report { path, format, output, options } => {
+            let path = path.as_deref().unwrap_or(Path::new("."));
+            let config = with_format_options(load_config_for(config_path, path, options.profile.as_deref(), lenient)?, options);
+            let path = std::path::absolute(path)?;
+            let planned = build_plan(&path, &config, config.timestamp, None)?;
+            let rendered = report::render(&planned, *format);
+            match output {
+                Some(file) => fs::write(file, rendered)?,
+                None => print!("{}", rendered),
+            }
+            Ok(())
+        }
+        Command::Clashes { path, ignore_case, disambiguate, inplace, options } => {
+            let path = path.as_deref().unwrap_or(Path::new("."));
+            let config = with_format_options(load_config_for(config_path, path, options.profile.as_deref(), lenient)?, options);
+            let mut planned = build_plan(path, &config, config.timestamp, None)?;
+            let found = clashes::find(&planned, *ignore_case);
+            print!("{}", clashes::render(&planned.root, planned.files.len(), &found));
+            if !disambiguate || found.is_empty() {
+                return Ok(());
+            }
+            
+            let rules: HashMap<&Path, &str> = planned.renames.iter().map(|r| (r.source.as_path(), r.rule.as_str())).collect();
+            let fixes: Vec<Rename> = found.iter().flat_map(|clash| clashes::disambiguate(&planned.root, clash, &rules)).collect();
+            let fixed: HashSet<PathBuf> = fixes.iter().map(|r| r.source.clone()).collect();
+            planned.renames.retain(|r| !fixed.contains(&r.source));
+            planned.renames.extend(fixes);
+            planned.renames.sort_by(|a, b| a.source.cmp(&b.source));
+            let conflicts = planned.conflicts();
+            if !conflicts.is_empty() {
+                for (rename, reason) in &conflicts {
+                    note!("Conflict: {} -> {}: {}", rename.source.display(), rename.target.display(), reason);
+                }
+                return Err(format!("{} conflicting rename(s), nothing was renamed", conflicts.len()).into());
+            }
+            
+            println!();
+            let run = journal.start_run(path);
+            let pairs: Vec<(PathBuf, PathBuf)> = planned.renames.into_iter().map(|r| (r.source, r.target)).collect();
+            let result = apply_renames(&pairs, *inplace, config.git, config.keep_going, &run, &Output::new(OutputFormat::Text));
+            if *inplace {
+                notify_run(&config, &run, path, &result, &[]);
+            }
+            result?;
+            if run.recorded() > 0 {
+                info!("Run ID: {}", run.id);
+            }
+            Ok(())
+        }
+        Command::Init { wizard, force } => init_config(config_path, *wizard, *force),
+        Command::Learn { path, output } => {
+            if !path.is_dir() {
+                return Err(format!("Not a directory: {}", path.display()).into());
+            }
+            let files = collect_files(path, &Config::default())?;
+            let learned = learn::learn(path, &files).to_toml();
+            match output {
+                Some(file) => fs::write(file, learned)?,
+                None => print!("{}", learned),
+            }
+            Ok(())
+        }
+        Command::DiffConfig { old, new, path, exit_code, options } => {
+            let old_config = with_format_options(read_config(old)?, options);
+            let new_config = with_format_options(read_config(new)?, options);
+            let path = path.as_deref().unwrap_or(Path::new("."));
+            let path = std::path::absolute(path)?;
+            let old_plan = build_plan(&path, &old_config, old_config.timestamp, None)?;
+            let new_plan = build_plan(&path, &new_config, new_config.timestamp, None)?;
+            
+            let differences = configdiff::compare(&old_plan, &new_plan);
+            print!("{}", configdiff::render(&new_plan.root, new_plan.files.len(), &differences));
+            if *exit_code && !differences.is_empty() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Tui { path, allow_root, format } => {
+            let path = path.as_deref().unwrap_or(Path::new("."));
+            let config = with_format_options(load_config_for(config_path, path, format.profile.as_deref(), lenient)?, format);
+            config.guardrails.check_root(path, *allow_root)?;
+            let planned = build_plan(path, &config, config.timestamp, None)?;
+            if planned.renames.is_empty() {
+                info!("Nothing to rename");
+                return Ok(());
+            }
+            let Some(pairs) = tui::run(&planned, config.platform)? else {
+                info!("Quit; nothing was renamed");
+                return Ok(());
+            };
+            let run = journal.start_run(path);
+            let result = apply_renames(&pairs, true, config.git, config.keep_going, &run, &Output::new(OutputFormat::Text));
+            notify_run(&config, &run, path, &result, &[]);
+            result?;
+            if run.recorded() > 0 {
+                info!("Run ID: {}", run.id);
+            }
+            Ok(())
+        }
+        Command::Watch { path, inplace, debounce, allow_root, format } => {
+            let config = with_format_options(load_config_for(config_path, path, format.profile.as_deref(), lenient)?, format);
+            if !path.is_dir() {
+                return Err(format!("Not a directory: {}", path.display()).into());
+            }
+            if *inplace {
+                config.guardrails.check_root(path, *allow_root)?;
+            }
+            let mut cache = identity_cache(&config);
+            let metrics = serve_metrics(&config);
+            // Watching starts before the first run so nothing dropped in
+            // meanwhile is missed
+            let mut watcher = watch::Watcher::new(path)?;
+            note!("Watching {} (Ctrl-C to stop)", path.display());
+            loop {
+                // Every run covers the whole directory, like `namefmt <path>`;
+                // the renames it makes wake the watcher once more, and that
+                // run finds nothing left to do
+                let run = journal.start_run(path);
+                let started = Instant::now();
+                metrics.run_started();
+                let result = process_path(path, &config, *inplace, false, &run, cache.as_mut(), &mut Output::new(OutputFormat::Text));
+                let failed = result.as_ref().map_or(&[][..], |tally| &tally.failed);
+                let stats = RunStats {
+                    scanned: result.as_ref().map_or(0, |tally| tally.scanned),
+                    renamed: run.recorded(),
+                    errors: result.is_err() as usize + failed.len(),
+                    elapsed: started.elapsed(),
+                };
+                metrics::report(&config.metrics, &metrics, &stats);
+                if *inplace && (run.recorded() > 0 || result.is_err() || !failed.is_empty()) {
+                    notify_run(&config, &run, path, &result, failed);
+                }
+                // Downloads too new to clean up are looked at again once
+                // they have settled, even if nothing else changes
+                let settling = result.as_ref().ok()
+                    .and_then(|tally| tally.skipped.iter()
+                        .filter(|(_, reason)| reason == "download in progress")
+                        .filter_map(|(file, _)| config.partial_downloads.settles_in(file))
+                        .min());
+                match result {
+                    Ok(_) if run.recorded() > 0 => info!("Run ID: {}", run.id),
+                    Ok(_) => {}
+                    // One bad batch doesn't end the watch
+                    Err(e) => error!("{}", e),
+                }
+                watcher.next_batch(Duration::from_millis(*debounce), settling, |pending| metrics.set_queue_depth(pending))?;
+                metrics.set_queue_depth(0);
+            }
+        }
+        Command::ApplyMap { mapping: map_path, root, format, inplace } => {
+            let config = load_config_for(config_path, root, None, lenient)?;
+            let format = format.unwrap_or_else(|| MapFormat::from_path(map_path));
+            let content = fs::read_to_string(map_path)
+                .map_err(|e| format!("Failed to read {}: {}", map_path.display(), e))?;
+            let pairs = mapping::parse(&content, format)
+                .map_err(|e| format!("{}: {}", map_path.display(), e))?;
+            let renames = mapping::resolve(root, &pairs, config.platform)?;
+            
+            let planned = Plan { root: root.clone(), files: Vec::new(), renames, skipped: Vec::new(), coverage: Vec::new(), suggestions: Vec::new() };
+            let conflicts = planned.conflicts();
+            if !conflicts.is_empty() {
+                for (rename, reason) in &conflicts {
+                    note!("Conflict: {} -> {}: {}", rename.source.display(), rename.target.display(), reason);
+                }
+                return Err(format!("{} conflicting rename(s) in the mapping, nothing was renamed", conflicts.len()).into());
+            }
+            
+            let run = journal.start_run(root);
+            let pairs: Vec<(PathBuf, PathBuf)> = mapping::order(planned.renames).into_iter()
+                .map(|r| (r.source, r.target))
+                .collect();
+            let result = apply_renames(&pairs, *inplace, config.git, config.keep_going, &run, &Output::new(OutputFormat::Text));
+            if *inplace {
+                notify_run(&config, &run, root, &result, &[]);
+            }
+            result?;
+            if run.recorded() > 0 {
+                info!("Run ID: {}", run.id);
+            }
+            Ok(())
+        }
+        Command::Apply { plan: plan_path, force, dry_run, allow_root, profile } => {
+            let plan_file = plan::PlanFile::load(plan_path)?;
+            let config = load_config_for(config_path, &plan_file.path, profile.as_deref(), lenient)?;
+            if !dry_run {
+                config.guardrails.check_root(&plan_file.root, *allow_root)?;
+            }
+            let config_hash = plan::config_hash(&config);
+            let (root, files) = collect_target(&plan_file.path, &config)?;
+            
+            let problems = plan_file.drift(&config_hash, &plan::files_hash(root, &files), files.len());
+            for problem in &problems {
+                warn!("{}", problem);
+            }
+            if !problems.is_empty() && !force {
+                return Err("Plan no longer matches the current state; re-run `namefmt plan` or pass --force".into());
+            }
+            // Targets may have been edited; --force doesn't cover names that can't work
+            let invalid = plan_file.invalid_targets(config.platform);
+            if !invalid.is_empty() {
+                let listed: String = invalid.iter().map(|problem| format!("\n    {}", problem)).collect();
+                return Err(format!("{} target(s) in {} can't be used, nothing was renamed:{}", invalid.len(), plan_path.display(), listed).into());
+            }
+            
+            let run = journal.start_run(&plan_file.root);
+            // A target edited back to the source name leaves the file alone
+            let pairs: Vec<(PathBuf, PathBuf)> = plan_file.renames.iter()
+                .filter(|r| r.source != r.target)
+                .map(|r| (plan_file.root.join(&r.source), plan_file.root.join(&r.target)))
+                .collect();
+            let result = apply_renames(&pairs, !dry_run, config.git, config.keep_going, &run, &Output::new(OutputFormat::Text));
+            if !dry_run {
+                notify_run(&config, &run, &plan_file.root, &result, &[]);
+            }
+            result?;
+            if run.recorded() > 0 {
+                info!("Run ID: {}", run.id);
+            }
+            Ok(())
+        }
+        Command::Resume { run_id, dry_run } => resume::resume(journal, run_id.as_deref(), *dry_run),
+        Command::Undo { run_id, only, since, last, dry_run } => {
+            if let Some(run_id) = run_id {
+                return undo::undo_run(journal, run_id, only.as_deref(), *dry_run);
+            }
+            if *last {
+                return undo::undo_last(journal, *dry_run);
+            }
+            let since = timeparse::parse_point_in_time(since.as_deref().unwrap_or_default())?;
+            undo::undo_since(journal, since, *dry_run)
+        }
+        Command::History { path, limit, since, until, trace, format } => {
+            let entries = journal.entries()?;
+            // A trace is of one file already; the dates narrow it down
+            let query = history::Query { path: path.clone().filter(|_| !*trace), since: *since, until: *until };
+            let mut moves: Vec<&journal::Entry> = match path.as_deref().filter(|_| *trace) {
+                Some(path) => history::trace(&entries, path),
+                None => history::moves(&entries).collect(),
+            };
+            moves.retain(|entry| query.matches(entry));
+            if *limit > 0 && moves.len() > *limit {
+                moves.drain(..moves.len() - limit);
+            }
+            if let Some(format) = format {
+                let moves: Vec<journal::Entry> = moves.into_iter().cloned().collect();
+                print!("{}", journal::export(&moves, *format));
+                return Ok(());
+            }
+            if moves.is_empty() {
+                note!("No renames recorded{}", path.as_ref().map(|p| format!(" for {}", p.display())).unwrap_or_default());
+                return Ok(());
+            }
+            for entry in &moves {
+                println!("{}", history::describe(entry));
+            }
+            if let Some(last) = moves.last().filter(|_| *trace) {
+                match last.target.exists() {
+                    true => println!("Now: {}", last.target.display()),
+                    false => println!("{} no longer exists", last.target.display()),
+                }
+            }
+            Ok(())
+        }
+        Command::Journal(JournalCommand::Export { format, output }) => {
+            let exported = journal::export(&journal.entries()?, *format);
+            match output {
+                Some(path) => fs::write(path, exported)?,
+                None => print!("{}", exported),
+            }
+            Ok(())
+        }
+        Command::Journal(JournalCommand::Import { file, format, relocate }) => {
+            let format = format.unwrap_or_else(|| {
+                match file.extension().and_then(|e| e.to_str()) {
+                    Some(ext) if ext.eq_ignore_ascii_case("csv") => ExportFormat::Csv,
+                    _ => ExportFormat::Json,
+                }
+            });
+            let content = fs::read_to_string(file)?;
+            let mut entries = journal::parse_export(&content, format)
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+            if let Some(relocate) = relocate {
+                let (from, to) = relocate.split_once('=')
+                    .ok_or("--relocate expects FROM=TO")?;
+                for entry in &mut entries {
+                    entry.relocate(Path::new(from), Path::new(to));
+                }
+            }
+            let added = journal.import(&entries)?;
+            info!("Imported {} of {} entries", added, entries.len());
+            Ok(())
+        }
+        Command::Journal(JournalCommand::Verify { head }) => {
+            let report = journal.verify(head.as_deref())?;
+            if let Some(head) = &report.head {
+                println!("Head: {}", head);
+            }
+            if report.problems.is_empty() {
+                println!("Journal OK: {} entries verified", report.verified);
+                return Ok(());
+            }
+            for problem in &report.problems {
+                eprintln!("{}", problem);
+            }
+            Err(format!("Journal verification failed: {} problem(s)", report.problems.len()).into())
+        }
+        Command::Preset(PresetCommand::List { registry }) => {
+            println!("Built-in: {}", presets::BUILT_IN.join(", "));
+            println!("Convention packs: {}", presets::PACKS.join(", "));
+            let installed = registry::installed()?;
+            if !installed.is_empty() {
+                println!("\nInstalled:");
+                for (name, pack) in &installed {
+                    println!("  {} {}  ({})", name, pack.version, pack.source);
+                }
+            }
+            let Some(registry) = registry.clone().or_else(|| load_config(config_path).registry) else {
+                return Ok(());
+            };
+            let mut available = registry::index(&registry)?.presets;
+            available.sort_by(|a, b| a.name.cmp(&b.name).then(registry::compare_versions(&b.version, &a.version)));
+            println!("\nAvailable from {}:", registry);
+            for (i, entry) in available.iter().enumerate() {
+                // Newest version of each pack first, the rest listed as older
+                if i > 0 && available[i - 1].name == entry.name {
+                    continue;
+                }
+                let older: Vec<&str> = available[i + 1..].iter().take_while(|e| e.name == entry.name).map(|e| e.version.as_str()).collect();
+                let older = if older.is_empty() { String::new() } else { format!(" (also {})", older.join(", ")) };
+                println!("{}", format!("  {} {}{}  {}", entry.name, entry.version, older, entry.description).trim_end());
+            }
+            Ok(())
+        }
+        Command::Preset(PresetCommand::Install { spec, registry, name, sha256 }) => {
+            let registry = registry.clone().or_else(|| load_config(config_path).registry);
+            let fetched = registry::fetch_pack(spec, registry.as_deref(), name.as_deref(), sha256.as_deref())?;
+            if presets::pack(&fetched.name).is_some() || presets::BUILT_IN.contains(&fetched.name.as_str()) {
+                return Err(format!("'{}' is a built-in preset; install the pack under another --name", fetched.name).into());
+            }
+            toml::from_str::<InstalledPack>(&fetched.content)
+                .map_err(|e| format!("{} is not a valid preset pack: {}", fetched.installed.source, e))?;
+            let path = registry::save(&fetched)?;
+            println!("Installed preset '{}' {} to {}", fetched.name, fetched.installed.version, path.display());
+            println!("  sha256 {}", fetched.installed.sha256);
+            println!("Enable it with --preset {} or presets = [\"{}\"] in the config", fetched.name, fetched.name);
+            Ok(())
         }
     }
-    
-    Ok(())
+}
+
+/// The identity cache for runs with `config`, if it is enabled.
+fn identity_cache(config: &Config) -> Option<IdentityCache> {
+    config.cache
+        .then(IdentityCache::default_path)
+        .flatten()
+        .map(|path| {
+            let options = format!("{}{}", plan::config_hash(config), config.timestamp);
+            IdentityCache::load(path, &hash::sha256_hex(options.as_bytes()))
+        })
+}
+
+fn serve_metrics(config: &Config) -> Arc<Metrics> {
+    let metrics = Arc::new(Metrics::default());
+    if let Some(address) = &config.metrics.listen {
+        if let Err(e) = metrics::serve(address, Arc::clone(&metrics)) {
+            warn!("Failed to serve metrics on {}: {}", address, e);
+        }
+    }
+    metrics
+}
+
+/// Tell the configured hooks how a run went: `result` and the renames
+/// that failed under --keep-going.
+fn notify_run<T>(config: &Config, run: &Run, root: &Path, result: &Result<T, Box<dyn std::error::Error>>, failed: &[(PathBuf, String)]) {
+    let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+    let failures = failed.iter().map(|(file, error)| format!("{}: {}", file.display(), error));
+    let summary = notify::Summary {
+        run_id: &run.id,
+        root: &root,
+        renamed: run.recorded(),
+        errors: result.as_ref().err().map(|e| e.to_string()).into_iter().chain(failures).collect(),
+    };
+    notify::run_completed(&config.notify, &summary);
+}
+
+/// Parse the command line. The global flags may come before a
+/// subcommand, so `fmt`'s own options are let through by clap and refused
+/// here instead.
+fn parse_args(argv: impl IntoIterator<Item = std::ffi::OsString>) -> Result<Args, clap::Error> {
+    let mut command = Args::command();
+    let matches = command.try_get_matches_from_mut(argv)?;
+    if let Some((name, _)) = matches.subcommand() {
+        let run = RunOptions::augment_args(clap::Command::new("fmt"));
+        let given = run.get_arguments()
+            .find(|arg| matches.value_source(arg.get_id().as_str()) == Some(clap::parser::ValueSource::CommandLine));
+        if let Some(arg) = given {
+            let shown = arg.get_long().map_or_else(|| arg.get_id().to_string().to_uppercase(), |long| format!("--{}", long));
+            let message = format!("'{}' can't be used with the subcommand '{}'; use `namefmt fmt {}` instead", shown, name, shown);
+            return Err(command.error(clap::error::ErrorKind::ArgumentConflict, message));
+        }
+    }
+    Args::from_arg_matches(&matches)
 }
 
 fn main() {
-    let args = Args::parse();
+    let args = parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit());
+    log::set_level(log::Level::from_flags(args.quiet, args.verbose));
+    
+    let journal = match Journal::default_path() {
+        Ok(path) => Journal::new(path),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
     
     let config_path = match get_config_path(args.config.as_ref()) {
         Ok(path) => path,
         Err(e) => {
-            eprintln!("Error: {}", e);
+            error!("{}", e);
             std::process::exit(1);
         }
     };
     
-    let config = load_config(&config_path);
+    let lenient = args.lenient;
+    let args = match args.command {
+        Some(Command::Fmt(run)) => run,
+        Some(command) => {
+            if let Err(e) = run_command(&command, &config_path, lenient, &journal) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => args.run,
+    };
     
-    let target_path = args.path.as_ref()
-        .map(|p| p.as_path())
+    let target_path = args.path.as_deref()
         .unwrap_or_else(|| Path::new("."));
+    let config = match load_config_for(&config_path, target_path, args.format.profile.as_deref(), lenient) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut config = with_format_options(config, &args.format);
+    config.shard = args.shard;
+    if config.shard.is_some() && config.paths {
+        // A directory renamed in one slice would move files another slice is renaming
+        error!("--shard can't be combined with paths = true; directory renames cross slices");
+        std::process::exit(1);
+    }
+    config.suggestions = match (args.suggest, args.apply_suggestions) {
+        (_, true) => Suggestions::Apply,
+        (true, false) => Suggestions::Show,
+        (false, false) => Suggestions::Off,
+    };
+    config.max_renames = if args.force { None } else { args.limit.or(config.max_renames) };
+    config.interactive = args.interactive;
+    config.edit = args.edit;
+    config.keep_going = args.keep_going;
+    let inplace = args.inplace || args.interactive || args.edit;
+    
+    config.guardrails.confirmed = args.yes;
     
-    match process_path(target_path, &config, args.inplace, args.timestamp) {
-        Ok(()) => {}
+    if inplace {
+        if let Err(e) = config.guardrails.check_root(target_path, args.allow_root) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    
+    // Held until exit so no second worker takes the same slice
+    let _claim = match args.shard.filter(|_| inplace) {
+        Some(shard) => match shard.claim(&journal.lock_dir(), target_path) {
+            Ok(claim) => Some(claim),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    
+    let mut cache = if args.no_cache { None } else { identity_cache(&config) };
+    let metrics = serve_metrics(&config);
+    
+    let run = journal.start_run(target_path);
+    let started = Instant::now();
+    metrics.run_started();
+    let mut output = Output::new(args.output).with_color(args.color);
+    debug!("Configuration: {}", config_path.display());
+    log::show_progress();
+    let result = process_path(target_path, &config, inplace, args.coverage, &run, cache.as_mut(), &mut output);
+    let failed = result.as_ref().map_or(&[][..], |tally| &tally.failed);
+    let stats = RunStats {
+        scanned: result.as_ref().map_or(0, |tally| tally.scanned),
+        renamed: run.recorded(),
+        errors: result.is_err() as usize + failed.len(),
+        elapsed: started.elapsed(),
+    };
+    metrics::report(&config.metrics, &metrics, &stats);
+    if inplace {
+        notify_run(&config, &run, target_path, &result, failed);
+    }
+    match result {
+        Ok(tally) => {
+            if run.recorded() > 0 && output.is_text() {
+                info!("Run ID: {}", run.id);
+            }
+            output.finish();
+            // Some renames failed under --keep-going
+            if !tally.failed.is_empty() {
+                std::process::exit(1);
+            }
+        }
         Err(e) => {
-            eprintln!("Error: {}", e);
+            // Errors about one rename are recorded where they happen
+            if !output.is_text() && !output.has_errors() {
+                output.error(None, None, &e.to_string());
+            }
+            output.finish();
+            error!("{}", e);
             std::process::exit(1);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, clap::Error> {
+        parse_args(line.split_whitespace().map(std::ffi::OsString::from))
+    }
+
+    #[test]
+    fn global_flags_come_before_or_after_subcommands() {
+        for line in ["namefmt -q check .", "namefmt check . -q", "namefmt -v undo --last", "namefmt -c f config validate", "namefmt --lenient check .", "namefmt -c x undo --last"] {
+            assert!(parse(line).is_ok_and(|args| args.command.is_some()), "{}", line);
+        }
+        let args = parse("namefmt -q --lenient -c conf.toml check .").unwrap();
+        assert!(args.quiet && args.lenient && args.config == Some(PathBuf::from("conf.toml")));
+        assert!(matches!(args.command, Some(Command::Check { .. })));
+    }
+
+    #[test]
+    fn bare_runs_take_a_path_and_fmt_options() {
+        let args = parse("namefmt -q --inplace photos").unwrap();
+        assert!(args.command.is_none() && args.quiet && args.run.inplace);
+        assert_eq!(args.run.path, Some(PathBuf::from("photos")));
+        assert!(parse("namefmt fmt --inplace photos").is_ok_and(|args| matches!(args.command, Some(Command::Fmt(_)))));
+    }
+
+    #[test]
+    fn fmt_options_are_refused_with_other_subcommands() {
+        let error = parse("namefmt --inplace check .").unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+        assert!(error.to_string().contains("'--inplace' can't be used with the subcommand 'check'"), "{}", error);
+    }
+
+    #[test]
+    fn oversized_runs_are_explained_by_directory_and_rule() {
+        let root = PathBuf::from("/photos");
+        let rename = |source: &str, rule: &str| Rename { source: root.join(source), target: root.join("x"), rule: rule.to_string() };
+        let plan = Plan {
+            root: root.clone(),
+            files: Vec::new(),
+            renames: vec![rename("2024/A.jpg", "snake_case"), rename("2024/B.jpg", "snake_case"), rename("C.jpg", "preset 'scanner'")],
+            skipped: Vec::new(),
+            coverage: Vec::new(),
+            suggestions: Vec::new(),
+        };
+        assert_eq!(volume_summary(&plan, 2), "\
+Refusing to rename 3 files, more than the limit of 2 (max_renames / --limit). Nothing was renamed.
+  Directories:
+           2  2024
+           1  .
+  Rules:
+           2  snake_case
+           1  preset 'scanner'
+Re-run with --force to go ahead anyway.");
+        let args = parse("namefmt --limit 10 --force .").unwrap();
+        assert_eq!((args.run.limit, args.run.force), (Some(10), true));
+    }
+
+    #[test]
+    fn platform_flag_overrides_the_configured_one() {
+        let windows = with_format_options(Config::default(), &parse("namefmt --platform windows photos").unwrap().run.format);
+        assert_eq!(windows.platform, Platform::Windows);
+        let path = Path::new("/nonexistent/namefmt-test/file");
+        assert_eq!(namefmt::format_filename_with_rule("Q1: Plan?.txt", &windows, path, false).0, "Q1_Plan.txt");
+        assert_eq!(namefmt::format_filename_with_rule("Q1: Plan?.txt", &Config::default(), path, false).0, "Q1:_Plan?.txt");
+        let mut configured = Config::default();
+        configured.platform = Platform::Windows;
+        assert_eq!(with_format_options(configured, &parse("namefmt photos").unwrap().run.format).platform, Platform::Windows);
+        assert!(parse("namefmt --platform dos photos").is_err());
+    }
+
+    #[test]
+    fn keep_going_renames_around_the_ones_that_would_fail() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        for name in ["A File", "B File", "C File", "taken"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        let renames = vec![
+            (dir.join("A File"), dir.join("a_file")),
+            (dir.join("B File"), dir.join("taken")),
+            (dir.join("C File"), dir.join("c_file")),
+        ];
+        let journal = Journal::new(dir.join("journal.jsonl"));
+        let output = Output::new(OutputFormat::Text);
+
+        let error = apply_renames(&renames, true, false, false, &journal.start_run(dir), &output).unwrap_err().to_string();
+        assert!(error.starts_with("1 of 3 rename(s) would fail, nothing was renamed:"), "{}", error);
+        assert!(dir.join("A File").exists() && dir.join("C File").exists());
+
+        let failed = apply_renames(&renames, true, false, true, &journal.start_run(dir), &output).unwrap();
+        assert_eq!(failed, [(dir.join("B File"), format!("{} already exists", dir.join("taken").display()))]);
+        assert!(dir.join("a_file").exists() && dir.join("c_file").exists() && dir.join("B File").exists());
+        assert_eq!(std::fs::read_to_string(dir.join("taken")).unwrap(), "taken");
+    }
+}
@@ -0,0 +1,149 @@
+use crate::csv;
+use crate::name::{Platform, SanitizedName};
+use crate::plan::Rename;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Header cells recognized (case-insensitively) as naming the two columns.
+const HEADERS: &[(&str, &str)] = &[("old", "new"), ("source", "target"), ("from", "to")];
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum MapFormat {
+    Csv,
+    Tsv,
+    Json,
+}
+
+impl MapFormat {
+    /// Guess from the file extension; CSV unless it says otherwise.
+    pub fn from_path(path: &Path) -> MapFormat {
+        match path.extension().map(|e| e.to_string_lossy().to_lowercase()).as_deref() {
+            Some("tsv" | "tab") => MapFormat::Tsv,
+            Some("json") => MapFormat::Json,
+            _ => MapFormat::Csv,
+        }
+    }
+}
+
+/// Old/new pairs from a mapping file.
+///
+/// CSV and TSV have two columns, with an optional `old,new` (or
+/// `source,target`, `from,to`) header. JSON is either an object of
+/// `"old": "new"` members or an array of `{"old": ..., "new": ...}`
+/// objects or `[old, new]` pairs.
+pub fn parse(content: &str, format: MapFormat) -> Result<Vec<(String, String)>, String> {
+    match format {
+        MapFormat::Csv => parse_delimited(content, ','),
+        MapFormat::Tsv => parse_delimited(content, '\t'),
+        MapFormat::Json => parse_json(content),
+    }
+}
+
+fn parse_delimited(content: &str, delimiter: char) -> Result<Vec<(String, String)>, String> {
+    let mut rows = csv::parse(content.trim_start_matches('\u{feff}'), delimiter)?;
+    let is_header = |row: &[String]| {
+        row.len() == 2 && HEADERS.iter().any(|(old, new)| row[0].trim().eq_ignore_ascii_case(old) && row[1].trim().eq_ignore_ascii_case(new))
+    };
+    if rows.first().is_some_and(|row| is_header(row)) {
+        rows.remove(0);
+    }
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, row)| match row.as_slice() {
+            [old, new] => Ok((old.clone(), new.clone())),
+            _ => Err(format!("row {}: expected 2 columns (old, new), found {}", i + 1, row.len())),
+        })
+        .collect()
+}
+
+fn parse_json(content: &str) -> Result<Vec<(String, String)>, String> {
+    let pair = |old: Option<&str>, new: Option<&str>, what: &str| match (old, new) {
+        (Some(old), Some(new)) => Ok((old.to_string(), new.to_string())),
+        _ => Err(format!("{}: expected an old and a new name", what)),
+    };
+    match serde_json::from_str(content).map_err(|e| e.to_string())? {
+        Value::Object(members) => members
+            .iter()
+            .map(|(old, new)| pair(Some(old), new.as_str(), &format!("\"{}\"", old)))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| match item {
+                Value::Array(pair_items) if pair_items.len() == 2 => {
+                    pair(pair_items[0].as_str(), pair_items[1].as_str(), &format!("item {}", i + 1))
+                }
+                item => {
+                    let field = |keys: [&str; 3]| keys.iter().find_map(|k| item.get(k).and_then(Value::as_str));
+                    pair(field(["old", "source", "from"]), field(["new", "target", "to"]), &format!("item {}", i + 1))
+                }
+            })
+            .collect(),
+        _ => Err(String::from("expected an object or an array")),
+    }
+}
+
+/// Resolve `pairs` against `root` into renames, checking every row first.
+///
+/// Old paths are relative to `root`. A new value without a `/` is a name
+/// in the old file's directory; otherwise it is a path relative to `root`.
+/// Fails listing every bad row: missing files, duplicate rows, names that
+/// aren't valid on `platform`.
+pub fn resolve(root: &Path, pairs: &[(String, String)], platform: Platform) -> Result<Vec<Rename>, String> {
+    let mut renames = Vec::new();
+    let mut problems = Vec::new();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+
+    for (old, new) in pairs {
+        let source = root.join(old.trim());
+        let new = new.trim();
+        let target = if new.contains('/') {
+            root.join(new)
+        } else {
+            source.with_file_name(new)
+        };
+        let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+        if !source.is_file() {
+            problems.push(format!("{}: no such file", old));
+        } else if !seen.insert(source.clone()) {
+            problems.push(format!("{}: listed more than once", old));
+        } else if name.is_empty() || SanitizedName::new(&name, platform).as_str() != name {
+            problems.push(format!("{} -> {}: not a valid file name", old, new));
+        } else if target != source {
+            renames.push(Rename { source, target, rule: String::from("mapping file") });
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(renames)
+    } else {
+        Err(format!("{} problem(s) in the mapping, nothing was renamed:\n  {}", problems.len(), problems.join("\n  ")))
+    }
+}
+
+/// Put `renames` in an order that never overwrites a file before it has
+/// been moved away: `b -> c` before `a -> b`. Cycles such as swaps go
+/// through a temporary name.
+pub fn order(renames: Vec<Rename>) -> Vec<Rename> {
+    let mut pending = renames;
+    let mut ordered = Vec::new();
+    let mut temporaries = 0;
+    while !pending.is_empty() {
+        let sources: HashSet<PathBuf> = pending.iter().map(|r| r.source.clone()).collect();
+        let (ready, blocked): (Vec<Rename>, Vec<Rename>) = pending.into_iter().partition(|r| !sources.contains(&r.target));
+        pending = blocked;
+        if !ready.is_empty() {
+            ordered.extend(ready);
+            continue;
+        }
+        // Everything left waits on something else: break a cycle
+        let rename = &mut pending[0];
+        temporaries += 1;
+        let temporary = rename.source.with_file_name(format!(".namefmt-swap-{}-{}", std::process::id(), temporaries));
+        ordered.push(Rename { source: rename.source.clone(), target: temporary.clone(), rule: rename.rule.clone() });
+        rename.source = temporary;
+    }
+    ordered
+}
@@ -0,0 +1,133 @@
+use crate::name;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// First private-use character; token `i` stands in as `PLACEHOLDER + i`
+/// while a name is being styled.
+const PLACEHOLDER: u32 = 0xE000;
+
+/// Technical tokens in video and audio release names (`1080p`, `x265`,
+/// `HDR`) that are kept whole and written in one canonical casing.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MediaTokens {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// Tokens as they should be written, matched regardless of case
+    #[serde(default = "default_tokens")]
+    pub tokens: Vec<String>,
+
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl Default for MediaTokens {
+    fn default() -> Self {
+        MediaTokens {
+            enabled: default_enabled(),
+            tokens: default_tokens(),
+            extensions: default_extensions(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_tokens() -> Vec<String> {
+    [
+        "480p", "576p", "720p", "1080p", "1440p", "2160p", "4K", "8K", "x264", "x265", "H264", "H265", "HEVC", "AVC",
+        "AV1", "VP9", "10bit", "HDR", "HDR10", "SDR", "AAC", "AC3", "EAC3", "DTS", "DTS-HD", "TrueHD", "Atmos", "DD5.1",
+        "DDP5.1", "FLAC", "MP3", "Opus", "WEB-DL", "WEBRip", "BluRay", "BDRip", "HDTV", "DVDRip", "REMUX",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_extensions() -> Vec<String> {
+    [
+        "mkv", "mp4", "m4v", "avi", "mov", "webm", "wmv", "m2ts", "mp3", "flac", "m4a", "aac", "ogg", "opus", "wav",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+impl MediaTokens {
+    /// The tokens that apply to `path`: none unless it is a media file.
+    pub fn for_file(&self, path: &Path) -> &[String] {
+        let is_media = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .is_some_and(|e| self.extensions.iter().any(|x| x.eq_ignore_ascii_case(&e)));
+        if self.enabled && is_media {
+            &self.tokens
+        } else {
+            &[]
+        }
+    }
+}
+
+/// Write every token found in `name` in its canonical casing.
+pub fn normalize(name: &str, tokens: &[String]) -> String {
+    restore(&hide(name, tokens), tokens)
+}
+
+/// Run `style` on `name` with its tokens hidden, so case converters can't
+/// split `4K` into `4_k`, and put them back afterwards.
+pub fn around(name: &str, tokens: &[String], style: impl Fn(&str) -> String) -> String {
+    if tokens.is_empty() {
+        return style(name);
+    }
+    restore(&style(&hide(name, tokens)), tokens)
+}
+
+/// Replace each whole token (not part of a longer word) by its placeholder.
+/// The extension is left alone: `.mp3` is not the `MP3` token.
+fn hide(name: &str, tokens: &[String]) -> String {
+    let (stem, extension) = name::split_extension(name);
+    let chars: Vec<char> = stem.chars().collect();
+    let lower: Vec<char> = stem.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect();
+    // Longest first, so `HDR10` wins over `HDR`
+    let mut order: Vec<(usize, Vec<char>)> = tokens
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (i, t.to_lowercase().chars().collect()))
+        .collect();
+    order.sort_by_key(|(_, t)| std::cmp::Reverse(t.len()));
+
+    let boundary = |at: usize| at == 0 || at >= chars.len() || !chars[at].is_alphanumeric() || !chars[at - 1].is_alphanumeric();
+    let mut out = String::new();
+    let mut at = 0;
+    while at < chars.len() {
+        let found = boundary(at)
+            .then(|| order.iter().find(|(_, t)| !t.is_empty() && lower[at..].starts_with(t) && boundary(at + t.len())))
+            .flatten();
+        match found.and_then(|(i, t)| Some((char::from_u32(PLACEHOLDER + *i as u32)?, t.len()))) {
+            Some((placeholder, len)) => {
+                out.push(placeholder);
+                at += len;
+            }
+            None => {
+                out.push(chars[at]);
+                at += 1;
+            }
+        }
+    }
+    out.push_str(extension);
+    out
+}
+
+fn restore(name: &str, tokens: &[String]) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        match (c as u32).checked_sub(PLACEHOLDER).and_then(|i| tokens.get(i as usize)) {
+            Some(token) => out.push_str(token),
+            None => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,528 @@
+use crate::pdf;
+use crate::template::Field;
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Template placeholders filled in from what a file says about itself.
+pub const TOKENS: &[&str] = &["artist", "album", "track", "title", "duration", "width", "height", "resolution", "created"];
+
+/// The most read of a tag or an MP4 `moov` box; cover art can make
+/// either run to a few megabytes.
+const READ_LIMIT: u64 = 16 << 20;
+
+/// How much of a Matroska file is read: its segment info and tracks come
+/// before the first cluster of media data.
+const MATROSKA_LIMIT: u64 = 1 << 20;
+
+/// Seconds from 1904, where MP4 times start, to 1970.
+const MP4_EPOCH: i64 = 2_082_844_800;
+
+/// Seconds from 1970 to 2001, where Matroska dates start.
+const MATROSKA_EPOCH: i64 = 978_307_200;
+
+/// What a file's tags say about it. Fields the file doesn't have are `None`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metadata {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// A song's title, or the title a PDF document gives itself
+    pub title: Option<String>,
+    /// The track's number on its album
+    pub track: Option<u64>,
+    /// Length of a recording, in whole seconds
+    pub duration: Option<u64>,
+    /// Size of a video's picture, in pixels
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    /// When a video was recorded, in local time
+    pub created: Option<NaiveDateTime>,
+}
+
+impl Metadata {
+    /// The value of the placeholder `token`; track numbers are two digits
+    /// unless the template gives a width, durations read like "3m05s" and
+    /// resolutions like "1080p".
+    pub fn field(&self, token: &str) -> Option<Field> {
+        match token {
+            "artist" => self.artist.clone().map(Field::Text),
+            "album" => self.album.clone().map(Field::Text),
+            "title" => self.title.clone().map(Field::Text),
+            "track" => self.track.map(|track| Field::Padded(track, 2)),
+            "duration" => self.duration.map(|seconds| Field::Text(format_duration(seconds))),
+            "width" => self.width.map(Field::Number),
+            "height" => self.height.map(Field::Number),
+            // Portrait videos are named by their width, as their players do
+            "resolution" => Some(Field::Text(format!("{}p", self.width?.min(self.height?)))),
+            "created" => self.created.map(Field::Date),
+            _ => None,
+        }
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// The metadata of the file at `path`: the ID3 tags of MP3s (v2, else
+/// v1), the Vorbis comments of FLAC files, the tags, length, picture size
+/// and recording time of MP4, QuickTime and Matroska files and the title
+/// of PDF documents. Empty for other files and ones that can't be read.
+pub fn read(path: &Path) -> Metadata {
+    File::open(path).ok().and_then(|mut file| from_reader(&mut file)).unwrap_or_default()
+}
+
+/// The metadata of the file `reader` reads, told apart by its first bytes.
+pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Option<Metadata> {
+    let mut head = [0u8; 12];
+    reader.read_exact(&mut head).ok()?;
+    let found = if head.starts_with(b"ID3") {
+        // The header gives the size of the tag that follows it
+        let size = syncsafe(&head[6..10]) as u64;
+        id3v2(&read_from(reader, 0, 10 + size)?)
+    } else if head.starts_with(b"fLaC") {
+        flac(reader)
+    } else if &head[4..8] == b"ftyp" {
+        mp4(reader)
+    } else if head.starts_with(b"\x1a\x45\xdf\xa3") {
+        matroska(&read_from(reader, 0, MATROSKA_LIMIT)?)
+    } else if head.starts_with(b"%PDF-") {
+        let mut data = Vec::new();
+        reader.seek(SeekFrom::Start(0)).ok()?;
+        reader.read_to_end(&mut data).ok()?;
+        Some(Metadata { title: pdf::title(&data).as_deref().and_then(clean), ..Metadata::default() })
+    } else {
+        None
+    };
+    found.filter(|tags| *tags != Metadata::default()).or_else(|| id3v1(reader))
+}
+
+/// Up to `length` bytes (and at most `READ_LIMIT`) from `offset`.
+fn read_from<R: Read + Seek>(reader: &mut R, offset: u64, length: u64) -> Option<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset)).ok()?;
+    let mut data = Vec::new();
+    reader.take(length.min(READ_LIMIT)).read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+/// A size stored in seven bits of each of four bytes.
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |size, &b| size << 7 | (b & 0x7f) as usize)
+}
+
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, &b| n << 8 | b as usize)
+}
+
+/// `text` without surrounding blanks and NULs, unless nothing is left.
+fn clean(text: &str) -> Option<String> {
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// The number of a track written as "3" or "3/12".
+fn track_number(text: &str) -> Option<u64> {
+    text.split('/').next()?.trim().parse().ok().filter(|&track| track > 0)
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// An ID3v2.2, 2.3 or 2.4 tag, header included.
+fn id3v2(tag: &[u8]) -> Option<Metadata> {
+    let (major, flags) = (*tag.get(3)?, *tag.get(5)?);
+    let mut body = Cow::Borrowed(tag.get(10..)?);
+    // Before 2.4 the whole tag may be unsynchronised: 0xFF 0x00 stands for 0xFF
+    if flags & 0x80 != 0 && major < 4 {
+        let mut restored = Vec::with_capacity(body.len());
+        for (i, &b) in body.iter().enumerate() {
+            if !(b == 0 && i > 0 && body[i - 1] == 0xFF) {
+                restored.push(b);
+            }
+        }
+        body = Cow::Owned(restored);
+    }
+    let mut at = 0;
+    if flags & 0x40 != 0 {
+        // The extended header's size counts itself in 2.4 only
+        at = match major {
+            3 => 4 + be(body.get(0..4)?),
+            _ => syncsafe(body.get(0..4)?),
+        };
+    }
+    let (id_length, header_length) = if major == 2 { (3, 6) } else { (4, 10) };
+    let mut tags = Metadata::default();
+    while let Some(header) = body.get(at..at + header_length) {
+        // Padding follows the last frame
+        if header[0] == 0 {
+            break;
+        }
+        let size = match major {
+            2 => be(&header[3..6]),
+            3 => be(&header[4..8]),
+            _ => syncsafe(&header[4..8]),
+        };
+        let Some(frame) = body.get(at + header_length..at + header_length + size) else {
+            break;
+        };
+        match &header[..id_length] {
+            b"TPE1" | b"TP1" => tags.artist = id3_text(frame),
+            b"TALB" | b"TAL" => tags.album = id3_text(frame),
+            b"TIT2" | b"TT2" => tags.title = id3_text(frame),
+            b"TRCK" | b"TRK" => tags.track = id3_text(frame).as_deref().and_then(track_number),
+            _ => {}
+        }
+        at += header_length + size;
+    }
+    Some(tags)
+}
+
+/// The first value of a text frame, in whichever encoding it names.
+fn id3_text(frame: &[u8]) -> Option<String> {
+    let (&encoding, data) = frame.split_first()?;
+    let text = match encoding {
+        0 => latin1(data),
+        1 | 2 => {
+            let (little_endian, data) = match data {
+                [0xFF, 0xFE, rest @ ..] => (true, rest),
+                [0xFE, 0xFF, rest @ ..] => (false, rest),
+                _ => (encoding == 1, data),
+            };
+            let units = data.chunks_exact(2).map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if little_endian { u16::from_le_bytes(pair) } else { u16::from_be_bytes(pair) }
+            });
+            char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+        }
+        _ => String::from_utf8_lossy(data).into_owned(),
+    };
+    // Several values are separated by NULs
+    clean(text.split('\0').next()?)
+}
+
+/// The 128-byte ID3v1 tag at the end of a file.
+fn id3v1<R: Read + Seek>(reader: &mut R) -> Option<Metadata> {
+    reader.seek(SeekFrom::End(-128)).ok()?;
+    let mut tag = [0u8; 128];
+    reader.read_exact(&mut tag).ok()?;
+    if !tag.starts_with(b"TAG") {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| clean(&latin1(&tag[range]));
+    Some(Metadata {
+        title: field(3..33),
+        artist: field(33..63),
+        album: field(63..93),
+        // ID3v1.1 keeps the track in the comment's last byte, after a NUL
+        track: (tag[125] == 0 && tag[126] != 0).then_some(tag[126] as u64),
+        ..Metadata::default()
+    })
+}
+
+/// The Vorbis comment block among a FLAC file's metadata blocks.
+fn flac<R: Read + Seek>(reader: &mut R) -> Option<Metadata> {
+    let mut at = 4;
+    loop {
+        let mut header = [0u8; 4];
+        reader.seek(SeekFrom::Start(at)).ok()?;
+        reader.read_exact(&mut header).ok()?;
+        let length = be(&header[1..4]) as u64;
+        if header[0] & 0x7f == 4 {
+            return vorbis_comments(&read_from(reader, at + 4, length)?);
+        }
+        // The high bit marks the last block
+        if header[0] & 0x80 != 0 {
+            return None;
+        }
+        at += 4 + length;
+    }
+}
+
+fn vorbis_comments(block: &[u8]) -> Option<Metadata> {
+    let le = |at: usize| block.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    // The encoder's name comes first
+    let mut at = 4 + le(0)?;
+    let count = le(at)?;
+    at += 4;
+    let mut tags = Metadata::default();
+    for _ in 0..count {
+        let length = le(at)?;
+        let comment = String::from_utf8_lossy(block.get(at + 4..at + 4 + length)?);
+        at += 4 + length;
+        let Some((key, value)) = comment.split_once('=') else {
+            continue;
+        };
+        // A field may be given more than once; the first is kept
+        match key.to_ascii_uppercase().as_str() {
+            "ARTIST" => tags.artist = tags.artist.take().or_else(|| clean(value)),
+            "ALBUM" => tags.album = tags.album.take().or_else(|| clean(value)),
+            "TITLE" => tags.title = tags.title.take().or_else(|| clean(value)),
+            "TRACKNUMBER" => tags.track = tags.track.or_else(|| track_number(value)),
+            _ => {}
+        }
+    }
+    Some(tags)
+}
+
+/// The `moov/udta/meta/ilst` items of an MP4 file, and what its movie and
+/// track headers say. `moov` is looked for among the top-level boxes,
+/// which may put it after the media data.
+fn mp4<R: Read + Seek>(reader: &mut R) -> Option<Metadata> {
+    let end = reader.seek(SeekFrom::End(0)).ok()?;
+    let mut at = 0;
+    let moov = loop {
+        if at + 8 > end {
+            return None;
+        }
+        let header = read_from(reader, at, 16)?;
+        let (size, body) = match be(header.get(0..4)?) as u64 {
+            0 => (end - at, 8),
+            1 => (be(header.get(8..16)?) as u64, 16),
+            size => (size, 8),
+        };
+        if size < body {
+            return None;
+        }
+        if header.get(4..8)? == b"moov" {
+            break read_from(reader, at + body, size - body)?;
+        }
+        at += size;
+    };
+    let mut tags = Metadata::default();
+    if let Some(mvhd) = child(&moov, b"mvhd") {
+        // Version 1 headers have 64-bit times
+        let (created, timescale, duration) = match mvhd.first()? {
+            0 => (mvhd.get(4..8)?, mvhd.get(12..16)?, mvhd.get(16..20)?),
+            _ => (mvhd.get(4..12)?, mvhd.get(20..24)?, mvhd.get(24..32)?),
+        };
+        // Zero means the time wasn't recorded
+        tags.created = Some(be(created) as i64).filter(|&seconds| seconds > 0).and_then(|seconds| local_time(seconds - MP4_EPOCH));
+        tags.duration = Some(be(timescale) as u64).filter(|&scale| scale > 0).map(|scale| be(duration) as u64 / scale);
+    }
+    // The picture's size ends each track header, as 16.16 fixed point;
+    // sound tracks have none
+    let picture = boxes(&moov)
+        .filter(|(kind, _)| *kind == b"trak")
+        .filter_map(|(_, trak)| child(trak, b"tkhd"))
+        .filter_map(|tkhd| tkhd.len().checked_sub(8).map(|at| (be(&tkhd[at..at + 2]) as u64, be(&tkhd[at + 4..at + 6]) as u64)))
+        .find(|&(width, height)| width > 0 && height > 0);
+    if let Some((width, height)) = picture {
+        (tags.width, tags.height) = (Some(width), Some(height));
+    }
+    // `meta` is a full box: its version and flags come before its children
+    let ilst = child(&moov, b"udta")
+        .and_then(|udta| child(udta, b"meta"))
+        .and_then(|meta| child(meta.get(4..)?, b"ilst"))
+        .unwrap_or_default();
+    for (kind, item) in boxes(ilst) {
+        let Some(value) = child(item, b"data").and_then(|data| data.get(8..)) else {
+            continue;
+        };
+        let text = || clean(&String::from_utf8_lossy(value));
+        match kind {
+            b"\xa9ART" => tags.artist = text(),
+            b"\xa9alb" => tags.album = text(),
+            b"\xa9nam" => tags.title = text(),
+            b"trkn" => tags.track = value.get(2..4).map(|n| be(n) as u64).filter(|&track| track > 0),
+            _ => {}
+        }
+    }
+    Some(tags)
+}
+
+/// The boxes one after the other in `data`, as type and content.
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> + '_ {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let size = match be(rest.get(0..4)?) {
+            0 => rest.len(),
+            size => size,
+        };
+        let found = (rest.get(4..8)?, rest.get(8..size)?);
+        rest = &rest[size..];
+        Some(found)
+    })
+}
+
+/// The content of the first box of type `kind` in `data`.
+fn child<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data).find(|(found, _)| *found == kind).map(|(_, content)| content)
+}
+
+/// A time given in seconds since 1970, in local time.
+fn local_time(seconds: i64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(seconds, 0).map(|time| time.with_timezone(&Local).naive_local())
+}
+
+const SEGMENT: u64 = 0x1853_8067;
+const INFO: u64 = 0x1549_A966;
+const TIMESTAMP_SCALE: u64 = 0x2A_D7B1;
+const DURATION: u64 = 0x4489;
+const DATE_UTC: u64 = 0x4461;
+const TRACKS: u64 = 0x1654_AE6B;
+const TRACK_ENTRY: u64 = 0xAE;
+const VIDEO: u64 = 0xE0;
+const PIXEL_WIDTH: u64 = 0xB0;
+const PIXEL_HEIGHT: u64 = 0xBA;
+const CLUSTER: u64 = 0x1F43_B675;
+
+/// The segment info and first video track of a Matroska or WebM file
+/// starting with `data`.
+fn matroska(data: &[u8]) -> Option<Metadata> {
+    let segment = elements(data).find(|&(id, _)| id == SEGMENT)?.1;
+    let mut tags = Metadata::default();
+    for (id, body) in elements(segment) {
+        match id {
+            INFO => {
+                // Durations count ticks of the scale, in nanoseconds
+                let mut scale = 1_000_000.0;
+                let mut duration = None;
+                for (id, value) in elements(body) {
+                    match id {
+                        TIMESTAMP_SCALE => scale = be(value) as f64,
+                        DURATION => duration = float(value),
+                        DATE_UTC => tags.created = local_time(signed(value) / 1_000_000_000 + MATROSKA_EPOCH),
+                        _ => {}
+                    }
+                }
+                tags.duration = duration.map(|ticks| (ticks * scale / 1e9) as u64);
+            }
+            TRACKS => {
+                let video = elements(body)
+                    .filter(|&(id, _)| id == TRACK_ENTRY)
+                    .find_map(|(_, entry)| elements(entry).find(|&(id, _)| id == VIDEO));
+                for (id, value) in video.map_or_else(Vec::new, |(_, video)| elements(video).collect()) {
+                    match id {
+                        PIXEL_WIDTH => tags.width = Some(be(value) as u64),
+                        PIXEL_HEIGHT => tags.height = Some(be(value) as u64),
+                        _ => {}
+                    }
+                }
+            }
+            // Media data follows
+            CLUSTER => break,
+            _ => {}
+        }
+    }
+    Some(tags)
+}
+
+/// The EBML elements one after the other in `data`, as ID and content;
+/// the last one may be cut short where `data` ends.
+fn elements(data: &[u8]) -> impl Iterator<Item = (u64, &[u8])> + '_ {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let (id, id_length) = vint(rest, false)?;
+        let (size, size_length) = vint(rest.get(id_length..)?, true)?;
+        let content = rest.get(id_length + size_length..)?;
+        // A size of all ones means the element runs to the end of its parent
+        let unknown = size == (1 << (7 * size_length)) - 1;
+        let (body, after) = content.split_at(if unknown { content.len() } else { (size as usize).min(content.len()) });
+        rest = after;
+        Some((id, body))
+    })
+}
+
+/// A variable-length EBML number and how many bytes it takes; IDs keep
+/// the marker bit that gives the length, sizes don't.
+fn vint(data: &[u8], size: bool) -> Option<(u64, usize)> {
+    let length = data.first()?.leading_zeros() as usize + 1;
+    let value = be(data.get(..length).filter(|_| length <= 8)?) as u64;
+    Some((if size { value & ((1 << (7 * length)) - 1) } else { value }, length))
+}
+
+fn float(bytes: &[u8]) -> Option<f64> {
+    match bytes.len() {
+        4 => Some(f32::from_be_bytes(bytes.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(bytes.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// A big-endian two's complement number of up to eight bytes.
+fn signed(bytes: &[u8]) -> i64 {
+    let unused = 64 - 8 * bytes.len().clamp(1, 8) as u32;
+    ((be(bytes) as u64) << unused) as i64 >> unused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8], content: &[u8]) -> Vec<u8> {
+        [&((content.len() + 8) as u32).to_be_bytes()[..], kind, content].concat()
+    }
+
+    #[test]
+    fn id3_flac_and_mp4_tags_are_read() {
+        let frame = |id: &[u8], text: &[u8]| [id, &((text.len() + 1) as u32).to_be_bytes(), &[0, 0, 3], text].concat();
+        let frames = [frame(b"TPE1", b"Daft Punk"), frame(b"TRCK", b"3/14"), frame(b"TIT2", b"Digital Love")].concat();
+        let mut mp3 = [&b"ID3\x03\0\0\0\0\0"[..], &[frames.len() as u8], &frames, b"\xff\xfb audio"].concat();
+        let found = from_reader(&mut Cursor::new(&mp3)).unwrap();
+        assert_eq!((found.artist.as_deref(), found.track, found.title.as_deref()), (Some("Daft Punk"), Some(3), Some("Digital Love")));
+
+        // Without an ID3v2 tag, the ID3v1 tag at the end is used
+        mp3.drain(..10 + frames.len());
+        let mut v1 = [0u8; 128];
+        v1[..3].copy_from_slice(b"TAG");
+        v1[3..8].copy_from_slice(b"Aerod");
+        v1[126] = 7;
+        mp3.extend(v1);
+        let found = from_reader(&mut Cursor::new(&mp3)).unwrap();
+        assert_eq!((found.title.as_deref(), found.track, found.artist), (Some("Aerod"), Some(7), None));
+
+        let comments: Vec<u8> = ["ARTIST=Boards of Canada", "title=Roygbiv", "TRACKNUMBER=6"].iter()
+            .flat_map(|c| [&(c.len() as u32).to_le_bytes()[..], c.as_bytes()].concat())
+            .collect();
+        let block = [&3u32.to_le_bytes()[..], b"enc", &3u32.to_le_bytes(), &comments].concat();
+        let flac = [&b"fLaC\x00\0\0\x04info\x84"[..], &(block.len() as u32).to_be_bytes()[1..], &block].concat();
+        let found = from_reader(&mut Cursor::new(&flac)).unwrap();
+        assert_eq!((found.artist.as_deref(), found.track, found.title.as_deref()), (Some("Boards of Canada"), Some(6), Some("Roygbiv")));
+
+        let item = |kind: &[u8], value: &[u8]| mp4_box(kind, &mp4_box(b"data", &[&[0u8; 8][..], value].concat()));
+        let ilst = mp4_box(b"ilst", &[item(b"\xa9alb", b"Discovery"), item(b"trkn", b"\0\0\0\x09\0\x0e")].concat());
+        let moov = mp4_box(b"moov", &mp4_box(b"udta", &mp4_box(b"meta", &[&[0u8; 4][..], &ilst].concat())));
+        let m4a = [mp4_box(b"ftyp", b"M4A \0\0\0\0"), mp4_box(b"mdat", b"audio"), moov].concat();
+        let found = from_reader(&mut Cursor::new(&m4a)).unwrap();
+        assert_eq!((found.album.as_deref(), found.track), (Some("Discovery"), Some(9)));
+    }
+
+    #[test]
+    fn video_headers_and_pdf_titles_are_read() {
+        let mvhd = [&[0u8; 4][..], &(MP4_EPOCH as u32 + 1_704_412_800).to_be_bytes(), &[0; 4], &600u32.to_be_bytes(), &(600u32 * 125).to_be_bytes()].concat();
+        let tkhd = |width: u32, height: u32| mp4_box(b"tkhd", &[&[0u8; 76][..], &(width << 16).to_be_bytes(), &(height << 16).to_be_bytes()].concat());
+        let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &mvhd), mp4_box(b"trak", &tkhd(0, 0)), mp4_box(b"trak", &tkhd(1920, 1080))].concat());
+        let mp4 = [mp4_box(b"ftyp", b"isom\0\0\0\0"), moov].concat();
+        let found = from_reader(&mut Cursor::new(&mp4)).unwrap();
+        assert_eq!((found.duration, found.width, found.height), (Some(125), Some(1920), Some(1080)));
+        assert_eq!(found.created, local_time(1_704_412_800));
+        let text = |token: &str| match found.field(token) {
+            Some(Field::Text(text)) => text,
+            _ => String::new(),
+        };
+        assert_eq!((text("resolution"), text("duration")), ("1080p".to_string(), "2m05s".to_string()));
+
+        // Sizes of one byte, with the marker bit set
+        let element = |id: &[u8], content: &[u8]| [id, &[0x80 | content.len() as u8], content].concat();
+        let info = element(b"\x15\x49\xa9\x66", &element(b"\x44\x89", &3_600_000f32.to_be_bytes()));
+        let video = element(b"\xe0", &[element(b"\xb0", &[0x05, 0x00]), element(b"\xba", &[0x02, 0xd0])].concat());
+        let tracks = element(b"\x16\x54\xae\x6b", &element(b"\xae", &video));
+        let mkv = [element(b"\x1a\x45\xdf\xa3", b"webm"), b"\x18\x53\x80\x67\x01\xff\xff\xff\xff\xff\xff\xff".to_vec(), info, tracks].concat();
+        let found = from_reader(&mut Cursor::new(&mkv)).unwrap();
+        assert_eq!((found.duration, found.width, found.height), (Some(3600), Some(1280), Some(720)));
+
+        let pdf = b"%PDF-1.4\n3 0 obj\n<< /Title (Chapter) >>\nendobj\n13 0 obj\n<< /Title <FEFF005100340020005200650070006F00720074> >>\nendobj\ntrailer << /Info 13 0 R >>";
+        assert_eq!(from_reader(&mut Cursor::new(&pdf[..])).unwrap().title.as_deref(), Some("Q4 Report"));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::warn;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Where run metrics go, for monitoring scheduled or long-running runs.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsRules {
+    /// statsd server that gets a UDP push after every run, e.g. "127.0.0.1:8125"
+    #[serde(default)]
+    pub statsd: Option<String>,
+
+    /// Prefix for statsd metric names
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+
+    /// Prometheus text file rewritten after every run, for node_exporter's
+    /// textfile collector
+    #[serde(default)]
+    pub textfile: Option<PathBuf>,
+
+    /// Address to serve Prometheus metrics on for as long as namefmt runs,
+    /// e.g. "127.0.0.1:9184"
+    #[serde(default)]
+    pub listen: Option<String>,
+}
+
+impl Default for MetricsRules {
+    fn default() -> Self {
+        MetricsRules {
+            statsd: None,
+            prefix: default_prefix(),
+            textfile: None,
+            listen: None,
+        }
+    }
+}
+
+fn default_prefix() -> String {
+    String::from("namefmt")
+}
+
+/// What one run did.
+pub struct RunStats {
+    pub scanned: usize,
+    pub renamed: usize,
+    pub errors: usize,
+    pub elapsed: Duration,
+}
+
+impl RunStats {
+    fn scan_rate(&self) -> f64 {
+        self.scanned as f64 / self.elapsed.as_secs_f64().max(0.001)
+    }
+}
+
+/// Totals since the process started, shared with the metrics endpoint.
+#[derive(Default)]
+pub struct Metrics {
+    runs: AtomicU64,
+    scanned: AtomicU64,
+    renamed: AtomicU64,
+    errors: AtomicU64,
+    /// Events waiting to be processed, in modes that queue them
+    queue_depth: AtomicU64,
+    running: AtomicBool,
+    /// f64 bits
+    last_duration: AtomicU64,
+    last_scan_rate: AtomicU64,
+}
+
+impl Metrics {
+    pub fn run_started(&self) {
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    pub fn run_finished(&self, stats: &RunStats) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        self.scanned.fetch_add(stats.scanned as u64, Ordering::Relaxed);
+        self.renamed.fetch_add(stats.renamed as u64, Ordering::Relaxed);
+        self.errors.fetch_add(stats.errors as u64, Ordering::Relaxed);
+        self.last_duration.store(stats.elapsed.as_secs_f64().to_bits(), Ordering::Relaxed);
+        self.last_scan_rate.store(stats.scan_rate().to_bits(), Ordering::Relaxed);
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.store(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed);
+        let float = |value: &AtomicU64| f64::from_bits(value.load(Ordering::Relaxed));
+        let metrics: [(&str, &str, &str, f64); 8] = [
+            ("namefmt_runs_total", "counter", "Runs completed", load(&self.runs) as f64),
+            ("namefmt_files_scanned_total", "counter", "Files looked at", load(&self.scanned) as f64),
+            ("namefmt_renames_total", "counter", "Files renamed", load(&self.renamed) as f64),
+            ("namefmt_errors_total", "counter", "Runs that ended in an error", load(&self.errors) as f64),
+            ("namefmt_queue_depth", "gauge", "Events waiting to be processed", load(&self.queue_depth) as f64),
+            ("namefmt_run_in_progress", "gauge", "1 while a run is in progress", self.running.load(Ordering::Relaxed) as u8 as f64),
+            ("namefmt_last_run_duration_seconds", "gauge", "Duration of the last run", float(&self.last_duration)),
+            ("namefmt_last_run_files_per_second", "gauge", "Files scanned per second in the last run", float(&self.last_scan_rate)),
+        ];
+        let mut out = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+        }
+        out
+    }
+}
+
+/// Report a finished run to statsd and the text file, whichever are set.
+/// Failures are warnings: monitoring must never fail the run it watches.
+pub fn report(rules: &MetricsRules, metrics: &Metrics, stats: &RunStats) {
+    metrics.run_finished(stats);
+    if let Some(address) = &rules.statsd {
+        if let Err(e) = push_statsd(address, &rules.prefix, stats) {
+            warn!("Failed to send metrics to statsd at {}: {}", address, e);
+        }
+    }
+    if let Some(path) = &rules.textfile {
+        if let Err(e) = write_textfile(path, &metrics.to_prometheus()) {
+            warn!("Failed to write metrics to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn push_statsd(address: &str, prefix: &str, stats: &RunStats) -> std::io::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    let lines = [
+        format!("{}.runs:1|c", prefix),
+        format!("{}.files_scanned:{}|c", prefix, stats.scanned),
+        format!("{}.renames:{}|c", prefix, stats.renamed),
+        format!("{}.errors:{}|c", prefix, stats.errors),
+        format!("{}.run_duration:{}|ms", prefix, stats.elapsed.as_millis()),
+        format!("{}.files_per_second:{:.1}|g", prefix, stats.scan_rate()),
+    ];
+    socket.send_to(lines.join("\n").as_bytes(), address)?;
+    Ok(())
+}
+
+/// Written to a temporary file first so the collector never reads half a file.
+fn write_textfile(path: &Path, content: &str) -> std::io::Result<()> {
+    let temporary = path.with_extension(format!("prom.{}", std::process::id()));
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)
+}
+
+/// Answer every HTTP request on `address` with the current metrics, from
+/// background threads that live as long as the process: one accepting
+/// connections and one per connection, so a client that connects and never
+/// sends anything can't hold up the next scrape. Returns the address bound,
+/// which differs from `address` when that asks for any free port.
+pub fn serve(address: &str, metrics: Arc<Metrics>) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let bound = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let metrics = Arc::clone(&metrics);
+            thread::spawn(move || answer(stream, &metrics));
+        }
+    });
+    Ok(bound)
+}
+
+fn answer(mut stream: TcpStream, metrics: &Metrics) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    // The request itself doesn't matter; read what was sent and answer
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request);
+    let body = metrics.to_prometheus();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrapes_are_answered_while_another_client_stalls() {
+        let metrics = Arc::new(Metrics::default());
+        metrics.run_finished(&RunStats { scanned: 40, renamed: 3, errors: 0, elapsed: Duration::from_secs(2) });
+        metrics.set_queue_depth(7);
+        let address = serve("127.0.0.1:0", Arc::clone(&metrics)).unwrap();
+
+        // Connected, but never sends a request
+        let _stalled = TcpStream::connect(address).unwrap();
+        let body = crate::http::get(&format!("http://{}/metrics", address), Duration::from_secs(2)).unwrap();
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains("# TYPE namefmt_renames_total counter\nnamefmt_renames_total 3\n"), "{}", body);
+        assert!(body.contains("\nnamefmt_files_scanned_total 40\n"), "{}", body);
+        assert!(body.contains("\nnamefmt_queue_depth 7\n"), "{}", body);
+        assert!(body.contains("\nnamefmt_last_run_files_per_second 20\n"), "{}", body);
+    }
+}
@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Longest name, in bytes, that every supported filesystem accepts.
+pub const MAX_NAME_BYTES: usize = 255;
+
+/// Extensions longer than this are not worth keeping over the name itself
+/// when a name has to be shortened.
+const MAX_KEPT_EXTENSION: usize = 16;
+
+/// Device names Windows reserves regardless of extension.
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "COM¹", "COM²", "COM³", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹",
+    "LPT²", "LPT³",
+];
+
+/// Which filesystems produced names have to be valid on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    /// Linux, macOS and other Unix filesystems: only `/` and NUL are illegal
+    #[default]
+    Posix,
+    /// Also valid on Windows and for files synced there
+    Windows,
+}
+
+/// A file name that is safe to create on a [`Platform`].
+///
+/// Every name namefmt produces goes through this type. For any input,
+/// `SanitizedName::new` returns a name that:
+///
+/// - is non-empty and is neither `.` nor `..`;
+/// - is at most [`MAX_NAME_BYTES`] bytes long, keeping the extension when
+///   it has to be shortened;
+/// - contains no path separator or NUL, and on Windows none of `<>:"\|?*`
+///   or control characters, no trailing dot or space and no reserved device
+///   name such as `CON` or `nul.txt`;
+/// - is returned unchanged when sanitized again.
+///
+/// Offending characters become `_` and device names get a `_` prefix.
+/// Names that are already valid are left exactly as they are.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SanitizedName(String);
+
+impl SanitizedName {
+    pub fn new(name: &str, platform: Platform) -> SanitizedName {
+        let mut name: String = name.chars().map(|c| if is_illegal(c, platform) { '_' } else { c }).collect();
+        truncate(&mut name, MAX_NAME_BYTES);
+        if platform == Platform::Windows {
+            trim_trailing(&mut name);
+            if reserved_device(&name) {
+                // A prefix survives restyling; `_` anywhere else may become `-`
+                name.insert(0, '_');
+                // Only possible with a very long extension
+                truncate_end(&mut name, MAX_NAME_BYTES);
+                trim_trailing(&mut name);
+            }
+        }
+        if name.is_empty() || name == "." || name == ".." {
+            name = String::from("_");
+        }
+        SanitizedName(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for SanitizedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for SanitizedName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for SanitizedName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for SanitizedName {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for SanitizedName {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+fn is_illegal(c: char, platform: Platform) -> bool {
+    match platform {
+        Platform::Posix => matches!(c, '/' | '\0'),
+        Platform::Windows => matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*') || c.is_ascii_control(),
+    }
+}
+
+/// Shorten to `max` bytes, cutting from the stem so the extension survives.
+fn truncate(name: &mut String, max: usize) {
+    if name.len() <= max {
+        return;
+    }
+    let extension = name
+        .rfind('.')
+        .filter(|&dot| dot > 0 && name.len() - dot > 1 && name.len() - dot <= MAX_KEPT_EXTENSION + 1)
+        .map(|dot| name.split_off(dot))
+        .unwrap_or_default();
+    truncate_end(name, max - extension.len());
+    name.push_str(&extension);
+}
+
+/// Split `report.final.pdf` into `report.final` and `.pdf`. Dotfiles and
+/// names without a dot have no extension.
+pub fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(i) if i > 0 => name.split_at(i),
+        _ => (name, ""),
+    }
+}
+
+/// Split `Backup.TAR.GZ` into `Backup` and `.TAR.GZ`, the part a style
+/// leaves alone. Only a short alphanumeric last suffix is an extension, so
+/// `v1.2 release notes` has none; `.tar` goes with the suffix after it.
+/// A stem of nothing but separators, which styling removes, has none
+/// either: ` .log` would become the dotfile `.log`.
+pub fn split_extensions(name: &str) -> (&str, &str) {
+    let (stem, extension) = split_extension(name);
+    let suffix = &extension[extension.len().min(1)..];
+    let bare_stem = stem.chars().all(|c| c.is_whitespace() || c == '_' || c == '-');
+    if bare_stem || suffix.is_empty() || suffix.chars().count() > MAX_KEPT_EXTENSION || !suffix.chars().all(char::is_alphanumeric) {
+        return (name, "");
+    }
+    match split_extension(stem) {
+        (inner, tar) if !inner.is_empty() && tar.eq_ignore_ascii_case(".tar") => name.split_at(inner.len()),
+        _ => (stem, extension),
+    }
+}
+
+/// What a length limit counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthUnit {
+    /// What most filesystems limit, 255 of them on most
+    #[default]
+    Bytes,
+    /// Characters, however many bytes they take
+    Chars,
+}
+
+pub fn length(name: &str, unit: LengthUnit) -> usize {
+    match unit {
+        LengthUnit::Bytes => name.len(),
+        LengthUnit::Chars => name.chars().count(),
+    }
+}
+
+/// `name` cut to at most `max` in `unit`, from the end of the stem so the
+/// extension survives, without a separator left dangling at the cut.
+/// Names that fit are returned as they are.
+pub fn shorten(name: &str, max: usize, unit: LengthUnit) -> String {
+    if length(name, unit) <= max {
+        return name.to_string();
+    }
+    let (stem, extension) = match split_extensions(name) {
+        (stem, extension) if length(extension, unit) < max => (stem, extension),
+        _ => (name, ""),
+    };
+    let mut stem = stem.to_string();
+    let budget = max - length(extension, unit);
+    match unit {
+        LengthUnit::Bytes => truncate_end(&mut stem, budget),
+        LengthUnit::Chars => {
+            if let Some((end, _)) = stem.char_indices().nth(budget) {
+                stem.truncate(end);
+            }
+        }
+    }
+    let trimmed = stem.trim_end_matches([' ', '_', '-', '.']).len();
+    if trimmed > 0 {
+        stem.truncate(trimmed);
+    }
+    stem + extension
+}
+
+/// Cut to at most `max` bytes without splitting a character.
+pub fn truncate_end(name: &mut String, max: usize) {
+    let mut end = max.min(name.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name.truncate(end);
+}
+
+/// Windows silently drops trailing dots and spaces, which would make the
+/// file unreachable under the name it was given.
+fn trim_trailing(name: &mut String) {
+    let len = name.trim_end_matches(['.', ' ']).len();
+    name.truncate(len);
+}
+
+/// True if the part before the first dot is a Windows device name
+/// (trailing spaces are ignored there too).
+fn reserved_device(name: &str) -> bool {
+    let base = name.split('.').next().unwrap_or_default().trim_end_matches(' ');
+    WINDOWS_RESERVED.iter().any(|r| r.eq_ignore_ascii_case(base))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use proptest::{collection, prelude::*, sample};
+
+    /// Characters that tend to break naive name handling: separators,
+    /// reserved punctuation, controls, case-changing and caseless Unicode.
+    const ALPHABET: &[char] = &[
+        'a', 'b', 'z', 'A', 'B', 'Z', '0', '1', '9', ' ', ' ', '_', '-', '.', '.', '/', '\\', ':', '*', '?', '"', '<',
+        '>', '|', '\0', '\t', '\n', 'é', 'É', 'ß', 'İ', 'ϒ', 'ǅ', 'Σ', 'ς', '日', '🎉', '¹', '$',
+    ];
+
+    /// Fragments that trigger special cases when they line up.
+    const FRAGMENTS: &[&str] = &["CON", "nul", "Com1", "LPT¹", ".log", ".1", ".gz", "2024-01-31", ".txt", "..", "__"];
+
+    /// Names built from those: mostly short, some far over the length limit.
+    pub(crate) fn names() -> impl Strategy<Value = String> {
+        let piece = prop_oneof![
+            5 => sample::select(ALPHABET).prop_map(String::from),
+            1 => sample::select(FRAGMENTS).prop_map(String::from),
+        ];
+        prop_oneof![9 => 0..24usize, 1 => 200..400usize]
+            .prop_flat_map(move |len| collection::vec(piece.clone(), len))
+            .prop_map(|pieces| pieces.concat())
+    }
+
+    pub(crate) fn platforms() -> impl Strategy<Value = Platform> {
+        prop_oneof![Just(Platform::Posix), Just(Platform::Windows)]
+    }
+
+    pub(crate) fn assert_valid(name: &str, platform: Platform, input: &str) {
+        assert!(!name.is_empty() && name != "." && name != "..", "{:?} -> {:?}", input, name);
+        assert!(name.len() <= MAX_NAME_BYTES, "{:?} -> {} bytes", input, name.len());
+        assert!(!name.chars().any(|c| is_illegal(c, platform)), "{:?} -> {:?}", input, name);
+        if platform == Platform::Windows {
+            assert!(!name.ends_with(['.', ' ']), "{:?} -> {:?}", input, name);
+            assert!(!reserved_device(name), "{:?} -> {:?}", input, name);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(5_000))]
+
+        #[test]
+        fn any_input_gives_a_valid_name(input in names(), platform in platforms()) {
+            assert_valid(SanitizedName::new(&input, platform).as_str(), platform, &input);
+        }
+
+        #[test]
+        fn sanitizing_is_idempotent(input in names(), platform in platforms()) {
+            let once = SanitizedName::new(&input, platform);
+            prop_assert_eq!(SanitizedName::new(once.as_str(), platform), once, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn valid_names_are_left_alone() {
+        for name in ["report.pdf", "My File (1).txt", ".bashrc", "con_notes.txt", "日本語.md", "a..b"] {
+            assert_eq!(SanitizedName::new(name, Platform::Windows).as_str(), name);
+        }
+        assert_eq!(SanitizedName::new("a:b?.txt", Platform::Posix).as_str(), "a:b?.txt");
+    }
+
+    #[test]
+    fn fixes_are_minimal() {
+        let windows = |name| SanitizedName::new(name, Platform::Windows).into_string();
+        assert_eq!(windows("a:b?.txt"), "a_b_.txt");
+        assert_eq!(windows("notes. "), "notes");
+        assert_eq!(windows("nul.txt"), "_nul.txt");
+        assert_eq!(windows("CON"), "_CON");
+        assert_eq!(windows("..."), "_");
+        assert_eq!(SanitizedName::new("", Platform::Posix).as_str(), "_");
+        assert_eq!(SanitizedName::new("a/b", Platform::Posix).as_str(), "a_b");
+    }
+
+    #[test]
+    fn shortening_keeps_the_extension() {
+        let long = format!("{}.tar", "é".repeat(200));
+        let name = SanitizedName::new(&long, Platform::Posix);
+        assert!(name.as_str().ends_with("é.tar"));
+        assert!(name.as_str().len() <= MAX_NAME_BYTES);
+    }
+
+    #[test]
+    fn shorten_cuts_the_stem_to_the_limit() {
+        assert_eq!(shorten("quarterly_results_2024.tar.gz", 16, LengthUnit::Bytes), "quarterly.tar.gz");
+        assert_eq!(shorten("réunion_générale.txt", 12, LengthUnit::Chars), "réunion.txt");
+        assert_eq!(shorten("réunion_générale.txt", 11, LengthUnit::Bytes), "réunio.txt");
+        assert_eq!(shorten("notes.txt", 16, LengthUnit::Bytes), "notes.txt");
+        assert_eq!(shorten("a_long_name.extension", 8, LengthUnit::Bytes), "a_long_n");
+    }
+}
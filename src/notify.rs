@@ -0,0 +1,108 @@
+use crate::http;
+use crate::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::Duration;
+
+/// Where to report finished runs, e.g. a Slack incoming webhook.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyRules {
+    /// URL that receives a JSON summary when an --inplace run completes
+    #[serde(default)]
+    pub webhook: Option<String>,
+
+    /// Also notify for runs that renamed nothing and hit no errors
+    #[serde(default)]
+    pub notify_empty: bool,
+
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for NotifyRules {
+    fn default() -> Self {
+        NotifyRules {
+            webhook: None,
+            notify_empty: false,
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+/// Outcome of one run, as sent to the webhook.
+pub struct Summary<'a> {
+    pub run_id: &'a str,
+    pub root: &'a Path,
+    pub renamed: usize,
+    pub errors: Vec<String>,
+}
+
+impl Summary<'_> {
+    /// The payload carries a `text` field so Slack-style webhooks can show
+    /// it as-is; generic receivers get the structured fields alongside.
+    fn to_json(&self) -> Value {
+        let mut text = format!(
+            "namefmt run {} in {}: renamed {} file(s)",
+            self.run_id,
+            self.root.display(),
+            self.renamed
+        );
+        if !self.errors.is_empty() {
+            text.push_str(&format!(", {} error(s): {}", self.errors.len(), self.errors.join("; ")));
+        }
+
+        json!({
+            "text": text,
+            "run_id": self.run_id,
+            "root": self.root.display().to_string(),
+            "status": if self.errors.is_empty() { "completed" } else { "failed" },
+            "renamed": self.renamed,
+            "errors": self.errors,
+        })
+    }
+}
+
+/// Post the summary if a webhook is configured. Delivery problems are only
+/// warnings: a notification must never fail the run it reports on.
+pub fn run_completed(rules: &NotifyRules, summary: &Summary) {
+    let Some(url) = &rules.webhook else {
+        return;
+    };
+    if summary.renamed == 0 && summary.errors.is_empty() && !rules.notify_empty {
+        return;
+    }
+    let timeout = Duration::from_secs(rules.timeout_secs.max(1));
+    if let Err(e) = http::post_json(url, &summary.to_json().to_string(), timeout) {
+        warn!("Failed to notify webhook {}: {}", url, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::tests::{response, serve};
+
+    #[test]
+    fn finished_runs_are_posted_to_the_webhook() {
+        let (url, server) = serve(vec![response("200 OK", "", "ok")]);
+        let rules = NotifyRules { webhook: Some(format!("{}/hook", url)), ..NotifyRules::default() };
+        let summary = Summary { run_id: "run-1", root: Path::new("/photos"), renamed: 2, errors: vec![String::from("a: busy")] };
+        run_completed(&rules, &summary);
+        // Quiet runs are not reported; the server would never be asked
+        run_completed(&rules, &Summary { renamed: 0, errors: Vec::new(), ..summary });
+
+        let request = server.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        assert_eq!(
+            body,
+            r#"{"text":"namefmt run run-1 in /photos: renamed 2 file(s), 1 error(s): a: busy","run_id":"run-1","root":"/photos","status":"failed","renamed":2,"errors":["a: busy"]}"#
+        );
+    }
+}
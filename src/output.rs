@@ -0,0 +1,192 @@
+use crate::plan::Rename;
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+/// How a run reports what it does on stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Lines for people to read
+    #[default]
+    Text,
+    /// One JSON array of records, written when the run ends
+    Json,
+    /// One JSON record per line, written as the run goes
+    Jsonl,
+}
+
+/// Whether text output is colored, from --color.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// When stdout is a terminal and NO_COLOR isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+            }
+        }
+    }
+}
+
+/// Records of a run for scripts: one per rename, skipped file, suggestion
+/// and error, each `{source, target, status, applied, reason, style}`.
+/// In text mode it records nothing and callers print as usual.
+pub struct Output {
+    format: OutputFormat,
+    /// Rule and style behind each planned rename, by source
+    reasons: HashMap<PathBuf, (String, Option<String>)>,
+    records: RefCell<Vec<Value>>,
+    errors: Cell<bool>,
+    /// Whether text lines may carry colors
+    color: bool,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Output {
+        Output { format, reasons: HashMap::new(), records: RefCell::new(Vec::new()), errors: Cell::new(false), color: false }
+    }
+
+    /// Color the text lines, as --color asks.
+    pub fn with_color(self, color: ColorChoice) -> Output {
+        Output { color: self.is_text() && color.enabled(), ..self }
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.format == OutputFormat::Text
+    }
+
+    pub fn is_colored(&self) -> bool {
+        self.color
+    }
+
+    /// Remember why each rename was planned, for the records of the
+    /// renames as they are made. `style` names the style behind a rename.
+    pub fn explain(&mut self, renames: &[Rename], style: impl Fn(&Rename) -> Option<String>) {
+        for rename in renames {
+            self.reasons.insert(rename.source.clone(), (rename.rule.clone(), style(rename)));
+        }
+    }
+
+    /// The rule behind the rename of `source`, as given to `explain`.
+    pub fn reason(&self, source: &Path) -> Option<&str> {
+        self.reasons.get(source).map(|(reason, _)| reason.as_str())
+    }
+
+    /// A rename that was made (`run_id` is set) or would be made.
+    pub fn rename(&self, source: &Path, target: &Path, run_id: Option<&str>) {
+        let (reason, style) = self.reasons.get(source).cloned().unwrap_or_default();
+        let status = if run_id.is_some() { "renamed" } else { "planned" };
+        let mut record = record(source, Some(target), status, Some(reason), style);
+        if let Some(run_id) = run_id {
+            record["run_id"] = run_id.into();
+        }
+        self.emit(record);
+    }
+
+    /// A rename that was made and then reverted with the rest of its batch.
+    pub fn rolled_back(&self, source: &Path, target: &Path) {
+        let (reason, style) = self.reasons.get(source).cloned().unwrap_or_default();
+        self.emit(record(source, Some(target), "rolled_back", Some(reason), style));
+    }
+
+    pub fn skipped(&self, source: &Path, reason: &str) {
+        self.emit(record(source, None, "skipped", Some(reason.to_string()), None));
+    }
+
+    pub fn suggested(&self, rename: &Rename, style: Option<String>) {
+        self.emit(record(&rename.source, Some(&rename.target), "suggested", Some(rename.rule.clone()), style));
+    }
+
+    /// An error, about one rename or (without `source`) the whole run.
+    pub fn error(&self, source: Option<&Path>, target: Option<&Path>, error: &str) {
+        let record = json!({
+            "source": source.map(|p| p.display().to_string()),
+            "target": target.map(|p| p.display().to_string()),
+            "status": "error",
+            "applied": false,
+            "reason": error,
+            "style": null,
+        });
+        self.errors.set(true);
+        self.emit(record);
+    }
+
+    /// Whether an error has been recorded.
+    pub fn has_errors(&self) -> bool {
+        self.errors.get()
+    }
+
+    fn emit(&self, record: Value) {
+        match self.format {
+            OutputFormat::Text => {}
+            OutputFormat::Json => self.records.borrow_mut().push(record),
+            OutputFormat::Jsonl => println!("{}", record),
+        }
+    }
+
+    /// Write what is still held back: the array in JSON mode.
+    pub fn finish(&self) {
+        if self.format == OutputFormat::Json {
+            println!("{}", Value::Array(self.records.take()));
+        }
+    }
+}
+
+fn record(source: &Path, target: Option<&Path>, status: &str, reason: Option<String>, style: Option<String>) -> Value {
+    json!({
+        "source": source.display().to_string(),
+        "target": target.map(|p| p.display().to_string()),
+        "status": status,
+        "applied": status == "renamed",
+        "reason": reason,
+        "style": style,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_carry_the_rule_and_style_behind_each_rename() {
+        let rename = Rename { source: PathBuf::from("/in/My File.txt"), target: PathBuf::from("/in/my_file.txt"), rule: String::from("*.txt (snake_case)") };
+        let mut output = Output::new(OutputFormat::Json);
+        output.explain(std::slice::from_ref(&rename), |_| Some(String::from("snake_case")));
+        assert_eq!(output.reason(&rename.source), Some("*.txt (snake_case)"));
+        output.rename(&rename.source, &rename.target, None);
+        output.rename(&rename.source, &rename.target, Some("20240131-120000-00ff"));
+        output.skipped(Path::new("/in/README"), "protected name");
+        assert!(!output.has_errors());
+        output.error(None, None, "disk full");
+        assert!(output.has_errors());
+
+        let records = output.records.borrow();
+        assert_eq!(records[0], json!({
+            "source": "/in/My File.txt", "target": "/in/my_file.txt", "status": "planned",
+            "applied": false, "reason": "*.txt (snake_case)", "style": "snake_case",
+        }));
+        assert_eq!((&records[1]["status"], &records[1]["applied"], &records[1]["run_id"]), (&json!("renamed"), &json!(true), &json!("20240131-120000-00ff")));
+        assert_eq!(records[2], json!({
+            "source": "/in/README", "target": null, "status": "skipped", "applied": false, "reason": "protected name", "style": null,
+        }));
+        assert_eq!((&records[3]["source"], &records[3]["status"], &records[3]["reason"]), (&Value::Null, &json!("error"), &json!("disk full")));
+
+        // Text mode leaves the printing to the callers
+        let text = Output::new(OutputFormat::Text);
+        text.skipped(Path::new("/in/README"), "protected name");
+        assert!(text.records.borrow().is_empty());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::plan::Rename;
+use crate::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// RAW+JPEG pairs written by cameras (`IMG_0042.CR3` + `IMG_0042.JPG`)
+/// are renamed together so editors keep the association.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PairRules {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    #[serde(default = "default_raw_extensions")]
+    pub raw_extensions: Vec<String>,
+
+    #[serde(default = "default_jpeg_extensions")]
+    pub jpeg_extensions: Vec<String>,
+}
+
+impl Default for PairRules {
+    fn default() -> Self {
+        PairRules {
+            enabled: default_enabled(),
+            raw_extensions: default_raw_extensions(),
+            jpeg_extensions: default_jpeg_extensions(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_raw_extensions() -> Vec<String> {
+    ["cr2", "cr3", "nef", "arw", "dng", "raf", "orf", "rw2", "pef", "srw"].iter().map(|s| s.to_string()).collect()
+}
+
+fn default_jpeg_extensions() -> Vec<String> {
+    vec!["jpg".to_string(), "jpeg".to_string()]
+}
+
+/// Subtitles follow their video, keeping tags such as `.en` or `.forced.de`
+/// exactly as written.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SubtitleRules {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    #[serde(default = "default_video_extensions")]
+    pub video_extensions: Vec<String>,
+
+    #[serde(default = "default_subtitle_extensions")]
+    pub subtitle_extensions: Vec<String>,
+}
+
+impl Default for SubtitleRules {
+    fn default() -> Self {
+        SubtitleRules {
+            enabled: default_enabled(),
+            video_extensions: default_video_extensions(),
+            subtitle_extensions: default_subtitle_extensions(),
+        }
+    }
+}
+
+fn default_video_extensions() -> Vec<String> {
+    ["mkv", "mp4", "m4v", "avi", "mov", "webm", "wmv", "mpg", "mpeg", "ts"].iter().map(|s| s.to_string()).collect()
+}
+
+fn default_subtitle_extensions() -> Vec<String> {
+    ["srt", "sub", "idx", "ass", "ssa", "vtt", "sup"].iter().map(|s| s.to_string()).collect()
+}
+
+/// Subtitle flags players understand alongside language codes.
+const SUBTITLE_FLAGS: &[&str] = &["forced", "sdh", "cc", "hi", "default", "full", "signs", "commentary"];
+
+/// `en`, `deu`, `pt-BR`, `zh-Hans`, or one of the known flags.
+fn is_subtitle_tag(segment: &str) -> bool {
+    if SUBTITLE_FLAGS.iter().any(|f| f.eq_ignore_ascii_case(segment)) {
+        return true;
+    }
+    let (language, region) = match segment.split_once('-') {
+        Some((language, region)) => (language, Some(region)),
+        None => (segment, None),
+    };
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && region.is_none_or(|r| (2..=4).contains(&r.len()) && r.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy())
+        .is_some_and(|e| extensions.iter().any(|x| x.eq_ignore_ascii_case(&e)))
+}
+
+/// Make paired files follow their RAW file: every member gets the stem the
+/// RAW file's rename produced, keeping its own extension. If any member's
+/// new name is taken the whole pair stays as it is.
+///
+/// Returns the adjusted renames and the files left alone, with reasons.
+pub fn link(rules: &PairRules, files: &[PathBuf], renames: Vec<Rename>) -> (Vec<Rename>, Vec<(PathBuf, String)>) {
+    if !rules.enabled {
+        return (renames, Vec::new());
+    }
+
+    // (directory, stem) -> files sharing it, in walk order
+    let mut groups: BTreeMap<(&Path, String), Vec<&PathBuf>> = BTreeMap::new();
+    for file in files {
+        if let (Some(dir), Some(stem)) = (file.parent(), file.file_stem()) {
+            groups.entry((dir, stem.to_string_lossy().to_string())).or_default().push(file);
+        }
+    }
+
+    let mut planned: HashMap<PathBuf, Rename> = renames.into_iter().map(|r| (r.source.clone(), r)).collect();
+    let moving_away: HashSet<PathBuf> = planned.keys().cloned().collect();
+    let mut skipped = Vec::new();
+
+    for members in groups.into_values() {
+        let Some(&raw) = members.iter().find(|f| has_extension(f, &rules.raw_extensions)) else {
+            continue;
+        };
+        if !members.iter().any(|f| has_extension(f, &rules.jpeg_extensions)) {
+            continue;
+        }
+        let others: Vec<&PathBuf> = members.iter().copied().filter(|f| *f != raw).collect();
+
+        // The RAW file decides; a pair where it keeps its name stays together as is
+        let Some(leader) = planned.get(raw) else {
+            for member in &others {
+                if planned.remove(*member).is_some() {
+                    skipped.push(((*member).clone(), String::from("its RAW pair keeps its name")));
+                }
+            }
+            continue;
+        };
+        let (Some(dir), Some(stem)) = (leader.target.parent(), leader.target.file_stem()) else {
+            continue;
+        };
+        let (dir, stem, rule) = (dir.to_path_buf(), stem.to_os_string(), leader.rule.clone());
+        let raw_name = raw.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        let mut linked = Vec::new();
+        for member in &others {
+            // Keep any extension normalisation the member's own rule applied
+            let own_target = planned.get(*member).map(|r| &r.target);
+            let Some(ext) = own_target.and_then(|t| t.extension()).or_else(|| member.extension()) else {
+                continue;
+            };
+            let mut name = stem.clone();
+            name.push(".");
+            name.push(ext);
+            linked.push(Rename {
+                source: (*member).clone(),
+                target: dir.join(name),
+                rule: format!("{} (paired with {})", rule, raw_name),
+            });
+        }
+
+        let taken = std::iter::once(&planned[raw].target)
+            .chain(linked.iter().map(|r| &r.target))
+            .find(|t| t.exists() && !moving_away.contains(*t))
+            .cloned();
+        match taken {
+            Some(target) => {
+                let reason = format!("paired file's new name {} is taken", target.display());
+                warn!("Not renaming the {} pair: {} already exists", raw.display(), target.display());
+                for member in &members {
+                    planned.remove(*member);
+                    skipped.push(((*member).clone(), reason.clone()));
+                }
+            }
+            None => {
+                for rename in linked {
+                    planned.insert(rename.source.clone(), rename);
+                }
+            }
+        }
+    }
+
+    // Same order the files were walked in
+    let mut renames: Vec<Rename> = planned.into_values().collect();
+    renames.sort_by(|a, b| a.source.cmp(&b.source));
+    (renames, skipped)
+}
+
+/// Rename sidecar files in lock-step with their primary file, whatever
+/// their own rules would say. Both `photo.xmp` and `photo.jpg.xmp` belong
+/// to `photo.jpg`; a sidecar without a primary is treated as a normal file.
+pub fn follow_sidecars(sidecars: &[String], files: &[PathBuf], renames: Vec<Rename>) -> (Vec<Rename>, Vec<(PathBuf, String)>) {
+    let extensions: Vec<String> = sidecars.iter().map(|s| s.trim_start_matches('.').to_string()).collect();
+    if extensions.is_empty() {
+        return (renames, Vec::new());
+    }
+
+    let present: HashSet<&PathBuf> = files.iter().collect();
+    let mut planned: HashMap<PathBuf, Rename> = renames.into_iter().map(|r| (r.source.clone(), r)).collect();
+    let moving_away: HashSet<PathBuf> = planned.keys().cloned().collect();
+    let mut skipped = Vec::new();
+
+    for sidecar in files.iter().filter(|f| has_extension(f, &extensions)) {
+        let (Some(base), Some(ext)) = (sidecar.file_stem(), sidecar.extension()) else {
+            continue;
+        };
+        let Some(primary) = primary_of(sidecar, base, &extensions, files, &present) else {
+            continue;
+        };
+
+        let Some(primary_target) = planned.get(&primary).map(|r| r.target.clone()) else {
+            if planned.remove(sidecar).is_some() {
+                skipped.push((sidecar.clone(), format!("follows {}, which keeps its name", primary.display())));
+            }
+            continue;
+        };
+
+        // Mirror the naming style: `photo.jpg.xmp` keeps the full primary name
+        let double = Path::new(base).extension().is_some() && primary.file_name() == Some(base);
+        let kept = if double { primary_target.file_name() } else { primary_target.file_stem() };
+        let Some(kept) = kept else {
+            continue;
+        };
+        let mut name = kept.to_os_string();
+        name.push(".");
+        name.push(ext);
+        let target = primary_target.with_file_name(name);
+
+        if target.exists() && !moving_away.contains(&target) && target != *sidecar {
+            warn!("Not renaming {} or its sidecar: {} already exists", primary.display(), target.display());
+            let reason = format!("sidecar's new name {} is taken", target.display());
+            for file in [&primary, sidecar] {
+                planned.remove(file);
+                skipped.push((file.clone(), reason.clone()));
+            }
+            continue;
+        }
+        let rule = planned[&primary].rule.clone();
+        let primary_name = primary.file_name().unwrap_or_default().to_string_lossy().to_string();
+        planned.insert(sidecar.clone(), Rename {
+            source: sidecar.clone(),
+            target,
+            rule: format!("{} (sidecar of {})", rule, primary_name),
+        });
+    }
+
+    let mut renames: Vec<Rename> = planned.into_values().collect();
+    renames.sort_by(|a, b| a.source.cmp(&b.source));
+    (renames, skipped)
+}
+
+/// The file a sidecar belongs to: an exact `base` match first, otherwise
+/// the first non-sidecar file sharing the stem.
+fn primary_of(sidecar: &Path, base: &std::ffi::OsStr, sidecars: &[String], files: &[PathBuf], present: &HashSet<&PathBuf>) -> Option<PathBuf> {
+    let exact = sidecar.with_file_name(base);
+    if present.contains(&exact) && !has_extension(&exact, sidecars) {
+        return Some(exact);
+    }
+    files
+        .iter()
+        .find(|f| f.parent() == sidecar.parent() && f.file_stem() == Some(base) && !has_extension(f, sidecars))
+        .cloned()
+}
+
+/// Rename subtitles together with the video they belong to. The part
+/// between the video's stem and the subtitle extension (`.en`, `.forced.de`)
+/// is carried over untouched rather than run through the naming rules.
+pub fn follow_subtitles(rules: &SubtitleRules, files: &[PathBuf], renames: Vec<Rename>) -> (Vec<Rename>, Vec<(PathBuf, String)>) {
+    if !rules.enabled {
+        return (renames, Vec::new());
+    }
+
+    let videos: Vec<&PathBuf> = files.iter().filter(|f| has_extension(f, &rules.video_extensions)).collect();
+    let mut planned: HashMap<PathBuf, Rename> = renames.into_iter().map(|r| (r.source.clone(), r)).collect();
+    let moving_away: HashSet<PathBuf> = planned.keys().cloned().collect();
+    let mut skipped = Vec::new();
+
+    for subtitle in files.iter().filter(|f| has_extension(f, &rules.subtitle_extensions)) {
+        let name = subtitle.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let Some((video, tags)) = video_of(subtitle, &name, &videos) else {
+            continue;
+        };
+
+        let Some(video_target) = planned.get(video).map(|r| r.target.clone()) else {
+            if planned.remove(subtitle).is_some() {
+                skipped.push((subtitle.clone(), format!("follows {}, which keeps its name", video.display())));
+            }
+            continue;
+        };
+        let Some(stem) = video_target.file_stem() else {
+            continue;
+        };
+        let target = video_target.with_file_name(format!("{}.{}", stem.to_string_lossy(), tags));
+
+        if target.exists() && !moving_away.contains(&target) && target != *subtitle {
+            warn!("Not renaming {} or its subtitle: {} already exists", video.display(), target.display());
+            let reason = format!("subtitle's new name {} is taken", target.display());
+            for file in [video, subtitle] {
+                planned.remove(file);
+                skipped.push((file.clone(), reason.clone()));
+            }
+            continue;
+        }
+        let rule = planned[video].rule.clone();
+        let video_name = video.file_name().unwrap_or_default().to_string_lossy().to_string();
+        planned.insert(subtitle.clone(), Rename {
+            source: subtitle.clone(),
+            target,
+            rule: format!("{} (subtitle of {})", rule, video_name),
+        });
+    }
+
+    let mut renames: Vec<Rename> = planned.into_values().collect();
+    renames.sort_by(|a, b| a.source.cmp(&b.source));
+    (renames, skipped)
+}
+
+/// Find the video a subtitle belongs to, returning it with everything after
+/// the video's stem (`en.srt`, `forced.de.srt`). The longest matching stem
+/// wins so `show.s01e01.en.srt` prefers `show.s01e01.mkv` over `show.mkv`.
+fn video_of<'a>(subtitle: &Path, name: &str, videos: &[&'a PathBuf]) -> Option<(&'a PathBuf, String)> {
+    videos
+        .iter()
+        .filter(|v| v.parent() == subtitle.parent())
+        .filter_map(|video| {
+            let stem = video.file_stem()?.to_string_lossy();
+            let rest = name.strip_prefix(stem.as_ref())?.strip_prefix('.')?;
+            let (tags, _ext) = rest.rsplit_once('.').unwrap_or(("", rest));
+            let tagged = tags.is_empty() || tags.split('.').all(is_subtitle_tag);
+            tagged.then(|| (*video, stem.len(), rest.to_string()))
+        })
+        .max_by_key(|(_, len, _)| *len)
+        .map(|(video, _, rest)| (video, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subtitles_follow_their_video_and_keep_language_tags() {
+        let dir = Path::new("/nonexistent/namefmt-pairs");
+        let files: Vec<PathBuf> = [
+            "Show.S01E01.mkv", "Show.S01E01.en.srt", "Show.S01E01.forced.pt-BR.srt", "Show.S01E01.Director Notes.srt",
+            "Other.mkv", "Other.srt",
+        ]
+        .iter()
+        .map(|name| dir.join(name))
+        .collect();
+        let rename = |source: &str, target: &str| Rename { source: dir.join(source), target: dir.join(target), rule: String::from("snake_case") };
+        let renames = vec![rename("Show.S01E01.mkv", "show_s01e01.mkv"), rename("Other.srt", "other.srt")];
+
+        let (renames, skipped) = follow_subtitles(&SubtitleRules::default(), &files, renames);
+        let renamed: Vec<_> = renames.iter().map(|r| (r.source.file_name().unwrap(), r.target.file_name().unwrap(), r.rule.as_str())).collect();
+        assert_eq!(renamed, [
+            ("Show.S01E01.en.srt".as_ref(), "show_s01e01.en.srt".as_ref(), "snake_case (subtitle of Show.S01E01.mkv)"),
+            ("Show.S01E01.forced.pt-BR.srt".as_ref(), "show_s01e01.forced.pt-BR.srt".as_ref(), "snake_case (subtitle of Show.S01E01.mkv)"),
+            ("Show.S01E01.mkv".as_ref(), "show_s01e01.mkv".as_ref(), "snake_case"),
+        ]);
+        assert_eq!(skipped, [(dir.join("Other.srt"), format!("follows {}, which keeps its name", dir.join("Other.mkv").display()))]);
+    }
+}
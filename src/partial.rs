@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Handling of in-progress download artifacts (`.crdownload`, `.part`, ...).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PartialDownloadRules {
+    #[serde(default = "default_partial_extensions")]
+    pub extensions: Vec<String>,
+
+    /// Strip the partial suffix once the file has stopped changing
+    #[serde(default)]
+    pub cleanup: bool,
+
+    /// Seconds since last modification before a partial file counts as finished
+    #[serde(default = "default_stable_secs")]
+    pub stable_secs: u64,
+}
+
+impl Default for PartialDownloadRules {
+    fn default() -> Self {
+        PartialDownloadRules {
+            extensions: default_partial_extensions(),
+            cleanup: false,
+            stable_secs: default_stable_secs(),
+        }
+    }
+}
+
+fn default_partial_extensions() -> Vec<String> {
+    vec!["crdownload".to_string(), "part".to_string(), "download".to_string()]
+}
+
+fn default_stable_secs() -> u64 {
+    300
+}
+
+impl PartialDownloadRules {
+    pub fn is_partial(&self, path: &Path) -> bool {
+        match path.extension() {
+            Some(ext) => {
+                let ext = ext.to_string_lossy().to_lowercase();
+                self.extensions.iter().any(|e| e.trim_start_matches('.').to_lowercase() == ext)
+            }
+            None => false,
+        }
+    }
+
+    /// The name the download will have once complete, if it is safe to
+    /// strip the suffix now.
+    pub fn completed_name(&self, path: &Path) -> Option<String> {
+        if !self.cleanup || !self.is_partial(path) || !self.is_stable(path) {
+            return None;
+        }
+        let stem = path.file_stem()?.to_string_lossy().to_string();
+        (!stem.is_empty()).then_some(stem)
+    }
+
+    /// How long until `path`, a download in progress, has been left alone
+    /// for `stable_secs`, so a watch can look at it again then. `None` if
+    /// it isn't one that will be cleaned up.
+    pub fn settles_in(&self, path: &Path) -> Option<Duration> {
+        let named = path.file_stem().is_some_and(|stem| !stem.is_empty());
+        if !self.cleanup || !self.is_partial(path) || !named {
+            return None;
+        }
+        Some(Duration::from_secs(self.stable_secs).saturating_sub(age(path)?))
+    }
+
+    fn is_stable(&self, path: &Path) -> bool {
+        matches!(age(path), Some(age) if age >= Duration::from_secs(self.stable_secs))
+    }
+}
+
+/// Time since `path` was last modified.
+fn age(path: &Path) -> Option<Duration> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|mtime| mtime.elapsed().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_plan, Config};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+
+    #[test]
+    fn downloads_are_left_alone_until_they_settle() {
+        let dir = tempfile::tempdir().unwrap();
+        let part = dir.path().join("holiday.zip.part");
+        fs::write(&part, "half").unwrap();
+        let config: Config = toml::from_str("[partial_downloads]\ncleanup = true\nstable_secs = 60\n").unwrap();
+        let rules = &config.partial_downloads;
+
+        let plan = build_plan(dir.path(), &config, false, None).unwrap();
+        assert!(plan.renames.is_empty());
+        assert_eq!(plan.skipped, [(part.clone(), String::from("download in progress"))]);
+        let left = rules.settles_in(&part).unwrap();
+        assert!(left > Duration::from_secs(50) && left <= Duration::from_secs(60), "{:?}", left);
+
+        // A minute without a write later, the suffix comes off
+        let written = SystemTime::now() - Duration::from_secs(61);
+        fs::File::options().write(true).open(&part).unwrap().set_modified(written).unwrap();
+        assert_eq!(rules.settles_in(&part), Some(Duration::ZERO));
+        let plan = build_plan(dir.path(), &config, false, None).unwrap();
+        let renames: Vec<(PathBuf, PathBuf)> = plan.renames.into_iter().map(|r| (r.source, r.target)).collect();
+        assert_eq!(renames, [(part, dir.path().join("holiday.zip"))]);
+    }
+
+    #[test]
+    fn only_downloads_that_will_be_cleaned_up_settle() {
+        let rules = PartialDownloadRules::default();
+        assert_eq!(rules.settles_in(Path::new("/nonexistent/a.zip.part")), None);
+        let rules = PartialDownloadRules { cleanup: true, ..rules };
+        assert_eq!(rules.settles_in(Path::new("/nonexistent/a.zip")), None);
+    }
+}
@@ -0,0 +1,416 @@
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Extract the embedded text layer of a PDF, best effort: uncompressed and
+/// `/FlateDecode` content streams are read, and `/ToUnicode` maps are used
+/// for fonts that need them. Scanned PDFs without OCR yield nothing.
+pub fn extract_text(data: &[u8]) -> String {
+    let streams = streams(data);
+    let mut cmap = CMap::default();
+    for stream in streams.iter().filter(|s| contains(s, b"begincmap")) {
+        cmap.parse(&String::from_utf8_lossy(stream));
+    }
+
+    let mut text = String::new();
+    for stream in streams.iter().filter(|s| contains(s, b"BT") && contains(s, b"ET") && !contains(s, b"begincmap")) {
+        content_text(stream, &cmap, &mut text);
+        text.push('\n');
+    }
+    text
+}
+
+/// The title a PDF gives itself: the `/Title` of its document information
+/// dictionary, else the `dc:title` of its XMP metadata.
+pub fn title(data: &[u8]) -> Option<String> {
+    info_title(data).or_else(|| {
+        let xmp = streams(data).into_iter().find(|s| contains(s, b"<dc:title"))?;
+        xmp_title(&String::from_utf8_lossy(&xmp))
+    })
+}
+
+/// The `/Title` in the object the trailer names as `/Info`.
+fn info_title(data: &[u8]) -> Option<String> {
+    // An updated file has a trailer per update; the last one is current
+    let info = rfind(data, b"/Info", data.len())?;
+    let reference = String::from_utf8_lossy(data.get(info + 5..(info + 40).min(data.len()))?);
+    let mut numbers = reference.split_whitespace();
+    let header = format!("{} {} obj", numbers.next()?, numbers.next()?);
+    let mut from = 0;
+    let object = loop {
+        let at = find(data, header.as_bytes(), from)?;
+        // `12 0 obj` must not match inside `112 0 obj`
+        if at == 0 || !data[at - 1].is_ascii_digit() {
+            break at + header.len();
+        }
+        from = at + 1;
+    };
+    let end = find(data, b"endobj", object).unwrap_or(data.len());
+    let title = find(&data[..end], b"/Title", object)? + 6;
+    let value = title + data[title..end].iter().position(|b| !b.is_ascii_whitespace())?;
+    let bytes = match data[value] {
+        b'(' => literal_string(data, value + 1).0,
+        b'<' => hex_bytes(&data[value + 1..find(data, b">", value)?]),
+        _ => return None,
+    };
+    Some(text_string(&bytes))
+}
+
+/// A string outside content streams: UTF-16 after a byte order mark,
+/// UTF-8 after one, else PDFDocEncoding, read as Latin-1.
+fn text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(b"\xfe\xff") {
+        let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+        String::from_utf16_lossy(&units)
+    } else if let Some(utf8) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        String::from_utf8_lossy(utf8).into_owned()
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+/// The first `rdf:li` under `dc:title` in an XMP packet.
+fn xmp_title(xmp: &str) -> Option<String> {
+    let title = &xmp[xmp.find("<dc:title")?..];
+    let item = &title[title.find("<rdf:li")?..];
+    let text = &item[item.find('>')? + 1..];
+    let text = &text[..text.find("</rdf:li>")?];
+    Some(text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&"))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    find(haystack, needle, 0).is_some()
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|i| i + from)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8], before: usize) -> Option<usize> {
+    haystack.get(..before)?.windows(needle.len()).rposition(|w| w == needle)
+}
+
+/// Decoded bodies of every stream the extractor understands.
+fn streams(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut streams = Vec::new();
+    let mut from = 0;
+    while let Some(keyword) = find(data, b"stream", from) {
+        from = keyword + 6;
+        // Skip the `stream` inside `endstream`
+        if keyword >= 3 && &data[keyword - 3..keyword] == b"end" {
+            continue;
+        }
+        let mut start = keyword + 6;
+        if data.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if data.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+        let Some(end) = find(data, b"endstream", start) else {
+            break;
+        };
+        from = end + 9;
+
+        let dictionary_start = rfind(data, b"obj", keyword).unwrap_or(0);
+        let dictionary = &data[dictionary_start..keyword];
+        let body = &data[start..end];
+        let filters = filters(dictionary);
+        let decoded = match filters.as_slice() {
+            [] => Some(body.to_vec()),
+            [only] if only == "FlateDecode" => inflate(body),
+            _ => None,
+        };
+        streams.extend(decoded);
+    }
+    streams
+}
+
+/// Decompress a `/FlateDecode` body: zlib-wrapped as the specification
+/// asks, or raw DEFLATE as some writers leave it.
+fn inflate(body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    if ZlibDecoder::new(body).read_to_end(&mut out).is_ok() {
+        return Some(out);
+    }
+    out.clear();
+    DeflateDecoder::new(body).read_to_end(&mut out).ok().map(|_| out)
+}
+
+/// Names listed under `/Filter`, e.g. `FlateDecode`.
+fn filters(dictionary: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(dictionary);
+    let Some(at) = text.find("/Filter") else {
+        return Vec::new();
+    };
+    let rest = text[at + 7..].trim_start();
+    let value = match rest.strip_prefix('[') {
+        Some(array) => array.split(']').next().unwrap_or_default(),
+        None => rest.split(|c: char| c.is_whitespace() || c == '>').find(|s| !s.is_empty()).unwrap_or_default(),
+    };
+    value.split('/').map(|s| s.trim()).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+/// Glyph code to Unicode mapping from `/ToUnicode` CMaps. All fonts' maps
+/// are merged, which is good enough for pulling out dates and names.
+#[derive(Default)]
+struct CMap {
+    map: HashMap<u32, String>,
+    two_byte: bool,
+}
+
+impl CMap {
+    fn parse(&mut self, text: &str) {
+        let mut section = "";
+        for line in text.lines() {
+            let line = line.trim();
+            if line.ends_with("beginbfchar") || line.ends_with("beginbfrange") || line.ends_with("begincodespacerange") {
+                section = line.rsplit(' ').next().unwrap_or_default();
+                continue;
+            }
+            if line.starts_with("end") {
+                section = "";
+                continue;
+            }
+            let hex: Vec<&str> = line
+                .split(['<', '>'])
+                .map(str::trim)
+                .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit()))
+                .collect();
+            match section {
+                "begincodespacerange" if hex.first().is_some_and(|h| h.len() >= 4) => self.two_byte = true,
+                "beginbfchar" if hex.len() >= 2 => {
+                    if let Ok(code) = u32::from_str_radix(hex[0], 16) {
+                        self.map.insert(code, utf16_hex(hex[1]));
+                    }
+                }
+                "beginbfrange" if hex.len() >= 3 => {
+                    let (Ok(low), Ok(high)) = (u32::from_str_radix(hex[0], 16), u32::from_str_radix(hex[1], 16)) else {
+                        continue;
+                    };
+                    if line.contains('[') {
+                        // <low> <high> [<dst> <dst> ...]
+                        for (code, dst) in (low..=high).zip(&hex[2..]) {
+                            self.map.insert(code, utf16_hex(dst));
+                        }
+                    } else if let Ok(first) = u32::from_str_radix(hex[2], 16) {
+                        for (offset, code) in (low..=high.min(low + 0xffff)).enumerate() {
+                            if let Some(c) = char::from_u32(first + offset as u32) {
+                                self.map.insert(code, c.to_string());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        if self.map.is_empty() {
+            return bytes.iter().map(|&b| b as char).collect();
+        }
+        if self.two_byte {
+            let mapped: Option<String> = bytes
+                .chunks(2)
+                .map(|pair| self.map.get(&pair.iter().fold(0u32, |acc, &b| acc << 8 | u32::from(b))).cloned())
+                .collect();
+            // Strings in a simple font next to a CID font are plain bytes
+            mapped.unwrap_or_else(|| bytes.iter().map(|&b| b as char).collect())
+        } else {
+            bytes.iter().map(|&b| self.map.get(&u32::from(b)).cloned().unwrap_or_else(|| (b as char).to_string())).collect()
+        }
+    }
+}
+
+fn utf16_hex(hex: &str) -> String {
+    let units: Vec<u16> = (0..hex.len() / 4).filter_map(|i| u16::from_str_radix(&hex[i * 4..i * 4 + 4], 16).ok()).collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Append the text shown by a content stream, one line per text line.
+fn content_text(stream: &[u8], cmap: &CMap, out: &mut String) {
+    let mut pending: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < stream.len() {
+        match stream[i] {
+            b'(' => {
+                let (bytes, next) = literal_string(stream, i + 1);
+                pending.push(cmap.decode(&bytes));
+                i = next;
+            }
+            b'<' if stream.get(i + 1) != Some(&b'<') => {
+                let end = stream[i..].iter().position(|&b| b == b'>').map_or(stream.len(), |p| i + p);
+                let bytes = hex_bytes(&stream[i + 1..end]);
+                pending.push(cmap.decode(&bytes));
+                i = end + 1;
+            }
+            // `<<` opens a dictionary (marked content), not a hex string
+            b'<' => i += 2,
+            b'-' | b'0'..=b'9' | b'.' if !pending.is_empty() => {
+                // A large negative kerning inside a TJ array is a word gap
+                let end = stream[i..].iter().position(|b| !matches!(b, b'-' | b'.' | b'0'..=b'9')).map_or(stream.len(), |p| i + p);
+                let number: f64 = String::from_utf8_lossy(&stream[i..end]).parse().unwrap_or(0.0);
+                if number < -200.0 {
+                    pending.push(" ".to_string());
+                }
+                i = end;
+            }
+            b'%' => {
+                i = stream[i..].iter().position(|&b| b == b'\n' || b == b'\r').map_or(stream.len(), |p| i + p);
+            }
+            c if c.is_ascii_alphabetic() || c == b'\'' || c == b'"' || c == b'*' => {
+                let end = stream[i..]
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphanumeric() || matches!(b, b'\'' | b'"' | b'*')))
+                    .map_or(stream.len(), |p| i + p);
+                match &stream[i..end] {
+                    b"Tj" | b"TJ" => out.extend(pending.drain(..)),
+                    b"'" | b"\"" => {
+                        out.push('\n');
+                        out.extend(pending.drain(..));
+                    }
+                    b"T*" | b"Td" | b"TD" | b"Tm" | b"ET" => {
+                        pending.clear();
+                        if !out.ends_with('\n') {
+                            out.push('\n');
+                        }
+                    }
+                    _ => pending.clear(),
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+/// Read a `(...)` string starting after the opening parenthesis; returns
+/// the bytes and the index after the closing one.
+fn literal_string(stream: &[u8], start: usize) -> (Vec<u8>, usize) {
+    let mut bytes = Vec::new();
+    let mut depth = 0;
+    let mut i = start;
+    while i < stream.len() {
+        let c = stream[i];
+        i += 1;
+        match c {
+            b'\\' => {
+                let Some(&escaped) = stream.get(i) else { break };
+                i += 1;
+                match escaped {
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'b' => bytes.push(8),
+                    b'f' => bytes.push(12),
+                    b'0'..=b'7' => {
+                        let mut value = u32::from(escaped - b'0');
+                        for _ in 0..2 {
+                            match stream.get(i) {
+                                Some(&d @ b'0'..=b'7') => {
+                                    value = value * 8 + u32::from(d - b'0');
+                                    i += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        bytes.push(value as u8);
+                    }
+                    b'\r' | b'\n' => {
+                        if escaped == b'\r' && stream.get(i) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    other => bytes.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                bytes.push(c);
+            }
+            b')' if depth == 0 => return (bytes, i),
+            b')' => {
+                depth -= 1;
+                bytes.push(c);
+            }
+            _ => bytes.push(c),
+        }
+    }
+    (bytes, i)
+}
+
+fn hex_bytes(hex: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = hex.iter().filter(|b| b.is_ascii_hexdigit()).copied().collect();
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let text = String::from_utf8_lossy(pair);
+            // An odd final digit is followed by an implicit 0
+            u8::from_str_radix(&format!("{:0<2}", text), 16).unwrap_or(0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// A PDF of the given objects, numbered from 1, with `trailer` after them.
+    fn pdf(objects: &[Vec<u8>], trailer: &str) -> Vec<u8> {
+        let mut data = b"%PDF-1.7\n".to_vec();
+        for (i, object) in objects.iter().enumerate() {
+            data.extend(format!("{} 0 obj\n", i + 1).bytes());
+            data.extend(object);
+            data.extend(b"\nendobj\n");
+        }
+        data.extend(trailer.bytes());
+        data
+    }
+
+    fn stream(dictionary: &str, body: &[u8]) -> Vec<u8> {
+        let mut object = format!("<< {} /Length {} >>\nstream\n", dictionary, body.len()).into_bytes();
+        object.extend(body);
+        object.extend(b"\nendstream");
+        object
+    }
+
+    fn deflated(body: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn text_comes_from_plain_and_compressed_streams() {
+        let data = pdf(&[
+            stream("", b"BT /F1 12 Tf 72 700 Td (Invoice 2024-03-05) Tj ET"),
+            stream("/Filter /FlateDecode", &deflated(b"BT 72 680 Td [(Total)-250(due:) 120 (42.00)] TJ ET")),
+            stream("/Filter /DCTDecode", b"BT (not text) Tj ET"),
+        ], "trailer << >>\n");
+        assert_eq!(extract_text(&data).lines().filter(|l| !l.is_empty()).collect::<Vec<_>>(), ["Invoice 2024-03-05", "Total due:42.00"]);
+    }
+
+    #[test]
+    fn compressed_cmaps_decode_two_byte_text() {
+        let cmap = b"begincmap\n1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n\
+                     2 beginbfchar\n<0001> <00DC>\n<0002> <0062>\nendbfchar\n1 beginbfrange\n<0003> <0004> <0065>\nendbfrange\nendcmap";
+        let data = pdf(&[
+            stream("/Filter /FlateDecode", &deflated(cmap)),
+            stream("/Filter /FlateDecode", &deflated(b"BT <0001000200030004> Tj ET")),
+        ], "trailer << >>\n");
+        assert_eq!(extract_text(&data).trim(), "Übef");
+    }
+
+    #[test]
+    fn titles_come_from_the_info_dictionary() {
+        let data = pdf(&[
+            b"<< /Type /Catalog >>".to_vec(),
+            b"<< /Title <FEFF00DC0062006500720073006900630068007400> /Producer (x) >>".to_vec(),
+        ], "trailer << /Root 1 0 R /Info 2 0 R >>\n");
+        assert_eq!(title(&data).as_deref(), Some("Übersicht"));
+    }
+}
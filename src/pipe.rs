@@ -0,0 +1,86 @@
+use crate::{format_filename, is_protected, Config};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Format every name or path read from `input`, one per `delimiter`, and
+/// write the results to `output` the same way, in the same order. Paths
+/// keep their directory; names namefmt leaves alone come out as they went
+/// in. Nothing is renamed. Returns how many names changed.
+pub fn filter(input: impl BufRead, mut output: impl Write, config: &Config, delimiter: u8) -> io::Result<usize> {
+    let mut changed = 0;
+    for entry in input.split(delimiter) {
+        let mut entry = entry?;
+        if delimiter == b'\n' && entry.last() == Some(&b'\r') {
+            entry.pop();
+        }
+        match formatted(&path_of(&entry), config) {
+            Some(path) => {
+                output.write_all(&bytes_of(&path))?;
+                changed += 1;
+            }
+            None => output.write_all(&entry)?,
+        }
+        output.write_all(&[delimiter])?;
+    }
+    output.flush()?;
+    Ok(changed)
+}
+
+/// `path` with the name a run would give it, or `None` if it keeps its name.
+fn formatted(path: &Path, config: &Config) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    if is_protected(path, config) {
+        return None;
+    }
+    let new_name = format_filename(&name, config, path, config.timestamp)?;
+    Some(path.with_file_name(new_name.as_str()))
+}
+
+#[cfg(unix)]
+fn path_of(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_of(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(unix)]
+fn bytes_of(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn bytes_of(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_styled_by_name_alone_unless_detecting() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("My Tool")).unwrap();
+        std::fs::write(root.join("My Tool/package.json"), "{}").unwrap();
+        let input = format!("{}\n{}\n", root.join("My Tool").display(), root.join("Read Me.txt").display());
+        let run = |config: &Config| {
+            let mut output = Vec::new();
+            filter(input.as_bytes(), &mut output, config, b'\n').unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let detecting = Config::default();
+        let mut by_name = Config::default();
+        by_name.detection.names_only();
+        let (detected, named) = (run(&detecting), run(&by_name));
+        let read_me = root.join("Read_Me.txt");
+        assert_eq!(detected, format!("{}\n{}\n", root.join("my-tool").display(), read_me.display()));
+        assert_eq!(named, format!("{}\n{}\n", root.join("My_Tool").display(), read_me.display()));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::hash::sha256_hex;
+use crate::Config;
+use crate::journal::file_fingerprint;
+use crate::name::{Platform, SanitizedName};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A rename decided during planning, with the rule that produced it.
+pub struct Rename {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub rule: String,
+}
+
+/// Everything a run decided before anything is touched.
+pub struct Plan {
+    pub root: PathBuf,
+    pub files: Vec<PathBuf>,
+    pub renames: Vec<Rename>,
+    /// Files deliberately left alone, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+    /// How each configured behavior fared, in configuration order
+    pub coverage: Vec<Coverage>,
+    /// Proposed renames for files no behavior matches, not yet accepted
+    pub suggestions: Vec<Rename>,
+}
+
+/// Files a behavior decided, and files it matched but lost to an earlier
+/// behavior (behaviors are first-match-wins).
+pub struct Coverage {
+    pub rule: String,
+    pub decided: usize,
+    pub shadowed: usize,
+}
+
+impl Coverage {
+    /// A behavior that decides nothing is dead (matches no file) or
+    /// shadowed (every file it matches is taken by an earlier one).
+    pub fn problem(&self) -> Option<&'static str> {
+        match (self.decided, self.shadowed) {
+            (0, 0) => Some("matched no files"),
+            (0, _) => Some("shadowed by earlier behaviors"),
+            _ => None,
+        }
+    }
+}
+
+impl Plan {
+    /// Renames whose target is already taken, either by an existing file
+    /// that is not itself being renamed away or by another rename.
+    pub fn conflicts(&self) -> Vec<(&Rename, String)> {
+        let mut claimed: HashMap<&Path, usize> = HashMap::new();
+        for rename in &self.renames {
+            *claimed.entry(rename.target.as_path()).or_default() += 1;
+        }
+        let moving_away: HashMap<&Path, ()> = self.renames.iter().map(|r| (r.source.as_path(), ())).collect();
+
+        self.renames
+            .iter()
+            .filter_map(|rename| {
+                if claimed[rename.target.as_path()] > 1 {
+                    Some((rename, "several files would get this name".to_string()))
+                } else if rename.target.exists() && !moving_away.contains_key(rename.target.as_path()) {
+                    Some((rename, "a file with this name already exists".to_string()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// A reviewed rename plan written by `namefmt plan` and executed by
+/// `namefmt apply`. Besides the renames it records everything that fed
+/// into the decisions, so `apply` can tell when the world has moved on.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlanFile {
+    pub version: String,
+    pub created: String,
+    /// SHA-256 of the configuration the plan was made with
+    pub config_hash: String,
+    /// SHA-256 over the sorted relative paths of every file considered
+    pub files_hash: String,
+    pub file_count: usize,
+    /// The path given on the command line; rename paths are relative to `root`
+    pub path: PathBuf,
+    pub root: PathBuf,
+    #[serde(default)]
+    pub timestamp: bool,
+    #[serde(default)]
+    pub presets: Vec<String>,
+    #[serde(default, rename = "rename")]
+    pub renames: Vec<PlannedRename>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlannedRename {
+    /// Paths are relative to the plan root
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub rule: String,
+    pub size: u64,
+    pub mtime: String,
+}
+
+pub fn config_hash(config: &Config) -> String {
+    let serialized = toml::to_string(&config.naming_rules()).unwrap_or_default();
+    sha256_hex(serialized.as_bytes())
+}
+
+pub fn files_hash(root: &Path, files: &[PathBuf]) -> String {
+    let mut relative: Vec<String> = files
+        .iter()
+        .map(|f| f.strip_prefix(root).unwrap_or(f).to_string_lossy().to_string())
+        .collect();
+    relative.sort();
+    sha256_hex(relative.join("\n").as_bytes())
+}
+
+/// Written at the top of a plan file for whoever opens it in an editor.
+const EDITING_HELP: &str = "\
+# Renames namefmt would perform. Change a `target` to choose another name
+# (paths are relative to `root`), or delete a [[rename]] block to leave that
+# file alone, then perform exactly these renames with `namefmt apply`.
+
+";
+
+impl PlanFile {
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, format!("{}{}", EDITING_HELP, toml::to_string(self)?))?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<PlanFile, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// Targets, edited by hand perhaps, that can't be used as they are:
+    /// ones leaving the plan root and ones with names `platform` can't store.
+    pub fn invalid_targets(&self, platform: Platform) -> Vec<String> {
+        let mut problems = Vec::new();
+        for rename in &self.renames {
+            let target = &rename.target;
+            if target.file_name().is_none() {
+                problems.push(format!("{}: target {:?} has no file name", rename.source.display(), target));
+                continue;
+            }
+            for component in target.components() {
+                let problem = match component {
+                    Component::Normal(name) => {
+                        let name = name.to_string_lossy();
+                        let sanitized = SanitizedName::new(&name, platform);
+                        (sanitized.as_str() != name).then(|| format!("\"{}\" is not a valid name (\"{}\" would be)", name, sanitized))
+                    }
+                    Component::CurDir => None,
+                    _ => Some("leaves the plan root".to_string()),
+                };
+                if let Some(problem) = problem {
+                    problems.push(format!("{}: target {}: {}", rename.source.display(), target.display(), problem));
+                    break;
+                }
+            }
+        }
+        problems
+    }
+
+    /// Compare the plan against the current state of the tree. Returns a
+    /// list of human-readable reasons the plan may no longer be what was
+    /// reviewed; empty means it is safe to apply.
+    pub fn drift(&self, config_hash: &str, files_hash: &str, file_count: usize) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.version != env!("CARGO_PKG_VERSION") {
+            problems.push(format!(
+                "plan was made with namefmt {}, this is {}",
+                self.version,
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+        if self.config_hash != config_hash {
+            problems.push("configuration has changed since the plan was made".to_string());
+        }
+        if self.files_hash != files_hash {
+            problems.push(format!(
+                "file set has changed since the plan was made ({} files then, {} now)",
+                self.file_count, file_count
+            ));
+        }
+
+        for rename in &self.renames {
+            let source = self.root.join(&rename.source);
+            match file_fingerprint(&source) {
+                Ok((size, mtime)) if size == rename.size && mtime == rename.mtime => {}
+                Ok(_) => problems.push(format!("{} was modified after planning", rename.source.display())),
+                Err(_) => problems.push(format!("{} no longer exists", rename.source.display())),
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan_for(root: &Path, renames: &[(&str, &str)]) -> PlanFile {
+        PlanFile {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created: String::from("2024-05-01T10:00:00+00:00"),
+            config_hash: String::from("config"),
+            files_hash: String::from("files"),
+            file_count: renames.len(),
+            path: root.to_path_buf(),
+            root: root.to_path_buf(),
+            timestamp: false,
+            presets: Vec::new(),
+            renames: renames
+                .iter()
+                .map(|(source, target)| {
+                    let (size, mtime) = file_fingerprint(&root.join(source)).unwrap_or_default();
+                    PlannedRename { source: source.into(), target: target.into(), rule: String::from("snake_case"), size, mtime }
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn saved_plans_load_back_and_notice_drift() {
+        let scratch = tempfile::tempdir().unwrap();
+        let root = scratch.path();
+        fs::write(root.join("My Notes.txt"), "draft").unwrap();
+        let file = root.join("plan.toml");
+        plan_for(root, &[("My Notes.txt", "my_notes.txt")]).save(&file).unwrap();
+
+        let plan = PlanFile::load(&file).unwrap();
+        assert!(fs::read_to_string(&file).unwrap().starts_with(EDITING_HELP));
+        assert_eq!((plan.renames[0].source.as_path(), plan.renames[0].target.as_path()), (Path::new("My Notes.txt"), Path::new("my_notes.txt")));
+        assert!(plan.drift("config", "files", 1).is_empty());
+
+        fs::write(root.join("My Notes.txt"), "final draft").unwrap();
+        assert_eq!(
+            plan.drift("other", "files", 1),
+            ["configuration has changed since the plan was made", "My Notes.txt was modified after planning"]
+        );
+        fs::remove_file(root.join("My Notes.txt")).unwrap();
+        assert_eq!(
+            plan.drift("config", "changed", 0),
+            ["file set has changed since the plan was made (1 files then, 0 now)", "My Notes.txt no longer exists"]
+        );
+    }
+
+    #[test]
+    fn edited_targets_must_stay_in_the_root_and_be_valid_names() {
+        let root = Path::new("/nonexistent/namefmt-plan");
+        let plan = plan_for(root, &[
+            ("a.txt", "docs/./a_notes.txt"),
+            ("b.txt", "../b.txt"),
+            ("c.txt", "/etc/c.txt"),
+            ("d.txt", "d:draft?.txt"),
+            ("e.txt", "docs/.."),
+        ]);
+        assert_eq!(plan.invalid_targets(Platform::Posix), [
+            "b.txt: target ../b.txt: leaves the plan root",
+            "c.txt: target /etc/c.txt: leaves the plan root",
+            "e.txt: target \"docs/..\" has no file name",
+        ]);
+        assert_eq!(plan.invalid_targets(Platform::Windows)[2], "d.txt: target d:draft?.txt: \"d:draft?.txt\" is not a valid name (\"d_draft_.txt\" would be)");
+    }
+}
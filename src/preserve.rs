@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+
+/// First private-use character for preserved text; piece `i` stands in as
+/// `PLACEHOLDER + i` while a name is being styled. Media tokens use the
+/// characters below it, acronyms the ones above.
+const PLACEHOLDER: u32 = 0xE800;
+const MAX_PIECES: usize = 0x800;
+
+/// Release tags a version may end in, as in `2.0.0-rc.1`.
+const PRE_RELEASES: &[&str] = &["alpha", "beta", "rc", "pre", "preview", "dev", "snapshot"];
+
+/// Text inside names that styling passes through as written, instead of
+/// splitting it into words: `backup 2024-01-05 v2.1.0` stays
+/// `backup_2024-01-05_v2.1.0` in snake_case.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreserveRules {
+    /// Version numbers: v1.2, 1.2.3, 2.0.0-rc.1
+    pub versions: bool,
+    /// ISO dates, with the time if one follows: 2024-01-05, 2024-01-05T10-30
+    pub dates: bool,
+    /// Hex digests and commit hashes of 7 or more digits, letters and at
+    /// least two numbers among them: 3f2a9c1, DEADBEEF1234
+    pub hashes: bool,
+}
+
+impl Default for PreserveRules {
+    fn default() -> Self {
+        PreserveRules { versions: true, dates: true, hashes: true }
+    }
+}
+
+impl PreserveRules {
+    /// Run `style` on `name` with the text these rules preserve hidden, and
+    /// put it back afterwards.
+    pub fn around(&self, name: &str, style: impl FnOnce(&str) -> String) -> String {
+        if !(self.versions || self.dates || self.hashes) {
+            return style(name);
+        }
+        let chars: Vec<char> = name.chars().collect();
+        let mut hidden = String::new();
+        let mut pieces: Vec<String> = Vec::new();
+        let mut at = 0;
+        while at < chars.len() {
+            let len = if starts_token(&chars, at) { self.literal(&chars[at..]) } else { 0 };
+            let placeholder = char::from_u32(PLACEHOLDER + pieces.len() as u32).filter(|_| len > 0 && pieces.len() < MAX_PIECES);
+            match placeholder {
+                Some(placeholder) => {
+                    pieces.push(chars[at..at + len].iter().collect());
+                    hidden.push(placeholder);
+                    at += len;
+                }
+                None => {
+                    hidden.push(chars[at]);
+                    at += 1;
+                }
+            }
+        }
+        if pieces.is_empty() {
+            return style(name);
+        }
+        let mut out = String::new();
+        for c in style(&hidden).chars() {
+            match (c as u32).checked_sub(PLACEHOLDER).and_then(|i| pieces.get(i as usize)) {
+                Some(piece) => out.push_str(piece),
+                None => out.push(c),
+            }
+        }
+        out
+    }
+
+    /// Length of the longest preserved text at the start of `chars` that
+    /// ends where a word could, or 0.
+    fn literal(&self, chars: &[char]) -> usize {
+        if !chars.first().is_some_and(|c| c.is_ascii_hexdigit() || matches!(c, 'v' | 'V')) {
+            return 0;
+        }
+        [(self.dates, date(chars)), (self.versions, version(chars)), (self.hashes, hash(chars))]
+            .into_iter()
+            .filter_map(|(on, len)| len.filter(|&len| on && ends_token(chars, len)))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Whether `c` stands in for preserved text. Unlike the other
+/// placeholders it doesn't make a word of its own but goes with the word
+/// it's part of, as digits do: `x2024-01-05` is one word, `2024-01-05Notes`
+/// two.
+pub(crate) fn is_placeholder(c: char) -> bool {
+    (PLACEHOLDER..PLACEHOLDER + MAX_PIECES as u32).contains(&(c as u32))
+}
+
+/// Whether a token may end at `len`. Styling may take out the separators
+/// after it, so what comes after them decides: not more digits after a
+/// digit (`2024-01-05 3` would read `2024-01-053`), letters after a letter,
+/// or a `.` and digits (`1.2024-01-31`; a run of dots is written as one).
+fn ends_token(chars: &[char], len: usize) -> bool {
+    let Some(&last) = len.checked_sub(1).and_then(|i| chars.get(i)) else {
+        return false;
+    };
+    let skip = |at: usize, dots: bool| {
+        at + chars[at.min(chars.len())..].iter().take_while(|&&c| matches!(c, ' ' | '_' | '-') || (dots && c == '.')).count()
+    };
+    let next = skip(len, false);
+    match chars.get(next) {
+        Some('.') => digits(chars, skip(next, true)) == 0,
+        Some(c) if c.is_alphanumeric() => c.is_ascii_digit() != last.is_ascii_digit(),
+        _ => true,
+    }
+}
+
+/// Whether a token may start at `at`: anywhere but inside a run of
+/// letters, since PascalCase joins words up to digits as well as letters
+/// (`Backup2024-01-05v2.1.0`, `Take22024-01-05`).
+fn starts_token(chars: &[char], at: usize) -> bool {
+    let letter = |c: &char| c.is_alphanumeric() && !c.is_ascii_digit();
+    !(at.checked_sub(1).and_then(|i| chars.get(i)).is_some_and(letter) && chars.get(at).is_some_and(letter))
+}
+
+fn digits(chars: &[char], at: usize) -> usize {
+    chars[at.min(chars.len())..].iter().take_while(|c| c.is_ascii_digit()).count()
+}
+
+/// `2024-01-05`, then `T10-30` or `T10:30:15` if the time follows.
+fn date(chars: &[char]) -> Option<usize> {
+    let number = |from: usize, len: usize| -> Option<u32> {
+        (digits(chars, from) >= len).then(|| chars[from..from + len].iter().fold(0, |n, c| n * 10 + c.to_digit(10).unwrap_or(0)))
+    };
+    let at = |i: usize, c: char| chars.get(i) == Some(&c);
+    number(0, 4)?;
+    let (month, day) = (number(5, 2)?, number(8, 2)?);
+    if !at(4, '-') || !at(7, '-') || !(1..=12).contains(&month) || !(1..=31).contains(&day) || digits(chars, 10) > 0 {
+        return None;
+    }
+    let mut len = 10;
+    if chars.get(10).is_some_and(|c| matches!(c, 'T' | 't')) && number(11, 2).is_some_and(|h| h < 24) {
+        len = 13;
+        for _ in 0..2 {
+            let separated = chars.get(len).is_some_and(|c| matches!(c, ':' | '-' | '_'));
+            if !separated || number(len + 1, 2).is_none_or(|n| n > 59) || digits(chars, len + 3) > 0 {
+                break;
+            }
+            len += 3;
+        }
+    }
+    Some(len)
+}
+
+/// `v1.2`, `1.2.3` or `2.0.0-rc.1`: numbers joined by dots, at least two.
+fn version(chars: &[char]) -> Option<usize> {
+    let mut len = usize::from(chars.first().is_some_and(|c| matches!(c, 'v' | 'V')));
+    let first = digits(chars, len);
+    if first == 0 {
+        return None;
+    }
+    len += first;
+    let mut parts = 1;
+    while chars.get(len) == Some(&'.') && digits(chars, len + 1) > 0 {
+        len += 1 + digits(chars, len + 1);
+        parts += 1;
+    }
+    if parts < 2 {
+        return None;
+    }
+    if chars.get(len) == Some(&'-') {
+        let tag: String = chars[len + 1..].iter().take_while(|c| c.is_ascii_alphabetic()).collect();
+        if PRE_RELEASES.iter().any(|known| known.eq_ignore_ascii_case(&tag)) {
+            len += 1 + tag.len();
+            let dot = usize::from(chars.get(len) == Some(&'.'));
+            len += match digits(chars, len + dot) {
+                0 => 0,
+                n => dot + n,
+            };
+        }
+    }
+    Some(len)
+}
+
+/// 7 to 128 hex digits in one case, with a letter and two numbers among
+/// them so that words like `face2face` aren't taken for one.
+fn hash(chars: &[char]) -> Option<usize> {
+    let lower = chars.iter().take(129).take_while(|c| matches!(c, '0'..='9' | 'a'..='f')).count();
+    let upper = chars.iter().take(129).take_while(|c| matches!(c, '0'..='9' | 'A'..='F')).count();
+    let len = lower.max(upper);
+    let run = &chars[..len];
+    let mixed = run.iter().filter(|c| c.is_ascii_digit()).count() >= 2 && run.iter().any(char::is_ascii_alphabetic);
+    ((7..=128).contains(&len) && mixed).then_some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apply_style, NamingStyle};
+
+    #[test]
+    fn versions_dates_and_hashes_are_styled_as_written() {
+        let rules = PreserveRules::default();
+        let style = |name: &str, style: NamingStyle| rules.around(name, |name| apply_style(name, &style));
+        assert_eq!(style("Backup 2024-01-05T10-30 v2.1.0", NamingStyle::SnakeCase), "backup_2024-01-05T10-30_v2.1.0");
+        assert_eq!(style("build 1.2.3-rc.1 deadbeef1234", NamingStyle::PascalCase), "Build1.2.3-rc.1deadbeef1234");
+        // Joined by PascalCase, they are still found on a second run
+        assert_eq!(style("Build1.2.3-rc.1deadbeef1234", NamingStyle::PascalCase), "Build1.2.3-rc.1deadbeef1234");
+        assert_eq!(style("Take22024-01-05", NamingStyle::PascalCase), "Take22024-01-05");
+        assert_eq!(style("face2face 2024-13-01", NamingStyle::SnakeCase), "face2face_2024_13_01");
+        let rules = PreserveRules { dates: false, ..PreserveRules::default() };
+        assert_eq!(rules.around("Due 2024-01-05", |name| apply_style(name, &NamingStyle::SnakeCase)), "due_2024_01_05");
+    }
+}
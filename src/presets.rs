@@ -0,0 +1,469 @@
+use crate::registry;
+use crate::warn;
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prefixes written by scanners and office copiers (matched case-insensitively).
+const SCANNER_PREFIXES: &[&str] = &["skmbt", "kmbt", "km_", "xerox scan", "scanned", "scan", "scn", "doc"];
+
+const SCANNER_EXTENSIONS: &[&str] = &["pdf", "tif", "tiff", "jpg", "jpeg", "png"];
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "m4b", "aac", "ogg", "oga", "opus", "flac", "wav", "wma"];
+
+const SHARD_EXTENSIONS: &[&str] = &[
+    "parquet", "arrow", "tfrecord", "tfrecords", "jsonl", "csv", "tsv", "npy", "npz", "bin", "tar", "safetensors", "pt",
+    "avro", "orc", "idx",
+];
+
+/// Words that introduce a chapter or track number (matched case-insensitively).
+const CHAPTER_WORDS: &[&str] = &["chapter", "chap", "ch", "track", "trk", "part", "pt"];
+
+/// A bundle of conventions for one ecosystem: per-extension styles (file
+/// pattern, style name) and names that must never change.
+pub struct Pack {
+    pub behaviors: &'static [(&'static str, &'static str)],
+    pub protected: &'static [&'static str],
+}
+
+/// Presets that plan renames themselves rather than contributing rules.
+pub const BUILT_IN: &[&str] = &["scanner", "audiobook", "shards"];
+
+/// Every convention pack `pack` knows.
+pub const PACKS: &[&str] = &["rust", "python", "node", "go", "web-assets"];
+
+/// Names every ecosystem expects to find exactly as spelled: project
+/// documents, build and package manifests, and tool configuration.
+const COMMON_PROTECTED: &[&str] = &[
+    "README*", "LICENSE*", "LICENCE*", "COPYING*", "CHANGELOG*", "CONTRIBUTING*", "AUTHORS*", "NOTICE*", "SECURITY*",
+    "CODEOWNERS", "Makefile", "GNUmakefile", "CMakeLists.txt", "Dockerfile*", "Containerfile", "docker-compose*",
+    "Jenkinsfile", "Vagrantfile", "Procfile", "Rakefile", "Gemfile", "Gemfile.lock", "Cargo.toml", "Cargo.lock",
+    "package.json", "package-lock.json", "go.mod", "go.sum", "pyproject.toml", "setup.py", "requirements*.txt",
+    ".git", ".gitignore", ".gitattributes", ".gitmodules", ".editorconfig", ".github", ".dockerignore", ".env*",
+];
+
+pub fn pack(name: &str) -> Option<Pack> {
+    let pack = match name {
+        "rust" => Pack {
+            behaviors: &[("*.rs", "snake_case")],
+            protected: &["Cargo.toml", "Cargo.lock", "build.rs", "rust-toolchain*", "rustfmt.toml", ".rustfmt.toml", "clippy.toml", "target"],
+        },
+        "python" => Pack {
+            behaviors: &[("*.py", "snake_case"), ("*.pyi", "snake_case")],
+            protected: &[
+                "__init__.py", "__main__.py", "setup.py", "setup.cfg", "pyproject.toml", "requirements*.txt", "Pipfile",
+                "Pipfile.lock", "poetry.lock", "tox.ini", "MANIFEST.in", "conftest.py", "__pycache__", ".venv", "venv",
+                "*.egg-info",
+            ],
+        },
+        "node" => Pack {
+            behaviors: &[
+                ("*.js", "kebab-case"), ("*.mjs", "kebab-case"), ("*.cjs", "kebab-case"), ("*.ts", "kebab-case"),
+                ("*.jsx", "kebab-case"), ("*.tsx", "kebab-case"),
+            ],
+            protected: &[
+                "package.json", "package-lock.json", "yarn.lock", "pnpm-lock.yaml", "tsconfig*.json", "jsconfig.json",
+                ".npmrc", ".nvmrc", ".eslintrc*", ".prettierrc*", "node_modules", "dist",
+            ],
+        },
+        "go" => Pack {
+            behaviors: &[("*.go", "snake_case")],
+            protected: &["go.mod", "go.sum", "go.work", "go.work.sum", "vendor", "testdata"],
+        },
+        "web-assets" => Pack {
+            behaviors: &[
+                ("*.html", "kebab-case"), ("*.css", "kebab-case"), ("*.scss", "kebab-case"), ("*.svg", "kebab-case"),
+                ("*.png", "kebab-case"), ("*.jpg", "kebab-case"), ("*.jpeg", "kebab-case"), ("*.gif", "kebab-case"),
+                ("*.webp", "kebab-case"), ("*.woff", "kebab-case"), ("*.woff2", "kebab-case"),
+            ],
+            protected: &[
+                "favicon.ico", "robots.txt", "humans.txt", "sitemap.xml", "manifest.json", "site.webmanifest",
+                ".htaccess", "CNAME", "_redirects", "_headers",
+            ],
+        },
+        _ => return None,
+    };
+    Some(pack)
+}
+
+/// Names protected on every run unless `default_protected` is off; the
+/// packs only add their own.
+pub fn common_protected() -> &'static [&'static str] {
+    COMMON_PROTECTED
+}
+
+/// Plan renames for the built-in presets. Presets that need to see a whole
+/// directory at once (numbering, sequences) return their decisions here so
+/// the per-file pass can pick them up.
+pub fn plan(presets: &[String], files: &[PathBuf]) -> HashMap<PathBuf, (String, String)> {
+    let mut renames = HashMap::new();
+    for preset in presets {
+        let planned = match preset.as_str() {
+            "scanner" => plan_scanner(files),
+            "audiobook" => plan_audiobook(files),
+            "shards" => plan_shards(files),
+            // Packs only contribute rules, already merged into the config
+            name if pack(name).is_some() || registry::is_installed(name) => continue,
+            other => {
+                warn!("Unknown preset '{}'", other);
+                continue;
+            }
+        };
+        renames.extend(planned.into_iter().map(|(file, name)| (file, (name, preset.clone()))));
+    }
+    renames
+}
+
+fn plan_scanner(files: &[PathBuf]) -> HashMap<PathBuf, String> {
+    // (directory, date) -> scans taken that day, in original order
+    let mut days: BTreeMap<(PathBuf, NaiveDate), Vec<(&PathBuf, String)>> = BTreeMap::new();
+
+    for file in files {
+        let (Some(stem), Some(ext)) = (file.file_stem(), file.extension()) else {
+            continue;
+        };
+        let stem = stem.to_string_lossy();
+        let ext = ext.to_string_lossy().to_lowercase();
+        if !SCANNER_EXTENSIONS.contains(&ext.as_str()) {
+            continue;
+        }
+        let Some(rest) = scanner_suffix(&stem) else {
+            continue;
+        };
+        let Some(date) = scan_date(rest).or_else(|| modified_date(file)) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        days.entry((dir, date)).or_default().push((file, ext));
+    }
+
+    let mut renames = HashMap::new();
+    for ((dir, date), mut scans) in days {
+        scans.sort_by(|a, b| a.0.cmp(b.0));
+        let prefix = format!("{}__scan_", date.format("%Y_%m_%d"));
+        let first = highest_existing_number(&dir, &prefix) + 1;
+        for (number, (file, ext)) in (first..).zip(scans) {
+            renames.insert(file.clone(), format!("{}{:03}.{}", prefix, number, ext));
+        }
+    }
+    renames
+}
+
+fn plan_audiobook(files: &[PathBuf]) -> HashMap<PathBuf, String> {
+    // directory -> (number, title, file, extension)
+    let mut chapters: BTreeMap<PathBuf, Vec<(u32, String, &PathBuf, String)>> = BTreeMap::new();
+    for file in files {
+        let (Some(stem), Some(ext)) = (file.file_stem(), file.extension()) else {
+            continue;
+        };
+        let ext = ext.to_string_lossy().to_string();
+        if !AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
+            continue;
+        }
+        let Some((number, title)) = chapter_number(&stem.to_string_lossy()) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        chapters.entry(dir).or_default().push((number, title, file, ext));
+    }
+
+    let mut renames = HashMap::new();
+    for (dir, mut tracks) in chapters {
+        // A lone numbered file is not a sequence
+        if tracks.len() < 2 {
+            continue;
+        }
+        tracks.sort_by_key(|t| t.0);
+        let numbers: Vec<u32> = tracks.iter().map(|t| t.0).collect();
+
+        let duplicates: Vec<String> = numbers.windows(2).filter(|w| w[0] == w[1]).map(|w| w[0].to_string()).collect();
+        if !duplicates.is_empty() {
+            warn!(
+                "{}: chapter number(s) {} appear more than once; not renumbering this directory",
+                dir.display(),
+                duplicates.join(", ")
+            );
+            continue;
+        }
+        let first = if numbers[0] <= 1 { numbers[0] } else { 1 };
+        let last = numbers[numbers.len() - 1];
+        let missing: Vec<String> = (first..=last).filter(|n| !numbers.contains(n)).map(|n| n.to_string()).collect();
+        if !missing.is_empty() {
+            warn!("{}: chapter sequence is missing {}", dir.display(), missing.join(", "));
+        }
+
+        let width = last.to_string().len().max(2);
+        for (number, title, file, ext) in tracks {
+            let name = match title.is_empty() {
+                true => format!("{:0width$}.{}", number, ext, width = width),
+                false => format!("{:0width$}_{}.{}", number, title, ext, width = width),
+            };
+            if file.file_name() != Some(name.as_ref()) {
+                renames.insert(file.clone(), name);
+            }
+        }
+    }
+    renames
+}
+
+/// (directory, dataset name, extension); shards are grouped by all three.
+type Dataset = (PathBuf, String, String);
+
+/// Rename numbered dataset files to `{name}-{n:05}-of-{total:05}.{ext}`,
+/// numbering from zero in the order of their current numbers.
+fn plan_shards(files: &[PathBuf]) -> HashMap<PathBuf, String> {
+    let mut datasets: BTreeMap<Dataset, Vec<(u64, &PathBuf)>> = BTreeMap::new();
+    for file in files {
+        let (Some(stem), Some(ext)) = (file.file_stem(), file.extension()) else {
+            continue;
+        };
+        let ext = ext.to_string_lossy().to_string();
+        if !SHARD_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
+            continue;
+        }
+        let Some((name, number)) = shard_number(&stem.to_string_lossy()) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let name = match name.is_empty() {
+            // `0.parquet`, `1.parquet`: name the dataset after its directory
+            true => std::path::absolute(&dir)
+                .ok()
+                .and_then(|d| d.file_name().map(|n| n.to_string_lossy().to_string()))
+                .unwrap_or_else(|| String::from("data")),
+            false => name,
+        };
+        datasets.entry((dir, name, ext)).or_default().push((number, file));
+    }
+
+    let mut renames = HashMap::new();
+    for ((dir, name, ext), mut shards) in datasets {
+        if shards.len() < 2 {
+            continue;
+        }
+        shards.sort_by_key(|s| s.0);
+        let numbers: Vec<u64> = shards.iter().map(|s| s.0).collect();
+        let first = numbers[0];
+        let contiguous = first <= 1 && numbers.iter().zip(first..).all(|(n, expected)| *n == expected);
+        if !contiguous {
+            warn!(
+                "{}: {} shards of '{}' are not numbered contiguously ({}); not renaming them",
+                dir.display(),
+                ext,
+                name,
+                describe_gaps(&numbers)
+            );
+            continue;
+        }
+
+        let total = shards.len();
+        for (n, (_, file)) in shards.into_iter().enumerate() {
+            let new_name = format!("{}-{:05}-of-{:05}.{}", name, n, total, ext);
+            if file.file_name() != Some(new_name.as_ref()) {
+                renames.insert(file.clone(), new_name);
+            }
+        }
+    }
+    renames
+}
+
+/// Split a shard stem into dataset name and number: `train-00003-of-00010`,
+/// `train_3`, `data.part-7`, `shard12`.
+fn shard_number(stem: &str) -> Option<(String, u64)> {
+    let base = match stem.rsplit_once("-of-") {
+        Some((base, total)) if !total.is_empty() && total.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => stem,
+    };
+    let digits = base.len() - base.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let number = base[base.len() - digits..].parse().ok()?;
+    let name = base[..base.len() - digits].trim_end_matches(['-', '_', '.', ' ']);
+    Some((name.to_string(), number))
+}
+
+fn describe_gaps(numbers: &[u64]) -> String {
+    if numbers[0] > 1 {
+        return format!("starts at {}", numbers[0]);
+    }
+    let duplicates: Vec<String> = numbers.windows(2).filter(|w| w[0] == w[1]).map(|w| w[0].to_string()).collect();
+    if !duplicates.is_empty() {
+        return format!("duplicate {}", duplicates.join(", "));
+    }
+    let missing: Vec<String> = (numbers[0]..numbers[numbers.len() - 1])
+        .filter(|n| !numbers.contains(n))
+        .map(|n| n.to_string())
+        .collect();
+    format!("missing {}", missing.join(", "))
+}
+
+/// Find the chapter or track number in a stem, in any of the usual forms
+/// (`Ch 1`, `Chapter-01`, `Track_3`, `1.`, `01 - Title`). Returns it with
+/// the rest of the name as the title.
+fn chapter_number(stem: &str) -> Option<(u32, String)> {
+    // ASCII lowering keeps byte offsets valid for slicing `stem`
+    let lower = stem.to_ascii_lowercase();
+
+    for word in CHAPTER_WORDS {
+        let mut from = 0;
+        while let Some(found) = lower[from..].find(word) {
+            let start = from + found;
+            from = start + word.len();
+            if lower[..start].chars().next_back().is_some_and(|c| c.is_alphanumeric()) {
+                continue;
+            }
+            let after = &stem[from..];
+            let digits_at = after.len() - after.trim_start_matches([' ', '.', '_', '-', '#']).len();
+            let digits: String = after[digits_at..].chars().take_while(|c| c.is_ascii_digit()).collect();
+            if digits.is_empty() {
+                continue;
+            }
+            let end = from + digits_at + digits.len();
+            let title = format!("{} {}", &stem[..start], &stem[end..]);
+            return Some((digits.parse().ok()?, chapter_title(&title)));
+        }
+    }
+
+    // Leading number: `1. Intro`, `01 - Intro`, `001_intro`
+    let digits: String = stem.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() || digits.len() > 4 {
+        return None;
+    }
+    let rest = &stem[digits.len()..];
+    if rest.starts_with(|c: char| c.is_alphanumeric()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, chapter_title(rest)))
+}
+
+fn chapter_title(text: &str) -> String {
+    let words: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || c == '_')
+        .filter(|w| !w.chars().all(|c| matches!(c, '-' | '.')))
+        .collect();
+    words.join("_").trim_matches(|c: char| matches!(c, '_' | '-' | '.')).to_string()
+}
+
+/// Return what follows a scanner prefix, if the stem looks machine-generated.
+fn scanner_suffix(stem: &str) -> Option<&str> {
+    let lower = stem.to_lowercase();
+    let prefix = SCANNER_PREFIXES.iter().find(|p| lower.starts_with(*p))?;
+    let rest = &stem[prefix.len()..];
+
+    // Only auto-numbered names: digits, separators and short model codes.
+    // A letter straight after the prefix means a real word ("scandinavia"),
+    // except for Konica Minolta names where a model code follows.
+    let model_code = matches!(*prefix, "skmbt" | "kmbt" | "km_");
+    let starts_with_letter = rest.starts_with(|c: char| c.is_alphabetic());
+    let digits = rest.chars().filter(|c| c.is_ascii_digit()).count();
+    let letters = rest.chars().filter(|c| c.is_alphabetic()).count();
+    let plain = rest.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ' '));
+    (digits > 0 && letters <= 3 && plain && (model_code || !starts_with_letter)).then_some(rest)
+}
+
+/// Find a date embedded in a scanner-generated name, e.g. `20240131`,
+/// `2024-01-31`, or the `YYMMDD` following a Konica Minolta model number.
+fn scan_date(rest: &str) -> Option<NaiveDate> {
+    let digits: String = rest.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    for i in 0..digits.len().saturating_sub(7) {
+        if let Some(date) = parse_date(&digits[i..i + 4], &digits[i + 4..i + 6], &digits[i + 6..i + 8]) {
+            return Some(date);
+        }
+    }
+
+    // SKMBT_C22024013110110: three model digits, then YYMMDD
+    let model = rest.trim_start_matches(|c: char| c == '_' || c.is_ascii_alphabetic());
+    if model.len() >= 9 && model[..9].bytes().all(|b| b.is_ascii_digit()) {
+        let year = format!("20{}", &model[3..5]);
+        return parse_date(&year, &model[5..7], &model[7..9]);
+    }
+
+    None
+}
+
+fn parse_date(year: &str, month: &str, day: &str) -> Option<NaiveDate> {
+    let date = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)?;
+    (1990..2100).contains(&date.year()).then_some(date)
+}
+
+fn modified_date(path: &Path) -> Option<NaiveDate> {
+    let mtime = fs::metadata(path).ok()?.modified().ok()?;
+    Some(DateTime::<Local>::from(mtime).date_naive())
+}
+
+/// Continue numbering after scans already filed for that day.
+fn highest_existing_number(dir: &Path, prefix: &str) -> u32 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let rest = name.strip_prefix(prefix)?;
+            let number: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            number.parse().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `plan` for `names` created in a fresh directory, as new names by old.
+    fn planned(preset: &str, names: &[&str]) -> BTreeMap<String, String> {
+        let scratch = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = names.iter().map(|name| scratch.path().join(name)).collect();
+        for file in &files {
+            fs::write(file, "").unwrap();
+        }
+        plan(&[preset.to_string()], &files)
+            .into_iter()
+            .map(|(file, (name, _))| (file.file_name().unwrap().to_string_lossy().into_owned(), name))
+            .collect()
+    }
+
+    #[test]
+    fn scans_are_named_by_date_and_numbered_after_those_filed() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        fs::write(dir.join("2024_01_31__scan_004.pdf"), "").unwrap();
+        let files: Vec<PathBuf> = ["SKMBT_C22024013110110.pdf", "scan_20240131_0002.PDF", "scandinavia trip.jpg", "notes.txt"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        let renames = plan(&[String::from("scanner")], &files);
+        assert_eq!(renames.len(), 2, "{:?}", renames);
+        assert_eq!(renames[&files[0]], (String::from("2024_01_31__scan_005.pdf"), String::from("scanner")));
+        assert_eq!(renames[&files[1]].0, "2024_01_31__scan_006.pdf");
+    }
+
+    #[test]
+    fn chapters_are_numbered_to_a_common_width() {
+        let renames = planned("audiobook", &["Chapter 1 - Intro.mp3", "Chapter 2.mp3", "ch-10 The End.MP3", "cover.jpg"]);
+        assert_eq!(renames, BTreeMap::from([
+            (String::from("Chapter 1 - Intro.mp3"), String::from("01_Intro.mp3")),
+            (String::from("Chapter 2.mp3"), String::from("02.mp3")),
+            (String::from("ch-10 The End.MP3"), String::from("10_The_End.MP3")),
+        ]));
+        // Two chapter 2s: which one is which is left to the user
+        assert!(planned("audiobook", &["Track 1.mp3", "Track 2.mp3", "02 Intro.mp3"]).is_empty());
+    }
+
+    #[test]
+    fn shards_are_numbered_from_zero_of_their_total() {
+        let renames = planned("shards", &["train_1.parquet", "train_2.parquet", "train_3.parquet", "eval-00000-of-00002.jsonl", "eval-00001-of-00002.jsonl"]);
+        assert_eq!(renames, BTreeMap::from([
+            (String::from("train_1.parquet"), String::from("train-00000-of-00003.parquet")),
+            (String::from("train_2.parquet"), String::from("train-00001-of-00003.parquet")),
+            (String::from("train_3.parquet"), String::from("train-00002-of-00003.parquet")),
+        ]));
+        // A gap means a shard is missing; renumbering would hide it
+        assert!(planned("shards", &["part0.csv", "part1.csv", "part3.csv"]).is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::glob_match;
+use crate::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Warn before renaming files that build manifests refer to by name.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BuildReferenceRules {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default = "default_manifests")]
+    pub manifests: Vec<String>,
+}
+
+impl Default for BuildReferenceRules {
+    fn default() -> Self {
+        BuildReferenceRules {
+            enabled: false,
+            manifests: default_manifests(),
+        }
+    }
+}
+
+fn default_manifests() -> Vec<String> {
+    ["Cargo.toml", "package.json", "CMakeLists.txt", "Makefile", "makefile", "GNUmakefile"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// A path mentioned in a manifest, resolved against the manifest's directory.
+struct Reference {
+    manifest: PathBuf,
+    target: PathBuf,
+}
+
+/// Cargo.toml keys whose values name files or directories.
+const CARGO_PATH_KEYS: &[&str] = &["path", "build", "readme", "license-file", "include", "exclude", "members", "workspace"];
+
+/// package.json fields that name files.
+const PACKAGE_FILE_KEYS: &[&str] = &["files", "main", "module", "types", "typings", "bin", "browser", "exports", "man"];
+
+impl BuildReferenceRules {
+    /// Print a warning for every renamed file referenced by a manifest in
+    /// the tree or in a directory above `root`.
+    pub fn check(&self, root: &Path, files: &[PathBuf], sources: &[&Path]) {
+        if !self.enabled || sources.is_empty() {
+            return;
+        }
+
+        // Running on `src/` must still see the Cargo.toml next to it
+        let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+        let above = root
+            .ancestors()
+            .skip(1)
+            .flat_map(|dir| self.manifests.iter().map(move |m| dir.join(m)))
+            .filter(|m| m.is_file());
+        let mut manifests: Vec<PathBuf> = files.iter().filter(|f| self.is_manifest(f)).cloned().collect();
+        manifests.extend(above);
+
+        let mut references = Vec::new();
+        for manifest in &manifests {
+            match fs::read_to_string(manifest) {
+                Ok(content) => references.extend(parse_manifest(manifest, &content)),
+                Err(e) => warn!("Failed to read {}: {}", manifest.display(), e),
+            }
+        }
+
+        for source in sources.iter().filter(|s| self.is_manifest(s)) {
+            warn!("{} is a build manifest; renaming it will break the build", source.display());
+        }
+        for source in sources {
+            for reference in references.iter().filter(|r| refers_to(&r.target, source)) {
+                warn!(
+                    "{} is referenced by {} ({}); renaming it may break the build",
+                    source.display(),
+                    reference.manifest.display(),
+                    reference.target.display()
+                );
+            }
+        }
+    }
+
+    fn is_manifest(&self, path: &Path) -> bool {
+        path.file_name()
+            .map(|n| n.to_string_lossy())
+            .is_some_and(|n| self.manifests.iter().any(|m| *m == n))
+    }
+}
+
+fn parse_manifest(manifest: &Path, content: &str) -> Vec<Reference> {
+    let name = manifest.file_name().unwrap_or_default().to_string_lossy();
+    let mentioned = match name.as_ref() {
+        "Cargo.toml" => cargo_paths(content),
+        "package.json" => package_paths(content),
+        "CMakeLists.txt" => cmake_paths(content),
+        _ => makefile_paths(content),
+    };
+
+    let manifest = std::path::absolute(manifest).unwrap_or_else(|_| manifest.to_path_buf());
+    let dir = manifest.parent().unwrap_or(Path::new("/"));
+    mentioned
+        .into_iter()
+        .filter(|m| !m.is_empty())
+        .map(|m| Reference { manifest: manifest.clone(), target: normalize(&dir.join(m)) })
+        // `.` would make the manifest claim its whole tree
+        .filter(|r| r.target != dir)
+        .collect()
+}
+
+fn cargo_paths(content: &str) -> Vec<String> {
+    let Ok(value) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let mut paths = Vec::new();
+    collect_toml(&toml::Value::Table(value), false, &mut paths);
+    paths
+}
+
+fn collect_toml(value: &toml::Value, wanted: bool, paths: &mut Vec<String>) {
+    match value {
+        toml::Value::String(s) if wanted => paths.push(s.clone()),
+        toml::Value::Array(items) => items.iter().for_each(|v| collect_toml(v, wanted, paths)),
+        toml::Value::Table(table) => {
+            for (key, v) in table {
+                collect_toml(v, CARGO_PATH_KEYS.contains(&key.as_str()), paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn package_paths(content: &str) -> Vec<String> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(content) else {
+        return Vec::new();
+    };
+    let mut paths = Vec::new();
+    for key in PACKAGE_FILE_KEYS {
+        if let Some(v) = value.get(key) {
+            collect_json(v, &mut paths);
+        }
+    }
+    paths
+}
+
+fn collect_json(value: &serde_json::Value, paths: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(s) => paths.push(s.trim_start_matches("./").to_string()),
+        serde_json::Value::Array(items) => items.iter().for_each(|v| collect_json(v, paths)),
+        serde_json::Value::Object(fields) => fields.iter().for_each(|(_, v)| collect_json(v, paths)),
+        _ => {}
+    }
+}
+
+/// Every argument in CMake commands that looks like a relative path.
+fn cmake_paths(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| words(line, &['(', ')']))
+        .map(|word| {
+            word.trim_start_matches("${CMAKE_CURRENT_SOURCE_DIR}/")
+                .trim_start_matches("${PROJECT_SOURCE_DIR}/")
+                .to_string()
+        })
+        .filter(|word| looks_like_path(word))
+        .collect()
+}
+
+/// Targets, prerequisites and variable values outside of recipes.
+fn makefile_paths(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('\t'))
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| words(line, &[':', '=', '|']))
+        .map(|word| word.trim_start_matches("$(srcdir)/").to_string())
+        .filter(|word| looks_like_path(word))
+        .collect()
+}
+
+/// Split on whitespace and `separators`, honouring `"quoted words"` and
+/// backslash-escaped spaces.
+fn words(line: &str, separators: &[char]) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => word.extend(chars.next()),
+            c if !quoted && (c.is_whitespace() || separators.contains(&c)) => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn looks_like_path(word: &str) -> bool {
+    !word.is_empty() && !word.contains('$') && !word.starts_with('-') && (word.contains('.') || word.contains('/'))
+}
+
+/// Whether a manifest reference covers `source`: the file itself, a
+/// directory containing it, or a glob matching it.
+fn refers_to(target: &Path, source: &Path) -> bool {
+    let source = normalize(&std::path::absolute(source).unwrap_or_else(|_| source.to_path_buf()));
+    if source == target || source.starts_with(target) {
+        return true;
+    }
+    let pattern = target.to_string_lossy();
+    pattern.contains(['*', '?', '[', '{']) && glob_match(&pattern, &source.to_string_lossy())
+}
+
+/// Resolve `.` and `..` without touching the filesystem.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether the manifest at `/project/{manifest}` holding `content` refers to `source`.
+    fn referenced(manifest: &str, content: &str, source: &str) -> bool {
+        parse_manifest(&Path::new("/project").join(manifest), content).iter().any(|r| refers_to(&r.target, Path::new(source)))
+    }
+
+    #[test]
+    fn manifests_refer_to_files_globs_and_directories() {
+        let cargo = "[package]\nname = \"app\"\nbuild = \"build.rs\"\ninclude = [\"assets/*.png\"]\n\n[lib]\npath = \"src/My Lib.rs\"\n";
+        assert!(referenced("Cargo.toml", cargo, "/project/src/My Lib.rs"));
+        assert!(referenced("Cargo.toml", cargo, "/project/assets/Logo Big.png"));
+        assert!(!referenced("Cargo.toml", cargo, "/project/src/main.rs"));
+        // The package name isn't a path
+        assert!(!referenced("Cargo.toml", cargo, "/project/app"));
+
+        let package = r#"{"name": "app.js", "main": "./dist/Index.js", "files": ["lib"]}"#;
+        assert!(referenced("sub/package.json", package, "/project/sub/dist/Index.js"));
+        assert!(referenced("sub/package.json", package, "/project/sub/lib/Util File.js"));
+        assert!(!referenced("sub/package.json", package, "/project/app.js"));
+
+        let cmake = "add_executable(app \"src/Main File.cpp\" ${CMAKE_CURRENT_SOURCE_DIR}/src/util.cpp) # src/old.cpp\n";
+        assert!(referenced("CMakeLists.txt", cmake, "/project/src/Main File.cpp"));
+        assert!(referenced("CMakeLists.txt", cmake, "/project/src/util.cpp"));
+        assert!(!referenced("CMakeLists.txt", cmake, "/project/src/old.cpp"));
+
+        let make = "SOURCES = src/a.c ../shared/b.c\napp: $(SOURCES) include/App\\ Config.h\n\tcc -o app src/ignored.c\n";
+        assert!(referenced("Makefile", make, "/project/src/a.c"));
+        assert!(referenced("Makefile", make, "/shared/b.c"));
+        assert!(referenced("Makefile", make, "/project/include/App Config.h"));
+        assert!(!referenced("Makefile", make, "/project/src/ignored.c"));
+    }
+}
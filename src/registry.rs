@@ -0,0 +1,284 @@
+use crate::{hash, http};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// File at the root of a git repository that holds its preset pack.
+const GIT_PACK_FILE: &str = "namefmt-preset.toml";
+
+/// Lists installed packs with the version and checksum they were installed at.
+const LOCK_FILE: &str = "installed.toml";
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One pack offered by a registry index.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub version: String,
+    /// Where the pack file lives; relative to the index's own location
+    pub url: String,
+    pub sha256: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A registry index: a TOML file of `[[preset]]` entries, one per
+/// published version of each pack.
+#[derive(Debug, Deserialize)]
+pub struct Index {
+    #[serde(default, rename = "preset")]
+    pub presets: Vec<Entry>,
+}
+
+/// Where an installed pack came from.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Installed {
+    pub version: String,
+    pub source: String,
+    pub sha256: String,
+}
+
+/// A fetched pack, checked against its checksum but not yet installed.
+pub struct Fetched {
+    pub name: String,
+    pub installed: Installed,
+    pub content: String,
+}
+
+/// Installed packs live next to the configuration, one TOML file each.
+pub fn presets_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("namefmt").join("presets"))
+}
+
+/// The contents of the installed pack `name`, if there is one.
+pub fn load(name: &str) -> Option<Result<String, String>> {
+    let path = presets_dir()?.join(format!("{}.toml", name));
+    path.exists().then(|| fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e)))
+}
+
+pub fn is_installed(name: &str) -> bool {
+    presets_dir().is_some_and(|dir| dir.join(format!("{}.toml", name)).exists())
+}
+
+/// Installed packs by name.
+pub fn installed() -> Result<BTreeMap<String, Installed>, String> {
+    let Some(path) = presets_dir().map(|dir| dir.join(LOCK_FILE)).filter(|p| p.exists()) else {
+        return Ok(BTreeMap::new());
+    };
+    let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    toml::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Write the pack and record it in the lock file, replacing any earlier version.
+pub fn save(fetched: &Fetched) -> Result<PathBuf, String> {
+    let dir = presets_dir().ok_or("Could not determine config directory")?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.toml", fetched.name));
+    fs::write(&path, &fetched.content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let mut lock = installed()?;
+    lock.insert(fetched.name.clone(), fetched.installed.clone());
+    let lock_path = dir.join(LOCK_FILE);
+    let content = toml::to_string_pretty(&lock).map_err(|e| e.to_string())?;
+    fs::write(&lock_path, content).map_err(|e| format!("Failed to write {}: {}", lock_path.display(), e))?;
+    Ok(path)
+}
+
+/// Fetch and parse the registry index at `url`.
+pub fn index(url: &str) -> Result<Index, String> {
+    let content = fetch(url)?;
+    let content = String::from_utf8(content).map_err(|_| format!("{}: index is not UTF-8", url))?;
+    toml::from_str(&content).map_err(|e| format!("Failed to parse registry index {}: {}", url, e))
+}
+
+/// Resolve `spec` and download the pack it names. `spec` is a registry
+/// name, optionally pinned (`photos@1.2.0`, otherwise the newest version),
+/// a `git+URL[#tag]` or `*.git` repository, or a direct URL or path to a
+/// pack file. Packs outside the registry are checked against `sha256` when
+/// given and installed as `name`, defaulting to the file or repository name.
+pub fn fetch_pack(spec: &str, registry: Option<&str>, name: Option<&str>, sha256: Option<&str>) -> Result<Fetched, String> {
+    let (content, source, version, expected) = if let Some(repo) = spec.strip_prefix("git+").or(spec.ends_with(".git").then_some(spec)) {
+        let (content, version) = clone_pack(repo)?;
+        (content, spec.to_string(), version, sha256.map(str::to_string))
+    } else if spec.contains("://") || spec.ends_with(".toml") {
+        let content = String::from_utf8(fetch(spec)?).map_err(|_| format!("{}: pack is not UTF-8", spec))?;
+        (content, spec.to_string(), String::from("unversioned"), sha256.map(str::to_string))
+    } else {
+        let registry = registry.ok_or("No registry configured: set `registry` in the config or pass --registry")?;
+        let (wanted, pinned) = match spec.split_once('@') {
+            Some((wanted, version)) => (wanted, Some(version)),
+            None => (spec, None),
+        };
+        let index = index(registry)?;
+        let entry = index
+            .presets
+            .iter()
+            .filter(|e| e.name == wanted && pinned.is_none_or(|v| e.version == v))
+            .max_by(|a, b| compare_versions(&a.version, &b.version))
+            .ok_or_else(|| match pinned {
+                Some(version) => format!("Registry has no version {} of preset '{}'", version, wanted),
+                None => format!("Registry has no preset '{}'", wanted),
+            })?;
+        let url = relative_to(registry, &entry.url);
+        let content = String::from_utf8(fetch(&url)?).map_err(|_| format!("{}: pack is not UTF-8", url))?;
+        let name = name.unwrap_or(wanted);
+        let fetched = (content, url, entry.version.clone(), Some(entry.sha256.clone()));
+        return checked(name, fetched);
+    };
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => default_name(spec).ok_or_else(|| format!("Can't tell what to call the pack from {}; pass --name", spec))?,
+    };
+    checked(&name, (content, source, version, expected))
+}
+
+fn checked(name: &str, (content, source, version, expected): (String, String, String, Option<String>)) -> Result<Fetched, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("Not a usable preset name: '{}'", name));
+    }
+    let sha256 = hash::sha256_hex(content.as_bytes());
+    if let Some(expected) = expected.filter(|e| !e.eq_ignore_ascii_case(&sha256)) {
+        return Err(format!("Checksum mismatch for {}: expected {}, got {}. Nothing was installed.", source, expected, sha256));
+    }
+    Ok(Fetched { name: name.to_string(), installed: Installed { version, source, sha256 }, content })
+}
+
+/// `photos` for `https://host/packs/photos.toml` or `git+https://host/photos.git#v1`.
+fn default_name(spec: &str) -> Option<String> {
+    let path = spec.split('#').next()?.trim_end_matches('/');
+    let last = path.rsplit(['/', ':']).next()?;
+    let name = last.trim_end_matches(".git").trim_end_matches(".toml");
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Newer versions compare greater: numeric parts numerically, the rest as text.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| v.trim_start_matches('v').split(['.', '-']).map(str::to_string).collect::<Vec<_>>();
+    for (x, y) in parts(a).iter().zip(parts(b).iter()) {
+        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    parts(a).len().cmp(&parts(b).len())
+}
+
+/// Resolve an index entry's URL against the index location.
+fn relative_to(index: &str, url: &str) -> String {
+    if url.contains("://") || url.starts_with('/') {
+        return url.to_string();
+    }
+    match index.rfind('/') {
+        Some(i) => format!("{}/{}", &index[..i], url),
+        None => url.to_string(),
+    }
+}
+
+/// Shallow-clone `repo` (`URL#tag` pins a tag or branch) and read its pack.
+/// Returns the pack and the commit it came from.
+fn clone_pack(repo: &str) -> Result<(String, String), String> {
+    let (url, rev) = match repo.split_once('#') {
+        Some((url, rev)) => (url, Some(rev)),
+        None => (repo, None),
+    };
+    // Removed again when it goes out of scope
+    let scratch = tempfile::Builder::new().prefix("namefmt-preset-").tempdir().map_err(|e| format!("Failed to create a directory to clone into: {}", e))?;
+    let dir = scratch.path().join("pack");
+    let mut git = Command::new("git");
+    git.args(["clone", "--quiet", "--depth", "1"]);
+    if let Some(rev) = rev {
+        git.args(["--branch", rev]);
+    }
+    // `--` so a URL starting with a dash isn't taken for an option
+    let output = git.arg("--").arg(url).arg(&dir).output().map_err(|e| format!("git repositories need git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("git clone {} failed: {}", url, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let content = fs::read_to_string(dir.join(GIT_PACK_FILE)).map_err(|e| format!("{}: no {} ({})", url, GIT_PACK_FILE, e))?;
+    let commit = Command::new("git").arg("-C").arg(&dir).args(["rev-parse", "--short", "HEAD"]).output();
+    let commit = commit.ok().map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string()).unwrap_or_default();
+    let version = match rev {
+        Some(rev) => format!("{} ({})", rev, commit),
+        None => commit,
+    };
+    Ok((content, version))
+}
+
+/// Download `url` over http or https, or read a `file://` URL or bare
+/// path from disk.
+fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return http::get(url, TIMEOUT);
+    }
+    let path = Path::new(url.strip_prefix("file://").unwrap_or(url));
+    fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::tests::{response, serve};
+
+    #[test]
+    fn registry_packs_are_fetched_newest_first_and_checked() {
+        let pack = "[[behaviors]]\npattern = \"*.jpg\"\nstyle = \"snake_case\"\n";
+        let index = |sha256: &str| format!(
+            "[[preset]]\nname = \"photos\"\nversion = \"1.10.0\"\nurl = \"packs/photos.toml\"\nsha256 = \"{}\"\n\n\
+             [[preset]]\nname = \"photos\"\nversion = \"1.9.0\"\nurl = \"packs/old.toml\"\nsha256 = \"{}\"\n",
+            sha256, sha256
+        );
+        let (url, server) = serve(vec![response("200 OK", "", &index(&hash::sha256_hex(pack.as_bytes()))), response("200 OK", "", pack)]);
+        let registry = format!("{}/index.toml", url);
+        let fetched = fetch_pack("photos", Some(&registry), None, None).unwrap();
+        assert_eq!(fetched.name, "photos");
+        assert_eq!(fetched.content, pack);
+        assert_eq!(fetched.installed.version, "1.10.0");
+        assert_eq!(fetched.installed.source, format!("{}/packs/photos.toml", url));
+        assert!(server.join().unwrap()[1].starts_with("GET /packs/photos.toml "));
+
+        let (url, server) = serve(vec![response("200 OK", "", &index(&"0".repeat(64))), response("200 OK", "", pack)]);
+        let error = fetch_pack("photos@1.9.0", Some(&format!("{}/index.toml", url)), None, None).err().unwrap();
+        assert!(error.starts_with(&format!("Checksum mismatch for {}/packs/old.toml", url)), "{}", error);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn git_packs_are_cloned_at_their_commit() {
+        let scratch = tempfile::tempdir().unwrap();
+        let repo = scratch.path().join("photos");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join(GIT_PACK_FILE), "[[behaviors]]\n").unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git").arg("-C").arg(&repo).args(["-c", "user.name=t", "-c", "user.email=t@example.com"]).args(args).output().unwrap();
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", GIT_PACK_FILE]);
+        git(&["commit", "--quiet", "-m", "pack"]);
+        let commit = git(&["rev-parse", "--short", "HEAD"]);
+
+        let fetched = fetch_pack(&format!("git+file://{}", repo.display()), None, None, None).unwrap();
+        assert_eq!(fetched.name, "photos");
+        assert_eq!(fetched.content, "[[behaviors]]\n");
+        assert_eq!(fetched.installed.version, commit);
+    }
+
+    #[test]
+    fn git_urls_are_never_options() {
+        let scratch = tempfile::tempdir().unwrap();
+        let marker = scratch.path().join("ran");
+        let spec = format!("git+--upload-pack=touch {}", marker.display());
+        let error = fetch_pack(&spec, None, Some("pack"), None).err().unwrap();
+        assert!(error.starts_with("git clone --upload-pack="), "{}", error);
+        assert!(!marker.exists());
+    }
+}
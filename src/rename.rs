@@ -0,0 +1,163 @@
+use crate::hash::sha256_file;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Rename,
+    Copy,
+    /// `git mv`, which also records the rename in the index
+    Git,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Rename => "rename",
+            Method::Copy => "copy",
+            Method::Git => "git",
+        }
+    }
+}
+
+/// How a file actually got moved. Hashes are only computed when the move
+/// degraded to copy+delete, since a plain rename cannot corrupt content.
+#[derive(Debug, Clone)]
+pub struct Moved {
+    pub method: Method,
+    pub source_sha256: Option<String>,
+    pub target_sha256: Option<String>,
+}
+
+pub fn move_file(from: &Path, to: &Path) -> io::Result<Moved> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(Moved {
+            method: Method::Rename,
+            source_sha256: None,
+            target_sha256: None,
+        }),
+        Err(e) if needs_copy_fallback(&e) => copy_and_verify(from, to),
+        Err(e) => Err(e),
+    }
+}
+
+/// Move a file git tracks with `git mv`, so the rename is staged and its
+/// history follows it. Anything git doesn't track is moved like any other.
+pub fn git_move(from: &Path, to: &Path) -> io::Result<Moved> {
+    let (Some(dir), Some(name)) = (from.parent(), from.file_name()) else {
+        return move_file(from, to);
+    };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let tracked = Command::new("git")
+        .arg("-C").arg(dir)
+        .args(["ls-files", "--error-unmatch", "--"]).arg(name)
+        .stdout(Stdio::null()).stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !tracked {
+        return move_file(from, to);
+    }
+    let output = Command::new("git")
+        .arg("-C").arg(dir)
+        .args(["mv", "--"]).arg(name).arg(std::path::absolute(to)?)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git mv {} {} failed: {}",
+            from.display(),
+            to.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(Moved {
+        method: Method::Git,
+        source_sha256: None,
+        target_sha256: None,
+    })
+}
+
+fn needs_copy_fallback(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::CrossesDevices | ErrorKind::Unsupported)
+}
+
+fn copy_and_verify(from: &Path, to: &Path) -> io::Result<Moved> {
+    if to.exists() {
+        return Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+
+    let source_sha256 = sha256_file(from)?;
+    let modified = fs::metadata(from)?.modified().ok();
+
+    fs::copy(from, to)?;
+    {
+        let dest = File::options().write(true).open(to)?;
+        if let Some(mtime) = modified {
+            dest.set_modified(mtime)?;
+        }
+        dest.sync_all()?;
+    }
+
+    let target_sha256 = match sha256_file(to) {
+        Ok(hash) => hash,
+        Err(e) => {
+            let _ = fs::remove_file(to);
+            return Err(e);
+        }
+    };
+
+    // Never delete the source unless the copy is byte-for-byte identical
+    if target_sha256 != source_sha256 {
+        let _ = fs::remove_file(to);
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "checksum mismatch copying {} -> {} (source {}, copy {}); source left in place",
+                from.display(),
+                to.display(),
+                source_sha256,
+                target_sha256
+            ),
+        ));
+    }
+
+    fs::remove_file(from)?;
+
+    Ok(Moved {
+        method: Method::Copy,
+        source_sha256: Some(source_sha256),
+        target_sha256: Some(target_sha256),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracked_files_are_moved_with_git_and_others_renamed() {
+        let scratch = tempfile::tempdir().unwrap();
+        let repo = scratch.path();
+        let git = |args: &[&str]| {
+            let output = Command::new("git").arg("-C").arg(repo).args(args).output().unwrap();
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&["init", "--quiet"]);
+        fs::write(repo.join("Tracked File.md"), "tracked").unwrap();
+        fs::write(repo.join("Loose File.md"), "loose").unwrap();
+        git(&["add", "Tracked File.md"]);
+
+        let moved = git_move(&repo.join("Tracked File.md"), &repo.join("tracked_file.md")).unwrap();
+        assert_eq!(moved.method, Method::Git);
+        let moved = git_move(&repo.join("Loose File.md"), &repo.join("loose_file.md")).unwrap();
+        assert_eq!(moved.method, Method::Rename);
+        assert_eq!(fs::read_to_string(repo.join("loose_file.md")).unwrap(), "loose");
+        // The index follows the tracked file only
+        assert_eq!(git(&["ls-files"]), "tracked_file.md");
+    }
+}
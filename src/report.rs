@@ -0,0 +1,307 @@
+use crate::plan::{Coverage, Plan, Rename};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    Markdown,
+}
+
+/// Render a plan as a self-contained document for review: a summary,
+/// the renames grouped by directory, conflicts, and skipped files.
+pub fn render(plan: &Plan, format: ReportFormat) -> String {
+    let report = Report::new(plan);
+    match format {
+        ReportFormat::Html => report.html(),
+        ReportFormat::Markdown => report.markdown(),
+    }
+}
+
+struct Report<'a> {
+    root: &'a Path,
+    scanned: usize,
+    directories: BTreeMap<String, Vec<&'a Rename>>,
+    conflicts: Vec<(&'a Rename, String)>,
+    skipped: Vec<(String, &'a str)>,
+    coverage: &'a [Coverage],
+    rename_count: usize,
+}
+
+impl<'a> Report<'a> {
+    fn new(plan: &'a Plan) -> Self {
+        let root = plan.root.as_path();
+        let mut directories: BTreeMap<String, Vec<&Rename>> = BTreeMap::new();
+        for rename in &plan.renames {
+            let dir = rename.source.parent().unwrap_or(root);
+            directories.entry(relative(root, dir)).or_default().push(rename);
+        }
+        let skipped = plan.skipped.iter().map(|(file, reason)| (relative(root, file), reason.as_str())).collect();
+
+        Report {
+            root,
+            scanned: plan.files.len(),
+            directories,
+            conflicts: plan.conflicts(),
+            skipped,
+            coverage: &plan.coverage,
+            rename_count: plan.renames.len(),
+        }
+    }
+
+    fn summary(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Root", self.root.display().to_string()),
+            ("Generated", chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z").to_string()),
+            ("namefmt", env!("CARGO_PKG_VERSION").to_string()),
+            ("Files scanned", self.scanned.to_string()),
+            ("Renames", self.rename_count.to_string()),
+            ("Conflicts", self.conflicts.len().to_string()),
+            ("Skipped", self.skipped.len().to_string()),
+        ]
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(out, "<title>namefmt report: {}</title>", escape_html(&self.root.display().to_string()));
+        out.push_str(STYLE);
+        out.push_str("</head>\n<body>\n<h1>namefmt rename report</h1>\n<table class=\"summary\">\n");
+        for (label, value) in self.summary() {
+            let _ = writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", label, escape_html(&value));
+        }
+        out.push_str("</table>\n");
+
+        out.push_str("<h2>Renames</h2>\n");
+        if self.directories.is_empty() {
+            out.push_str("<p>No files need renaming.</p>\n");
+        }
+        for (dir, renames) in &self.directories {
+            let _ = writeln!(out, "<h3>{}</h3>", escape_html(dir));
+            out.push_str("<table>\n<tr><th>Original</th><th>New name</th><th>Rule</th></tr>\n");
+            for rename in renames {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(&file_name(&rename.source)),
+                    escape_html(&relative(rename.source.parent().unwrap_or(self.root), &rename.target)),
+                    escape_html(&rename.rule)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("<h2>Conflicts</h2>\n");
+        if self.conflicts.is_empty() {
+            out.push_str("<p>None.</p>\n");
+        } else {
+            out.push_str("<table class=\"conflicts\">\n<tr><th>File</th><th>Target</th><th>Problem</th></tr>\n");
+            for (rename, problem) in &self.conflicts {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(&relative(self.root, &rename.source)),
+                    escape_html(&relative(self.root, &rename.target)),
+                    escape_html(problem)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("<h2>Skipped</h2>\n");
+        if self.skipped.is_empty() {
+            out.push_str("<p>None.</p>\n");
+        } else {
+            out.push_str("<table>\n<tr><th>File</th><th>Reason</th></tr>\n");
+            for (file, reason) in &self.skipped {
+                let _ = writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", escape_html(file), escape_html(reason));
+            }
+            out.push_str("</table>\n");
+        }
+
+        if !self.coverage.is_empty() {
+            out.push_str("<h2>Rule coverage</h2>\n<table>\n<tr><th>Rule</th><th>Files</th><th>Taken by earlier behaviors</th><th>Problem</th></tr>\n");
+            for rule in self.coverage {
+                let _ = writeln!(
+                    out,
+                    "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    if rule.problem().is_some() { " class=\"dead\"" } else { "" },
+                    escape_html(&rule.rule),
+                    rule.decided,
+                    rule.shadowed,
+                    rule.problem().unwrap_or_default()
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = String::from("# namefmt rename report\n\n| | |\n|---|---|\n");
+        for (label, value) in self.summary() {
+            let _ = writeln!(out, "| {} | {} |", label, escape_md(&value));
+        }
+
+        out.push_str("\n## Renames\n\n");
+        if self.directories.is_empty() {
+            out.push_str("No files need renaming.\n\n");
+        }
+        for (dir, renames) in &self.directories {
+            let _ = writeln!(out, "### {}\n", escape_md(dir));
+            out.push_str("| Original | New name | Rule |\n|---|---|---|\n");
+            for rename in renames {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    escape_md(&file_name(&rename.source)),
+                    escape_md(&relative(rename.source.parent().unwrap_or(self.root), &rename.target)),
+                    escape_md(&rename.rule)
+                );
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Conflicts\n\n");
+        if self.conflicts.is_empty() {
+            out.push_str("None.\n\n");
+        } else {
+            out.push_str("| File | Target | Problem |\n|---|---|---|\n");
+            for (rename, problem) in &self.conflicts {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    escape_md(&relative(self.root, &rename.source)),
+                    escape_md(&relative(self.root, &rename.target)),
+                    escape_md(problem)
+                );
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Skipped\n\n");
+        if self.skipped.is_empty() {
+            out.push_str("None.\n");
+        } else {
+            out.push_str("| File | Reason |\n|---|---|\n");
+            for (file, reason) in &self.skipped {
+                let _ = writeln!(out, "| {} | {} |", escape_md(file), escape_md(reason));
+            }
+        }
+
+        if !self.coverage.is_empty() {
+            out.push_str("\n## Rule coverage\n\n| Rule | Files | Taken by earlier behaviors | Problem |\n|---|---|---|---|\n");
+            for rule in self.coverage {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} |",
+                    escape_md(&rule.rule),
+                    rule.decided,
+                    rule.shadowed,
+                    rule.problem().unwrap_or_default()
+                );
+            }
+        }
+        out
+    }
+}
+
+const STYLE: &str = "<style>
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; font-family: ui-monospace, monospace; }
+th { background: #f0f0f0; font-family: system-ui, sans-serif; }
+table.summary th { width: 10em; }
+table.conflicts td { background: #fff0f0; }
+tr.dead td { background: #fff8e0; }
+h3 { font-family: ui-monospace, monospace; font-size: 1em; }
+</style>
+";
+
+fn relative(root: &Path, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(rel) if rel.as_os_str().is_empty() => String::from("."),
+        Ok(rel) => rel.display().to_string(),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Keep names from breaking out of a table cell or turning into markup.
+fn escape_md(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '|' | '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            '\n' => out.push(' '),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn rename(root: &Path, source: &str, target: &str, rule: &str) -> Rename {
+        Rename { source: root.join(source), target: root.join(target), rule: rule.to_string() }
+    }
+
+    fn plan(root: &Path) -> Plan {
+        Plan {
+            root: root.to_path_buf(),
+            files: vec![root.join("a"), root.join("b"), root.join("c"), root.join("d")],
+            renames: vec![
+                rename(root, "docs/My Notes.md", "docs/my_notes.md", "*.md (snake_case)"),
+                rename(root, "docs/<draft>.md", "docs/my_notes.md", "*.md (snake_case)"),
+                rename(root, "Photo|1.JPG", "photo_1.jpg", "*.JPG (snake_case)"),
+            ],
+            skipped: vec![(root.join("README"), String::from("protected name"))],
+            coverage: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reports_group_renames_by_directory_and_escape_names() {
+        let root = PathBuf::from("/nonexistent/namefmt-report");
+        let markdown = render(&plan(&root), ReportFormat::Markdown);
+        assert!(markdown.contains("| Files scanned | 4 |\n| Renames | 3 |\n| Conflicts | 2 |\n| Skipped | 1 |\n"), "{}", markdown);
+        assert!(markdown.contains("### .\n\n| Original | New name | Rule |\n|---|---|---|\n| Photo\\|1.JPG | photo\\_1.jpg |"), "{}", markdown);
+        assert!(markdown.contains("### docs\n\n| Original | New name | Rule |\n|---|---|---|\n| My Notes.md | my\\_notes.md |"), "{}", markdown);
+        assert!(markdown.contains("| docs/\\<draft\\>.md | docs/my\\_notes.md | several files would get this name |"), "{}", markdown);
+        assert!(markdown.contains("| README | protected name |"), "{}", markdown);
+        assert!(!markdown.contains("Rule coverage"), "{}", markdown);
+
+        let html = render(&plan(&root), ReportFormat::Html);
+        assert!(html.contains("<tr><td>&lt;draft&gt;.md</td><td>my_notes.md</td><td>*.md (snake_case)</td></tr>"), "{}", html);
+        assert!(html.contains("<table class=\"conflicts\">"), "{}", html);
+        assert!(!html.contains("<draft>"), "{}", html);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::journal::{Entry, Journal, Op};
+use crate::hash::sha256_file;
+use crate::rename::{self, Method, Moved};
+use crate::{info, note, warn};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+enum Recovery {
+    /// The rename finished but was never marked complete
+    Done(Moved),
+    /// Nothing happened yet; perform the rename again
+    Redo,
+    Ambiguous(&'static str),
+}
+
+/// Work out from the filesystem what became of a rename whose intent was
+/// journaled but whose completion never was.
+fn recover(source: &Path, target: &Path, dry_run: bool) -> Recovery {
+    match (source.exists(), target.exists()) {
+        (false, true) => Recovery::Done(Moved { method: Method::Rename, source_sha256: None, target_sha256: None }),
+        (true, false) => Recovery::Redo,
+        (false, false) => Recovery::Ambiguous("neither the original nor the new name exists"),
+        (true, true) => {
+            // A copy fallback that finished copying but never deleted the source
+            match (sha256_file(source), sha256_file(target)) {
+                (Ok(a), Ok(b)) if a == b && dry_run => {
+                    Recovery::Done(Moved { method: Method::Copy, source_sha256: Some(a), target_sha256: Some(b) })
+                }
+                (Ok(a), Ok(b)) if a == b => match fs::remove_file(source) {
+                    Ok(()) => Recovery::Done(Moved { method: Method::Copy, source_sha256: Some(a), target_sha256: Some(b) }),
+                    Err(_) => Recovery::Ambiguous("copy completed but the original could not be removed"),
+                },
+                _ => Recovery::Ambiguous("both names exist with different content"),
+            }
+        }
+    }
+}
+
+/// Find the newest run that checkpointed a plan but never completed.
+fn interrupted_run<'a>(entries: &'a [Entry], run_id: Option<&str>) -> Option<&'a Entry> {
+    let completed: HashSet<&str> = entries
+        .iter()
+        .filter(|e| e.op == Op::Complete)
+        .filter_map(|e| e.run.as_deref())
+        .collect();
+    entries
+        .iter()
+        .rev()
+        .filter(|e| e.op == Op::Plan)
+        .filter(|e| run_id.is_none_or(|id| e.run.as_deref() == Some(id)))
+        .find(|e| e.run.as_deref().is_some_and(|id| !completed.contains(id)))
+}
+
+/// Finish the renames an interrupted `--inplace` run never got to, using
+/// the plan it checkpointed in the journal instead of re-planning the tree.
+pub fn resume(journal: &Journal, run_id: Option<&str>, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = journal.entries()?;
+    let Some(checkpoint) = interrupted_run(&entries, run_id) else {
+        match run_id {
+            Some(id) => return Err(format!("Run {} has no unfinished plan in the journal", id).into()),
+            None => {
+                info!("No interrupted runs to resume");
+                return Ok(());
+            }
+        }
+    };
+    let id = checkpoint.run.as_deref().unwrap_or_default();
+
+    let done: HashSet<(&PathBuf, &PathBuf)> = entries
+        .iter()
+        .filter(|e| e.op == Op::Rename && e.run.as_deref() == Some(id))
+        .map(|e| (&e.source, &e.target))
+        .collect();
+    let remaining: Vec<&(PathBuf, PathBuf)> = checkpoint
+        .renames
+        .iter()
+        .filter(|(source, target)| !done.contains(&(source, target)))
+        .collect();
+
+    info!("Resuming run {}: {} of {} renames remaining", id, remaining.len(), checkpoint.renames.len());
+
+    // Intents without a matching completion: the rename may or may not
+    // have happened before the interruption
+    let completed_intents: HashSet<&str> = entries
+        .iter()
+        .filter(|e| e.run.as_deref() == Some(id))
+        .filter_map(|e| e.intent.as_deref())
+        .collect();
+    let dangling: HashMap<(&PathBuf, &PathBuf), &str> = entries
+        .iter()
+        .filter(|e| e.op == Op::Intent && e.run.as_deref() == Some(id))
+        .filter(|e| !completed_intents.contains(e.id.as_str()))
+        .map(|e| ((&e.source, &e.target), e.id.as_str()))
+        .collect();
+
+    // A run that renamed through git mv goes on doing so
+    let git = entries.iter().any(|e| e.op == Op::Rename && e.run.as_deref() == Some(id) && e.method == Method::Git.as_str());
+
+    let root = checkpoint.root.clone().unwrap_or_default();
+    let run = journal.resume_run(id, &root);
+    let mut skipped = 0;
+    for (source, target) in remaining {
+        let intent = dangling.get(&(source, target)).copied();
+        if let Some(intent) = intent {
+            match recover(source, target, dry_run) {
+                Recovery::Done(moved) => {
+                    info!("Recovered: {} -> {} (completed before the interruption)", source.display(), target.display());
+                    if !dry_run {
+                        run.record(source, target, &moved, Some(intent))?;
+                    }
+                    continue;
+                }
+                Recovery::Redo => {}
+                Recovery::Ambiguous(reason) => {
+                    note!("Skipping: {} -> {}: {}; resolve by hand", source.display(), target.display(), reason);
+                    skipped += 1;
+                    continue;
+                }
+            }
+        }
+
+        if !source.exists() {
+            note!("Skipping: {} no longer exists", source.display());
+            skipped += 1;
+            continue;
+        }
+        if target.exists() {
+            note!("Skipping: {} already exists", target.display());
+            skipped += 1;
+            continue;
+        }
+
+        if dry_run {
+            info!("Would rename: {} -> {}", source.display(), target.display());
+            continue;
+        }
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let intent = run.intend(source, target)?;
+        let moved = if git { rename::git_move(source, target)? } else { rename::move_file(source, target)? };
+        info!("Renamed: {} -> {}", source.display(), target.display());
+        if let Err(e) = run.record(source, target, &moved, Some(&intent)) {
+            warn!("Failed to write journal entry: {}", e);
+        }
+    }
+
+    if !dry_run {
+        run.complete()?;
+        info!("Run {} complete ({} skipped)", id, skipped);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dangling_intents_are_recovered_from_the_disk() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let journal = Journal::new(dir.join("journal.jsonl"));
+        let pairs: Vec<(PathBuf, PathBuf)> = ["moved", "pending", "copied", "untouched"]
+            .iter()
+            .map(|name| (dir.join(format!("{} file.txt", name)), dir.join(format!("{}_file.txt", name))))
+            .collect();
+        for (source, _) in &pairs {
+            fs::write(source, "content").unwrap();
+        }
+
+        // Interrupted: the first rename went through, the second never
+        // started and the third was a copy that didn't get to delete its
+        // original; none of them was recorded as done
+        let run = journal.start_run(dir);
+        run.begin(&pairs).unwrap();
+        for (source, target) in &pairs[..3] {
+            run.intend(source, target).unwrap();
+        }
+        fs::rename(&pairs[0].0, &pairs[0].1).unwrap();
+        fs::copy(&pairs[2].0, &pairs[2].1).unwrap();
+        let id = run.id.clone();
+
+        resume(&journal, None, false).unwrap();
+        for (source, target) in &pairs {
+            assert!(!source.exists(), "{} is still there", source.display());
+            assert_eq!(fs::read_to_string(target).unwrap(), "content");
+        }
+
+        let entries = journal.entries().unwrap();
+        let renamed: Vec<&Path> = entries.iter()
+            .filter(|e| e.op == Op::Rename && e.run.as_deref() == Some(id.as_str()))
+            .map(|e| e.source.as_path())
+            .collect();
+        assert_eq!(renamed.len(), 4);
+        for (source, _) in &pairs {
+            assert!(renamed.contains(&source.as_path()));
+        }
+        // Renames found done complete the intents written before the
+        // interruption; the redone one was journaled afresh
+        let intent = |source: &Path| entries.iter()
+            .find(|e| e.op == Op::Intent && e.source == source)
+            .map(|e| e.id.as_str());
+        let completes = |source: &Path| entries.iter()
+            .find(|e| e.op == Op::Rename && e.source == source)
+            .and_then(|e| e.intent.as_deref());
+        assert_eq!(completes(&pairs[0].0), intent(&pairs[0].0));
+        assert_eq!(completes(&pairs[2].0), intent(&pairs[2].0));
+        assert!(completes(&pairs[1].0).is_some_and(|id| Some(id) != intent(&pairs[1].0)));
+        assert_eq!(entries.iter().filter(|e| e.op == Op::Complete).count(), 1);
+        assert!(interrupted_run(&entries, None).is_none());
+    }
+
+    #[test]
+    fn dangling_intents_with_both_names_differing_are_left_alone() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        let journal = Journal::new(dir.join("journal.jsonl"));
+        let (source, target) = (dir.join("a b.txt"), dir.join("a_b.txt"));
+        fs::write(&source, "old").unwrap();
+        fs::write(&target, "new").unwrap();
+
+        let run = journal.start_run(dir);
+        run.begin(&[(source.clone(), target.clone())]).unwrap();
+        run.intend(&source, &target).unwrap();
+
+        resume(&journal, None, false).unwrap();
+        assert_eq!(fs::read_to_string(&source).unwrap(), "old");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        let entries = journal.entries().unwrap();
+        assert!(!entries.iter().any(|e| e.op == Op::Rename));
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::Path;
+
+/// Which files a run looks at, from --ext, --min-size/--max-size and
+/// --newer-than/--older-than. Files outside them are left alone as if
+/// excluded; directories are not filtered.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filters {
+    /// Extensions without the dot, lowercased; any when empty
+    pub extensions: Vec<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Modified after this point in time
+    pub newer_than: Option<DateTime<Utc>>,
+    /// Modified before this point in time
+    pub older_than: Option<DateTime<Utc>>,
+}
+
+impl Filters {
+    pub fn is_empty(&self) -> bool {
+        *self == Filters::default()
+    }
+
+    /// Whether the file at `path` passes every filter.
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        if !self.extensions.is_empty() {
+            let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+            if !extension.is_some_and(|extension| self.extensions.contains(&extension)) {
+                return false;
+            }
+        }
+        if self.min_size.is_none() && self.max_size.is_none() && self.newer_than.is_none() && self.older_than.is_none() {
+            return true;
+        }
+        let Ok(metadata) = fs::metadata(path) else {
+            return false;
+        };
+        let size = metadata.len();
+        let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+        self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self.newer_than.is_none_or(|after| modified.is_some_and(|m| m > after))
+            && self.older_than.is_none_or(|before| modified.is_some_and(|m| m < before))
+    }
+}
+
+/// Parse a size given on the command line: bytes, or a number with a
+/// K, M, G or T suffix (`500k`, `1.5M`, `2GiB`), counted in 1024s like
+/// `du` and `find`.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let text = input.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let number: f64 = text[..split].parse()
+        .map_err(|_| format!("could not understand size '{}' (try \"500k\", \"10M\" or \"1.5G\")", input))?;
+    let unit = text[split..].trim().to_lowercase();
+    let power = match unit.trim_end_matches("ib").trim_end_matches('b') {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return Err(format!("unknown size unit '{}' in '{}' (use K, M, G or T)", &text[split..], input)),
+    };
+    Ok((number * 1024f64.powi(power)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_take_binary_suffixes() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("10k"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5M"), Ok(3 * 512 * 1024));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("3 MB"), Ok(3 << 20));
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("M").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::name::split_extensions;
+use crate::NamingStyle;
+use serde::{Deserialize, Serialize};
+
+/// Tidying the separators a styled name is left with: `My  Report .txt`
+/// becomes `My_Report.txt` rather than `My__Report_.txt`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SeparatorRules {
+    /// Write a run of the same separator (`__`, `--`, `..`, two spaces) once
+    pub collapse: bool,
+    /// Take separators off the start and end of the name, before the
+    /// extension; a dotfile keeps its leading `.`
+    pub trim: bool,
+    /// Styles whose names keep their separators as written, e.g. ["dot.case"]
+    pub except: Vec<NamingStyle>,
+}
+
+impl Default for SeparatorRules {
+    fn default() -> Self {
+        SeparatorRules { collapse: true, trim: true, except: Vec::new() }
+    }
+}
+
+impl SeparatorRules {
+    /// `name`, styled in `style` if a style applied, with its separators
+    /// tidied. Python's `__init__` and the like are left as they are.
+    pub fn apply(&self, name: &str, style: Option<NamingStyle>) -> String {
+        if !(self.collapse || self.trim) || style.is_some_and(|style| self.except.contains(&style)) {
+            return name.to_string();
+        }
+        // Trimming can make an extension of what was part of the name
+        // (`notes.v2_`), so tidy until nothing changes
+        let mut name = name.to_string();
+        while let Some(tidied) = self.tidy(&name).filter(|tidied| *tidied != name) {
+            name = tidied;
+        }
+        name
+    }
+
+    fn tidy(&self, name: &str) -> Option<String> {
+        let (stem, extension) = split_extensions(name);
+        if stem.len() > 4 && stem.starts_with("__") && stem.ends_with("__") {
+            return None;
+        }
+        let mut tidied = String::new();
+        for c in stem.chars() {
+            if !(self.collapse && is_separator(c) && tidied.ends_with(c)) {
+                tidied.push(c);
+            }
+        }
+        if self.trim {
+            tidied = tidied.trim_start_matches([' ', '_', '-']).trim_end_matches(is_separator).to_string();
+        }
+        // A name of nothing but separators is kept rather than emptied
+        if tidied.is_empty() || tidied == "." {
+            return None;
+        }
+        Some(tidied + extension)
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, ' ' | '_' | '-' | '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_are_collapsed_and_edges_trimmed() {
+        let rules = SeparatorRules::default();
+        assert_eq!(rules.apply("My__Report_.txt", None), "My_Report.txt");
+        assert_eq!(rules.apply("-draft--notes..v2-.md", Some(NamingStyle::KebabCase)), "draft-notes.v2.md");
+        assert_eq!(rules.apply("Artist_-_Title.mp3", None), "Artist_-_Title.mp3");
+        assert_eq!(rules.apply("__init__.py", None), "__init__.py");
+        assert_eq!(rules.apply(".bashrc_", None), ".bashrc");
+        assert_eq!(rules.apply("___", None), "___");
+        let rules = SeparatorRules { trim: false, except: vec![NamingStyle::DotCase], ..SeparatorRules::default() };
+        assert_eq!(rules.apply("a..b", Some(NamingStyle::DotCase)), "a..b");
+        assert_eq!(rules.apply("_a__b_", None), "_a_b_");
+    }
+}
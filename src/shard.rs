@@ -0,0 +1,99 @@
+use crate::hash::sha256_hex;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::str::FromStr;
+
+/// One slice of a tree split between several workers, written `K/N`.
+///
+/// Files are assigned by their directory, so everything a preset or pairing
+/// rule looks at together (a scanned batch, a RAW+JPEG pair, a video and its
+/// subtitles) is always handled by the same worker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Shard, String> {
+        let (index, count) = s.split_once('/').ok_or_else(|| format!("invalid shard '{}' (expected K/N, e.g. 2/8)", s))?;
+        let (Ok(index), Ok(count)) = (index.trim().parse::<u32>(), count.trim().parse::<u32>()) else {
+            return Err(format!("invalid shard '{}' (expected K/N, e.g. 2/8)", s));
+        };
+        if count == 0 || index == 0 || index > count {
+            return Err(format!("invalid shard '{}' (K must be between 1 and N)", s));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl Shard {
+    /// True if `file` under `root` belongs to this shard.
+    pub fn contains(&self, root: &Path, file: &Path) -> bool {
+        let dir = file.parent().unwrap_or(Path::new(""));
+        let relative = dir.strip_prefix(root).unwrap_or(dir);
+        let digest = sha256_hex(relative.to_string_lossy().as_bytes());
+        let bucket = u64::from_str_radix(&digest[..16], 16).unwrap_or(0) % u64::from(self.count);
+        bucket == u64::from(self.index - 1)
+    }
+
+    /// Claim this shard of `root` for the lifetime of the returned file, so
+    /// two workers can't be started on the same slice by mistake. The lock
+    /// is released by the OS when the process exits, however it exits.
+    pub fn claim(&self, lock_dir: &Path, root: &Path) -> Result<File, Box<dyn std::error::Error>> {
+        let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+        let key = sha256_hex(root.to_string_lossy().as_bytes());
+        fs::create_dir_all(lock_dir)?;
+        let path = lock_dir.join(format!("{}-{}of{}.lock", &key[..16], self.index, self.count));
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(file),
+            Err(fs::TryLockError::WouldBlock) => {
+                Err(format!("shard {} of {} is already being processed by another namefmt", self, root.display()).into())
+            }
+            Err(fs::TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_directory_belongs_to_exactly_one_shard() {
+        let shards: Vec<Shard> = (1..=4).map(|k| format!("{}/4", k).parse().unwrap()).collect();
+        let root = Path::new("/photos");
+        for dir in ["", "2023", "2024/trip", "2024/trip/raw", "misc"] {
+            let file = root.join(dir).join("IMG_1.CR3");
+            let owners: Vec<&Shard> = shards.iter().filter(|shard| shard.contains(root, &file)).collect();
+            assert_eq!(owners.len(), 1, "{}", dir);
+            assert!(owners[0].contains(root, &root.join(dir).join("IMG_1.JPG")));
+        }
+        assert_eq!("0/4".parse::<Shard>().unwrap_err(), "invalid shard '0/4' (K must be between 1 and N)");
+        assert_eq!("2of4".parse::<Shard>().unwrap_err(), "invalid shard '2of4' (expected K/N, e.g. 2/8)");
+    }
+
+    #[test]
+    fn a_shard_is_claimed_by_one_worker_at_a_time() {
+        let scratch = tempfile::tempdir().unwrap();
+        let (locks, root) = (scratch.path().join("locks"), scratch.path().join("tree"));
+        let shard = Shard { index: 2, count: 3 };
+        let held = shard.claim(&locks, &root).unwrap();
+        let error = shard.claim(&locks, &root).unwrap_err().to_string();
+        assert_eq!(error, format!("shard 2/3 of {} is already being processed by another namefmt", root.display()));
+        // Other shards of the tree are free
+        Shard { index: 1, count: 3 }.claim(&locks, &root).unwrap();
+        drop(held);
+        shard.claim(&locks, &root).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDateTime;
+use std::fmt;
+use std::fmt::Write as _;
+
+/// Dates render like this unless the token gives a format.
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A name template such as `{show} - S{season:02}E{episode:02}`.
+///
+/// Tokens are `{name}` or `{name:spec}` where the spec is a minimum width,
+/// zero-padded when it starts with `0`, the number of characters kept of
+/// a digest (`{hash:12}`), or a strftime format for dates
+/// (`{date:%Y%m%d}`). `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Token { name: String, width: usize, zero_pad: bool, format: Option<String> },
+}
+
+/// A value substituted for a token.
+pub enum Field {
+    Text(String),
+    Number(u64),
+    /// A number zero-padded to the width given, unless the token gives one
+    Padded(u64, usize),
+    /// Hex digits of which the token's width keeps the first ones
+    Digest(String),
+    Date(NaiveDateTime),
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => token.push(c),
+                            None => return Err(format!("unclosed '{{' in template \"{}\"", source)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_token(&token).map_err(|e| format!("{} in template \"{}\"", e, source))?);
+                }
+                '}' => return Err(format!("unmatched '}}' in template \"{}\"", source)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Names of the tokens used, in order of appearance.
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|p| match p {
+            Part::Token { name, .. } => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Fill in the template. Tokens `lookup` doesn't know render as empty,
+    /// and separators left dangling at either end are trimmed.
+    pub fn render(&self, lookup: impl Fn(&str) -> Option<Field>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Token { name, width, zero_pad, format } => {
+                    let value = match lookup(name) {
+                        Some(Field::Padded(n, padding)) if *width == 0 => format!("{:0width$}", n, width = padding),
+                        Some(Field::Number(n) | Field::Padded(n, _)) if *zero_pad => format!("{:0width$}", n, width = width),
+                        Some(Field::Number(n) | Field::Padded(n, _)) => format!("{:width$}", n, width = width),
+                        Some(Field::Digest(hex)) if *width > 0 => hex.chars().take(*width).collect(),
+                        Some(Field::Digest(hex)) => hex,
+                        Some(Field::Text(text)) => format!("{:width$}", text, width = width),
+                        Some(Field::Date(date)) => {
+                            let mut text = String::new();
+                            let _ = write!(text, "{}", date.format(format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT)));
+                            text
+                        }
+                        None => String::new(),
+                    };
+                    out.push_str(&value);
+                }
+            }
+        }
+        out.trim_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '_' | '.')).to_string()
+    }
+}
+
+fn parse_token(token: &str) -> Result<Part, String> {
+    let (name, spec) = token.split_once(':').unwrap_or((token, ""));
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return Err(format!("invalid token '{{{}}}'", token));
+    }
+    if spec.contains('%') {
+        if StrftimeItems::new(spec).any(|item| matches!(item, Item::Error)) {
+            return Err(format!("invalid date format '{}' for token '{}'", spec, name));
+        }
+        return Ok(Part::Token { name: name.to_string(), width: 0, zero_pad: false, format: Some(spec.to_string()) });
+    }
+    let width = if spec.is_empty() {
+        0
+    } else {
+        spec.parse().map_err(|_| format!("invalid width '{}' for token '{}'", spec, name))?
+    };
+    Ok(Part::Token { name: name.to_string(), width, zero_pad: spec.starts_with('0'), format: None })
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => write!(f, "{}", text.replace('{', "{{").replace('}', "}}"))?,
+                Part::Token { name, format: Some(format), .. } => write!(f, "{{{}:{}}}", name, format)?,
+                Part::Token { name, width: 0, .. } => write!(f, "{{{}}}", name)?,
+                Part::Token { name, width, zero_pad: true, .. } => write!(f, "{{{}:0{}}}", name, width)?,
+                Part::Token { name, width, zero_pad: false, .. } => write!(f, "{{{}:{}}}", name, width)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// Parse a point in time given on the command line: relative forms like
+/// `2 hours ago`, `90m`, `yesterday`, or absolute local dates such as
+/// `2024-01-31` and `2024-01-31 14:30`.
+pub fn parse_point_in_time(input: &str) -> Result<DateTime<Utc>, String> {
+    let text = input.trim().to_lowercase();
+    let now = Utc::now();
+
+    match text.as_str() {
+        "now" => return Ok(now),
+        "today" => return local_midnight(Local::now().date_naive()),
+        "yesterday" => return local_midnight(Local::now().date_naive() - Duration::days(1)),
+        _ => {}
+    }
+
+    if let Some(duration) = parse_duration(text.trim_end_matches("ago").trim()) {
+        return Ok(now - duration);
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(input.trim()) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, format) {
+            return local_to_utc(naive);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return local_midnight(date);
+    }
+
+    Err(format!(
+        "could not understand time '{}' (try \"2 hours ago\", \"30m\", \"yesterday\" or \"2024-01-31 14:30\")",
+        input
+    ))
+}
+
+/// Parse `2 hours`, `1 day`, `45min`, `3d` and similar.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let amount: i64 = text[..split].parse().ok()?;
+    let unit = text[split..].trim();
+
+    let seconds = match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600,
+        "d" | "day" | "days" => 86_400,
+        "w" | "week" | "weeks" => 604_800,
+        _ => return None,
+    };
+    Some(Duration::seconds(amount * seconds))
+}
+
+fn local_midnight(date: NaiveDate) -> Result<DateTime<Utc>, String> {
+    local_to_utc(date.and_hms_opt(0, 0, 0).expect("midnight is always valid"))
+}
+
+fn local_to_utc(naive: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} does not exist in the local timezone", naive))
+}
@@ -0,0 +1,285 @@
+use crate::journal::{Entry, Run};
+use crate::rename::{self, Method, Moved};
+use crate::warn;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many problems the refusal of a batch lists.
+const LISTED_PROBLEMS: usize = 5;
+
+/// Everything that would make a rename of the batch fail once the ones
+/// before it are done: sources that are gone, names that are taken or
+/// claimed twice, directories that can't be written to. Renames are taken
+/// in order, so a name freed earlier in the batch may be reused later;
+/// one that would fail is taken as not happening. Returns the index of
+/// each rename that would fail, with the reason.
+pub fn preflight(renames: &[(PathBuf, PathBuf)]) -> Vec<(usize, String)> {
+    // What the batch has done to a path so far: moved a file there (from
+    // the source given) or away from it
+    let mut batch: HashMap<&Path, Option<&Path>> = HashMap::new();
+    let mut writable: HashMap<PathBuf, bool> = HashMap::new();
+    let mut problems = Vec::new();
+    for (i, (source, target)) in renames.iter().enumerate() {
+        let present = |path: &Path| match batch.get(path) {
+            Some(moved) => moved.is_some(),
+            None => fs::symlink_metadata(path).is_ok(),
+        };
+        let problem = if !present(source) {
+            Some("no longer exists".to_string())
+        } else if let Some(Some(other)) = batch.get(target.as_path()) {
+            Some(format!("{} is also the new name of {}", target.display(), other.display()))
+        } else if present(target) && !same_file(source, target) {
+            Some(format!("{} already exists", target.display()))
+        } else if let Some(dir) = [source.parent(), writable_ancestor(target)].into_iter().flatten()
+            .find(|dir| !*writable.entry(dir.to_path_buf()).or_insert_with(|| can_write(dir)))
+        {
+            Some(format!("no permission to write to {}", dir.display()))
+        } else {
+            movable(source).err()
+        };
+        match problem {
+            Some(problem) => problems.push((i, problem)),
+            None => {
+                batch.insert(source, None);
+                batch.insert(target, Some(source));
+            }
+        }
+    }
+    problems
+}
+
+/// Why the batch of `renames` was refused, for the `problems` `preflight`
+/// found in it.
+pub fn refusal(renames: &[(PathBuf, PathBuf)], problems: &[(usize, String)]) -> String {
+    let examples: String = problems.iter()
+        .take(LISTED_PROBLEMS)
+        .map(|(i, problem)| format!("\n    {}: {}", renames[*i].0.display(), problem))
+        .collect();
+    let more = match problems.len().saturating_sub(LISTED_PROBLEMS) {
+        0 => String::new(),
+        n => format!("\n    ... and {} more", n),
+    };
+    format!("{} of {} rename(s) would fail, nothing was renamed:{}{}", problems.len(), renames.len(), examples, more)
+}
+
+/// The directory `target` will be created in: its parent, or the closest
+/// ancestor that exists when the parent still has to be made.
+fn writable_ancestor(target: &Path) -> Option<&Path> {
+    target.ancestors().skip(1).find(|dir| dir.is_dir())
+}
+
+/// Whether `a` and `b` name the same file, as a case-only rename does on a
+/// case-insensitive filesystem.
+#[cfg(unix)]
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+}
+
+#[cfg(unix)]
+fn can_write(dir: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // Also false on a read-only filesystem
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn can_write(dir: &Path) -> bool {
+    fs::metadata(dir).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+/// In a sticky directory (mode 1000) such as /tmp, only the owner of a file (or of
+/// the directory) may rename it, however writable the directory is.
+#[cfg(unix)]
+fn movable(source: &Path) -> Result<(), String> {
+    use std::os::unix::fs::MetadataExt;
+    let (Some(dir), Ok(file)) = (source.parent(), fs::symlink_metadata(source)) else {
+        return Ok(());
+    };
+    let Ok(dir) = fs::metadata(if dir.as_os_str().is_empty() { Path::new(".") } else { dir }) else {
+        return Ok(());
+    };
+    let user = unsafe { libc::geteuid() };
+    if dir.mode() & 0o1000 != 0 && user != 0 && file.uid() != user && dir.uid() != user {
+        return Err("owned by another user in a sticky directory".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn movable(_source: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// One rename of the batch that went through.
+struct Done {
+    source: PathBuf,
+    target: PathBuf,
+    method: Method,
+    /// Its journal entry, unless writing that failed
+    entry: Option<Entry>,
+}
+
+/// What `Transaction::rollback` managed to put back.
+#[derive(Debug, Default)]
+pub struct Rollback {
+    /// (original, new name) of every rename reverted
+    pub restored: Vec<(PathBuf, PathBuf)>,
+    /// Renames that stayed, with the reason
+    pub stuck: Vec<(PathBuf, PathBuf, String)>,
+}
+
+/// The renames of one --inplace batch, performed through the journal and
+/// remembered so that the batch can be put back as a whole when one of
+/// them fails.
+pub struct Transaction<'a> {
+    run: &'a Run<'a>,
+    git: bool,
+    done: Vec<Done>,
+    /// Directories made for new names, outermost first
+    created: Vec<PathBuf>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(run: &'a Run<'a>, git: bool) -> Self {
+        Transaction { run, git, done: Vec::new(), created: Vec::new() }
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run.id
+    }
+
+    /// Rename `source` to `target`, journaling the intent first and the
+    /// rename after.
+    pub fn rename(&mut self, source: &Path, target: &Path) -> Result<Moved, Box<dyn std::error::Error>> {
+        // An earlier rename of the batch that didn't happen may have left
+        // the name taken; never rename over a file
+        if fs::symlink_metadata(target).is_ok() && !same_file(source, target) {
+            return Err(format!("{} already exists", target.display()).into());
+        }
+        if let Some(dir) = target.parent() {
+            let missing: Vec<&Path> = dir.ancestors().take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists()).collect();
+            fs::create_dir_all(dir)?;
+            self.created.extend(missing.into_iter().rev().map(Path::to_path_buf));
+        }
+        let intent = self.run.intend(source, target)
+            .map_err(|e| format!("Failed to write journal entry, not renaming {}: {}", source.display(), e))?;
+        let moved = if self.git {
+            rename::git_move(source, target)?
+        } else {
+            rename::move_file(source, target)?
+        };
+        let entry = self.run.record(source, target, &moved, Some(&intent))
+            .map_err(|e| warn!("Failed to write journal entry: {}", e))
+            .ok();
+        self.done.push(Done { source: source.to_path_buf(), target: target.to_path_buf(), method: moved.method, entry });
+        Ok(moved)
+    }
+
+    /// Put back every rename made so far, newest first so that chained
+    /// renames unwind, and remove the directories made for them. Each
+    /// one is journaled as an undo, and the run marked complete so that
+    /// `namefmt resume` leaves it alone.
+    pub fn rollback(self) -> Rollback {
+        let mut rollback = Rollback::default();
+        for done in self.done.into_iter().rev() {
+            let intent = self.run.intend(&done.target, &done.source).ok();
+            // Renames made with git mv are staged; so is putting them back
+            let moved = match done.method {
+                Method::Git => rename::git_move(&done.target, &done.source),
+                _ => rename::move_file(&done.target, &done.source),
+            };
+            match moved {
+                Ok(moved) => {
+                    if let Some(entry) = &done.entry {
+                        if let Err(e) = self.run.record_undo(entry, &moved, intent.as_deref()) {
+                            warn!("Failed to write journal entry: {}", e);
+                        }
+                    }
+                    rollback.restored.push((done.source, done.target));
+                }
+                Err(e) => rollback.stuck.push((done.source, done.target, e.to_string())),
+            }
+        }
+        // Only empty ones go; a directory still in use stays
+        for dir in self.created.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+        if let Err(e) = self.run.complete() {
+            warn!("Failed to write journal entry: {}", e);
+        }
+        rollback
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::{Journal, Op};
+
+    #[test]
+    fn preflight_follows_the_batch_in_order() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        for name in ["a", "b", "x", "taken"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let pair = |from: &str, to: &str| (dir.join(from), dir.join(to));
+        // b makes way for a, then c is claimed twice and taken is in the way
+        let renames = [pair("b", "c"), pair("a", "b"), pair("b", "c"), pair("gone", "d"), pair("x", "taken")];
+        let problems = preflight(&renames);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        let indices: Vec<usize> = problems.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, [2, 3, 4]);
+        assert!(problems[0].1.contains("is also the new name of"), "{}", problems[0].1);
+        assert_eq!(problems[1].1, "no longer exists");
+        assert!(problems[2].1.ends_with("taken already exists"), "{}", problems[2].1);
+    }
+
+    #[test]
+    fn failed_batches_are_put_back_and_journaled() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        for name in ["a", "b", "c", "taken"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let journal = Journal::new(dir.join("journal.jsonl"));
+        let run = journal.start_run(dir);
+        let mut transaction = Transaction::new(&run, false);
+        transaction.rename(&dir.join("a"), &dir.join("A")).unwrap();
+        transaction.rename(&dir.join("b"), &dir.join("sorted/B")).unwrap();
+        // The third rename finds its new name taken
+        let error = transaction.rename(&dir.join("c"), &dir.join("taken")).unwrap_err();
+        assert!(error.to_string().ends_with("taken already exists"), "{}", error);
+
+        let rollback = transaction.rollback();
+        assert_eq!(rollback.restored, [(dir.join("b"), dir.join("sorted/B")), (dir.join("a"), dir.join("A"))]);
+        assert!(rollback.stuck.is_empty());
+        for name in ["a", "b", "c", "taken"] {
+            assert_eq!(fs::read_to_string(dir.join(name)).unwrap(), name);
+        }
+        assert!(!dir.join("A").exists());
+        assert!(!dir.join("sorted").exists(), "the directory made for B is removed");
+
+        let entries = journal.entries().unwrap();
+        let renamed: Vec<&Entry> = entries.iter().filter(|e| e.op == Op::Rename).collect();
+        let undone: Vec<&Entry> = entries.iter().filter(|e| e.op == Op::Undo).collect();
+        assert_eq!(renamed.len(), 2);
+        assert_eq!(undone.iter().map(|e| e.undoes.as_deref()).collect::<Vec<_>>(), [Some(renamed[1].id.as_str()), Some(renamed[0].id.as_str())]);
+        assert_eq!(entries.last().unwrap().op, Op::Complete);
+        assert!(journal.verify(None).unwrap().problems.is_empty());
+    }
+}
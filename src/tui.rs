@@ -0,0 +1,328 @@
+use crate::name::{Platform, SanitizedName};
+use crate::plan::{Plan, Rename};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+const HELP: &str = "up/down move  space toggle  a all  e edit  enter apply  q quit";
+
+/// A planned rename in the review list.
+struct Row {
+    source: PathBuf,
+    target: PathBuf,
+    rule: String,
+    selected: bool,
+}
+
+/// A key press, as far as the review cares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+    Char(char),
+    /// Ctrl-C or Ctrl-D: quit, also while editing a name
+    Interrupt,
+}
+
+/// The review list and what the user has done to it so far.
+struct Review {
+    root: PathBuf,
+    rows: Vec<Row>,
+    platform: Platform,
+    cursor: usize,
+    /// Where the list is scrolled to; ratatui keeps the cursor on screen
+    list: ListState,
+    /// The new name being typed for the row under the cursor
+    editing: Option<String>,
+    message: String,
+    /// Reasons by source, for the selected renames that can't be made
+    conflicts: HashMap<PathBuf, String>,
+}
+
+enum Outcome {
+    Apply(Vec<(PathBuf, PathBuf)>),
+    Quit,
+}
+
+impl Review {
+    fn new(plan: &Plan, platform: Platform) -> Review {
+        let rows = plan.renames.iter()
+            .map(|r| Row { source: r.source.clone(), target: r.target.clone(), rule: r.rule.clone(), selected: true })
+            .collect();
+        let mut review = Review {
+            root: plan.root.clone(),
+            rows,
+            platform,
+            cursor: 0,
+            list: ListState::default(),
+            editing: None,
+            message: String::from(HELP),
+            conflicts: HashMap::new(),
+        };
+        review.find_conflicts();
+        review
+    }
+
+    /// Check the selected renames against each other and the disk; renames
+    /// left out keep their files where they are.
+    fn find_conflicts(&mut self) {
+        let selected: Vec<Rename> = self.rows.iter()
+            .filter(|row| row.selected)
+            .map(|row| Rename { source: row.source.clone(), target: row.target.clone(), rule: row.rule.clone() })
+            .collect();
+        let plan = Plan { root: self.root.clone(), files: Vec::new(), renames: selected, skipped: Vec::new(), coverage: Vec::new(), suggestions: Vec::new() };
+        self.conflicts = plan.conflicts().into_iter().map(|(rename, reason)| (rename.source.clone(), reason)).collect();
+    }
+
+    fn handle(&mut self, key: Key, page: usize) -> Option<Outcome> {
+        if self.editing.is_some() {
+            self.edit(key);
+            return None;
+        }
+        let last = self.rows.len().saturating_sub(1);
+        match key {
+            Key::Up | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.cursor = (self.cursor + 1).min(last),
+            Key::PageUp => self.cursor = self.cursor.saturating_sub(page),
+            Key::PageDown => self.cursor = (self.cursor + page).min(last),
+            Key::Home | Key::Char('g') => self.cursor = 0,
+            Key::End | Key::Char('G') => self.cursor = last,
+            Key::Char(' ') => {
+                if let Some(row) = self.rows.get_mut(self.cursor) {
+                    row.selected = !row.selected;
+                    self.find_conflicts();
+                }
+            }
+            Key::Char('a') => {
+                let select = !self.rows.iter().all(|row| row.selected);
+                self.rows.iter_mut().for_each(|row| row.selected = select);
+                self.find_conflicts();
+            }
+            Key::Char('e') => {
+                if let Some(row) = self.rows.get(self.cursor) {
+                    self.editing = Some(file_name(&row.target));
+                }
+            }
+            Key::Enter | Key::Char('y') => {
+                if !self.conflicts.is_empty() {
+                    self.message = format!("{} selected rename(s) conflict; toggle or edit them first", self.conflicts.len());
+                    return None;
+                }
+                let pairs = self.rows.iter()
+                    .filter(|row| row.selected)
+                    .map(|row| (row.source.clone(), row.target.clone()))
+                    .collect();
+                return Some(Outcome::Apply(pairs));
+            }
+            Key::Char('q') | Key::Escape => return Some(Outcome::Quit),
+            _ => {}
+        }
+        None
+    }
+
+    fn edit(&mut self, key: Key) {
+        let Some(name) = self.editing.as_mut() else {
+            return;
+        };
+        match key {
+            Key::Char(c) => name.push(c),
+            Key::Backspace => {
+                name.pop();
+            }
+            Key::Escape => {
+                self.editing = None;
+                self.message = String::from(HELP);
+            }
+            Key::Enter => {
+                let name = self.editing.take().unwrap_or_default();
+                self.message = String::from(HELP);
+                if name.is_empty() {
+                    return;
+                }
+                let sanitized = SanitizedName::new(&name, self.platform);
+                if sanitized.as_str() != name {
+                    self.message = format!("\"{}\" is not a valid name; using \"{}\"", name, sanitized);
+                }
+                if let Some(row) = self.rows.get_mut(self.cursor) {
+                    row.target = row.target.with_file_name(sanitized.as_str());
+                    row.rule = String::from("edited");
+                    row.selected = row.target != row.source;
+                }
+                self.find_conflicts();
+            }
+            _ => {}
+        }
+    }
+
+    /// Draw the header, the rows around the cursor and the status line.
+    fn render(&mut self, frame: &mut Frame) {
+        let [header, list, status] = Layout::vertical([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)])
+            .areas(frame.area());
+
+        let selected = self.rows.iter().filter(|row| row.selected).count();
+        let title = format!(
+            "namefmt: {} of {} rename(s) selected, {} conflict(s) in {}",
+            selected,
+            self.rows.len(),
+            self.conflicts.len(),
+            self.root.display()
+        );
+        frame.render_widget(Paragraph::new(title).bold(), header);
+
+        let items: Vec<ListItem> = self.rows.iter()
+            .map(|row| {
+                let source = row.source.strip_prefix(&self.root).unwrap_or(&row.source);
+                let mut line = format!("[{}] {} -> {}  ({})", if row.selected { "x" } else { " " }, source.display(), file_name(&row.target), row.rule);
+                let conflict = self.conflicts.get(&row.source);
+                if let Some(reason) = conflict {
+                    line.push_str(&format!("  ! {}", reason));
+                }
+                let style = if conflict.is_some() {
+                    Style::new().red()
+                } else if row.selected {
+                    Style::new()
+                } else {
+                    Style::new().dim()
+                };
+                ListItem::new(Line::styled(line, style))
+            })
+            .collect();
+        self.list.select(Some(self.cursor));
+        let rows = List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(rows, list, &mut self.list);
+
+        let text = match &self.editing {
+            Some(name) => format!("New name: {}", name),
+            None => self.message.clone(),
+        };
+        frame.render_widget(Paragraph::new(text), status);
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Show `plan` full-screen for review. Returns the renames to make, with
+/// edited targets, or None if the user quit.
+pub fn run(plan: &Plan, platform: Platform) -> io::Result<Option<Vec<(PathBuf, PathBuf)>>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(io::Error::other("namefmt tui needs a terminal"));
+    }
+    let mut review = Review::new(plan, platform);
+    let mut screen = Screen::enter();
+    loop {
+        screen.terminal.draw(|frame| review.render(frame))?;
+        // Rows between the header and the status line
+        let page = usize::from(screen.terminal.size()?.height.saturating_sub(2).max(1));
+        // A resize is just another event; the next draw fits the new size
+        let Some(key) = read_key(event::read()?) else {
+            continue;
+        };
+        if key == Key::Interrupt {
+            return Ok(None);
+        }
+        match review.handle(key, page) {
+            Some(Outcome::Apply(pairs)) => return Ok(Some(pairs)),
+            Some(Outcome::Quit) => return Ok(None),
+            None => {}
+        }
+    }
+}
+
+/// Raw input on the alternate screen for as long as it lives. The terminal
+/// is restored on drop, also after an error, and by ratatui's panic hook.
+struct Screen {
+    terminal: DefaultTerminal,
+}
+
+impl Screen {
+    fn enter() -> Screen {
+        Screen { terminal: ratatui::init() }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// The key `event` is, as far as the review cares; None for events it
+/// ignores.
+fn read_key(event: Event) -> Option<Key> {
+    let Event::Key(press) = event else {
+        return None;
+    };
+    if press.kind == KeyEventKind::Release {
+        return None;
+    }
+    if press.modifiers.contains(KeyModifiers::CONTROL) {
+        return match press.code {
+            KeyCode::Char('c' | 'd') => Some(Key::Interrupt),
+            _ => None,
+        };
+    }
+    let key = match press.code {
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Escape,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts_must_be_resolved_before_applying() {
+        let root = PathBuf::from("/nonexistent/namefmt-tui");
+        let rename = |source: &str, target: &str| Rename { source: root.join(source), target: root.join(target), rule: String::from("snake_case") };
+        let plan = Plan {
+            root: root.clone(),
+            files: Vec::new(),
+            renames: vec![rename("A B", "a_b"), rename("a-b", "a_b"), rename("C D", "c_d")],
+            skipped: Vec::new(),
+            coverage: Vec::new(),
+            suggestions: Vec::new(),
+        };
+        let mut review = Review::new(&plan, Platform::Posix);
+        assert_eq!(review.conflicts.len(), 2);
+        assert!(review.handle(Key::Enter, 10).is_none());
+        assert_eq!(review.message, "2 selected rename(s) conflict; toggle or edit them first");
+
+        // Rename the second one to "a_b2", then leave the third out
+        for key in [Key::Down, Key::Char('e'), Key::Char('2'), Key::Enter, Key::End, Key::Char(' ')] {
+            assert!(review.handle(key, 10).is_none());
+        }
+        assert!(review.conflicts.is_empty());
+        assert_eq!(review.rows[1].rule, "edited");
+        let Some(Outcome::Apply(pairs)) = review.handle(Key::Enter, 10) else {
+            panic!("expected the review to apply");
+        };
+        assert_eq!(pairs, [(root.join("A B"), root.join("a_b")), (root.join("a-b"), root.join("a_b2"))]);
+        assert!(matches!(review.handle(Key::Char('q'), 10), Some(Outcome::Quit)));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::glob_match;
+use crate::journal::{file_fingerprint, Entry, Journal, Op};
+use crate::rename::{self, Method};
+use crate::transaction::same_file;
+use crate::{info, note, warn};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Rename entries that have not been reverted yet, oldest first.
+fn revertible(entries: &[Entry]) -> Vec<&Entry> {
+    let undone: HashSet<&str> = entries
+        .iter()
+        .filter(|e| e.op == Op::Undo)
+        .filter_map(|e| e.undoes.as_deref())
+        .collect();
+    entries
+        .iter()
+        .filter(|e| e.op == Op::Rename && !undone.contains(e.id.as_str()))
+        .collect()
+}
+
+/// Revert every rename performed at or after `since`, newest first so that
+/// chained renames (a -> b, then b -> c) unwind correctly.
+pub fn undo_since(journal: &Journal, since: DateTime<Utc>, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = journal.entries()?;
+    let selected: Vec<&Entry> = revertible(&entries)
+        .into_iter()
+        .filter(|e| e.time >= since)
+        .collect();
+    revert(journal, &selected, dry_run)
+}
+
+/// Revert the renames of one run, optionally only those whose path relative
+/// to the run root matches the `only` glob.
+pub fn undo_run(journal: &Journal, run_id: &str, only: Option<&str>, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = journal.entries()?;
+    if !entries.iter().any(|e| e.run.as_deref() == Some(run_id)) {
+        return Err(format!("No run with ID {} in the journal", run_id).into());
+    }
+    let selected: Vec<&Entry> = revertible(&entries)
+        .into_iter()
+        .filter(|e| e.run.as_deref() == Some(run_id))
+        .filter(|e| only.is_none_or(|pattern| matches_relative(e, pattern)))
+        .collect();
+    revert(journal, &selected, dry_run)
+}
+
+/// Revert the most recent run that still has renames left to revert, so
+/// repeated calls step back one batch at a time.
+pub fn undo_last(journal: &Journal, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = journal.entries()?;
+    let revertible = revertible(&entries);
+    let Some(run_id) = revertible.iter().rev().find_map(|e| e.run.as_deref()) else {
+        info!("Nothing to undo");
+        return Ok(());
+    };
+    info!("Undoing run {}", run_id);
+    let selected: Vec<&Entry> = revertible.iter().copied().filter(|e| e.run.as_deref() == Some(run_id)).collect();
+    revert(journal, &selected, dry_run)
+}
+
+fn matches_relative(entry: &Entry, pattern: &str) -> bool {
+    let root = entry.root.as_deref().unwrap_or(Path::new("/"));
+    [&entry.source, &entry.target].iter().any(|path| {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        glob_match(pattern, &relative.to_string_lossy())
+    })
+}
+
+fn revert(journal: &Journal, selected: &[&Entry], dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if selected.is_empty() {
+        info!("Nothing to undo");
+        return Ok(());
+    }
+
+    let run = journal.start_run(Path::new("/"));
+
+    let mut reverted = 0;
+    let mut skipped = 0;
+    for entry in selected.iter().rev() {
+        if let Err(reason) = check_revertible(entry) {
+            note!("Skipping: {} -> {}: {}", entry.target.display(), entry.source.display(), reason);
+            skipped += 1;
+            continue;
+        }
+
+        if dry_run {
+            info!("Would restore: {} -> {}", entry.target.display(), entry.source.display());
+        } else {
+            if let Some(dir) = entry.source.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let intent = run.intend(&entry.target, &entry.source)
+                .map_err(|e| format!("Failed to write journal entry, not restoring {}: {}", entry.target.display(), e))?;
+            // Renames made with git mv are staged; so is putting them back
+            let moved = if entry.method == Method::Git.as_str() {
+                rename::git_move(&entry.target, &entry.source)?
+            } else {
+                rename::move_file(&entry.target, &entry.source)?
+            };
+            info!("Restored: {} -> {}", entry.target.display(), entry.source.display());
+            if let Err(e) = run.record_undo(entry, &moved, Some(&intent)) {
+                warn!("Failed to write journal entry: {}", e);
+            }
+        }
+        reverted += 1;
+    }
+
+    let verb = if dry_run { "Would restore" } else { "Restored" };
+    info!("{} {} file(s), skipped {}", verb, reverted, skipped);
+    if run.recorded() > 0 {
+        info!("Run ID: {}", run.id);
+    }
+    Ok(())
+}
+
+/// A rename can only be reverted if its target is still where we left it,
+/// unchanged, and nothing has taken the original name since. After a
+/// case-only rename on a case-insensitive filesystem the original name
+/// finds the renamed file itself, which doesn't count as taken.
+fn check_revertible(entry: &Entry) -> Result<(), String> {
+    if !entry.target.exists() {
+        return Err("renamed file no longer exists".to_string());
+    }
+    if entry.source.exists() && !same_file(&entry.source, &entry.target) {
+        return Err("original name is taken".to_string());
+    }
+    if let (Some(size), Some(mtime)) = (entry.target_size, &entry.target_mtime) {
+        let (current_size, current_mtime) = file_fingerprint(&entry.target).map_err(|e| e.to_string())?;
+        if current_size != size || &current_mtime != mtime {
+            return Err("file was modified after the rename".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// A journal in `dir` recording one run that renamed a.txt, b.txt and
+    /// c.txt to A.txt, B.txt and C.txt. Returns the run ID.
+    fn renamed(dir: &Path, journal: &Journal) -> String {
+        fs::create_dir_all(dir).unwrap();
+        let run = journal.start_run(dir);
+        for name in ["a", "b", "c"] {
+            let source = dir.join(format!("{}.txt", name));
+            let target = dir.join(format!("{}.txt", name.to_uppercase()));
+            fs::write(&source, name).unwrap();
+            let moved = rename::move_file(&source, &target).unwrap();
+            run.record(&source, &target, &moved, None).unwrap();
+        }
+        run.id.clone()
+    }
+
+    /// Change `file`'s modification time, as editing it would.
+    fn touch(file: &Path) {
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        fs::File::options().write(true).open(file).unwrap().set_modified(later).unwrap();
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".txt"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn scratch() -> (tempfile::TempDir, Journal) {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path().join("journal.jsonl"));
+        (dir, journal)
+    }
+
+    #[test]
+    fn undoing_a_run_leaves_modified_files_alone() {
+        let (scratch, journal) = scratch();
+        let dir = scratch.path();
+        let id = renamed(dir, &journal);
+        touch(&dir.join("B.txt"));
+
+        undo_run(&journal, &id, None, false).unwrap();
+        assert_eq!(names(dir), ["B.txt", "a.txt", "c.txt"]);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        // The skipped rename stays revertible; the others are done
+        let entries = journal.entries().unwrap();
+        let left: Vec<&Path> = revertible(&entries).iter().map(|e| e.target.as_path()).collect();
+        assert_eq!(left, [dir.join("B.txt")]);
+    }
+
+    #[test]
+    fn undoing_since_a_time_leaves_modified_files_alone() {
+        let (scratch, journal) = scratch();
+        let dir = scratch.path();
+        let before = Utc::now();
+        renamed(dir, &journal);
+        touch(&dir.join("B.txt"));
+
+        undo_since(&journal, Utc::now() + chrono::Duration::seconds(60), false).unwrap();
+        assert_eq!(names(dir), ["A.txt", "B.txt", "C.txt"]);
+        undo_since(&journal, before, false).unwrap();
+        assert_eq!(names(dir), ["B.txt", "a.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn undoing_a_run_only_for_matching_names() {
+        let (scratch, journal) = scratch();
+        let dir = scratch.path();
+        let run = journal.start_run(dir);
+        for (from, to) in [("a b.jpg", "a_b.jpg"), ("c d.txt", "c_d.txt"), ("e f.jpg", "e_f.jpg"), ("g h.jpeg", "g_h.jpeg")] {
+            fs::write(dir.join(from), from).unwrap();
+            let moved = rename::move_file(&dir.join(from), &dir.join(to)).unwrap();
+            run.record(&dir.join(from), &dir.join(to), &moved, None).unwrap();
+        }
+        let id = run.id.clone();
+
+        undo_run(&journal, &id, Some("*.jpg"), false).unwrap();
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name != "journal.jsonl")
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a b.jpg", "c_d.txt", "e f.jpg", "g_h.jpeg"]);
+
+        undo_run(&journal, &id, Some("*.{txt,jpeg}"), false).unwrap();
+        assert!(dir.join("c d.txt").exists() && dir.join("g h.jpeg").exists());
+    }
+
+    #[test]
+    fn case_only_renames_stay_revertible() {
+        let (scratch, _) = scratch();
+        let dir = scratch.path();
+        let entry = Entry { source: dir.join("a.txt"), target: dir.join("A.txt"), ..Entry::default() };
+        fs::write(&entry.target, "a").unwrap();
+        assert_eq!(check_revertible(&entry), Ok(()));
+        // A case-insensitive filesystem finds the same file under both names
+        fs::hard_link(&entry.target, &entry.source).unwrap();
+        assert_eq!(check_revertible(&entry), Ok(()));
+        fs::remove_file(&entry.source).unwrap();
+        fs::write(&entry.source, "other").unwrap();
+        assert_eq!(check_revertible(&entry), Err("original name is taken".to_string()));
+    }
+
+    #[test]
+    fn undoing_the_last_run_steps_back_one_run_at_a_time() {
+        let (scratch, journal) = scratch();
+        let (first, second) = (scratch.path().join("first"), scratch.path().join("second"));
+        renamed(&first, &journal);
+        renamed(&second, &journal);
+
+        undo_last(&journal, true).unwrap();
+        assert_eq!(names(&second), ["A.txt", "B.txt", "C.txt"]);
+        undo_last(&journal, false).unwrap();
+        assert_eq!(names(&first), ["A.txt", "B.txt", "C.txt"]);
+        assert_eq!(names(&second), ["a.txt", "b.txt", "c.txt"]);
+        undo_last(&journal, false).unwrap();
+        assert_eq!(names(&first), ["a.txt", "b.txt", "c.txt"]);
+        let entries = journal.entries().unwrap();
+        assert!(revertible(&entries).is_empty());
+        undo_last(&journal, false).unwrap();
+    }
+}
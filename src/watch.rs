@@ -0,0 +1,131 @@
+use crate::warn;
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Changes below a directory, reported as the paths they touched: files
+/// created, written to, or moved in, including in directories created
+/// later. The platform's own notifications are used (inotify, FSEvents,
+/// ReadDirectoryChangesW) through the `notify` crate.
+pub struct Watcher {
+    root: PathBuf,
+    /// Sends to `events` for as long as it is kept
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl Watcher {
+    pub fn new(root: &Path) -> io::Result<Watcher> {
+        let (sender, events) = mpsc::channel();
+        let failed = |e: notify::Error| io::Error::other(format!("Failed to watch {}: {}", root.display(), e));
+        let mut watcher = notify::recommended_watcher(sender).map_err(failed)?;
+        watcher.watch(root, RecursiveMode::Recursive).map_err(failed)?;
+        Ok(Watcher { root: root.to_path_buf(), _watcher: watcher, events })
+    }
+
+    /// The paths changed since the last call, waiting up to `timeout` (or
+    /// for as long as it takes, without one) for the first change.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Vec<PathBuf>> {
+        let stopped = || io::Error::other("The file watcher stopped");
+        let first = match timeout {
+            None => self.events.recv().map_err(|_| stopped())?,
+            Some(timeout) => match self.events.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => return Ok(Vec::new()),
+                Err(RecvTimeoutError::Disconnected) => return Err(stopped()),
+            },
+        };
+        let mut changed = Vec::new();
+        for event in std::iter::once(first).chain(self.events.try_iter()) {
+            let event = event.map_err(|e| io::Error::other(format!("Watching {}: {}", self.root.display(), e)))?;
+            if event.need_rescan() {
+                // The queue overflowed and changes were dropped; the root
+                // stands for all of them, as every run covers it whole
+                warn!("Missed some changes below {}; looking at all of it again", self.root.display());
+                changed.push(self.root.clone());
+                continue;
+            }
+            match event.kind {
+                EventKind::Create(_)
+                | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any)
+                | EventKind::Access(AccessKind::Close(AccessMode::Write)) => changed.extend(event.paths),
+                // The name a file was moved away from is gone
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {}
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => changed.extend(event.paths.into_iter().last()),
+                EventKind::Modify(ModifyKind::Name(_)) => changed.extend(event.paths),
+                _ => {}
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Wait for a change, then for `debounce` to pass without another, so a
+    /// file still being copied in or a burst of files dropped at once comes
+    /// back as one batch. `pending` hears the size of the batch as it grows.
+    /// With `wake`, an empty batch comes back once that long has passed
+    /// without a change.
+    pub fn next_batch(&mut self, debounce: Duration, wake: Option<Duration>, pending: impl Fn(usize)) -> io::Result<Vec<PathBuf>> {
+        let deadline = wake.map(|wake| Instant::now() + wake);
+        let mut changed = Vec::new();
+        while changed.is_empty() {
+            let left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if left.is_some_and(|left| left.is_zero()) {
+                return Ok(changed);
+            }
+            changed = self.wait(left)?;
+        }
+        let mut quiet_since = Instant::now();
+        loop {
+            pending(changed.len());
+            let left = debounce.saturating_sub(quiet_since.elapsed());
+            if left.is_zero() {
+                break;
+            }
+            let more = self.wait(Some(left))?;
+            if !more.is_empty() {
+                changed.extend(more);
+                quiet_since = Instant::now();
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn files_created_in_new_directories_are_seen() {
+        let scratch = tempfile::tempdir().unwrap();
+        // Events come with the path as watched, so compare canonical paths
+        let root = scratch.path().canonicalize().unwrap();
+        let mut watcher = Watcher::new(&root).unwrap();
+        assert!(watcher.wait(Some(Duration::from_millis(50))).unwrap().is_empty());
+
+        fs::create_dir(root.join("new")).unwrap();
+        fs::write(root.join("new").join("Some File.txt"), "text").unwrap();
+        let mut seen = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !seen.contains(&root.join("new").join("Some File.txt")) && Instant::now() < deadline {
+            seen.extend(watcher.wait(Some(Duration::from_millis(200))).unwrap());
+        }
+        assert!(seen.contains(&root.join("new").join("Some File.txt")), "saw {:?}", seen);
+    }
+
+    #[test]
+    fn quiet_directories_wake_on_time() {
+        let scratch = tempfile::tempdir().unwrap();
+        let mut watcher = Watcher::new(scratch.path()).unwrap();
+        let started = Instant::now();
+        let batch = watcher.next_batch(Duration::from_millis(10), Some(Duration::from_millis(200)), |_| {}).unwrap();
+        assert!(batch.is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+}
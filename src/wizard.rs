@@ -0,0 +1,228 @@
+use crate::presets::PACKS;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Files whose presence suggests a convention pack.
+const MARKERS: &[(&str, &str)] = &[
+    ("Cargo.toml", "rust"),
+    ("pyproject.toml", "python"),
+    ("setup.py", "python"),
+    ("requirements.txt", "python"),
+    ("package.json", "node"),
+    ("go.mod", "go"),
+    ("index.html", "web-assets"),
+];
+
+const STYLES: &[(Option<&str>, &str)] = &[
+    (None, "keep words as they are, replace spaces with underscores"),
+    (Some("snake_case"), "snake_case"),
+    (Some("kebab-case"), "kebab-case"),
+    (Some("camelCase"), "camelCase"),
+    (Some("PascalCase"), "PascalCase"),
+    (Some("Title Case"), "Title Case, keeping spaces"),
+    (Some("lowercase"), "lowercase, keeping separators"),
+];
+
+/// Files looked at and the renames some answers would lead to.
+pub type Preview = Result<(usize, Vec<(PathBuf, PathBuf)>), Box<dyn std::error::Error>>;
+
+/// How many proposed renames the preview lists.
+const PREVIEW_LINES: usize = 10;
+
+/// What the user chose.
+#[derive(Debug, Clone, Default)]
+pub struct Answers {
+    pub packs: Vec<String>,
+    /// Style applied to every other file; `None` only replaces spaces
+    pub style: Option<&'static str>,
+    pub timestamp: bool,
+}
+
+impl Answers {
+    /// The configuration file these answers describe.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from("replace_spaces = true\n");
+        if self.timestamp {
+            out.push_str("timestamp = true\n");
+        }
+        if !self.packs.is_empty() {
+            let packs: Vec<String> = self.packs.iter().map(|p| format!("\"{}\"", p)).collect();
+            out.push_str(&format!("presets = [{}]\n", packs.join(", ")));
+        }
+        if let Some(style) = self.style {
+            out.push_str(&format!("\n[[behaviors]]\npattern = \"*\"\nstyle = \"{}\"\n", style));
+        }
+        out.push_str("\n[detection]\nexe_extensions = [\"exe\", \"bin\", \"app\"]\npackage_dirs = [\"package.json\", \"Cargo.toml\", \"pyproject.toml\"]\n");
+        out
+    }
+}
+
+/// Packs suggested by marker files directly in `dir`.
+pub fn detect_packs(dir: &Path) -> Vec<String> {
+    let mut packs: Vec<String> = Vec::new();
+    for (marker, pack) in MARKERS {
+        if dir.join(marker).exists() && !packs.iter().any(|p| p == pack) {
+            packs.push(pack.to_string());
+        }
+    }
+    packs
+}
+
+/// Ask the setup questions, previewing the effect on `dir` before anything
+/// is written. `None` if the user gave up.
+pub fn run<P>(input: &mut impl BufRead, out: &mut impl Write, dir: &Path, preview: P) -> io::Result<Option<Answers>>
+where
+    P: Fn(&Answers) -> Preview,
+{
+    writeln!(out, "Answers in [brackets] are the defaults; press Enter to keep them.\n")?;
+    let detected = detect_packs(dir);
+    let mut answers = Answers { packs: detected.clone(), ..Answers::default() };
+
+    loop {
+        if !detected.is_empty() {
+            writeln!(out, "Found project files for: {}", detected.join(", "))?;
+        }
+        let Some(packs) = ask_packs(input, out, &answers.packs)? else {
+            return Ok(None);
+        };
+        answers.packs = packs;
+
+        writeln!(out, "\nHow should other file names be styled?")?;
+        for (i, (_, description)) in STYLES.iter().enumerate() {
+            writeln!(out, "  {}) {}", i + 1, description)?;
+        }
+        let current = STYLES.iter().position(|(style, _)| *style == answers.style).unwrap_or(0);
+        let Some(choice) = ask_choice(input, out, STYLES.len(), current + 1)? else {
+            return Ok(None);
+        };
+        answers.style = STYLES[choice - 1].0;
+
+        let Some(timestamp) = ask_yes_no(input, out, "Prefix names with today's date, e.g. 2024_01_31__report.pdf?", answers.timestamp)? else {
+            return Ok(None);
+        };
+        answers.timestamp = timestamp;
+
+        match preview(&answers) {
+            Ok((checked, renames)) => show_preview(out, dir, checked, &renames)?,
+            Err(e) => writeln!(out, "\n(No preview: {})", e)?,
+        }
+        match ask_yes_no(input, out, "\nUse these settings?", true)? {
+            Some(true) => return Ok(Some(answers)),
+            Some(false) => writeln!(out)?,
+            None => return Ok(None),
+        }
+    }
+}
+
+fn show_preview(out: &mut impl Write, dir: &Path, checked: usize, renames: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    writeln!(out, "\nPreview for {}: {} of {} file(s) would be renamed", dir.display(), renames.len(), checked)?;
+    for (source, target) in renames.iter().take(PREVIEW_LINES) {
+        let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).display().to_string();
+        writeln!(out, "  {} -> {}", relative(source), target.file_name().unwrap_or_default().to_string_lossy())?;
+    }
+    if renames.len() > PREVIEW_LINES {
+        writeln!(out, "  ... and {} more", renames.len() - PREVIEW_LINES)?;
+    }
+    Ok(())
+}
+
+/// One line of input, trimmed; `None` at end of input.
+fn read_answer(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+fn ask_packs(input: &mut impl BufRead, out: &mut impl Write, current: &[String]) -> io::Result<Option<Vec<String>>> {
+    let default = if current.is_empty() { String::from("none") } else { current.join(", ") };
+    loop {
+        write!(out, "Convention packs to use ({}; comma-separated or \"none\") [{}]: ", PACKS.join(", "), default)?;
+        out.flush()?;
+        let Some(answer) = read_answer(input)? else {
+            return Ok(None);
+        };
+        if answer.is_empty() {
+            return Ok(Some(current.to_vec()));
+        }
+        if answer.eq_ignore_ascii_case("none") {
+            return Ok(Some(Vec::new()));
+        }
+        let packs: Vec<String> = answer.split(',').map(|p| p.trim().to_lowercase()).filter(|p| !p.is_empty()).collect();
+        match packs.iter().find(|p| !PACKS.contains(&p.as_str())) {
+            Some(unknown) => writeln!(out, "Unknown pack '{}'", unknown)?,
+            None => return Ok(Some(packs)),
+        }
+    }
+}
+
+fn ask_choice(input: &mut impl BufRead, out: &mut impl Write, count: usize, default: usize) -> io::Result<Option<usize>> {
+    loop {
+        write!(out, "Choice [{}]: ", default)?;
+        out.flush()?;
+        let Some(answer) = read_answer(input)? else {
+            return Ok(None);
+        };
+        if answer.is_empty() {
+            return Ok(Some(default));
+        }
+        match answer.parse() {
+            Ok(n) if (1..=count).contains(&n) => return Ok(Some(n)),
+            _ => writeln!(out, "Please enter a number from 1 to {}", count)?,
+        }
+    }
+}
+
+fn ask_yes_no(input: &mut impl BufRead, out: &mut impl Write, question: &str, default: bool) -> io::Result<Option<bool>> {
+    loop {
+        write!(out, "{} [{}]: ", question, if default { "Y/n" } else { "y/N" })?;
+        out.flush()?;
+        let Some(answer) = read_answer(input)? else {
+            return Ok(None);
+        };
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(Some(default)),
+            "y" | "yes" => return Ok(Some(true)),
+            "n" | "no" => return Ok(Some(false)),
+            _ => writeln!(out, "Please answer y or n")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn answers_are_asked_again_until_valid_and_previewed() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        let mut input = io::Cursor::new("cobol\nrust, Python\n9\n2\n\ny\n");
+        let mut out = Vec::new();
+        let preview = |answers: &Answers| -> Preview {
+            assert_eq!(answers.style, Some("snake_case"));
+            Ok((3, vec![(dir.join("src/My File.rs"), dir.join("src/my_file.rs"))]))
+        };
+        let answers = run(&mut input, &mut out, dir, preview).unwrap().unwrap();
+        assert_eq!((answers.packs, answers.style, answers.timestamp), (vec![String::from("rust"), String::from("python")], Some("snake_case"), false));
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Found project files for: rust\n"), "{}", out);
+        assert!(out.contains("[rust]: Unknown pack 'cobol'\n"), "{}", out);
+        assert!(out.contains("Please enter a number from 1 to 7\n"), "{}", out);
+        assert!(out.contains("1 of 3 file(s) would be renamed\n  src/My File.rs -> my_file.rs\n"), "{}", out);
+        // Giving up part way writes nothing
+        assert!(run(&mut io::Cursor::new("\n"), &mut Vec::new(), dir, preview).unwrap().is_none());
+    }
+
+    #[test]
+    fn answers_make_a_valid_configuration() {
+        let answers = Answers { packs: vec![String::from("node")], style: Some("kebab-case"), timestamp: true };
+        let config: crate::Config = toml::from_str(&answers.to_toml()).unwrap();
+        assert_eq!(config.presets, ["node"]);
+        assert!(config.timestamp && config.behaviors.len() == 1);
+    }
+}